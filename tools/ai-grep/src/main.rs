@@ -1,22 +1,45 @@
-use clap::Parser;
-use regex::Regex;
-use serde_json;
-use std::collections::HashMap;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use clap::parser::ValueSource;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
 use std::fs;
-use std::io::{self, BufRead, BufReader, Read};
-use std::path::PathBuf;
-use anyhow::Result;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+use ai_toolkit_core::{
+    apply_severity_overrides, severity_override_names, AnomalyType, Capture, Categories, Category, ColorMode,
+    ContextLine, CorpusIndex, Hit, JsonField, Match, Normalize, ScanOptions, Scanner, Severity,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "ai-grep")]
 #[command(about = "AI-aware grep for anomaly detection in AI outputs and code")]
+#[command(disable_help_flag = true)]
+#[command(group(clap::ArgGroup::new("rewrite").args(["redact", "annotate"])))]
 struct Args {
-    /// Pattern to search for (optional with preset modes)
+    /// Pattern to search for (optional with preset modes, -e or --file)
     pattern: Option<String>,
     
-    /// Input files (reads from stdin if none provided)
+    /// Input files, - for stdin (reads from stdin if none provided)
     files: Vec<PathBuf>,
     
+    /// Name shown for stdin in output and reports
+    #[arg(long, value_name = "NAME", default_value = "<stdin>")]
+    label: String,
+    
+    /// Read the files to scan from FILE (- for stdin), one path per line
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+    
+    /// Paths in --files-from are separated by NUL bytes, as printed by `find -print0`
+    #[arg(short = '0', long, requires = "files_from")]
+    null: bool,
+    
     /// Search for AI hallucination markers
     #[arg(long)]
     hallucinations: bool,
@@ -37,6 +60,56 @@ struct Args {
     #[arg(long)]
     low_confidence: bool,
     
+    /// Search for API keys, tokens and other high-entropy secrets
+    #[arg(long)]
+    secrets: bool,
+    
+    /// Search for prompt-injection and jailbreak attempts
+    #[arg(long)]
+    prompt_injection: bool,
+    
+    /// With --low-confidence, report paragraphs dense in hedges instead of every single hedge
+    #[arg(long, requires = "low_confidence", conflicts_with_all = [
+        "hallucinations", "code_issues", "security", "data_leakage", "secrets", "prompt_injection",
+        "memorization_check", "multiline", "follow", "json_input", "invert_match", "count", "count_matches",
+        "count_by", "files_with_matches", "score", "annotate", "after_context", "before_context", "context",
+    ])]
+    density: bool,
+    
+    /// Hedges per 100 words above which --density reports a paragraph
+    #[arg(long, value_name = "N", default_value = "3.0", requires = "density")]
+    density_threshold: f64,
+    
+    /// Flag lines that repeat text verbatim from the reference files under CORPUS_DIR
+    #[arg(long, value_name = "CORPUS_DIR", conflicts_with_all = ["multiline", "redact"])]
+    memorization_check: Option<PathBuf>,
+    
+    /// Words per n-gram in the --memorization-check index
+    #[arg(long, value_name = "N", default_value = "8", requires = "memorization_check")]
+    ngram_size: usize,
+    
+    /// Consecutive words that must match the corpus for a finding (default: --ngram-size)
+    #[arg(long, value_name = "N", requires = "memorization_check")]
+    min_overlap: Option<usize>,
+    
+    /// Most distinct n-grams kept in the --memorization-check index (about 32 bytes each)
+    #[arg(long, value_name = "N", default_value = "4000000", requires = "memorization_check")]
+    corpus_max_ngrams: usize,
+    
+    /// Minimum Shannon entropy (bits/char) for generic secret candidates
+    #[arg(long, default_value = "3.5")]
+    entropy_threshold: f64,
+    
+    /// Search for PATTERN; repeat to match any of several, like grep -e. The first
+    /// positional argument is then a file
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN", allow_hyphen_values = true)]
+    regexps: Vec<String>,
+    
+    /// Search for the patterns in FILE, one per line (blank lines are skipped), like grep -f;
+    /// repeatable, and combines with -e. Long form only: -f is --follow here
+    #[arg(long = "file", value_name = "FILE")]
+    pattern_files: Vec<PathBuf>,
+    
     /// Use extended regex patterns (-E flag)
     #[arg(short = 'E', long)]
     extended_regex: bool,
@@ -45,570 +118,2790 @@ struct Args {
     #[arg(short = 'P', long)]
     perl_regex: bool,
     
-    /// Case insensitive search (-i flag)
+    /// Match across line breaks: `.` matches newlines and spaces in preset or fixed-string
+    /// patterns match any whitespace, so wrapped phrases are found
+    #[arg(short = 'U', long, conflicts_with_all = ["invert_match", "follow", "redact", "after_context", "before_context", "context"])]
+    multiline: bool,
+    
+    /// Bytes of input held in memory by --multiline; larger inputs are scanned through a
+    /// sliding window, and matches longer than half of it may be cut short
+    #[arg(long, value_name = "BYTES", default_value = "1048576", requires = "multiline",
+          value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1024..))]
+    multiline_window: usize,
+    
+    /// Case insensitive search (-i flag), applies to preset patterns too
     #[arg(short = 'i', long)]
     ignore_case: bool,
     
+    /// Force exact case matching for every preset pattern
+    #[arg(long, conflicts_with = "ignore_case")]
+    case_sensitive_presets: bool,
+    
+    /// Match against NFKC-normalized text with curly quotes, dashes and odd spaces folded to
+    /// ASCII (always on for presets; this turns it on for custom patterns too)
+    #[arg(long, overrides_with = "no_normalize")]
+    normalize: bool,
+    
+    /// Match presets against the text exactly as written
+    #[arg(long, overrides_with = "normalize")]
+    no_normalize: bool,
+    
+    /// Override the severity of a category (e.g. email=low), repeatable
+    #[arg(long, value_name = "CATEGORY=LEVEL", value_parser = parse_severity_override)]
+    severity_override: Vec<(String, Severity)>,
+    
+    /// Only report findings at or above this severity
+    #[arg(long, value_enum)]
+    min_severity: Option<Severity>,
+    
+    /// Report every pattern hit, even where patterns of the same category match overlapping text
+    #[arg(long)]
+    no_dedupe: bool,
+    
     /// Show line numbers (-n flag)
     #[arg(short = 'n', long)]
     line_number: bool,
     
-    /// Count matches only (-c flag)
+    /// Prefix each line with its file name, even for a single input
+    #[arg(short = 'H', long, overrides_with = "no_filename")]
+    with_filename: bool,
+    
+    /// Never prefix lines with the file name
+    #[arg(short = 'h', long, overrides_with = "with_filename")]
+    no_filename: bool,
+    
+    /// Show the 1-based column, in characters, of the match after its line number
+    #[arg(long)]
+    column: bool,
+    
+    /// Show the byte offset in the file of the match start (-b flag)
+    #[arg(short = 'b', long)]
+    byte_offset: bool,
+    
+    /// Count matching lines only (-c flag)
     #[arg(short = 'c', long)]
     count: bool,
     
+    /// Count every match rather than matching lines
+    #[arg(long)]
+    count_matches: bool,
+    
+    /// Print counts broken down by category, severity or file (honours --count-matches)
+    #[arg(long, value_enum, value_name = "KEY")]
+    count_by: Option<CountBy>,
+    
     /// Invert match (show non-matching lines) (-v flag)
     #[arg(short = 'v', long)]
     invert_match: bool,
     
+    /// Print only the matched text of each match, one per line (-o flag); context is ignored
+    #[arg(short = 'o', long, conflicts_with = "invert_match")]
+    only_matching: bool,
+    
     /// Show only filenames with matches (-l flag)
     #[arg(short = 'l', long)]
     files_with_matches: bool,
     
-    /// Highlight matches with color (--color)
+    /// Highlight matches with color (--color); auto also honours NO_COLOR
     #[arg(long, value_enum, default_value = "auto")]
     color: ColorMode,
     
-    /// Context lines after match
-    #[arg(short = 'A', long, default_value = "0")]
-    after_context: usize,
+    /// SGR color codes per part of the output, e.g. critical=91;1:high=31:medium=33:low=36:line_no=32
+    /// (keys: critical, high, medium, low, line_no, file; an empty value turns that color off)
+    #[arg(long, value_name = "SPEC", value_parser = Colors::parse)]
+    colors: Option<Colors>,
+    
+    /// Context lines after match; hunks of nearby matches are separated by `--` as in grep
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
     
     /// Context lines before match
-    #[arg(short = 'B', long, default_value = "0")]
-    before_context: usize,
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
     
-    /// Context lines around match
-    #[arg(short = 'C', long)]
+    /// Context lines around match (-A and -B take precedence)
+    #[arg(short = 'C', long, value_name = "NUM")]
     context: Option<usize>,
     
     /// Suppress error messages
     #[arg(short = 's', long)]
     no_messages: bool,
     
-    /// Output format: text, json
+    /// Output format: text, json, ndjson (one JSON object per match), csv, markdown
     #[arg(long, default_value = "text")]
     format: String,
     
+    /// Truncate matched lines in --format markdown reports to this many characters
+    #[arg(long, value_name = "CHARS", default_value = "100", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(10..=10_000))]
+    report_width: usize,
+    
+    /// Cut displayed lines longer than NUM characters down to NUM around the match, marking
+    /// what was left out (text output only; JSON keeps whole lines)
+    #[arg(short = 'M', long, value_name = "NUM", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_columns: Option<usize>,
+    
+    /// With --max-columns, show the first NUM characters of a long line, like ripgrep,
+    /// instead of the part around the match
+    #[arg(long, requires = "max_columns")]
+    max_columns_preview: bool,
+    
+    /// Omit the header row of --format csv, e.g. when appending to an existing file
+    #[arg(long)]
+    no_header: bool,
+    
     /// Show anomaly severity score
     #[arg(long)]
     severity: bool,
     
+    /// Show the named capture groups of each match (JSON output always includes them)
+    #[arg(long)]
+    show_captures: bool,
+    
+    /// Print a summary across all inputs: per category, severity and file, with the
+    /// aggregate risk score
+    #[arg(long, visible_alias = "summary")]
+    stats: bool,
+    
+    /// Number of noisiest files listed in the --stats summary
+    #[arg(long, default_value = "10")]
+    top_files: usize,
+    
+    /// Print each distinct finding once, with how often and where it occurs in all inputs;
+    /// findings match when their category and case- and whitespace-folded text are the same
+    #[arg(long, conflicts_with_all = [
+        "count", "count_matches", "count_by", "files_with_matches", "score", "density", "follow",
+        "after_context", "before_context", "context",
+    ])]
+    group_duplicates: bool,
+    
+    /// Suppress findings recorded in this baseline file
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+    
+    /// Record every current finding into the --baseline file instead of suppressing
+    #[arg(long, requires = "baseline")]
+    write_baseline: bool,
+    
+    /// Exit with status 1 if any reported finding is at or above this severity
+    #[arg(long, value_enum, value_name = "SEVERITY")]
+    fail_on: Option<Severity>,
+    
+    /// Exit with a status telling the highest severity reported: 0 nothing found, 10 low,
+    /// 11 medium, 12 high, 13 critical, or 2 if an error occurred (an unreadable input
+    /// included, even when others had findings)
+    #[arg(long, conflicts_with_all = ["fail_on", "fail_on_score", "redact", "annotate"])]
+    severity_exit_codes: bool,
+    
+    /// Print nothing to stdout; with --severity-exit-codes or --fail-on only the exit
+    /// status reports the result
+    #[arg(short = 'q', long, conflicts_with_all = ["redact", "annotate", "list_presets"])]
+    quiet: bool,
+    
+    /// Print one 0-100 risk score per file (`<file>\t<score>`) instead of the matches
+    #[arg(long, conflicts_with_all = ["count", "count_matches", "count_by", "files_with_matches", "follow", "redact"])]
+    score: bool,
+    
+    /// Weights of the --score components, e.g. severity=0.6,density=0.25,diversity=0.15
+    #[arg(long, value_name = "SPEC", requires = "score", value_parser = ScoreWeights::parse)]
+    score_weights: Option<ScoreWeights>,
+    
+    /// Exit with status 1 if any file's --score is above N
+    #[arg(long, value_name = "N", requires = "score")]
+    fail_on_score: Option<f64>,
+    
+    /// Ignore matches inside Markdown code (fenced ``` blocks and indented code)
+    #[arg(long)]
+    skip_code_blocks: bool,
+    
+    /// Ignore matches inside Markdown > blockquotes
+    #[arg(long)]
+    skip_quotes: bool,
+    
+    /// Parse inputs as JSON or JSONL and scan the strings at PATH (e.g. choices[*].message.content);
+    /// without PATH, every string field named "content" or "text" is scanned
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true, conflicts_with = "redact")]
+    json_input: Option<Option<String>>,
+    
+    /// How to treat files with a NUL byte in their first 8 KB: report only that they match,
+    /// scan them as text, or skip them
+    #[arg(long, value_enum, value_name = "TYPE", default_value = "binary")]
+    binary_files: BinaryFiles,
+    
+    /// When to decompress inputs: by .gz/.zst extension, also by magic bytes, or never
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "ext")]
+    decompress: DecompressMode,
+    
+    /// Number of files scanned in parallel (defaults to available parallelism)
+    #[arg(short = 'j', long, value_name = "N")]
+    threads: Option<usize>,
+    
+    /// Print each file's results as soon as it is scanned instead of in argument order
+    #[arg(long)]
+    unordered: bool,
+    
+    /// Keep reading the files as they grow, like `tail -f`, starting from their current end
+    #[arg(short = 'f', long, conflicts_with_all = ["count", "count_matches", "count_by", "files_with_matches", "redact", "json_input", "after_context", "before_context", "context"])]
+    follow: bool,
+    
+    /// With --follow, stop at the first finding that meets --fail-on instead of waiting for Ctrl-C
+    #[arg(long, requires_all = ["follow", "fail_on"])]
+    exit_on_match: bool,
+    
     /// List available preset patterns
     #[arg(long)]
     list_presets: bool,
+    
+    /// Write the input with data-leakage findings masked instead of listing matches
+    #[arg(long)]
+    redact: bool,
+    
+    /// Mask redacted text with this character, preserving its length
+    #[arg(long, value_name = "CHAR", requires = "redact")]
+    redact_char: Option<char>,
+    
+    /// Write the whole input with a marker under every finding, for review
+    #[arg(long, conflicts_with_all = [
+        "count", "count_matches", "count_by", "files_with_matches", "score", "follow", "json_input",
+        "multiline", "invert_match", "after_context", "before_context", "context",
+    ])]
+    annotate: bool,
+    
+    /// How --annotate marks findings
+    #[arg(long, value_enum, default_value = "caret", requires = "annotate")]
+    annotate_style: AnnotateStyle,
+    
+    /// Write redacted or annotated output to a file instead of stdout
+    #[arg(long, requires = "rewrite")]
+    output: Option<PathBuf>,
+    
+    /// Don't read ai-grep.toml or ~/.config/ai-toolkit/ai-grep.toml
+    #[arg(long)]
+    no_config: bool,
+    
+    /// Print the options in effect, from the config file and the command line, as TOML and exit
+    #[arg(long)]
+    print_config: bool,
+    
+    /// Print help (-h is --no-filename, as in grep)
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
-enum ColorMode {
-    Always,
-    Never,
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum DecompressMode {
+    /// Decompress files named *.gz or *.zst
+    Ext,
+    /// Also detect gzip and zstd inputs by their magic bytes
     Auto,
+    /// Read every input as plain text
+    Never,
 }
 
-#[derive(Debug)]
-struct Match {
-    line_number: usize,
-    content: String,
-    matched_text: String,
-    start_pos: usize,
-    end_pos: usize,
-    anomaly_type: AnomalyType,
-    severity: Severity,
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum BinaryFiles {
+    /// Print "Binary file X matches" instead of the matching lines
+    Binary,
+    /// Scan binary files like text, replacing invalid UTF-8
+    Text,
+    /// Assume binary files do not match
+    WithoutMatch,
 }
 
-#[derive(Debug)]
-enum AnomalyType {
-    Hallucination { marker_type: String },
-    CodeIssue { issue_type: String },
-    Security { vulnerability_type: String },
-    DataLeakage { leak_type: String },
-    LowConfidence { confidence_marker: String },
-    Custom,
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum AnnotateStyle {
+    /// `^^^^ [HIGH category/subtype]` under the match
+    Caret,
+    /// `<!-- ai-grep: ... -->` lines, which Markdown renderers hide
+    Comment,
 }
 
-#[derive(Debug, Clone)]
-enum Severity {
-    Low,
-    Medium,
-    High,
-    Critical,
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum CountBy {
+    Category,
+    Severity,
+    File,
 }
 
-impl Severity {
-    fn to_score(&self) -> f64 {
+impl CountBy {
+    fn name(self) -> &'static str {
         match self {
-            Severity::Low => 0.25,
-            Severity::Medium => 0.5,
-            Severity::High => 0.75,
-            Severity::Critical => 1.0,
+            CountBy::Category => "category",
+            CountBy::Severity => "severity",
+            CountBy::File => "file",
         }
     }
     
-    fn to_emoji(&self) -> &'static str {
+    /// Counts for one input, most severe first for severities and by name otherwise.
+    fn breakdown(self, matches: &[Match], filename: &str, args: &Args) -> Vec<(String, usize)> {
+        let mut counts = count_findings(matches, args, |m| match self {
+            CountBy::Category => m.anomaly_type.category().to_string(),
+            CountBy::Severity => format!("{:?}", m.severity).to_lowercase(),
+            CountBy::File => filename.to_string(),
+        });
         match self {
-            Severity::Low => "ℹ️",
-            Severity::Medium => "⚠️",
-            Severity::High => "🚨",
-            Severity::Critical => "💀",
+            CountBy::Severity => sort_by_severity(&mut counts),
+            CountBy::File if counts.is_empty() => counts.push((filename.to_string(), 0)),
+            _ => {}
+        }
+        counts
+    }
+}
+
+/// ANSI SGR parameters for each part of text output, in the spirit of GREP_COLORS.
+#[derive(Debug, Clone)]
+struct Colors {
+    critical: String,
+    high: String,
+    medium: String,
+    low: String,
+    line_no: String,
+    file: String,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            critical: "91;1".to_string(),
+            high: "31;1".to_string(),
+            medium: "33".to_string(),
+            low: "36".to_string(),
+            line_no: "32".to_string(),
+            file: "35".to_string(),
         }
     }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+impl Colors {
+    /// Parse `key=sgr` pairs separated by `:`; keys left out keep their default color.
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut colors = Colors::default();
+        for entry in spec.split(':').filter(|entry| !entry.is_empty()) {
+            let (key, sgr) = entry.split_once('=')
+                .ok_or_else(|| format!("expected KEY=SGR, got '{}'", entry))?;
+            if !sgr.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                return Err(format!("'{}' is not an SGR code such as 31 or 1;33", sgr));
+            }
+            let slot = match key {
+                "critical" => &mut colors.critical,
+                "high" => &mut colors.high,
+                "medium" => &mut colors.medium,
+                "low" => &mut colors.low,
+                "line_no" => &mut colors.line_no,
+                "file" => &mut colors.file,
+                _ => return Err(format!("unknown color key '{}' (expected critical, high, medium, low, line_no or file)", key)),
+            };
+            *slot = sgr.to_string();
+        }
+        Ok(colors)
+    }
     
-    if args.list_presets {
-        list_preset_patterns();
-        return Ok(());
+    fn severity(&self, severity: Severity) -> &str {
+        match severity {
+            Severity::Critical => &self.critical,
+            Severity::High => &self.high,
+            Severity::Medium => &self.medium,
+            Severity::Low => &self.low,
+        }
+    }
+}
+
+impl Args {
+    /// Whether patterns come from -e or --file rather than the first positional argument.
+    fn uses_pattern_flags(&self) -> bool {
+        !self.regexps.is_empty() || !self.pattern_files.is_empty()
     }
     
-    let pattern = get_pattern(&args)?;
-    let use_color = should_use_color(&args);
+    fn uses_preset(&self) -> bool {
+        self.hallucinations || self.code_issues || self.security || self.data_leakage
+            || self.low_confidence || self.secrets || self.prompt_injection || self.redact
+            || self.memorization_check.is_some()
+    }
     
-    if args.files.is_empty() {
-        let input = read_stdin()?;
-        process_input(&input, "<stdin>", &pattern, &args, use_color)?;
-    } else {
-        for file in &args.files {
-            let input = fs::read_to_string(file).map_err(|e| {
-                if !args.no_messages {
-                    eprintln!("ai-grep: {}: {}", file.display(), e);
-                }
-                e
-            });
-            
-            match input {
-                Ok(content) => {
-                    process_input(&content, &file.to_string_lossy(), &pattern, &args, use_color)?;
-                }
-                Err(_) => continue,
-            }
+    /// Matching options for the engine, from -i, --case-sensitive-presets, --multiline,
+    /// --normalize/--no-normalize, --entropy-threshold and --no-dedupe.
+    fn scan_options(&self) -> ScanOptions {
+        let normalize = if self.normalize {
+            Normalize::Always
+        } else if self.no_normalize {
+            Normalize::Never
+        } else {
+            Normalize::Presets
+        };
+        ScanOptions {
+            ignore_case: self.ignore_case,
+            case_sensitive_presets: self.case_sensitive_presets,
+            multiline: self.multiline,
+            normalize,
+            entropy_threshold: self.entropy_threshold,
+            dedupe: !self.no_dedupe,
         }
     }
     
-    Ok(())
+    /// Whether output lines carry the file name: with several inputs (stdin counting as
+    /// one), unless -H or -h says otherwise.
+    fn with_filename(&self) -> bool {
+        !self.no_filename && (self.with_filename || self.files.len() > 1)
+    }
+    
+    /// Whether any of -A/-B/-C was given, even as 0: matches are then grouped into hunks.
+    fn shows_context(&self) -> bool {
+        self.after_context.is_some() || self.before_context.is_some() || self.context.is_some()
+    }
+    
+    /// Lines of context shown before each match: -B, or else -C.
+    fn lines_before(&self) -> usize {
+        self.before_context.or(self.context).unwrap_or(0)
+    }
+    
+    /// Lines of context shown after each match: -A, or else -C.
+    fn lines_after(&self) -> usize {
+        self.after_context.or(self.context).unwrap_or(0)
+    }
+    
+    /// Display name of an input path, with `-` shown as the --label.
+    fn input_name(&self, path: &Path) -> String {
+        if is_stdin(path) {
+            self.label.clone()
+        } else {
+            path.to_string_lossy().into_owned()
+        }
+    }
 }
 
-fn read_stdin() -> Result<String> {
-    let mut buffer = String::new();
-    io::stdin().read_to_string(&mut buffer)?;
-    Ok(buffer)
+/// `-` among the files means stdin, as in grep.
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
 }
 
-fn get_pattern(args: &Args) -> Result<AnomalyPattern> {
-    if args.hallucinations {
-        Ok(AnomalyPattern::Preset(PresetPattern::Hallucinations))
-    } else if args.code_issues {
-        Ok(AnomalyPattern::Preset(PresetPattern::CodeIssues))
-    } else if args.security {
-        Ok(AnomalyPattern::Preset(PresetPattern::Security))
-    } else if args.data_leakage {
-        Ok(AnomalyPattern::Preset(PresetPattern::DataLeakage))
-    } else if args.low_confidence {
-        Ok(AnomalyPattern::Preset(PresetPattern::LowConfidence))
-    } else if let Some(pattern) = &args.pattern {
-        Ok(AnomalyPattern::Custom(pattern.clone()))
+/// Open a file argument, where `-` reads stdin.
+fn open_path(path: &Path, mode: DecompressMode) -> io::Result<Box<dyn BufRead>> {
+    if is_stdin(path) {
+        open_stdin(mode)
     } else {
-        anyhow::bail!("No pattern specified. Use a custom pattern or one of the preset flags (--hallucinations, --code-issues, etc.)")
+        open_input(path, mode)
     }
 }
 
-fn should_use_color(args: &Args) -> bool {
-    match args.color {
-        ColorMode::Always => true,
-        ColorMode::Never => false,
-        ColorMode::Auto => atty::is(atty::Stream::Stdout),
+fn main() {
+    let (args, config) = parse_args();
+    if args.print_config {
+        config.print();
+        return;
+    }
+    let error_status = if args.severity_exit_codes { 2 } else { 1 };
+    if let Err(e) = run(args) {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(error_status);
     }
 }
 
-fn list_preset_patterns() {
-    println!("Available preset patterns:");
-    println!("  --hallucinations    AI model limitations and disclaimers");
-    println!("  --code-issues       Development markers (TODO, FIXME, HACK, etc.)");
-    println!("  --security          Security vulnerability patterns");
-    println!("  --data-leakage      Training data or PII leakage indicators");
-    println!("  --low-confidence    Uncertainty and hedging language");
-    println!();
-    println!("Examples:");
-    println!("  ai-grep --hallucinations < ai_output.txt");
-    println!("  ai-grep --code-issues --count src/**/*.rs");
-    println!("  ai-grep --security --color=always *.py");
-    println!("  ai-grep \"custom pattern\" file.txt");
+/// Name of the config file, looked up in the current directory and its parents.
+const CONFIG_FILE: &str = "ai-grep.toml";
+
+/// Flags that can't be set in a config file.
+const NOT_CONFIGURABLE: &[&str] = &["help", "no-config", "print-config"];
+
+/// An option read from the config file: its key and value, and the arguments it stands for.
+struct ConfigOption {
+    key: String,
+    value: toml::Value,
+    args: Vec<String>,
 }
 
-#[derive(Debug)]
-enum AnomalyPattern {
-    Preset(PresetPattern),
-    Custom(String),
+/// The options in effect and where each was set, for --print-config.
+struct EffectiveConfig {
+    file: Option<PathBuf>,
+    disabled: bool,
+    settings: Vec<(String, toml::Value, &'static str)>,
 }
 
-#[derive(Debug)]
-enum PresetPattern {
-    Hallucinations,
-    CodeIssues,
-    Security,
-    DataLeakage,
-    LowConfidence,
+impl EffectiveConfig {
+    fn print(&self) {
+        match &self.file {
+            Some(path) => println!("# config file: {}", path.display()),
+            None if self.disabled => println!("# config file: disabled by --no-config"),
+            None => println!("# config file: none found"),
+        }
+        for (key, value, source) in &self.settings {
+            println!("{} = {}  # {}", key, value, source);
+        }
+    }
 }
 
-fn process_input(input: &str, filename: &str, pattern: &AnomalyPattern, args: &Args, use_color: bool) -> Result<()> {
-    let matches = find_matches(input, pattern, args)?;
+/// Parse the command line with the options of the config file in front of it. Options given
+/// on the command line, and those they conflict with, are dropped from the config so that
+/// explicit flags always win. Exits on errors, as `Args::parse` does.
+fn parse_args() -> (Args, EffectiveConfig) {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let mut command = Args::command();
+    command.build();
+    let cli = command.clone().get_matches_from(&argv);
     
-    if args.files_with_matches {
-        if !matches.is_empty() {
-            println!("{}", filename);
+    let disabled = cli.get_flag("no_config");
+    let file = if disabled { None } else { find_config() };
+    let options = match &file {
+        Some(path) => load_config(path, &command, &cli).unwrap_or_else(|e| {
+            eprintln!("ai-grep: {}: {:#}", path.display(), e);
+            std::process::exit(2);
+        }),
+        None => Vec::new(),
+    };
+    
+    let matches = match &file {
+        Some(path) => {
+            let config_args = options.iter().flat_map(|option| option.args.iter().map(OsString::from));
+            let merged: Vec<OsString> = argv[..1].iter().cloned().chain(config_args).chain(argv[1..].iter().cloned()).collect();
+            command.clone().try_get_matches_from(merged).unwrap_or_else(|e| {
+                eprintln!("ai-grep: with the options in {}:", path.display());
+                e.exit()
+            })
+        }
+        None => cli.clone(),
+    };
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    
+    // Config options overridden by a flag like -h, or set to false, no longer show in the matches
+    let mut settings = Vec::new();
+    for arg in configurable_args(&command) {
+        let key = arg.get_long().expect("configurable args have a long flag");
+        let id = arg.get_id().as_str();
+        if cli.value_source(id) == Some(ValueSource::CommandLine) {
+            settings.push((key.to_string(), command_line_value(arg, &cli), "command line"));
+        } else if matches.value_source(id) == Some(ValueSource::CommandLine) {
+            if let Some(option) = options.iter().find(|option| option.key == key) {
+                settings.push((key.to_string(), option.value.clone(), "config file"));
+            }
         }
-        return Ok(());
     }
+    (args, EffectiveConfig { file, disabled, settings })
+}
+
+/// The nearest ai-grep.toml in the current directory or one of its parents, else
+/// ~/.config/ai-toolkit/ai-grep.toml.
+fn find_config() -> Option<PathBuf> {
+    let local = std::env::current_dir().ok().and_then(|cwd| {
+        cwd.ancestors().map(|dir| dir.join(CONFIG_FILE)).find(|path| path.is_file())
+    });
+    local.or_else(|| {
+        let home = PathBuf::from(std::env::var_os("HOME")?);
+        Some(home.join(".config/ai-toolkit").join(CONFIG_FILE)).filter(|path| path.is_file())
+    })
+}
+
+/// Flags whose long names are valid config keys.
+fn configurable_args(command: &clap::Command) -> impl Iterator<Item = &clap::Arg> {
+    command.get_arguments()
+        .filter(|arg| arg.get_long().is_some_and(|long| !NOT_CONFIGURABLE.contains(&long)))
+}
+
+/// Read a config file whose keys are long flag names. Unknown keys are warned about and
+/// skipped; options replaced by the command line are left out.
+fn load_config(path: &Path, command: &clap::Command, cli: &clap::ArgMatches) -> Result<Vec<ConfigOption>> {
+    let table: toml::Table = fs::read_to_string(path)?.parse()?;
+    let given: Vec<&clap::Arg> = command.get_arguments()
+        .filter(|arg| cli.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .collect();
     
-    if args.count {
-        let count = if args.invert_match {
-            input.lines().count() - matches.len()
-        } else {
-            matches.len()
+    let mut options = Vec::new();
+    for (key, value) in table {
+        let Some(arg) = configurable_args(command).find(|arg| arg.get_long() == Some(key.as_str())) else {
+            eprintln!("ai-grep: warning: {}: unknown option `{}`", path.display(), key);
+            continue;
         };
-        
-        if args.files.len() > 1 {
-            println!("{}:{}", filename, count);
-        } else {
-            println!("{}", count);
+        let args = config_args(arg, &value).with_context(|| format!("`{}`", key))?;
+        if given.iter().any(|given| replaces(command, given, arg)) {
+            continue;
         }
-        return Ok(());
+        options.push(ConfigOption { key, value, args });
     }
-    
-    display_matches(&matches, filename, args, use_color)?;
-    Ok(())
+    Ok(options)
 }
 
-fn find_matches(input: &str, pattern: &AnomalyPattern, args: &Args) -> Result<Vec<Match>> {
-    let mut all_matches = Vec::new();
-    
-    match pattern {
-        AnomalyPattern::Preset(preset) => {
-            all_matches.extend(find_preset_matches(input, preset, args)?);
+/// Whether `given` on the command line replaces `arg` from the config file: it is the same
+/// option, or the two can't be used together.
+fn replaces(command: &clap::Command, given: &clap::Arg, arg: &clap::Arg) -> bool {
+    let conflicts = |a: &clap::Arg, b: &clap::Arg| {
+        command.get_arg_conflicts_with(a).iter().any(|other| other.get_id() == b.get_id())
+    };
+    let exclusive_group = command.get_groups().any(|group| {
+        !group.clone().is_multiple()
+            && group.get_args().any(|id| id == given.get_id())
+            && group.get_args().any(|id| id == arg.get_id())
+    });
+    given.get_id() == arg.get_id() || conflicts(given, arg) || conflicts(arg, given) || exclusive_group
+}
+
+/// The arguments a config value stands for: `true` sets a flag and `false` leaves it unset,
+/// other values become `--key=value`, and an array repeats an option that can be repeated.
+fn config_args(arg: &clap::Arg, value: &toml::Value) -> Result<Vec<String>> {
+    let flag = format!("--{}", arg.get_long().expect("configurable args have a long flag"));
+    let takes_values = arg.get_action().takes_values();
+    let optional_value = arg.get_num_args().is_some_and(|range| range.min_values() == 0);
+    match value {
+        toml::Value::Boolean(set) if !takes_values || optional_value => {
+            Ok(if *set { vec![flag] } else { Vec::new() })
         }
-        AnomalyPattern::Custom(pattern_str) => {
-            all_matches.extend(find_custom_matches(input, pattern_str, args)?);
+        _ if !takes_values => bail!("expected true or false"),
+        toml::Value::Array(items) if matches!(arg.get_action(), clap::ArgAction::Append) => {
+            items.iter().map(|item| Ok(format!("{}={}", flag, config_value(item)?))).collect()
         }
+        toml::Value::Array(_) => bail!("expected a single value, not an array"),
+        value => Ok(vec![format!("{}={}", flag, config_value(value)?)]),
     }
-    
-    // Apply invert match filter
-    if args.invert_match {
-        let matching_lines: std::collections::HashSet<usize> = all_matches
-            .iter()
-            .map(|m| m.line_number)
-            .collect();
-            
-        all_matches = input
-            .lines()
-            .enumerate()
-            .filter_map(|(i, line)| {
-                let line_num = i + 1;
-                if !matching_lines.contains(&line_num) {
-                    Some(Match {
-                        line_number: line_num,
-                        content: line.to_string(),
-                        matched_text: line.to_string(),
-                        start_pos: 0,
-                        end_pos: line.len(),
-                        anomaly_type: AnomalyType::Custom,
-                        severity: Severity::Low,
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect();
-    }
-    
-    Ok(all_matches)
 }
 
-fn find_preset_matches(input: &str, preset: &PresetPattern, args: &Args) -> Result<Vec<Match>> {
-    match preset {
-        PresetPattern::Hallucinations => find_hallucination_matches(input, args),
-        PresetPattern::CodeIssues => find_code_issue_matches(input, args),
-        PresetPattern::Security => find_security_matches(input, args),
-        PresetPattern::DataLeakage => find_data_leakage_matches(input, args),
-        PresetPattern::LowConfidence => find_low_confidence_matches(input, args),
+fn config_value(value: &toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(text) => Ok(text.clone()),
+        toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => Ok(value.to_string()),
+        _ => bail!("expected a string, number or boolean"),
     }
 }
 
-fn find_custom_matches(input: &str, pattern_str: &str, args: &Args) -> Result<Vec<Match>> {
-    let regex_flags = if args.ignore_case { "(?i)" } else { "" };
-    
-    let pattern = if args.extended_regex || args.perl_regex {
-        // For extended/perl regex, use the pattern as-is (with case flag)
-        Regex::new(&format!("{}{}", regex_flags, pattern_str))?
+/// An option given on the command line, written as the config value that would set it.
+fn command_line_value(arg: &clap::Arg, cli: &clap::ArgMatches) -> toml::Value {
+    let values: Vec<toml::Value> = cli.get_raw(arg.get_id().as_str())
+        .into_iter()
+        .flatten()
+        .map(|value| toml::Value::String(value.to_string_lossy().into_owned()))
+        .collect();
+    if !arg.get_action().takes_values() {
+        toml::Value::Boolean(true)
+    } else if matches!(arg.get_action(), clap::ArgAction::Append) {
+        toml::Value::Array(values)
     } else {
-        // For basic grep, escape special characters
-        Regex::new(&format!("{}{}", regex_flags, regex::escape(pattern_str)))?
-    };
+        values.into_iter().next().unwrap_or(toml::Value::Boolean(true))
+    }
+}
+
+fn run(mut args: Args) -> Result<()> {
+    // Preset modes and -e/--file take no pattern, so the first positional argument is really a file
+    if args.uses_preset() || args.uses_pattern_flags() {
+        if let Some(first_file) = args.pattern.take() {
+            args.files.insert(0, PathBuf::from(first_file));
+        }
+    }
     
-    let mut matches = Vec::new();
-    
-    for (line_num, line) in input.lines().enumerate() {
-        for mat in pattern.find_iter(line) {
-            matches.push(Match {
-                line_number: line_num + 1,
-                content: line.to_string(),
-                matched_text: mat.as_str().to_string(),
-                start_pos: mat.start(),
-                end_pos: mat.end(),
-                anomaly_type: AnomalyType::Custom,
-                severity: Severity::Medium,
-            });
+    if let Some(list) = &args.files_from {
+        if !args.files.is_empty() {
+            anyhow::bail!("--files-from cannot be combined with files on the command line");
         }
+        args.files = read_file_list(list, args.null)?;
     }
     
-    Ok(matches)
-}
-
-fn find_hallucination_matches(input: &str, args: &Args) -> Result<Vec<Match>> {
-    let patterns = [
-        ("knowledge_cutoff", vec![
-            (r"(?i)\b(september 2021|knowledge cutoff|training data|cutoff date)\b", Severity::High),
-            (r"(?i)\b(my last update|as of my last training)\b", Severity::High),
-        ]),
-        ("capability_disclaimer", vec![
-            (r"(?i)\b(as an ai|i cannot|i don't have access|i'm not able to)\b", Severity::Critical),
-            (r"(?i)\b(i don't have the ability|i cannot browse|cannot access the internet)\b", Severity::High),
-        ]),
-        ("uncertainty", vec![
-            (r"(?i)\b(i'm not sure|i cannot verify|unconfirmed|i don't know)\b", Severity::Medium),
-            (r"(?i)\b(i cannot confirm|i'm uncertain|unclear)\b", Severity::Medium),
-        ]),
-        ("browsing_limitation", vec![
-            (r"(?i)\b(real-time|cannot browse|cannot access websites)\b", Severity::High),
-            (r"(?i)\b(i don't have internet access|cannot search the web)\b", Severity::High),
-        ]),
-    ];
+    if args.list_presets {
+        return list_preset_patterns(&args);
+    }
     
-    let mut matches = Vec::new();
+    if args.redact {
+        return run_redaction(&args);
+    }
     
-    for (marker_type, pattern_list) in &patterns {
-        for (pattern_str, severity) in pattern_list {
-            let regex = Regex::new(pattern_str)?;
-            
-            for (line_num, line) in input.lines().enumerate() {
-                for mat in regex.find_iter(line) {
-                    matches.push(Match {
-                        line_number: line_num + 1,
-                        content: line.to_string(),
-                        matched_text: mat.as_str().to_string(),
-                        start_pos: mat.start(),
-                        end_pos: mat.end(),
-                        anomaly_type: AnomalyType::Hallucination {
-                            marker_type: marker_type.to_string(),
-                        },
-                        severity: severity.clone(),
-                    });
+    if args.score && matches!(args.format.as_str(), "csv" | "markdown") {
+        anyhow::bail!("--score prints text, json or ndjson, not {}", args.format);
+    }
+    if args.density && matches!(args.format.as_str(), "csv" | "markdown") {
+        anyhow::bail!("--density prints text, json or ndjson, not {}", args.format);
+    }
+    if args.group_duplicates && matches!(args.format.as_str(), "csv" | "markdown") {
+        anyhow::bail!("--group-duplicates prints text, json or ndjson, not {}", args.format);
+    }
+    
+    let pattern = get_pattern(&args)?;
+    let use_color = args.color.enabled();
+    let mut report = ScanReport::default();
+    if let Some(path) = args.baseline.as_ref().filter(|_| !args.write_baseline) {
+        report.baseline = load_baseline(path)?;
+    }
+    
+    let engine = build_engine(&pattern, &args)?;
+    
+    if args.annotate {
+        return run_annotation(&engine, &args);
+    }
+    
+    // One CSV stream for the whole run, however many files are scanned
+    if args.format == "csv" && !args.no_header && !(args.quiet || args.count || args.count_matches || args.count_by.is_some() || args.files_with_matches) {
+        println!("{}", CSV_HEADER.join(","));
+    }
+    let selector = args.json_input.as_ref()
+        .map(|path| path.as_deref().map_or(Ok(JsonSelector::Default), JsonSelector::parse))
+        .transpose()?;
+    
+    if args.follow {
+        if args.files.is_empty() {
+            anyhow::bail!("--follow needs at least one file to watch");
+        }
+        if args.files.iter().any(|file| is_stdin(file)) {
+            anyhow::bail!("--follow watches files and cannot read stdin");
+        }
+        if args.format == "markdown" {
+            anyhow::bail!("--format markdown reports on a finished scan and cannot be used with --follow");
+        }
+        follow_files(&engine, &args, use_color, &mut report)?;
+    } else if args.files.is_empty() && args.files_from.is_none() {
+        let stdin = open_stdin(args.decompress)?;
+        let scanned = scan_input(stdin, &args.label, &engine, selector.as_ref(), &args)?;
+        process_input(scanned, &args.label, &args, use_color, &mut report)?;
+    } else {
+        scan_files(&engine, selector.as_ref(), &args, |file, scanned| {
+            match scanned {
+                Ok(scanned) => process_input(scanned, &args.input_name(file), &args, use_color, &mut report),
+                Err(e) => {
+                    if !args.no_messages {
+                        eprintln!("ai-grep: {}: {}", args.input_name(file), e);
+                    }
+                    report.errors += 1;
+                    Ok(())
                 }
             }
-        }
+        })?;
     }
     
-    Ok(matches)
+    finish(&args, report)
 }
 
-fn find_code_issue_matches(input: &str, args: &Args) -> Result<Vec<Match>> {
-    let patterns = [
-        (r"(?i)\b(todo|fixme|hack|bug|xxx|note|warn|warning)\b", "development_marker", Severity::Medium),
-        (r"(?i)\b(deprecated|obsolete|legacy|remove|delete)\b", "deprecation", Severity::Low),
-        (r"(?i)\b(temporary|temp|quick.?fix|workaround)\b", "temporary_code", Severity::Medium),
-        (r"(?i)\b(unsafe|danger|critical|urgent)\b", "safety_concern", Severity::High),
-        (r"(?i)//\s*(TODO|FIXME|HACK|BUG|XXX)", "comment_marker", Severity::Medium),
-        (r"#\s*(TODO|FIXME|HACK|BUG|XXX)", "comment_marker", Severity::Medium),
-    ];
+/// End-of-run output shared by every mode: summaries, baseline handling and the --fail-on exit status.
+fn finish(args: &Args, mut report: ScanReport) -> Result<()> {
+    if !args.quiet {
+        print_report(args, &mut report)?;
+    }
     
-    let mut matches = Vec::new();
-    
-    for (pattern_str, issue_type, severity) in &patterns {
-        let regex = Regex::new(pattern_str)?;
-        
-        for (line_num, line) in input.lines().enumerate() {
-            for mat in regex.find_iter(line) {
-                matches.push(Match {
-                    line_number: line_num + 1,
-                    content: line.to_string(),
-                    matched_text: mat.as_str().to_string(),
-                    start_pos: mat.start(),
-                    end_pos: mat.end(),
-                    anomaly_type: AnomalyType::CodeIssue {
-                        issue_type: issue_type.to_string(),
-                    },
-                    severity: severity.clone(),
-                });
-            }
+    if let Some(path) = args.baseline.as_ref().filter(|_| args.write_baseline) {
+        write_baseline(path, &report.baseline_entries)?;
+        if !args.no_messages {
+            eprintln!("ai-grep: wrote {} fingerprint(s) to {}", report.baseline_entries.len(), path.display());
         }
+    } else if report.suppressed > 0 && !args.no_messages {
+        eprintln!("ai-grep: {} known finding(s) suppressed by baseline", report.suppressed);
     }
     
-    Ok(matches)
-}
-
-fn find_security_matches(input: &str, args: &Args) -> Result<Vec<Match>> {
-    let patterns = [
-        (r"(?i)\b(password|passwd|pwd)\s*=\s*[\x22\x27][^\x22\x27]*[\x22\x27]", "hardcoded_password", Severity::Critical),
-        (r"(?i)\b(api.?key|secret.?key|private.?key)\s*[=:]\s*[\x22\x27][^\x22\x27]+[\x22\x27]", "hardcoded_secret", Severity::Critical),
-        (r"(?i)\b(eval|exec|system|shell_exec)\s*\(", "code_injection", Severity::High),
-        (r"(?i)\b(sql\s+injection|xss|csrf|rce|lfi|rfi)\b", "vulnerability_mention", Severity::High),
-        (r"(?i)\b(unsafe|memcpy|strcpy|gets|scanf)\b", "unsafe_function", Severity::High),
-        (r"(?i)\b(admin|root|administrator)\s*[/:]\s*\w+", "privileged_access", Severity::Medium),
-    ];
-    
-    let mut matches = Vec::new();
-    
-    for (pattern_str, vuln_type, severity) in &patterns {
-        let regex = Regex::new(pattern_str)?;
-        
-        for (line_num, line) in input.lines().enumerate() {
-            for mat in regex.find_iter(line) {
-                matches.push(Match {
-                    line_number: line_num + 1,
-                    content: line.to_string(),
-                    matched_text: mat.as_str().to_string(),
-                    start_pos: mat.start(),
-                    end_pos: mat.end(),
-                    anomaly_type: AnomalyType::Security {
-                        vulnerability_type: vuln_type.to_string(),
-                    },
-                    severity: severity.clone(),
-                });
-            }
+    if let Some(fail_on) = args.fail_on {
+        if report.per_severity.range(fail_on..).any(|(_, count)| *count > 0) {
+            std::process::exit(1);
+        }
+    }
+    if args.fail_on_score.is_some_and(|limit| report.max_score > limit) {
+        std::process::exit(1);
+    }
+    if args.severity_exit_codes {
+        if report.errors > 0 {
+            std::process::exit(2);
+        }
+        if let Some(&highest) = report.per_severity.keys().next_back() {
+            std::process::exit(severity_exit_code(highest));
         }
     }
     
-    Ok(matches)
+    Ok(())
 }
 
-fn find_data_leakage_matches(input: &str, args: &Args) -> Result<Vec<Match>> {
-    let patterns = [
-        (r"\b\d{3}-\d{2}-\d{4}\b", "ssn", Severity::Critical),
-        (r"\b\d{4}[\s-]?\d{4}[\s-]?\d{4}[\s-]?\d{4}\b", "credit_card", Severity::Critical),
-        (r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b", "email", Severity::Medium),
-        (r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b", "ip_address", Severity::Low),
-        (r"(?i)\b(training.?data|dataset|corpus|model.?weights)\b", "training_reference", Severity::Medium),
-        (r"(?i)\b(openai|anthropic|google|microsoft|meta)\s+(internal|confidential)", "internal_reference", Severity::High),
-    ];
-    
-    let mut matches = Vec::new();
-    
-    for (pattern_str, leak_type, severity) in &patterns {
-        let regex = Regex::new(pattern_str)?;
-        
-        for (line_num, line) in input.lines().enumerate() {
-            for mat in regex.find_iter(line) {
-                matches.push(Match {
-                    line_number: line_num + 1,
-                    content: line.to_string(),
-                    matched_text: mat.as_str().to_string(),
-                    start_pos: mat.start(),
-                    end_pos: mat.end(),
-                    anomaly_type: AnomalyType::DataLeakage {
-                        leak_type: leak_type.to_string(),
-                    },
-                    severity: severity.clone(),
-                });
-            }
-        }
+/// --severity-exit-codes status for a run whose most severe finding is `severity`.
+fn severity_exit_code(severity: Severity) -> i32 {
+    match severity {
+        Severity::Low => 10,
+        Severity::Medium => 11,
+        Severity::High => 12,
+        Severity::Critical => 13,
     }
-    
-    Ok(matches)
 }
 
-fn find_low_confidence_matches(input: &str, args: &Args) -> Result<Vec<Match>> {
-    let patterns = [
-        (r"(?i)\b(might|maybe|perhaps|possibly|potentially|could be)\b", "uncertainty", Severity::Low),
-        (r"(?i)\b(likely|probably|seems|appears|suggests)\b", "hedging", Severity::Low),
-        (r"(?i)\b(i think|i believe|i guess|i assume)\b", "opinion", Severity::Medium),
-        (r"(?i)\b(allegedly|supposedly|reportedly|apparently)\b", "hearsay", Severity::Medium),
-        (r"(?i)\b(unverified|unconfirmed|uncertain|unclear)\b", "verification_issue", Severity::High),
-    ];
+/// Output that needs every input scanned first: count totals, reports and summaries.
+fn print_report(args: &Args, report: &mut ScanReport) -> Result<()> {
+    if let Some(by) = args.count_by {
+        report.print_count_totals(by, args)?;
+    }
     
-    let mut matches = Vec::new();
-    
-    for (pattern_str, confidence_type, severity) in &patterns {
-        let regex = Regex::new(pattern_str)?;
-        
-        for (line_num, line) in input.lines().enumerate() {
-            for mat in regex.find_iter(line) {
-                matches.push(Match {
-                    line_number: line_num + 1,
-                    content: line.to_string(),
-                    matched_text: mat.as_str().to_string(),
-                    start_pos: mat.start(),
-                    end_pos: mat.end(),
-                    anomaly_type: AnomalyType::LowConfidence {
-                        confidence_marker: confidence_type.to_string(),
-                    },
-                    severity: severity.clone(),
-                });
-            }
-        }
+    if args.group_duplicates && args.format != "json" {
+        print_duplicates(&report.duplicate_groups(), args.format == "ndjson");
     }
     
-    Ok(matches)
+    if args.format == "markdown" {
+        report.print_markdown(args.report_width);
+    } else if args.group_duplicates && args.format == "json" {
+        let groups: Vec<serde_json::Value> = report.duplicate_groups().iter().map(DuplicateGroup::to_json).collect();
+        let mut output = serde_json::Map::new();
+        output.insert("group_count".to_string(), serde_json::Value::Number(serde_json::Number::from(groups.len())));
+        output.insert("duplicates".to_string(), serde_json::Value::Array(groups));
+        if args.stats {
+            output.insert("summary".to_string(), report.summary_json(args.top_files));
+        }
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else if args.score && args.format == "json" {
+        let mut output = serde_json::Map::new();
+        output.insert("files".to_string(), serde_json::Value::Array(std::mem::take(&mut report.score_rows)));
+        if args.stats {
+            output.insert("summary".to_string(), report.summary_json(args.top_files));
+        }
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else if combined_json(args) {
+        let mut output = serde_json::Map::new();
+        output.insert("files".to_string(), serde_json::Value::Array(std::mem::take(&mut report.files_json)));
+        if args.stats {
+            output.insert("summary".to_string(), report.summary_json(args.top_files));
+        }
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else if args.stats && args.format == "ndjson" {
+        println!("{}", serde_json::json!({ "summary": report.summary_json(args.top_files) }));
+    } else if args.stats {
+        report.print_summary(args.top_files, args.color.enabled());
+    }
+    Ok(())
 }
 
-fn display_matches(matches: &[Match], filename: &str, args: &Args, use_color: bool) -> Result<()> {
-    if args.format == "json" {
-        return output_json(matches, filename, args);
+/// JSON results from several inputs (or with a summary) are emitted as one document at the end.
+fn combined_json(args: &Args) -> bool {
+    args.format == "json" && !args.follow && args.count_by.is_none() && !args.score && !args.group_duplicates
+        && (args.files.len() > 1 || args.stats)
+}
+
+/// Roll-up of findings across every input scanned in one run.
+#[derive(Default)]
+struct ScanReport {
+    files_scanned: usize,
+    lines_scanned: usize,
+    total_matches: usize,
+    weighted_score: f64,
+    per_category: BTreeMap<String, usize>,
+    per_severity: BTreeMap<Severity, usize>,
+    per_file: Vec<(String, usize)>,
+    files_json: Vec<serde_json::Value>,
+    baseline: HashSet<String>,
+    baseline_entries: Vec<serde_json::Value>,
+    suppressed: usize,
+    /// Matches dropped by --skip-code-blocks and --skip-quotes
+    skipped: usize,
+    /// Findings per input, kept for the --format markdown report
+    markdown_files: Vec<(String, Vec<Match>)>,
+    /// --count-by breakdown of each input
+    count_rows: Vec<(String, Vec<(String, usize)>)>,
+    /// Binary inputs with matches, whose lines are not printed
+    binary_files: usize,
+    /// --score results of each input for --format json
+    score_rows: Vec<serde_json::Value>,
+    /// Highest --score of any input, checked by --fail-on-score
+    max_score: f64,
+    /// Inputs that could not be read, for --severity-exit-codes
+    errors: usize,
+    /// --group-duplicates clusters, keyed by category and folded matched text
+    duplicates: HashMap<(String, String), DuplicateGroup>,
+}
+
+impl ScanReport {
+    fn record(&mut self, filename: &str, matches: &[Match]) {
+        let index = self.add_file(filename);
+        self.tally(index, matches);
     }
     
-    for (i, m) in matches.iter().enumerate() {
-        let line_prefix = if args.line_number {
-            if args.files.len() > 1 {
-                format!("{}:{}:", filename, m.line_number)
-            } else {
-                format!("{}:", m.line_number)
-            }
-        } else if args.files.len() > 1 {
-            format!("{}:", filename)
-        } else {
-            String::new()
+    /// Register an input, returning its index for later `tally` calls.
+    fn add_file(&mut self, filename: &str) -> usize {
+        self.files_scanned += 1;
+        self.per_file.push((filename.to_string(), 0));
+        self.per_file.len() - 1
+    }
+    
+    /// Count findings against an input registered with `add_file`.
+    fn tally(&mut self, index: usize, matches: &[Match]) {
+        self.total_matches += matches.len();
+        self.per_file[index].1 += matches.len();
+        
+        for m in matches {
+            self.weighted_score += m.severity.to_score();
+            *self.per_category.entry(m.anomaly_type.category().to_string()).or_insert(0) += 1;
+            *self.per_severity.entry(m.severity).or_insert(0) += 1;
+        }
+    }
+    
+    /// Add an input's findings to the --group-duplicates clusters.
+    fn group(&mut self, filename: &str, matches: &[Match]) {
+        for m in matches {
+            let folded = m.matched_text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+            let key = (m.anomaly_type.category().to_string(), folded);
+            let location = (filename.to_string(), m.line_number, m.column());
+            let group = self.duplicates.entry(key).or_insert_with(|| DuplicateGroup {
+                anomaly_type: m.anomaly_type.clone(),
+                matched_text: m.matched_text.clone(),
+                severity: m.severity,
+                first: location.clone(),
+                locations: Vec::new(),
+            });
+            // The first location in sorted order speaks for the group, whatever order inputs arrive in
+            if location < group.first {
+                group.anomaly_type = m.anomaly_type.clone();
+                group.matched_text = m.matched_text.clone();
+                group.first = location.clone();
+            }
+            group.severity = group.severity.max(m.severity);
+            group.locations.push(location);
+        }
+    }
+    
+    /// The --group-duplicates clusters, most frequent first. Locations are sorted by file and
+    /// position, so the output doesn't depend on the order inputs were scanned in.
+    fn duplicate_groups(&mut self) -> Vec<DuplicateGroup> {
+        let mut groups: Vec<((String, String), DuplicateGroup)> = self.duplicates.drain().collect();
+        for (_, group) in &mut groups {
+            group.locations.sort();
+        }
+        groups.sort_by(|(a_key, a), (b_key, b)| {
+            b.locations.len().cmp(&a.locations.len())
+                .then(b.severity.cmp(&a.severity))
+                .then_with(|| a_key.cmp(b_key))
+        });
+        groups.into_iter().map(|(_, group)| group).collect()
+    }
+    
+    /// Mean severity score of all findings (0.0 when nothing matched).
+    fn risk_score(&self) -> f64 {
+        if self.total_matches == 0 {
+            0.0
+        } else {
+            self.weighted_score / self.total_matches as f64
+        }
+    }
+    
+    /// Total severity score per 100 lines scanned, so long and short inputs compare fairly.
+    fn score_per_100_lines(&self) -> f64 {
+        if self.lines_scanned == 0 {
+            0.0
+        } else {
+            self.weighted_score * 100.0 / self.lines_scanned as f64
+        }
+    }
+    
+    fn noisiest_files(&self, top: usize) -> Vec<&(String, usize)> {
+        let mut files: Vec<_> = self.per_file.iter().filter(|(_, count)| *count > 0).collect();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        files.truncate(top);
+        files
+    }
+    
+    fn print_summary(&self, top: usize, emoji: bool) {
+        let files_with_matches = self.per_file.iter().filter(|(_, count)| *count > 0).count();
+        
+        if self.total_matches > 0 {
+            println!();
+        }
+        println!("=== Summary ===");
+        println!("Files scanned: {} ({} with matches)", self.files_scanned, files_with_matches);
+        println!("Lines scanned: {}", self.lines_scanned);
+        println!("Total matches: {}", self.total_matches);
+        if self.skipped > 0 {
+            println!("Skipped in code blocks/quotes: {}", self.skipped);
+        }
+        println!("Weighted risk score: {:.2} (total {:.2}, {:.2} per 100 lines)",
+                 self.risk_score(), self.weighted_score, self.score_per_100_lines());
+        
+        if !self.per_category.is_empty() {
+            println!();
+            println!("By category:");
+            for (category, count) in &self.per_category {
+                println!("  {:<20} {}", category, count);
+            }
+        }
+        
+        if !self.per_severity.is_empty() {
+            println!();
+            println!("By severity:");
+            for (severity, count) in self.per_severity.iter().rev() {
+                if emoji {
+                    println!("  {} {:<17} {}", severity.to_emoji(), format!("{:?}", severity), count);
+                } else {
+                    println!("  {:<20} {}", severity.to_tag(), count);
+                }
+            }
+        }
+        
+        let noisiest = self.noisiest_files(top);
+        if !noisiest.is_empty() {
+            println!();
+            println!("Noisiest files:");
+            for (file, count) in noisiest {
+                println!("  {:<40} {}", file, count);
+            }
+        }
+    }
+    
+    /// Finish --count-by output: a totals row after several inputs, or the JSON document.
+    fn print_count_totals(&self, by: CountBy, args: &Args) -> Result<()> {
+        let mut totals: BTreeMap<String, usize> = BTreeMap::new();
+        for (key, count) in self.count_rows.iter().flat_map(|(_, counts)| counts) {
+            *totals.entry(key.clone()).or_insert(0) += count;
+        }
+        let mut totals: Vec<(String, usize)> = totals.into_iter().collect();
+        if matches!(by, CountBy::Severity) {
+            sort_by_severity(&mut totals);
+        }
+        
+        if args.format == "json" {
+            let counts_json = |counts: &[(String, usize)]| -> serde_json::Value {
+                serde_json::Value::Object(counts.iter().map(|(key, count)| (key.clone(), serde_json::json!(count))).collect())
+            };
+            let files: Vec<serde_json::Value> = self.count_rows.iter()
+                .map(|(file, counts)| serde_json::json!({ "file": file, "counts": counts_json(counts) }))
+                .collect();
+            let output = serde_json::json!({
+                "count_by": by.name(),
+                "files": files,
+                "totals": counts_json(&totals),
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        } else if self.count_rows.len() > 1 {
+            if matches!(by, CountBy::File) {
+                println!("total:{}", totals.iter().map(|(_, count)| count).sum::<usize>());
+            } else {
+                println!("total: {}", format_counts(&totals));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Print the whole scan as a Markdown report: an overview table, a table per file
+    /// with findings, and the risk score and command line that produced it.
+    fn print_markdown(&self, width: usize) {
+        let severities = [Severity::Critical, Severity::High, Severity::Medium, Severity::Low];
+        let files_with_matches = self.per_file.iter().filter(|(_, count)| *count > 0).count();
+        
+        println!("# ai-grep report");
+        println!();
+        println!("Scanned {} file(s), {} with matches, {} match(es) in total.",
+                 self.files_scanned, files_with_matches, self.total_matches);
+        if self.skipped > 0 {
+            println!("{} match(es) in code blocks or quotes were skipped.", self.skipped);
+        }
+        if self.binary_files > 0 {
+            println!("{} binary file(s) matched; their matches are counted but not listed.", self.binary_files);
+        }
+        println!();
+        
+        if self.total_matches > 0 {
+            let mut counts: BTreeMap<&str, [usize; 4]> = BTreeMap::new();
+            for m in self.markdown_files.iter().flat_map(|(_, matches)| matches) {
+                let column = severities.iter().position(|&s| s == m.severity).unwrap_or(0);
+                counts.entry(m.anomaly_type.category()).or_default()[column] += 1;
+            }
+            
+            let headers: Vec<&str> = severities.iter().map(|s| s.to_tag()).collect();
+            println!("| Category | {} | Total |", headers.join(" | "));
+            println!("|---|{}---:|", "---:|".repeat(severities.len()));
+            for (category, row) in &counts {
+                let cells: Vec<String> = row.iter().map(|count| count.to_string()).collect();
+                println!("| {} | {} | {} |", category, cells.join(" | "), row.iter().sum::<usize>());
+            }
+            // Totals of the rows above, which leave out binary files
+            let totals: Vec<usize> = (0..severities.len()).map(|i| counts.values().map(|row| row[i]).sum()).collect();
+            let cells: Vec<String> = totals.iter().map(|count| count.to_string()).collect();
+            println!("| **Total** | {} | {} |", cells.join(" | "), totals.iter().sum::<usize>());
+            println!();
+        }
+        
+        for (filename, matches) in self.markdown_files.iter().filter(|(_, matches)| !matches.is_empty()) {
+            println!("## {}", code_span(filename));
+            println!();
+            println!("| Line | Severity | Category | Match |");
+            println!("|---:|---|---|---|");
+            for m in matches {
+                let lines = match m.end_line {
+                    Some(end_line) if end_line > m.line_number => format!("{}-{}", m.line_number, end_line),
+                    _ => m.line_number.to_string(),
+                };
+                let line = match &m.field {
+                    Some(field) => format!("{}:{}:{}", field.record, field.pointer, lines),
+                    None => lines,
+                };
+                let category = match m.anomaly_type.subtype() {
+                    Some(subtype) => format!("{} / {}", m.anomaly_type.category(), subtype),
+                    None => m.anomaly_type.category().to_string(),
+                };
+                // Newlines would break the table row; a space keeps the byte offsets valid
+                let content = truncate_around(&m.content.replace('\n', " "), m.start_pos, m.end_pos, width);
+                println!("| {} | {} | {} | {} |",
+                 line.replace('|', "\\|"), m.severity.to_tag(), category,
+                 code_span(&content).replace('|', "\\|"));
+            }
+            println!();
+        }
+        
+        println!("**Risk score:** {:.2} (weighted total {:.2})", self.risk_score(), self.weighted_score);
+        println!();
+        let command: Vec<String> = std::env::args().map(|arg| shell_quote(&arg)).collect();
+        println!("```sh");
+        println!("{}", command.join(" "));
+        println!("```");
+    }
+    
+    fn summary_json(&self, top: usize) -> serde_json::Value {
+        let counts = |entries: Vec<(String, usize)>| -> serde_json::Value {
+            serde_json::Value::Object(entries.into_iter()
+                .map(|(key, count)| (key, serde_json::Value::Number(serde_json::Number::from(count))))
+                .collect())
+        };
+        
+        let mut summary = serde_json::Map::new();
+        summary.insert("files_scanned".to_string(), serde_json::Value::Number(serde_json::Number::from(self.files_scanned)));
+        summary.insert("total_matches".to_string(), serde_json::Value::Number(serde_json::Number::from(self.total_matches)));
+        summary.insert("skipped_matches".to_string(), serde_json::Value::Number(serde_json::Number::from(self.skipped)));
+        summary.insert("risk_score".to_string(), serde_json::json!(self.risk_score()));
+        summary.insert("weighted_score".to_string(), serde_json::json!(self.weighted_score));
+        summary.insert("lines_scanned".to_string(), serde_json::json!(self.lines_scanned));
+        summary.insert("score_per_100_lines".to_string(), serde_json::json!(self.score_per_100_lines()));
+        summary.insert("by_category".to_string(), counts(self.per_category.iter().map(|(k, v)| (k.clone(), *v)).collect()));
+        summary.insert("by_severity".to_string(), counts(self.per_severity.iter().map(|(k, v)| (format!("{:?}", k), *v)).collect()));
+        summary.insert("noisiest_files".to_string(), serde_json::Value::Array(self.noisiest_files(top).into_iter()
+            .map(|(file, count)| serde_json::json!({ "file": file, "match_count": count }))
+            .collect()));
+        serde_json::Value::Object(summary)
+    }
+}
+
+/// Locations listed per --group-duplicates cluster in text output before "+N more".
+const GROUP_LOCATIONS: usize = 5;
+
+/// A distinct finding for --group-duplicates: the type and text of its `first` occurrence by
+/// file and position, the highest severity, and the file, line and column of every occurrence.
+struct DuplicateGroup {
+    anomaly_type: AnomalyType,
+    matched_text: String,
+    severity: Severity,
+    first: (String, usize, usize),
+    locations: Vec<(String, usize, usize)>,
+}
+
+impl DuplicateGroup {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "anomaly": self.anomaly_type,
+            "matched_text": self.matched_text,
+            "severity": format!("{:?}", self.severity),
+            "count": self.locations.len(),
+            "locations": self.locations.iter()
+                .map(|(file, line_number, column)| serde_json::json!({ "file": file, "line_number": line_number, "column": column }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Print --group-duplicates clusters as text, one per paragraph, or as NDJSON.
+fn print_duplicates(groups: &[DuplicateGroup], ndjson: bool) {
+    for (i, group) in groups.iter().enumerate() {
+        if ndjson {
+            println!("{}", group.to_json());
+            continue;
+        }
+        if i > 0 {
+            println!();
+        }
+        let count = group.locations.len();
+        println!("[{}] {} ({} occurrence{})",
+                 finding_label(group.severity, &group.anomaly_type),
+                 group.matched_text.replace('\n', "\\n"),
+                 count,
+                 if count == 1 { "" } else { "s" });
+        let mut locations: Vec<String> = group.locations.iter()
+            .take(GROUP_LOCATIONS)
+            .map(|(file, line_number, _)| format!("{}:{}", file, line_number))
+            .collect();
+        if count > GROUP_LOCATIONS {
+            locations.push(format!("+{} more", count - GROUP_LOCATIONS));
+        }
+        println!("  {}", locations.join(", "));
+    }
+}
+
+/// Weights of the --score components. Only their ratios matter.
+#[derive(Debug, Clone, Copy)]
+struct ScoreWeights {
+    severity: f64,
+    density: f64,
+    diversity: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        ScoreWeights { severity: 0.6, density: 0.25, diversity: 0.15 }
+    }
+}
+
+impl ScoreWeights {
+    /// Parse `name=weight` pairs separated by commas; names left out keep their default.
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut weights = ScoreWeights::default();
+        for entry in spec.split(',').filter(|entry| !entry.is_empty()) {
+            let (name, value) = entry.split_once('=')
+                .ok_or_else(|| format!("expected NAME=WEIGHT, got '{}'", entry))?;
+            let value: f64 = value.parse()
+                .ok().filter(|value: &f64| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("weight '{}' is not a non-negative number", value))?;
+            match name {
+                "severity" => weights.severity = value,
+                "density" => weights.density = value,
+                "diversity" => weights.diversity = value,
+                _ => return Err(format!("unknown score component '{}' (expected severity, density or diversity)", name)),
+            }
+        }
+        if weights.severity + weights.density + weights.diversity == 0.0 {
+            return Err("at least one score weight must be positive".to_string());
+        }
+        Ok(weights)
+    }
+}
+
+/// Weighted severity at which the --score severity component reaches 63%.
+const SCORE_SEVERITY_SCALE: f64 = 5.0;
+/// Matches per KB at which the density component reaches 63%.
+const SCORE_DENSITY_SCALE: f64 = 1.0;
+/// Distinct categories at which the diversity component reaches 63%.
+const SCORE_DIVERSITY_SCALE: f64 = 2.0;
+
+/// One input's --score: three components in 0..1, each `1 - e^(-x / scale)` of a raw
+/// measure, averaged with the --score-weights and scaled to 0-100.
+struct FileScore {
+    per_severity: BTreeMap<Severity, usize>,
+    /// Sum of `Severity::to_score` over the matches
+    weighted: f64,
+    /// Matches per KB of input; inputs under 1 KB count as 1 KB
+    density: f64,
+    categories: usize,
+    components: [f64; 3],
+    score: f64,
+}
+
+impl FileScore {
+    fn new(matches: &[Match], bytes: u64, weights: ScoreWeights) -> Self {
+        let mut per_severity = BTreeMap::new();
+        for m in matches {
+            *per_severity.entry(m.severity).or_insert(0) += 1;
+        }
+        let weighted: f64 = matches.iter().map(|m| m.severity.to_score()).sum();
+        let density = matches.len() as f64 / (bytes as f64 / 1024.0).max(1.0);
+        let categories = matches.iter().map(|m| m.anomaly_type.category()).collect::<HashSet<_>>().len();
+        
+        let saturate = |value: f64, scale: f64| 1.0 - (-value / scale).exp();
+        let components = [
+            saturate(weighted, SCORE_SEVERITY_SCALE),
+            saturate(density, SCORE_DENSITY_SCALE),
+            saturate(categories as f64, SCORE_DIVERSITY_SCALE),
+        ];
+        let total_weight = weights.severity + weights.density + weights.diversity;
+        let score = 100.0 * (weights.severity * components[0] + weights.density * components[1]
+            + weights.diversity * components[2]) / total_weight;
+        
+        FileScore { per_severity, weighted, density, categories, components, score }
+    }
+    
+    fn to_json(&self, filename: &str) -> serde_json::Value {
+        let by_severity: serde_json::Map<String, serde_json::Value> = self.per_severity.iter()
+            .map(|(severity, count)| (format!("{:?}", severity), serde_json::json!(count)))
+            .collect();
+        serde_json::json!({
+            "file": filename,
+            "score": self.score,
+            "by_severity": by_severity,
+            "weighted_severity": self.weighted,
+            "density_per_kb": self.density,
+            "categories": self.categories,
+            "components": {
+                "severity": self.components[0],
+                "density": self.components[1],
+                "diversity": self.components[2],
+            },
+        })
+    }
+}
+
+/// Wrap text in a Markdown code span, using a longer backtick fence than any run inside it.
+fn code_span(text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run + 1);
+    // A space keeps backticks at either end from merging with the fence
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
+}
+
+/// Shorten `content` to `width` characters, keeping the match at byte range `start..end`
+/// in view and marking each cut with an ellipsis.
+fn truncate_around(content: &str, start: usize, end: usize, width: usize) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    if chars.len() <= width {
+        return content.to_string();
+    }
+    
+    let match_start = content[..start].chars().count();
+    let match_len = content[start..end].chars().count();
+    // Show some leading context, unless the match fits from the start of the line
+    let mut begin = if match_start + match_len < width {
+        0
+    } else {
+        match_start.saturating_sub(width / 4).min(chars.len() - width)
+    };
+    
+    let mut budget = width;
+    let mut shortened = String::new();
+    if begin > 0 {
+        shortened.push('…');
+        budget -= 1;
+        begin += 1;
+    }
+    if chars.len() - begin > budget {
+        shortened.extend(&chars[begin..begin + budget - 1]);
+        shortened.push('…');
+    } else {
+        shortened.extend(&chars[begin..]);
+    }
+    shortened
+}
+
+/// Quote a command-line argument for display if a POSIX shell would split or expand it.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Paths listed for --files-from, separated by newlines or, with -0, NUL bytes.
+fn read_file_list(list: &Path, null: bool) -> Result<Vec<PathBuf>> {
+    let bytes = if list == Path::new("-") {
+        let mut buffer = Vec::new();
+        io::stdin().read_to_end(&mut buffer)?;
+        buffer
+    } else {
+        fs::read(list).map_err(|e| anyhow::anyhow!("{}: {}", list.display(), e))?
+    };
+    
+    let separator = if null { b'\0' } else { b'\n' };
+    Ok(bytes.split(|&b| b == separator)
+        .filter(|path| !path.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn get_pattern(args: &Args) -> Result<AnomalyPattern> {
+    let enabled = [
+        (args.hallucinations, Category::Hallucinations),
+        (args.code_issues, Category::CodeIssues),
+        (args.security, Category::Security),
+        (args.data_leakage, Category::DataLeakage),
+        (args.low_confidence, Category::LowConfidence),
+        (args.secrets, Category::Secrets),
+        (args.prompt_injection, Category::PromptInjection),
+    ];
+    let presets: Categories = enabled.into_iter()
+        .filter_map(|(on, preset)| on.then_some(preset))
+        .collect();
+    
+    if (!presets.is_empty() || args.memorization_check.is_some()) && args.uses_pattern_flags() {
+        anyhow::bail!("-e and --file patterns cannot be combined with preset flags or --memorization-check");
+    }
+    
+    // --memorization-check needs no regex rules of its own
+    if !presets.is_empty() || args.memorization_check.is_some() {
+        Ok(AnomalyPattern::Presets(presets))
+    } else if args.uses_pattern_flags() {
+        let mut patterns = args.regexps.clone();
+        for file in &args.pattern_files {
+            let list = fs::read_to_string(file)
+                .with_context(|| format!("reading patterns from {}", file.display()))?;
+            patterns.extend(list.lines().map(|line| line.trim_end_matches('\r')).filter(|line| !line.is_empty()).map(String::from));
+        }
+        if patterns.is_empty() {
+            anyhow::bail!("No pattern specified: the --file pattern files are empty");
+        }
+        Ok(AnomalyPattern::Custom(patterns))
+    } else if let Some(pattern) = &args.pattern {
+        Ok(AnomalyPattern::Custom(vec![pattern.clone()]))
+    } else {
+        anyhow::bail!("No pattern specified. Use a custom pattern or one of the preset flags (--hallucinations, --code-issues, etc.)")
+    }
+}
+
+fn parse_severity_override(spec: &str) -> Result<(String, Severity)> {
+    let (category, level) = spec.split_once('=').ok_or_else(|| {
+        anyhow::anyhow!("expected CATEGORY=LEVEL, got '{}'", spec)
+    })?;
+    let category = category.trim().replace('-', "_");
+    let names = severity_override_names();
+    if !names.contains(&category.as_str()) {
+        bail!("unknown category or type '{}'; expected one of: {}", category, names.join(", "));
+    }
+    Ok((category, level.trim().parse()?))
+}
+
+
+/// Print every preset rule with the regex that will actually run, given -i,
+/// --case-sensitive-presets and --multiline.
+fn list_preset_patterns(args: &Args) -> Result<()> {
+    let engine = Scanner::with_options(Categories::all(), args.scan_options())?;
+    let rules = engine.rules();
+    
+    if args.format == "json" {
+        let mut categories = serde_json::Map::new();
+        for rule in rules {
+            let entry = serde_json::json!({
+                "pattern": rule.regex.as_str(),
+                "subtype": rule.subtype,
+                "severity": format!("{:?}", rule.severity),
+                "check": rule.check.name(),
+            });
+            if let serde_json::Value::Array(entries) = categories.entry(rule.anomaly_type().category())
+                .or_insert_with(|| serde_json::Value::Array(Vec::new()))
+            {
+                entries.push(entry);
+            }
+        }
+        println!("{}", serde_json::to_string_pretty(&categories)?);
+        return Ok(());
+    }
+    
+    println!("Available preset patterns:");
+    for preset in Category::ALL {
+        println!();
+        println!("  {:<20}{}", format!("--{}", preset.name()), preset.description());
+        for rule in rules.iter().filter(|rule| rule.category == Some(preset)) {
+            let check = rule.check.name().map_or(String::new(), |name| format!("  [{} check]", name));
+            println!("    {:<24} {:<9} {}{}", rule.subtype, format!("{:?}", rule.severity), rule.regex.as_str(), check);
+        }
+    }
+    println!();
+    println!("Preset flags can be combined, e.g. --security --secrets");
+    println!();
+    println!("Examples:");
+    println!("  ai-grep --hallucinations < ai_output.txt");
+    println!("  ai-grep --code-issues --count src/**/*.rs");
+    println!("  ai-grep --security --color=always *.py");
+    println!("  ai-grep \"custom pattern\" file.txt");
+    Ok(())
+}
+
+#[derive(Debug)]
+enum AnomalyPattern {
+    Presets(Categories),
+    /// One or more patterns; a line matching any of them matches
+    Custom(Vec<String>),
+}
+
+/// Scan `args.files` on a pool of worker threads, handing each file's findings to `emit`
+/// on the calling thread. Files are emitted in argument order unless --unordered is given,
+/// in which case they are emitted as soon as they finish.
+fn scan_files<F>(engine: &Scanner, selector: Option<&JsonSelector>, args: &Args, mut emit: F) -> Result<()>
+where
+    F: FnMut(&PathBuf, io::Result<InputScan>) -> Result<()>,
+{
+    let threads = args.threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, args.files.len().max(1));
+    let next_file = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let next_file = &next_file;
+            scope.spawn(move || loop {
+                let index = next_file.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = args.files.get(index) else { break };
+                let scanned = open_path(file, args.decompress)
+                    .and_then(|reader| scan_input(reader, &args.input_name(file), engine, selector, args));
+                if sender.send((index, scanned)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        
+        // Results that finished ahead of an earlier file wait here until their turn
+        let mut pending = BTreeMap::new();
+        let mut next_to_emit = 0;
+        for (index, scanned) in receiver {
+            if args.unordered {
+                emit(&args.files[index], scanned)?;
+                continue;
+            }
+            pending.insert(index, scanned);
+            while let Some(scanned) = pending.remove(&next_to_emit) {
+                emit(&args.files[next_to_emit], scanned)?;
+                next_to_emit += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// How much of an input is checked for NUL bytes by --binary-files.
+const BINARY_PROBE_BYTES: u64 = 8192;
+
+/// Find matches in one input, either as plain text or field by field for --json-input.
+/// Plain-text inputs are streamed line by line; --json-input needs the whole document.
+fn scan_input(
+    mut reader: impl BufRead,
+    filename: &str,
+    engine: &Scanner,
+    selector: Option<&JsonSelector>,
+    args: &Args,
+) -> io::Result<InputScan> {
+    let Some(selector) = selector else {
+        // Like grep, a NUL byte near the start marks a file as binary
+        let mut head = Vec::new();
+        reader.by_ref().take(BINARY_PROBE_BYTES).read_to_end(&mut head)?;
+        let binary = !matches!(args.binary_files, BinaryFiles::Text) && head.contains(&0);
+        if binary && matches!(args.binary_files, BinaryFiles::WithoutMatch) {
+            return Ok(InputScan::default());
+        }
+        
+        let mut scan = find_matches(head.as_slice().chain(reader), engine, args)?;
+        scan.binary = binary;
+        return Ok(scan);
+    };
+    
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let input = String::from_utf8_lossy(&bytes);
+    
+    let mut scan = InputScan { bytes: bytes.len() as u64, ..InputScan::default() };
+    for (record, document) in json_records(&input, filename, args) {
+        for (pointer, text) in selector.extract(&document) {
+            let field_scan = find_matches(text.as_bytes(), engine, args)?;
+            scan.skipped += field_scan.skipped;
+            scan.lines += field_scan.lines;
+            scan.matches.extend(field_scan.matches.into_iter().map(|mut m| {
+                m.field = Some(JsonField { record, pointer: pointer.clone() });
+                m
+            }));
+        }
+    }
+    Ok(scan)
+}
+
+/// Open an input file, transparently decompressing gzip and zstd according to `mode`.
+/// Decompression is streamed, so large archives are never expanded in memory.
+fn open_input(path: &Path, mode: DecompressMode) -> io::Result<Box<dyn BufRead>> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    let by_extension = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Some(Compression::Gzip),
+        Some("zst") => Some(Compression::Zstd),
+        _ => None,
+    };
+    
+    match mode {
+        DecompressMode::Never => Ok(Box::new(reader)),
+        DecompressMode::Ext => decompress(reader, by_extension),
+        DecompressMode::Auto => decompress_detected(reader, by_extension),
+    }
+}
+
+fn open_stdin(mode: DecompressMode) -> io::Result<Box<dyn BufRead>> {
+    let stdin = io::stdin().lock();
+    match mode {
+        DecompressMode::Auto => decompress_detected(stdin, None),
+        _ => Ok(Box::new(stdin)),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+/// Pick the compression from the leading magic bytes, falling back to `fallback`.
+fn decompress_detected(mut reader: impl BufRead + 'static, fallback: Option<Compression>) -> io::Result<Box<dyn BufRead>> {
+    let head = reader.fill_buf()?;
+    let detected = if head.starts_with(&[0x1f, 0x8b]) {
+        Some(Compression::Gzip)
+    } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else {
+        fallback
+    };
+    decompress(reader, detected)
+}
+
+fn decompress(reader: impl BufRead + 'static, compression: Option<Compression>) -> io::Result<Box<dyn BufRead>> {
+    Ok(match compression {
+        // Rotated logs are often several gzip members concatenated
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+/// How long --follow waits before polling files that had nothing new.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Set by the Ctrl-C handler so --follow can stop between polls and print its summary.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Watch every file for appended lines until interrupted, reporting findings as they appear.
+fn follow_files(engine: &Scanner, args: &Args, use_color: bool, report: &mut ScanReport) -> Result<()> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))?;
+    
+    let mut files: Vec<FollowedFile> = args.files.iter()
+        .map(|path| FollowedFile::new(path, report.add_file(&path.to_string_lossy())))
+        .collect();
+    for file in &mut files {
+        if let Err(e) = file.open_at_end() {
+            file.report_error(&e, args);
+        }
+    }
+    
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        let mut idle = true;
+        
+        for file in &mut files {
+            let scan = match file.poll(engine, args) {
+                Ok(Some(scan)) => scan,
+                Ok(None) => continue,
+                Err(e) => {
+                    file.report_error(&e, args);
+                    continue;
+                }
+            };
+            idle = false;
+            
+            let mut matches = scan.matches;
+            report.skipped += scan.skipped;
+            report.lines_scanned += scan.lines;
+            apply_baseline(&mut matches, &file.name, args, report);
+            report.tally(file.report_index, &matches);
+            if !matches.is_empty() && !args.quiet {
+                display_matches(&matches, &file.name, args, use_color)?;
+            }
+            
+            if args.exit_on_match && args.fail_on.is_some_and(|fail_on| matches.iter().any(|m| m.severity >= fail_on)) {
+                return Ok(());
+            }
+        }
+        
+        if idle {
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+        }
+    }
+    
+    Ok(())
+}
+
+/// A file watched by --follow. Truncation rewinds to the start, and when the path is
+/// rotated to a new file the old one is read to its end before the new one is opened.
+struct FollowedFile {
+    path: PathBuf,
+    name: String,
+    report_index: usize,
+    reader: Option<BufReader<fs::File>>,
+    identity: Option<(u64, u64)>,
+    /// Bytes consumed from the open file, including an incomplete last line
+    position: u64,
+    /// A line still being written
+    partial: Vec<u8>,
+    scanner: LineScanner,
+    /// Whether the last error was already shown, so a missing file is not reported every poll
+    failed: bool,
+}
+
+impl FollowedFile {
+    fn new(path: &Path, report_index: usize) -> Self {
+        FollowedFile {
+            path: path.to_path_buf(),
+            name: path.to_string_lossy().into_owned(),
+            report_index,
+            reader: None,
+            identity: None,
+            position: 0,
+            partial: Vec::new(),
+            scanner: LineScanner::default(),
+            failed: false,
+        }
+    }
+    
+    /// Open the file and skip its current contents; only lines appended later are scanned.
+    fn open_at_end(&mut self) -> io::Result<()> {
+        self.open()?;
+        self.read_lines(|scanner, line| scanner.skip(line))?;
+        Ok(())
+    }
+    
+    fn open(&mut self) -> io::Result<()> {
+        let file = fs::File::open(&self.path)?;
+        self.identity = file_identity(&file.metadata()?);
+        self.reader = Some(BufReader::new(file));
+        self.rewind();
+        Ok(())
+    }
+    
+    fn rewind(&mut self) {
+        self.position = 0;
+        self.partial.clear();
+        self.scanner = LineScanner::default();
+    }
+    
+    /// Scan the complete lines appended since the last poll, or `None` if there were none.
+    fn poll(&mut self, engine: &Scanner, args: &Args) -> io::Result<Option<InputScan>> {
+        let mut scan = InputScan::default();
+        let mut lines = 0;
+        let mut scan_line = |scanner: &mut LineScanner, line: &[u8]| {
+            lines += 1;
+            scanner.scan(line, engine, args, &mut scan);
+        };
+        
+        if self.reader.is_none() {
+            // A file that does not exist yet (or mid-rotation) is scanned from its start once it appears
+            match self.open() {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+        
+        if let Some(reader) = self.reader.as_mut() {
+            if reader.get_ref().metadata()?.len() < self.position {
+                reader.seek(SeekFrom::Start(0))?;
+                self.rewind();
+            }
+        }
+        self.read_lines(&mut scan_line)?;
+        
+        // The old handle has been drained, so switch to whatever the path names now
+        let current = fs::metadata(&self.path).ok().and_then(|meta| file_identity(&meta));
+        if current != self.identity {
+            self.reader = None;
+            if current.is_some() {
+                self.open()?;
+                self.read_lines(&mut scan_line)?;
+            }
+        }
+        
+        self.failed = false;
+        scan.lines = lines;
+        Ok((lines > 0).then_some(scan))
+    }
+    
+    /// Hand each complete line read from the open file to `each`, keeping any incomplete
+    /// last line for the next call.
+    fn read_lines(&mut self, mut each: impl FnMut(&mut LineScanner, &[u8])) -> io::Result<()> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(());
+        };
+        
+        loop {
+            let read = reader.read_until(b'\n', &mut self.partial)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.position += read as u64;
+            
+            if self.partial.ends_with(b"\n") {
+                each(&mut self.scanner, &self.partial);
+                self.partial.clear();
+            }
+        }
+    }
+    
+    fn report_error(&mut self, e: &io::Error, args: &Args) {
+        if !self.failed && !args.no_messages {
+            eprintln!("ai-grep: {}: {}", self.name, e);
+        }
+        self.failed = true;
+    }
+}
+
+/// Device and inode of a file, used to notice when a followed path is rotated.
+#[cfg(unix)]
+fn file_identity(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Parse an input as a single JSON document, falling back to one record per line (JSONL).
+/// Records that fail to parse are reported and skipped.
+fn json_records(input: &str, filename: &str, args: &Args) -> Vec<(usize, serde_json::Value)> {
+    let document_error = match serde_json::from_str(input) {
+        Ok(document) => return vec![(0, document)],
+        Err(e) => e,
+    };
+    
+    let lines: Vec<&str> = input.lines().filter(|line| !line.trim().is_empty()).collect();
+    // A malformed pretty-printed document is one error, not one per line
+    let is_jsonl = lines.first().is_some_and(|line| serde_json::from_str::<serde_json::Value>(line).is_ok());
+    if !is_jsonl {
+        if !args.no_messages {
+            eprintln!("ai-grep: {}: invalid JSON: {}", filename, document_error);
+        }
+        return Vec::new();
+    }
+    
+    lines.into_iter()
+        .enumerate()
+        .filter_map(|(record, line)| match serde_json::from_str(line) {
+            Ok(document) => Some((record, document)),
+            Err(e) => {
+                if !args.no_messages {
+                    eprintln!("ai-grep: {}: record {}: invalid JSON: {}", filename, record, e);
+                }
+                None
+            }
+        })
+        .collect()
+}
+
+/// Which strings of a JSON record --json-input scans.
+#[derive(Debug)]
+enum JsonSelector {
+    /// Every string field named `content` or `text`, which covers
+    /// `choices[*].message.content` and most other response formats
+    Default,
+    Path(Vec<PathSegment>),
+}
+
+/// One step of a --json-input path.
+#[derive(Debug)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+    /// `*` or `[*]`: every element of an array or value of an object
+    Any,
+}
+
+impl JsonSelector {
+    /// Parse a dotted path such as `choices[*].message.content` or `$.data.*.text`.
+    fn parse(path: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("invalid JSON path '{}'", path);
+        let mut segments = Vec::new();
+        
+        for part in path.strip_prefix('$').unwrap_or(path).split('.').filter(|part| !part.is_empty()) {
+            let (name, mut brackets) = part.split_at(part.find('[').unwrap_or(part.len()));
+            match name {
+                "" => {}
+                "*" => segments.push(PathSegment::Any),
+                _ => segments.push(PathSegment::Key(name.to_string())),
+            }
+            while !brackets.is_empty() {
+                let index = brackets.strip_prefix('[')
+                    .and_then(|rest| rest.split_once(']'))
+                    .ok_or_else(invalid)?;
+                segments.push(match index.0 {
+                    "*" => PathSegment::Any,
+                    n => PathSegment::Index(n.parse().map_err(|_| invalid())?),
+                });
+                brackets = index.1;
+            }
+        }
+        
+        Ok(JsonSelector::Path(segments))
+    }
+    
+    /// Every selected string in `document`, with its JSON pointer, in document order.
+    fn extract<'a>(&self, document: &'a serde_json::Value) -> Vec<(String, &'a str)> {
+        let mut fields = Vec::new();
+        match self {
+            JsonSelector::Default => collect_text_fields(document, String::new(), &mut fields),
+            JsonSelector::Path(segments) => select_path(document, segments, String::new(), &mut fields),
+        }
+        fields
+    }
+}
+
+fn collect_text_fields<'a>(value: &'a serde_json::Value, pointer: String, fields: &mut Vec<(String, &'a str)>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map {
+                let child_pointer = format!("{}/{}", pointer, escape_pointer(key));
+                match child {
+                    serde_json::Value::String(text) if key == "content" || key == "text" => {
+                        fields.push((child_pointer, text));
+                    }
+                    _ => collect_text_fields(child, child_pointer, fields),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                collect_text_fields(child, format!("{}/{}", pointer, i), fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn select_path<'a>(value: &'a serde_json::Value, segments: &[PathSegment], pointer: String, fields: &mut Vec<(String, &'a str)>) {
+    let Some((segment, rest)) = segments.split_first() else {
+        if let serde_json::Value::String(text) = value {
+            fields.push((pointer, text));
+        }
+        return;
+    };
+    
+    match (segment, value) {
+        (PathSegment::Key(key), serde_json::Value::Object(map)) => {
+            if let Some(child) = map.get(key) {
+                select_path(child, rest, format!("{}/{}", pointer, escape_pointer(key)), fields);
+            }
+        }
+        (PathSegment::Index(i), serde_json::Value::Array(items)) => {
+            if let Some(child) = items.get(*i) {
+                select_path(child, rest, format!("{}/{}", pointer, i), fields);
+            }
+        }
+        (PathSegment::Any, serde_json::Value::Array(items)) => {
+            for (i, child) in items.iter().enumerate() {
+                select_path(child, rest, format!("{}/{}", pointer, i), fields);
+            }
+        }
+        (PathSegment::Any, serde_json::Value::Object(map)) => {
+            for (key, child) in map {
+                select_path(child, rest, format!("{}/{}", pointer, escape_pointer(key)), fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Escape a key for use in an RFC 6901 JSON pointer.
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn process_input(
+    scanned: InputScan,
+    filename: &str,
+    args: &Args,
+    use_color: bool,
+    report: &mut ScanReport,
+) -> Result<()> {
+    let mut matches = scanned.matches;
+    let paragraphs = scanned.paragraphs;
+    report.skipped += scanned.skipped;
+    report.lines_scanned += scanned.lines;
+    apply_baseline(&mut matches, filename, args, report);
+    let binary = scanned.binary;
+    let bytes = scanned.bytes;
+    
+    report.record(filename, &matches);
+    
+    if args.quiet {
+        // Nothing is printed, but --fail-on-score still needs the score
+        if args.score {
+            let score = FileScore::new(&matches, bytes, args.score_weights.unwrap_or_default());
+            report.max_score = report.max_score.max(score.score);
+        }
+        return Ok(());
+    }
+    
+    if args.files_with_matches {
+        if !matches.is_empty() {
+            println!("{}", filename);
+        }
+        return Ok(());
+    }
+    
+    if let Some(by) = args.count_by {
+        let counts = by.breakdown(&matches, filename, args);
+        if args.format != "json" {
+            if args.with_filename() && !matches!(by, CountBy::File) {
+                println!("{}: {}", filename, format_counts(&counts));
+            } else {
+                println!("{}", format_counts(&counts));
+            }
+        }
+        report.count_rows.push((filename.to_string(), counts));
+        return Ok(());
+    }
+    
+    if args.score {
+        let score = FileScore::new(&matches, bytes, args.score_weights.unwrap_or_default());
+        report.max_score = report.max_score.max(score.score);
+        match args.format.as_str() {
+            "json" => report.score_rows.push(score.to_json(filename)),
+            "ndjson" => println!("{}", score.to_json(filename)),
+            _ => println!("{}\t{:.1}", filename, score.score),
+        }
+        return Ok(());
+    }
+    
+    if args.count || args.count_matches {
+        let count = count_findings(&matches, args, |_| String::new()).first().map_or(0, |(_, count)| *count);
+        
+        if args.with_filename() {
+            println!("{}:{}", filename, count);
+        } else {
+            println!("{}", count);
+        }
+        return Ok(());
+    }
+    
+    // Lines from binary files would only print garbage; their matches are still counted
+    if binary && !matches.is_empty() {
+        report.binary_files += 1;
+        if args.format == "text" {
+            println!("Binary file {} matches", filename);
+        } else if !args.no_messages {
+            eprintln!("ai-grep: {}: binary file matches", filename);
+        }
+        return Ok(());
+    }
+    
+    if args.group_duplicates {
+        report.group(filename, &matches);
+        return Ok(());
+    }
+    
+    if args.density {
+        let dense = dense_paragraphs(&matches, &paragraphs, args.density_threshold);
+        if combined_json(args) {
+            report.files_json.push(paragraphs_to_json(&dense, filename));
+        } else {
+            display_paragraphs(&dense, filename, args, use_color)?;
+        }
+        return Ok(());
+    }
+    
+    if combined_json(args) {
+        report.files_json.push(matches_to_json(&matches, filename, args));
+        return Ok(());
+    }
+    
+    if args.format == "markdown" {
+        report.markdown_files.push((filename.to_string(), matches));
+        return Ok(());
+    }
+    
+    display_matches(&matches, filename, args, use_color)?;
+    Ok(())
+}
+
+/// A paragraph reported by --density, with the hedges found in it.
+struct DenseParagraph<'a> {
+    paragraph: &'a Paragraph,
+    hedges: Vec<&'a Match>,
+    /// Hedges per 100 words
+    density: f64,
+}
+
+/// The paragraphs whose hedge density is above `threshold`, in input order. `matches` must
+/// be in line order, as the scan leaves them.
+fn dense_paragraphs<'a>(matches: &'a [Match], paragraphs: &'a [Paragraph], threshold: f64) -> Vec<DenseParagraph<'a>> {
+    let mut rest = matches;
+    paragraphs.iter()
+        .filter_map(|paragraph| {
+            let before = rest.partition_point(|m| m.line_number < paragraph.start_line);
+            let inside = rest[before..].partition_point(|m| m.line_number <= paragraph.end_line);
+            let hedges: Vec<&Match> = rest[before..before + inside].iter().collect();
+            rest = &rest[before + inside..];
+            
+            let density = hedges.len() as f64 * 100.0 / paragraph.words as f64;
+            (density > threshold).then_some(DenseParagraph { paragraph, hedges, density })
+        })
+        .collect()
+}
+
+/// The distinct hedges of a paragraph in order of first use, compared case-insensitively,
+/// with how often each occurs.
+fn hedge_counts(hedges: &[&Match]) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for m in hedges {
+        match counts.iter_mut().find(|(text, _)| text.eq_ignore_ascii_case(&m.matched_text)) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((m.matched_text.clone(), 1)),
+        }
+    }
+    counts
+}
+
+fn display_paragraphs(dense: &[DenseParagraph], filename: &str, args: &Args, use_color: bool) -> Result<()> {
+    match args.format.as_str() {
+        "json" => {
+            println!("{}", serde_json::to_string_pretty(&paragraphs_to_json(dense, filename))?);
+            return Ok(());
+        }
+        "ndjson" => {
+            for paragraph in dense {
+                let mut record = paragraph_to_json(paragraph);
+                if let serde_json::Value::Object(obj) = &mut record {
+                    obj.insert("file".to_string(), serde_json::Value::String(filename.to_string()));
+                }
+                println!("{}", record);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    
+    let colors = use_color.then(|| args.colors.clone().unwrap_or_default());
+    for (i, flagged) in dense.iter().enumerate() {
+        let paragraph = flagged.paragraph;
+        let lines = if paragraph.start_line == paragraph.end_line {
+            paragraph.start_line.to_string()
+        } else {
+            format!("{}-{}", paragraph.start_line, paragraph.end_line)
         };
+        let mut prefix = paint(colors.as_ref().map(|c| c.line_no.as_str()), &lines) + ":";
+        if args.with_filename() {
+            prefix = format!("{}:{}", paint(colors.as_ref().map(|c| c.file.as_str()), filename), prefix);
+        }
+        println!("{} {:.1} hedges/100 words: {}", prefix, flagged.density, paragraph.first_line);
+        
+        let markers: Vec<String> = hedge_counts(&flagged.hedges).into_iter()
+            .map(|(text, count)| if count > 1 { format!("{} ({})", text, count) } else { text })
+            .collect();
+        let severity = flagged.hedges.iter().map(|m| m.severity).max().unwrap_or(Severity::Low);
+        let severity_color = colors.as_ref().map(|c| c.severity(severity));
+        let hedges = with_icon(colors.is_some(), "📉", &format!("Hedges: {}", markers.join(", ")));
+        println!("  {}", paint(severity_color, &hedges));
+        if i + 1 < dense.len() {
+            println!();
+        }
+    }
+    Ok(())
+}
+
+fn paragraphs_to_json(dense: &[DenseParagraph], filename: &str) -> serde_json::Value {
+    serde_json::json!({
+        "file": filename,
+        "paragraph_count": dense.len(),
+        "paragraphs": dense.iter().map(paragraph_to_json).collect::<Vec<_>>(),
+    })
+}
+
+fn paragraph_to_json(dense: &DenseParagraph) -> serde_json::Value {
+    let markers: Vec<serde_json::Value> = dense.hedges.iter()
+        .map(|m| serde_json::json!({
+            "text": m.matched_text,
+            "confidence_marker": m.anomaly_type.subtype(),
+            "line_number": m.line_number,
+            "column": m.column(),
+        }))
+        .collect();
+    serde_json::json!({
+        "start_line": dense.paragraph.start_line,
+        "end_line": dense.paragraph.end_line,
+        "first_line": dense.paragraph.first_line,
+        "words": dense.paragraph.words,
+        "hedge_count": dense.hedges.len(),
+        "density": dense.density,
+        "markers": markers,
+    })
+}
+
+/// Record findings for --write-baseline, or drop the ones the loaded baseline already knows.
+fn apply_baseline(matches: &mut Vec<Match>, filename: &str, args: &Args, report: &mut ScanReport) {
+    if args.write_baseline {
+        report.baseline_entries.extend(matches.iter().map(|m| baseline_entry(filename, m)));
+    } else if !report.baseline.is_empty() {
+        let before = matches.len();
+        matches.retain(|m| !report.baseline.contains(&fingerprint(filename, m)));
+        report.suppressed += before - matches.len();
+    }
+}
+
+/// Count what -c counts, grouped by `key`: matching lines, or every match with --count-matches.
+/// find_matches has already applied -v and --min-severity, so every count sees the same findings.
+fn count_findings(matches: &[Match], args: &Args, key: impl Fn(&Match) -> String) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut lines = HashSet::new();
+    
+    for m in matches {
+        let key = key(m);
+        // Lines are only unique within one JSON field
+        let line = (m.field.as_ref().map(|f| (f.record, f.pointer.as_str())), m.line_number);
+        if args.count_matches || lines.insert((key.clone(), line)) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    
+    counts.into_iter().collect()
+}
+
+/// Order severity counts from critical down to low.
+fn sort_by_severity(counts: &mut [(String, usize)]) {
+    counts.sort_by_key(|(level, _)| std::cmp::Reverse(level.parse::<Severity>().ok()));
+}
+
+/// `key:count` pairs for --count-by text output, or 0 when nothing matched.
+fn format_counts(counts: &[(String, usize)]) -> String {
+    if counts.is_empty() {
+        return "0".to_string();
+    }
+    counts.iter().map(|(key, count)| format!("{}:{}", key, count)).collect::<Vec<_>>().join(" ")
+}
+
+/// Identify a finding independently of its line number, so baselines survive edits
+/// elsewhere in the file.
+fn fingerprint(filename: &str, m: &Match) -> String {
+    let key = format!(
+        "{}\0{}\0{}\0{}\0{:016x}",
+        filename,
+        m.anomaly_type.category(),
+        m.anomaly_type.subtype().unwrap_or(""),
+        m.matched_text,
+        fnv1a(m.content.trim().as_bytes()),
+    );
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+/// 64-bit FNV-1a; stable across Rust releases unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn baseline_entry(filename: &str, m: &Match) -> serde_json::Value {
+    serde_json::json!({
+        "fingerprint": fingerprint(filename, m),
+        "file": filename,
+        "category": m.anomaly_type.category(),
+        "matched_text": m.matched_text,
+        "line_hash": format!("{:016x}", fnv1a(m.content.trim().as_bytes())),
+    })
+}
+
+fn load_baseline(path: &PathBuf) -> Result<HashSet<String>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("cannot read baseline {}: {}", path.display(), e))?;
+    let baseline: serde_json::Value = serde_json::from_str(&content)?;
+    
+    let fingerprints = baseline.get("findings")
+        .and_then(|findings| findings.as_array())
+        .ok_or_else(|| anyhow::anyhow!("baseline {} has no \"findings\" array", path.display()))?
+        .iter()
+        .filter_map(|entry| entry.get("fingerprint")?.as_str().map(str::to_string))
+        .collect();
+    
+    Ok(fingerprints)
+}
+
+fn write_baseline(path: &PathBuf, entries: &[serde_json::Value]) -> Result<()> {
+    let baseline = serde_json::json!({
+        "version": 1,
+        "findings": entries,
+    });
+    fs::write(path, serde_json::to_string_pretty(&baseline)? + "\n")?;
+    Ok(())
+}
+
+/// Compile every regex needed for `pattern` up front so each input (and each worker
+/// thread) reuses the same automata, and index the --memorization-check corpus.
+fn build_engine(pattern: &AnomalyPattern, args: &Args) -> Result<Scanner> {
+    let options = args.scan_options();
+    let engine = match pattern {
+        AnomalyPattern::Presets(presets) => Scanner::with_options(presets.clone(), options)?,
+        // For extended/perl regex the patterns are used as-is; basic grep patterns are literal
+        AnomalyPattern::Custom(patterns) if args.extended_regex || args.perl_regex => Scanner::regexes(patterns, options)?,
+        AnomalyPattern::Custom(patterns) => Scanner::literals(patterns, options)?,
+    };
+    
+    let Some(dir) = &args.memorization_check else {
+        return Ok(engine);
+    };
+    if args.ngram_size == 0 {
+        anyhow::bail!("--ngram-size must be at least 1");
+    }
+    let min_overlap = args.min_overlap.unwrap_or(args.ngram_size);
+    if min_overlap < args.ngram_size {
+        anyhow::bail!("--min-overlap cannot be smaller than --ngram-size ({})", args.ngram_size);
+    }
+    let corpus = CorpusIndex::build(dir, args.ngram_size, min_overlap, args.corpus_max_ngrams)?;
+    if corpus.is_full() && !args.no_messages {
+        eprintln!("ai-grep: memorization index is full at {} n-grams; the rest of {} is not checked (see --corpus-max-ngrams)",
+                  args.corpus_max_ngrams, dir.display());
+    }
+    Ok(engine.with_corpus(corpus))
+}
+
+/// Findings for one input, and how many were dropped by --skip-code-blocks/--skip-quotes.
+#[derive(Debug, Default)]
+struct InputScan {
+    matches: Vec<Match>,
+    skipped: usize,
+    /// The input looked binary, so its matching lines are not printed
+    binary: bool,
+    /// Size of the scanned text, for the --score match density
+    bytes: u64,
+    /// Lines of the scanned text, for the --stats score per 100 lines
+    lines: usize,
+    /// Blank-line separated paragraphs, collected for --density only
+    paragraphs: Vec<Paragraph>,
+}
+
+/// A run of non-blank lines.
+#[derive(Debug)]
+struct Paragraph {
+    start_line: usize,
+    end_line: usize,
+    /// Whitespace-separated words in all of its lines
+    words: usize,
+    first_line: String,
+}
+
+/// Scan `reader` one line at a time, so memory use does not grow with the input size.
+fn find_matches(mut reader: impl BufRead, engine: &Scanner, args: &Args) -> io::Result<InputScan> {
+    if args.multiline {
+        return find_multiline_matches(reader, engine, args);
+    }
+    
+    let mut scan = InputScan::default();
+    let mut scanner = LineScanner::default();
+    let mut line = Vec::new();
+    
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        scan.bytes += line.len() as u64;
+        scanner.scan(&line, engine, args, &mut scan);
+        line.clear();
+    }
+    scanner.finish(&mut scan);
+    scan.lines = scanner.line_number;
+    
+    Ok(scan)
+}
+
+/// Decode one line without its terminator. Invalid UTF-8 is replaced rather than failing
+/// the whole input.
+fn decode_line(bytes: &[u8]) -> String {
+    let line = match bytes.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => bytes,
+    };
+    String::from_utf8_lossy(line).into_owned()
+}
+
+/// State carried from one line of an input to the next.
+#[derive(Debug, Default)]
+struct LineScanner {
+    line_number: usize,
+    /// Bytes of the input before the next line
+    offset: u64,
+    markdown: MarkdownTracker,
+    /// The last -B lines, each with whether it had findings
+    recent: VecDeque<(ContextLine, bool)>,
+    /// Indexes into the scan's matches still taking -A lines, with the last line each takes
+    awaiting: Vec<(usize, usize)>,
+    /// The --density paragraph the last line belongs to, if it was not blank
+    paragraph: Option<Paragraph>,
+}
+
+impl LineScanner {
+    /// Scan the next line of the input, terminator included, adding its findings to `scan`.
+    fn scan(&mut self, bytes: &[u8], engine: &Scanner, args: &Args, scan: &mut InputScan) {
+        let line = decode_line(bytes);
+        self.line_number += 1;
+        let line_number = self.line_number;
+        let line_offset = self.offset;
+        self.offset += bytes.len() as u64;
+        let region = self.markdown.classify(&line);
+        if args.density {
+            self.track_paragraph(line_number, &line, scan);
+        }
+        
+        let mut line_matches = engine.scan_line(line_number, line_offset, &line);
+        apply_severity_overrides(&mut line_matches, &args.severity_override);
+        if let Some(min_severity) = args.min_severity {
+            line_matches.retain(|m| m.severity >= min_severity);
+        }
+        
+        // Lines inside skipped Markdown regions are not scanned at all, not even by -v
+        if (args.skip_code_blocks && region.code) || (args.skip_quotes && region.quote) {
+            scan.skipped += line_matches.len();
+            line_matches.clear();
+        } else if args.invert_match {
+            // Apply invert match filter
+            line_matches = if line_matches.is_empty() {
+                vec![Match {
+                    line_number,
+                    matched_text: line.clone(),
+                    start_pos: 0,
+                    end_pos: line.len(),
+                    content: line.clone(),
+                    anomaly_type: AnomalyType::Custom,
+                    severity: Severity::Low,
+                    field: None,
+                    captures: Vec::new(),
+                    end_line: None,
+                    line_offset,
+                    overlaps: Vec::new(),
+                    context_before: Vec::new(),
+                    context_after: Vec::new(),
+                }]
+            } else {
+                Vec::new()
+            };
+        }
+        
+        let line = ContextLine { line_number, offset: line_offset, content: line };
+        self.add_line(line, line_matches, args, scan);
+    }
+    
+    /// Extend the current paragraph with `line`, or close it at a blank line.
+    fn track_paragraph(&mut self, line_number: usize, line: &str, scan: &mut InputScan) {
+        let words = line.split_whitespace().count();
+        if words == 0 {
+            scan.paragraphs.extend(self.paragraph.take());
+            return;
+        }
+        let paragraph = self.paragraph.get_or_insert_with(|| Paragraph {
+            start_line: line_number,
+            end_line: line_number,
+            words: 0,
+            first_line: line.to_string(),
+        });
+        paragraph.end_line = line_number;
+        paragraph.words += words;
+    }
+    
+    /// Close the paragraph still open at the end of the input.
+    fn finish(&mut self, scan: &mut InputScan) {
+        scan.paragraphs.extend(self.paragraph.take());
+    }
+    
+    /// Add the findings of one line to `scan`, or pass the line on as context to the
+    /// findings around it when it has none.
+    fn add_line(&mut self, line: ContextLine, mut line_matches: Vec<Match>, args: &Args, scan: &mut InputScan) {
+        let (before, after) = (args.lines_before(), args.lines_after());
+        let matched = !line_matches.is_empty();
+        
+        if matched {
+            let context_before: Vec<ContextLine> = self.recent.iter()
+                .filter(|(_, matched)| !matched)
+                .map(|(line, _)| line.clone())
+                .collect();
+            if after > 0 {
+                let first = scan.matches.len();
+                self.awaiting.extend((first..first + line_matches.len()).map(|i| (i, line.line_number + after)));
+            }
+            for m in &mut line_matches {
+                m.context_before = context_before.clone();
+            }
+            scan.matches.extend(line_matches);
+        } else {
+            for &(i, _) in &self.awaiting {
+                scan.matches[i].context_after.push(line.clone());
+            }
+        }
+        self.awaiting.retain(|&(_, last)| last > line.line_number);
+        
+        if before > 0 {
+            if self.recent.len() == before {
+                self.recent.pop_front();
+            }
+            self.recent.push_back((line, matched));
+        }
+    }
+    
+    /// Advance past a line without scanning it, keeping line numbers, offsets and Markdown
+    /// state right.
+    fn skip(&mut self, bytes: &[u8]) {
+        self.line_number += 1;
+        self.offset += bytes.len() as u64;
+        self.markdown.classify(&decode_line(bytes));
+    }
+}
+
+/// Markdown context of one line of input.
+#[derive(Debug, Clone, Copy, Default)]
+struct MarkdownRegion {
+    /// Inside a fenced or indented code block (fence lines included)
+    code: bool,
+    /// Inside a blockquote, at any nesting depth
+    quote: bool,
+}
+
+/// Tracks Markdown block structure across lines. An unterminated fence runs to the end
+/// of the input, as in CommonMark.
+#[derive(Debug, Default)]
+struct MarkdownTracker {
+    /// Fence character and length of the open fenced block, if any
+    fence: Option<(char, usize)>,
+    in_indented_code: bool,
+    prev_text: bool,
+    prev_quote: bool,
+}
+
+impl MarkdownTracker {
+    fn classify(&mut self, line: &str) -> MarkdownRegion {
+        // Strip any number of `>` markers so nested quotes and code inside quotes are seen
+        let mut rest = line;
+        let mut quote_depth = 0;
+        while let Some(stripped) = rest.trim_start_matches([' ', '\t']).strip_prefix('>') {
+            rest = stripped.strip_prefix(' ').unwrap_or(stripped);
+            quote_depth += 1;
+        }
+        let blank = rest.trim().is_empty();
+        // Without a marker, a non-blank line continues the previous quote's paragraph
+        let quote = quote_depth > 0 || (self.prev_quote && !blank && self.fence.is_none());
+        
+        let trimmed = rest.trim_start_matches(' ');
+        let indent = rest.len() - trimmed.len();
+        let fence_marker = (indent < 4)
+            .then(|| ['`', '~'].into_iter().find(|&c| trimmed.starts_with(&c.to_string().repeat(3))))
+            .flatten()
+            .map(|c| (c, trimmed.chars().take_while(|&t| t == c).count()));
         
-        let content = if use_color {
-            highlight_match(&m.content, &m.matched_text, m.start_pos, m.end_pos)
+        let code = if let Some((open_char, open_len)) = self.fence {
+            if let Some((c, len)) = fence_marker {
+                if c == open_char && len >= open_len && trimmed[len..].trim().is_empty() {
+                    self.fence = None;
+                }
+            }
+            true
+        } else if let Some(marker) = fence_marker {
+            self.fence = Some(marker);
+            self.in_indented_code = false;
+            true
         } else {
-            m.content.clone()
+            // Indented code cannot interrupt a paragraph, but blank lines do not end it
+            let indented = !blank && (rest.starts_with('\t') || indent >= 4);
+            if !blank {
+                self.in_indented_code = indented && (!self.prev_text || self.in_indented_code);
+            }
+            self.in_indented_code && !blank
         };
         
-        println!("{}{}", line_prefix, content);
+        self.prev_text = !blank;
+        self.prev_quote = quote && !blank;
+        MarkdownRegion { code, quote }
+    }
+}
+
+/// --multiline counterpart of `find_matches`: lines are buffered into a window of at most
+/// `--multiline-window` bytes and matched as one text.
+fn find_multiline_matches(mut reader: impl BufRead, engine: &Scanner, args: &Args) -> io::Result<InputScan> {
+    let mut scan = InputScan::default();
+    let mut window = MultilineWindow { resume: vec![0; engine.rules().len()], ..MultilineWindow::default() };
+    let mut line = Vec::new();
+    
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        scan.bytes += line.len() as u64;
+        scan.lines += 1;
+        window.push(&line, engine, args, &mut scan);
+        line.clear();
+    }
+    window.scan_until(window.text.len(), engine, args, &mut scan);
+    
+    Ok(scan)
+}
+
+/// Buffered lines of an input in --multiline mode. Once the buffer outgrows the window,
+/// matches starting in its first half are reported and that half is dropped; inputs that
+/// fit in the window are matched as a whole.
+#[derive(Debug, Default)]
+struct MultilineWindow {
+    /// Buffered lines, each terminated by `\n`
+    text: String,
+    /// Byte offset of each buffered line in `text`, with its Markdown context
+    lines: Vec<(usize, MarkdownRegion)>,
+    /// Byte offset of each buffered line in the input, which may differ from its place in
+    /// `text` once line terminators and invalid UTF-8 have been normalised
+    line_offsets: Vec<u64>,
+    /// Bytes of the input pushed so far
+    input_bytes: u64,
+    /// Number of lines already dropped from the front of the window
+    dropped_lines: usize,
+    /// Per rule, the offset in `text` up to which matches have been reported, so a match
+    /// straddling the dropped half is not reported again in part
+    resume: Vec<usize>,
+    markdown: MarkdownTracker,
+}
+
+impl MultilineWindow {
+    /// Buffer the next line of the input, terminator included.
+    fn push(&mut self, bytes: &[u8], engine: &Scanner, args: &Args, scan: &mut InputScan) {
+        let line = decode_line(bytes);
+        self.lines.push((self.text.len(), self.markdown.classify(&line)));
+        self.line_offsets.push(self.input_bytes);
+        self.input_bytes += bytes.len() as u64;
+        self.text.push_str(&line);
+        self.text.push('\n');
         
-        // Show anomaly details if requested
-        if args.severity {
-            println!("  {} Severity: {:?} ({:.2})", 
-                     m.severity.to_emoji(), 
-                     m.severity, 
-                     m.severity.to_score());
+        if self.text.len() >= args.multiline_window {
+            let half = self.text.len() / 2;
+            let keep_from = self.lines.iter()
+                .map(|(start, _)| *start)
+                .find(|&start| start > 0 && start >= half)
+                .unwrap_or(self.text.len());
+            self.scan_until(keep_from, engine, args, scan);
+            self.drop_before(keep_from);
+        }
+    }
+    
+    /// Report the matches that start before byte `limit` of the window.
+    fn scan_until(&mut self, limit: usize, engine: &Scanner, args: &Args, scan: &mut InputScan) {
+        let mut found = Vec::new();
+        let mut regions = Vec::new();
+        for hit in engine.hits(&self.text) {
+            if hit.start >= limit || hit.start < self.resume[hit.rule] {
+                continue;
+            }
+            self.resume[hit.rule] = hit.end;
+            let (m, region) = self.to_match(hit, engine);
+            found.push(m);
+            regions.push(region);
         }
         
-        match &m.anomaly_type {
-            AnomalyType::Hallucination { marker_type } => {
-                println!("  🚨 AI hallucination marker: {}", marker_type);
+        apply_severity_overrides(&mut found, &args.severity_override);
+        for (m, region) in found.into_iter().zip(regions) {
+            if args.min_severity.is_some_and(|min_severity| m.severity < min_severity) {
+                continue;
             }
-            AnomalyType::CodeIssue { issue_type } => {
-                println!("  🔧 Code issue: {}", issue_type);
+            // Matches are placed by the line they start on
+            if (args.skip_code_blocks && region.code) || (args.skip_quotes && region.quote) {
+                scan.skipped += 1;
+            } else {
+                scan.matches.push(m);
             }
-            AnomalyType::Security { vulnerability_type } => {
-                println!("  🔒 Security concern: {}", vulnerability_type);
+        }
+    }
+    
+    fn to_match(&self, hit: Hit, engine: &Scanner) -> (Match, MarkdownRegion) {
+        let line_of = |offset: usize| self.lines.partition_point(|(start, _)| *start <= offset) - 1;
+        let first = line_of(hit.start);
+        let last = line_of(if hit.end > hit.start { hit.end - 1 } else { hit.start });
+        let (line_start, region) = self.lines[first];
+        // Every buffered line ends in a newline, which `content` leaves out
+        let line_end = self.lines.get(last + 1).map_or(self.text.len(), |(start, _)| *start) - 1;
+        
+        let content = &self.text[line_start..line_end];
+        let start_pos = hit.start - line_start;
+        let end_pos = hit.end.min(line_end) - line_start;
+        let captures = hit.captures.into_iter()
+            .map(|capture| Capture {
+                span: capture.span.map(|(start, end)| (start - line_start, end.min(line_end).max(start) - line_start)),
+                ..capture
+            })
+            .collect();
+        let rule = &engine.rules()[hit.rule];
+        
+        let m = Match {
+            line_number: self.dropped_lines + first + 1,
+            content: content.to_string(),
+            matched_text: content[start_pos..end_pos].to_string(),
+            start_pos,
+            end_pos,
+            anomaly_type: rule.anomaly_type(),
+            severity: rule.severity,
+            field: None,
+            captures,
+            end_line: Some(self.dropped_lines + last + 1),
+            line_offset: self.line_offsets[first],
+            overlaps: hit.overlaps,
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+        };
+        (m, region)
+    }
+    
+    fn drop_before(&mut self, offset: usize) {
+        let count = self.lines.partition_point(|(start, _)| *start < offset);
+        self.lines.drain(..count);
+        self.line_offsets.drain(..count);
+        for (start, _) in &mut self.lines {
+            *start -= offset;
+        }
+        self.dropped_lines += count;
+        self.text.drain(..offset);
+        for resume in &mut self.resume {
+            *resume = resume.saturating_sub(offset);
+        }
+    }
+}
+
+fn display_matches(matches: &[Match], filename: &str, args: &Args, use_color: bool) -> Result<()> {
+    if args.format == "json" {
+        return output_json(matches, filename, args);
+    }
+    if args.format == "ndjson" {
+        return output_ndjson(matches, filename, args);
+    }
+    if args.format == "csv" {
+        return output_csv(matches, filename);
+    }
+    
+    let colors = use_color.then(|| args.colors.clone().unwrap_or_default());
+    let file_name = paint(colors.as_ref().map(|c| c.file.as_str()), filename);
+    
+    // grep -o ignores context too: only the matched text is printed
+    if let Some(hunks) = context_hunks(matches, args).filter(|_| !args.only_matching) {
+        display_hunks(matches, &hunks, &file_name, args, colors.as_ref());
+        return Ok(());
+    }
+    
+    for (i, m) in matches.iter().enumerate() {
+        let severity_color = colors.as_ref().map(|c| c.severity(m.severity));
+        // file:record:pointer:line:column:offset:, each part only when it applies
+        let line_prefix = |line_number: usize, column: usize, offset: u64| {
+            let number = |n: String| paint(colors.as_ref().map(|c| c.line_no.as_str()), &n);
+            let mut parts = Vec::new();
+            if args.with_filename() {
+                parts.push(file_name.clone());
             }
-            AnomalyType::DataLeakage { leak_type } => {
-                println!("  📊 Data leakage: {}", leak_type);
+            if let Some(field) = &m.field {
+                parts.push(field.record.to_string());
+                parts.push(field.pointer.clone());
             }
-            AnomalyType::LowConfidence { confidence_marker } => {
-                println!("  📉 Low confidence: {}", confidence_marker);
+            if m.field.is_some() || args.line_number || args.column {
+                parts.push(number(line_number.to_string()));
             }
-            AnomalyType::Custom => {}
-        }
+            if m.field.is_some() || args.column {
+                parts.push(number(column.to_string()));
+            }
+            if args.byte_offset {
+                parts.push(number(offset.to_string()));
+            }
+            parts.iter().map(|part| format!("{}:", part)).collect::<String>()
+        };
         
-        // Add context lines if requested
-        if args.after_context > 0 || args.before_context > 0 {
-            // Implementation for context lines would go here
+        // A --multiline match prints every line it spans, highlighted on each
+        let mut line_start = 0;
+        for (offset, full_line) in m.content.split('\n').enumerate() {
+            let line_begin = line_start;
+            line_start += full_line.len() + 1;
+            let start = m.start_pos.clamp(line_begin, line_begin + full_line.len()) - line_begin;
+            let end = m.end_pos.clamp(line_begin, line_begin + full_line.len()) - line_begin;
+            // With -o the matched part of the line stands in for the line
+            let (line, start, end) = if args.only_matching {
+                if start == end {
+                    continue;
+                }
+                (&full_line[start..end], 0, end - start)
+            } else {
+                (full_line, start, end)
+            };
+            let clipped = ClippedLine::new(line, Some((start, end)), args);
+            let (start, end) = clipped.span(start, end);
+            let content = match severity_color {
+                Some(sgr) => highlight_match(clipped.text, &clipped.text[start..end], start, end, sgr),
+                None => clipped.text.to_string(),
+            };
+            let content = clipped.display(&content);
+            // Continuation lines start at column 1; their offset assumes `\n` terminators
+            let (column, byte_offset) = if offset == 0 {
+                (m.column(), m.absolute_offset())
+            } else {
+                (1, m.line_offset + line_begin as u64)
+            };
+            println!("{}{}", line_prefix(m.line_number + offset, column, byte_offset), content);
         }
         
-        if i < matches.len() - 1 && (args.severity || !matches!(m.anomaly_type, AnomalyType::Custom)) {
+        print_details(m, args, severity_color);
+        
+        let has_details = args.severity || (args.show_captures && !m.captures.is_empty());
+        let has_annotation = !args.only_matching && !matches!(m.anomaly_type, AnomalyType::Custom);
+        if i < matches.len() - 1 && (has_details || has_annotation) {
             println!();
         }
     }
@@ -616,78 +2909,636 @@ fn display_matches(matches: &[Match], filename: &str, args: &Args, use_color: bo
     Ok(())
 }
 
-fn highlight_match(content: &str, matched_text: &str, start_pos: usize, end_pos: usize) -> String {
+/// Print the severity, preset annotation and captures shown under a match.
+fn print_details(m: &Match, args: &Args, severity_color: Option<&str>) {
+    // There is a severity color exactly when color, and with it emoji, is on
+    let emoji = severity_color.is_some();
+    
+    // Show anomaly details if requested
+    if args.severity {
+        let severity = format!("{} Severity: {:?} ({:.2})", 
+                               m.severity.marker(emoji), 
+                               m.severity, 
+                               m.severity.to_score());
+        println!("  {}", paint(severity_color, &severity));
+    }
+    
+    let annotation = match &m.anomaly_type {
+        AnomalyType::Hallucination { marker_type } => {
+            Some(("🚨", format!("AI hallucination marker: {}", marker_type)))
+        }
+        AnomalyType::CodeIssue { issue_type } => {
+            Some(("🔧", format!("Code issue: {}", issue_type)))
+        }
+        AnomalyType::Security { vulnerability_type } => {
+            Some(("🔒", format!("Security concern: {}", vulnerability_type)))
+        }
+        AnomalyType::DataLeakage { leak_type } => {
+            Some(("📊", format!("Data leakage: {}", leak_type)))
+        }
+        AnomalyType::LowConfidence { confidence_marker } => {
+            Some(("📉", format!("Low confidence: {}", confidence_marker)))
+        }
+        AnomalyType::Secret { secret_type } => {
+            Some(("🔑", format!("Secret: {}", secret_type)))
+        }
+        AnomalyType::PromptInjection { technique } => {
+            Some(("💉", format!("Prompt injection: {}", technique)))
+        }
+        AnomalyType::Memorization { source_file, source_offset } => {
+            Some(("📚", format!("Memorized text: {} at byte {}", source_file, source_offset)))
+        }
+        AnomalyType::Custom => None,
+    };
+    // -o output is meant for extraction, so only the details asked for are added
+    if let Some((icon, annotation)) = annotation.filter(|_| !args.only_matching) {
+        println!("  {}", paint(severity_color, &with_icon(emoji, icon, &annotation)));
+    }
+    
+    if args.show_captures && !m.captures.is_empty() {
+        let groups: Vec<String> = m.captures.iter()
+            .map(|capture| match capture.span {
+                Some((start, end)) => format!("{}={:?}", capture.name, &m.content[start..end]),
+                None => format!("{}=null", capture.name),
+            })
+            .collect();
+        let captures = with_icon(emoji, "🏷️", &format!("Captures: {}", groups.join(" ")));
+        println!("  {}", paint(severity_color, &captures));
+    }
+}
+
+/// `text` behind its emoji icon, or bare when emoji are off.
+fn with_icon(emoji: bool, icon: &str, text: &str) -> String {
+    if emoji { format!("{} {}", icon, text) } else { text.to_string() }
+}
+
+/// Number each match with the grep-style hunk it is printed in: matches whose context
+/// windows overlap or touch share a hunk, and a new --json-input field starts one.
+/// `None` when no context was asked for.
+fn context_hunks(matches: &[Match], args: &Args) -> Option<Vec<usize>> {
+    if !args.shows_context() {
+        return None;
+    }
+    
+    let (before, after) = (args.lines_before(), args.lines_after());
+    let mut hunks = Vec::with_capacity(matches.len());
+    let mut previous: Option<(&Match, usize)> = None;
+    let mut hunk = 0;
+    for m in matches {
+        let first = m.line_number.saturating_sub(before);
+        let last = m.end_line.unwrap_or(m.line_number) + after;
+        let same_field = |p: &Match| match (&p.field, &m.field) {
+            (Some(a), Some(b)) => a.record == b.record && a.pointer == b.pointer,
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        let end = match previous {
+            Some((p, end)) if same_field(p) && first <= end + 1 => end.max(last),
+            Some(_) => {
+                hunk += 1;
+                last
+            }
+            None => last,
+        };
+        hunks.push(hunk);
+        previous = Some((m, end));
+    }
+    Some(hunks)
+}
+
+/// Set once a hunk has been printed, so later ones (in any file) are preceded by `--`.
+static PRINTED_HUNK: AtomicBool = AtomicBool::new(false);
+
+/// Print matches with their context the way grep does: every line once, with all of its
+/// findings highlighted, `:` after the prefix of matching lines and `-` after that of
+/// context lines, and `--` between hunks.
+fn display_hunks(matches: &[Match], hunks: &[usize], file_name: &str, args: &Args, colors: Option<&Colors>) {
+    let number = |n: String| paint(colors.map(|c| c.line_no.as_str()), &n);
+    
+    let mut start = 0;
+    while start < matches.len() {
+        let len = hunks[start..].iter().take_while(|&&hunk| hunk == hunks[start]).count();
+        let hunk = &matches[start..start + len];
+        start += len;
+        if PRINTED_HUNK.swap(true, Ordering::Relaxed) {
+            println!("--");
+        }
+        
+        let mut lines: BTreeMap<usize, (u64, &str, Vec<&Match>)> = BTreeMap::new();
+        for m in hunk {
+            for line in m.context_before.iter().chain(&m.context_after) {
+                lines.entry(line.line_number).or_insert((line.offset, &line.content, Vec::new()));
+            }
+            lines.entry(m.line_number).or_insert((m.line_offset, &m.content, Vec::new())).2.push(m);
+        }
+        
+        let field = hunk[0].field.as_ref();
+        for (line_number, (offset, content, found)) in lines {
+            let separator = if found.is_empty() { "-" } else { ":" };
+            let mut parts = Vec::new();
+            if args.with_filename() {
+                parts.push(file_name.to_string());
+            }
+            if let Some(field) = field {
+                parts.push(field.record.to_string());
+                parts.push(field.pointer.clone());
+            }
+            if field.is_some() || args.line_number || args.column {
+                parts.push(number(line_number.to_string()));
+            }
+            // Context lines have no match to point at, so they go without a column
+            if let Some(first) = found.first() {
+                if field.is_some() || args.column {
+                    parts.push(number(first.column().to_string()));
+                }
+            }
+            if args.byte_offset {
+                parts.push(number(found.first().map_or(offset, |m| m.absolute_offset()).to_string()));
+            }
+            let prefix: String = parts.iter().map(|part| format!("{}{}", part, separator)).collect();
+            
+            let focus = found.iter().min_by_key(|m| m.start_pos).map(|m| (m.start_pos, m.end_pos));
+            let clipped = ClippedLine::new(content, focus, args);
+            let content = match colors {
+                Some(colors) => highlight_matches(&clipped, &found, colors),
+                None => clipped.text.to_string(),
+            };
+            println!("{}{}", prefix, clipped.display(&content));
+            for m in found {
+                print_details(m, args, colors.map(|c| c.severity(m.severity)));
+            }
+        }
+    }
+}
+
+/// Where --redact and --annotate write: the --output file, or stdout.
+fn rewrite_output(args: &Args) -> Result<Box<dyn Write>> {
+    Ok(match &args.output {
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn run_redaction(args: &Args) -> Result<()> {
+    let mut writer = rewrite_output(args)?;
+    let mut replacements: BTreeMap<String, usize> = BTreeMap::new();
+    let engine = Scanner::with_options(Category::DataLeakage.into(), args.scan_options())?;
+    
+    let mut redact_one = |input: &str| -> Result<()> {
+        let mut matches = engine.scan(input);
+        apply_severity_overrides(&mut matches, &args.severity_override);
+        if let Some(min_severity) = args.min_severity {
+            matches.retain(|m| m.severity >= min_severity);
+        }
+        
+        let (redacted, counts) = redact_matches(input, &matches, args.redact_char);
+        for (leak_type, count) in counts {
+            *replacements.entry(leak_type).or_insert(0) += count;
+        }
+        writer.write_all(redacted.as_bytes())?;
+        Ok(())
+    };
+    
+    // Invalid UTF-8 is replaced rather than skipped, so no input goes out unchecked
+    let mut errors = 0;
+    if args.files.is_empty() {
+        let mut content = Vec::new();
+        io::stdin().read_to_end(&mut content)?;
+        redact_one(&String::from_utf8_lossy(&content))?;
+    } else {
+        for file in &args.files {
+            let content = open_path(file, args.decompress).and_then(|mut reader| {
+                let mut content = Vec::new();
+                reader.read_to_end(&mut content).map(|_| content)
+            });
+            match content {
+                Ok(content) => redact_one(&String::from_utf8_lossy(&content))?,
+                Err(e) => {
+                    errors += 1;
+                    if !args.no_messages {
+                        eprintln!("ai-grep: {}: {}", args.input_name(file), e);
+                    }
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    
+    let total: usize = replacements.values().sum();
+    eprintln!("ai-grep: {} redaction(s)", total);
+    for (leak_type, count) in &replacements {
+        eprintln!("  {}: {}", leak_type, count);
+    }
+    
+    // Output missing an input is not safe to share
+    if errors > 0 {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+/// Copy every input to the output unchanged, adding a marker line after each line with
+/// findings, so the result diffs cleanly against the original.
+fn run_annotation(engine: &Scanner, args: &Args) -> Result<()> {
+    let mut writer = rewrite_output(args)?;
+    
+    let mut annotate_one = |mut reader: Box<dyn BufRead>| -> io::Result<()> {
+        let mut scanner = LineScanner::default();
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            let mut scan = InputScan::default();
+            scanner.scan(&line, engine, args, &mut scan);
+            writer.write_all(&line)?;
+            if !scan.matches.is_empty() {
+                write_annotations(&mut writer, &line, &scan.matches, args.annotate_style)?;
+            }
+            line.clear();
+        }
+        Ok(())
+    };
+    
+    let mut errors = 0;
+    if args.files.is_empty() {
+        annotate_one(open_stdin(args.decompress)?)?;
+    } else {
+        for file in &args.files {
+            if let Err(e) = open_path(file, args.decompress).and_then(&mut annotate_one) {
+                errors += 1;
+                if !args.no_messages {
+                    eprintln!("ai-grep: {}: {}", args.input_name(file), e);
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    
+    // As with --redact, output missing an input is not the whole review
+    if errors > 0 {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+/// "CRITICAL secret/openai_key": the severity, category and subtype of a finding.
+fn finding_label(severity: Severity, anomaly_type: &AnomalyType) -> String {
+    let severity = format!("{:?}", severity).to_uppercase();
+    match anomaly_type.subtype() {
+        Some(subtype) => format!("{} {}/{}", severity, anomaly_type.category(), subtype),
+        None => format!("{} {}", severity, anomaly_type.category()),
+    }
+}
+
+/// Write one marker per finding after `line`, in column order, ending each with the line's
+/// own terminator. A last line without a terminator gets one before the markers, and the
+/// final marker goes without, so the end of the file is unchanged.
+fn write_annotations(writer: &mut impl Write, line: &[u8], matches: &[Match], style: AnnotateStyle) -> io::Result<()> {
+    let terminator: &[u8] = if line.ends_with(b"\r\n") {
+        b"\r\n"
+    } else if line.ends_with(b"\n") {
+        b"\n"
+    } else {
+        writer.write_all(b"\n")?;
+        b""
+    };
+    
+    for (i, m) in matches.iter().enumerate() {
+        let label = finding_label(m.severity, &m.anomaly_type);
+        let marker = match style {
+            AnnotateStyle::Caret => {
+                // Tabs are kept so the carets line up whatever the tab width
+                let indent: String = m.content[..m.start_pos].chars()
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let carets = "^".repeat(m.matched_text.chars().count().max(1));
+                format!("{}{} [{}]", indent, carets, label)
+            }
+            AnnotateStyle::Comment => format!("<!-- ai-grep: {} at column {} -->", label, m.column()),
+        };
+        writer.write_all(marker.as_bytes())?;
+        if i + 1 < matches.len() || !terminator.is_empty() {
+            writer.write_all(if terminator.is_empty() { b"\n" } else { terminator })?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace every match in `input` with a placeholder, leaving all other bytes untouched.
+/// Overlapping matches are merged into a single span labelled after its most severe finding.
+fn redact_matches(input: &str, matches: &[Match], redact_char: Option<char>) -> (String, HashMap<String, usize>) {
+    // Byte offset of the start of every line, matching the numbering of str::lines()
+    let mut line_starts = vec![0];
+    line_starts.extend(input.match_indices('\n').map(|(i, _)| i + 1));
+    
+    let mut spans: Vec<(usize, usize, Severity, &str)> = matches.iter()
+        .filter_map(|m| {
+            let line_start = *line_starts.get(m.line_number - 1)?;
+            let label = m.anomaly_type.subtype().unwrap_or(m.anomaly_type.category());
+            Some((line_start + m.start_pos, line_start + m.end_pos, m.severity, label))
+        })
+        .collect();
+    spans.sort_by_key(|&(start, end, _, _)| (start, std::cmp::Reverse(end)));
+    
+    let mut merged: Vec<(usize, usize, Severity, &str)> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.0 < last.1 => {
+                last.1 = last.1.max(span.1);
+                if span.2 > last.2 {
+                    last.2 = span.2;
+                    last.3 = span.3;
+                }
+            }
+            _ => merged.push(span),
+        }
+    }
+    
+    let mut output = String::with_capacity(input.len());
+    let mut counts = HashMap::new();
+    let mut cursor = 0;
+    
+    for (start, end, _, label) in merged {
+        output.push_str(&input[cursor..start]);
+        match redact_char {
+            Some(c) => output.extend(std::iter::repeat_n(c, input[start..end].chars().count())),
+            None => output.push_str(&format!("[REDACTED:{}]", label)),
+        }
+        *counts.entry(label.to_string()).or_insert(0) += 1;
+        cursor = end;
+    }
+    output.push_str(&input[cursor..]);
+    
+    (output, counts)
+}
+
+fn highlight_match(content: &str, matched_text: &str, start_pos: usize, end_pos: usize, sgr: &str) -> String {
     let before = &content[..start_pos];
     let after = &content[end_pos..];
-    format!("{}{}{}{}{}", 
-            before, 
-            "\x1b[31m\x1b[1m", // Red + bold
-            matched_text, 
-            "\x1b[0m", // Reset
-            after)
+    format!("{}{}{}", before, paint(Some(sgr), matched_text), after)
+}
+
+/// Highlight every finding on the shown part of a line, each in its severity's color.
+/// Where findings overlap, the earlier one wins.
+fn highlight_matches(clipped: &ClippedLine, found: &[&Match], colors: &Colors) -> String {
+    let mut spans: Vec<&Match> = found.to_vec();
+    spans.sort_by_key(|m| m.start_pos);
+    
+    let line = clipped.text;
+    let mut output = String::with_capacity(line.len());
+    let mut cursor = 0;
+    for m in spans {
+        let (start, end) = clipped.span(m.start_pos, m.end_pos);
+        // Findings cut off entirely by --max-columns have nothing left to paint
+        if start < cursor || (start == end && m.start_pos != m.end_pos) {
+            continue;
+        }
+        output.push_str(&line[cursor..start]);
+        output.push_str(&paint(Some(colors.severity(m.severity)), &line[start..end]));
+        cursor = end;
+    }
+    output.push_str(&line[cursor..]);
+    output
+}
+
+/// The part of a line that is displayed: all of it, or --max-columns characters of it.
+/// Highlighting is applied to `text` alone, so escape sequences never straddle a cut.
+struct ClippedLine<'a> {
+    text: &'a str,
+    /// Byte offset of `text` in the line
+    start: usize,
+    /// Characters cut before and after `text`
+    omitted_before: usize,
+    omitted_after: usize,
+}
+
+impl<'a> ClippedLine<'a> {
+    /// Clip `line` to --max-columns characters. The window keeps the byte range `focus` in
+    /// view with some leading context, or starts the line when it fits there, the focus
+    /// is `None`, or --max-columns-preview is set.
+    fn new(line: &'a str, focus: Option<(usize, usize)>, args: &Args) -> Self {
+        let whole = ClippedLine { text: line, start: 0, omitted_before: 0, omitted_after: 0 };
+        let Some(width) = args.max_columns else { return whole };
+        let len = line.chars().count();
+        if len <= width {
+            return whole;
+        }
+        
+        let first = match focus {
+            Some((start, end)) if !args.max_columns_preview && line[..end].chars().count() > width => {
+                line[..start].chars().count().saturating_sub(width / 4).min(len - width)
+            }
+            _ => 0,
+        };
+        let byte = |n: usize| line.char_indices().nth(n).map_or(line.len(), |(i, _)| i);
+        let (start, end) = (byte(first), byte(first + width));
+        ClippedLine {
+            text: &line[start..end],
+            start,
+            omitted_before: first,
+            omitted_after: len - first - width,
+        }
+    }
+    
+    /// Map the byte range `start..end` of the line onto `text`, clamped to what is shown.
+    fn span(&self, start: usize, end: usize) -> (usize, usize) {
+        let clamp = |pos: usize| pos.clamp(self.start, self.start + self.text.len()) - self.start;
+        (clamp(start), clamp(end))
+    }
+    
+    /// Surround the (possibly highlighted) `content` with markers for what was cut.
+    fn display(&self, content: &str) -> String {
+        let omitted = |count: usize| if count > 0 { format!("… [{} chars omitted] …", count) } else { String::new() };
+        format!("{}{}{}", omitted(self.omitted_before), content, omitted(self.omitted_after))
+    }
+}
+
+/// Wrap `text` in the SGR sequence `sgr`; no color or an empty sequence leaves it plain.
+fn paint(sgr: Option<&str>, text: &str) -> String {
+    match sgr {
+        Some(sgr) if !sgr.is_empty() => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+        _ => text.to_string(),
+    }
 }
 
 fn output_json(matches: &[Match], filename: &str, args: &Args) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&matches_to_json(matches, filename, args))?);
+    Ok(())
+}
+
+/// One compact JSON object per match, each naming its file, for line-oriented consumers.
+fn output_ndjson(matches: &[Match], filename: &str, args: &Args) -> Result<()> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let hunks = context_hunks(matches, args);
+    
+    for (i, m) in matches.iter().enumerate() {
+        let mut match_json = match_to_json(m, hunks.as_ref().map(|hunks| hunks[i]));
+        if let serde_json::Value::Object(obj) = &mut match_json {
+            obj.insert("file".to_string(), serde_json::Value::String(filename.to_string()));
+        }
+        writeln!(out, "{}", match_json)?;
+    }
+    
+    out.flush()?;
+    Ok(())
+}
+
+const CSV_HEADER: [&str; 9] = [
+    "file", "line", "column", "category", "subtype", "severity", "severity_score", "matched_text", "content",
+];
+
+fn output_csv(matches: &[Match], filename: &str) -> Result<()> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    
+    for m in matches {
+        let row = [
+            filename.to_string(),
+            m.line_number.to_string(),
+            m.column().to_string(),
+            m.anomaly_type.category().to_string(),
+            m.anomaly_type.subtype().unwrap_or("").to_string(),
+            format!("{:?}", m.severity),
+            format!("{:.2}", m.severity.to_score()),
+            m.matched_text.clone(),
+            m.content.clone(),
+        ];
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    
+    out.flush()?;
+    Ok(())
+}
+
+/// Quote a CSV field (RFC 4180) if it contains a delimiter, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn matches_to_json(matches: &[Match], filename: &str, args: &Args) -> serde_json::Value {
     let mut output = serde_json::Map::new();
     
     output.insert("file".to_string(), serde_json::Value::String(filename.to_string()));
     output.insert("match_count".to_string(), serde_json::Value::Number(serde_json::Number::from(matches.len())));
     
-    let matches_json: Vec<serde_json::Value> = matches.iter().map(|m| {
-        let mut match_obj = serde_json::Map::new();
-        match_obj.insert("line_number".to_string(), serde_json::Value::Number(serde_json::Number::from(m.line_number)));
-        match_obj.insert("content".to_string(), serde_json::Value::String(m.content.clone()));
-        match_obj.insert("matched_text".to_string(), serde_json::Value::String(m.matched_text.clone()));
-        match_obj.insert("start_pos".to_string(), serde_json::Value::Number(serde_json::Number::from(m.start_pos)));
-        match_obj.insert("end_pos".to_string(), serde_json::Value::Number(serde_json::Number::from(m.end_pos)));
-        match_obj.insert("severity".to_string(), serde_json::Value::String(format!("{:?}", m.severity)));
-        match_obj.insert("severity_score".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(m.severity.to_score()).unwrap()));
-        
-        let anomaly_info = match &m.anomaly_type {
-            AnomalyType::Hallucination { marker_type } => {
-                let mut info = serde_json::Map::new();
-                info.insert("type".to_string(), serde_json::Value::String("hallucination".to_string()));
-                info.insert("marker_type".to_string(), serde_json::Value::String(marker_type.clone()));
-                serde_json::Value::Object(info)
-            }
-            AnomalyType::CodeIssue { issue_type } => {
-                let mut info = serde_json::Map::new();
-                info.insert("type".to_string(), serde_json::Value::String("code_issue".to_string()));
-                info.insert("issue_type".to_string(), serde_json::Value::String(issue_type.clone()));
-                serde_json::Value::Object(info)
-            }
-            AnomalyType::Security { vulnerability_type } => {
-                let mut info = serde_json::Map::new();
-                info.insert("type".to_string(), serde_json::Value::String("security".to_string()));
-                info.insert("vulnerability_type".to_string(), serde_json::Value::String(vulnerability_type.clone()));
-                serde_json::Value::Object(info)
-            }
-            AnomalyType::DataLeakage { leak_type } => {
-                let mut info = serde_json::Map::new();
-                info.insert("type".to_string(), serde_json::Value::String("data_leakage".to_string()));
-                info.insert("leak_type".to_string(), serde_json::Value::String(leak_type.clone()));
-                serde_json::Value::Object(info)
-            }
-            AnomalyType::LowConfidence { confidence_marker } => {
-                let mut info = serde_json::Map::new();
-                info.insert("type".to_string(), serde_json::Value::String("low_confidence".to_string()));
-                info.insert("confidence_marker".to_string(), serde_json::Value::String(confidence_marker.clone()));
-                serde_json::Value::Object(info)
-            }
-            AnomalyType::Custom => {
-                let mut info = serde_json::Map::new();
-                info.insert("type".to_string(), serde_json::Value::String("custom".to_string()));
-                serde_json::Value::Object(info)
-            }
-        };
-        
-        match_obj.insert("anomaly".to_string(), anomaly_info);
-        serde_json::Value::Object(match_obj)
-    }).collect();
+    let hunks = context_hunks(matches, args);
+    let matches_json: Vec<serde_json::Value> = matches.iter()
+        .enumerate()
+        .map(|(i, m)| match_to_json(m, hunks.as_ref().map(|hunks| hunks[i])))
+        .collect();
     
     output.insert("matches".to_string(), serde_json::Value::Array(matches_json));
+    serde_json::Value::Object(output)
+}
+
+/// JSON for one match; `hunk` is its context hunk when -A/-B/-C is on.
+fn match_to_json(m: &Match, hunk: Option<usize>) -> serde_json::Value {
+    let mut match_obj = serde_json::Map::new();
+    match_obj.insert("line_number".to_string(), serde_json::Value::Number(serde_json::Number::from(m.line_number)));
+    match_obj.insert("content".to_string(), serde_json::Value::String(m.content.clone()));
+    match_obj.insert("matched_text".to_string(), serde_json::Value::String(m.matched_text.clone()));
+    match_obj.insert("start_pos".to_string(), serde_json::Value::Number(serde_json::Number::from(m.start_pos)));
+    match_obj.insert("end_pos".to_string(), serde_json::Value::Number(serde_json::Number::from(m.end_pos)));
+    match_obj.insert("absolute_offset".to_string(), serde_json::Value::Number(serde_json::Number::from(m.absolute_offset())));
+    match_obj.insert("severity".to_string(), serde_json::Value::String(format!("{:?}", m.severity)));
+    match_obj.insert("severity_score".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(m.severity.to_score()).unwrap()));
+    if let Some(field) = &m.field {
+        match_obj.insert("record".to_string(), serde_json::Value::Number(serde_json::Number::from(field.record)));
+        match_obj.insert("json_pointer".to_string(), serde_json::Value::String(field.pointer.clone()));
+        match_obj.insert("column".to_string(), serde_json::Value::Number(serde_json::Number::from(m.column())));
+    }
+    if let Some(end_line) = m.end_line {
+        match_obj.insert("start_line".to_string(), serde_json::Value::Number(serde_json::Number::from(m.line_number)));
+        match_obj.insert("start_column".to_string(), serde_json::Value::Number(serde_json::Number::from(m.column())));
+        match_obj.insert("end_line".to_string(), serde_json::Value::Number(serde_json::Number::from(end_line)));
+        match_obj.insert("end_column".to_string(), serde_json::Value::Number(serde_json::Number::from(m.end_column())));
+    }
+    if !m.overlaps.is_empty() {
+        match_obj.insert("overlaps".to_string(), serde_json::json!(m.overlaps));
+    }
+    if let Some(hunk) = hunk {
+        let context = |lines: &[ContextLine]| -> serde_json::Value {
+            lines.iter()
+                .map(|line| serde_json::json!({ "line_number": line.line_number, "content": line.content }))
+                .collect()
+        };
+        match_obj.insert("context_before".to_string(), context(&m.context_before));
+        match_obj.insert("context_after".to_string(), context(&m.context_after));
+        match_obj.insert("hunk_id".to_string(), serde_json::Value::Number(serde_json::Number::from(hunk)));
+    }
+    if !m.captures.is_empty() {
+        let captures = m.captures.iter()
+            .map(|capture| {
+                let value = capture.span.map_or(serde_json::Value::Null, |(start, end)| serde_json::json!({
+                    "text": &m.content[start..end],
+                    "start": start,
+                    "end": end,
+                }));
+                (capture.name.clone(), value)
+            })
+            .collect();
+        match_obj.insert("captures".to_string(), serde_json::Value::Object(captures));
+    }
     
-    println!("{}", serde_json::to_string_pretty(&output)?);
-    Ok(())
+    let anomaly_info = match &m.anomaly_type {
+        AnomalyType::Hallucination { marker_type } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("hallucination".to_string()));
+            info.insert("marker_type".to_string(), serde_json::Value::String(marker_type.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::CodeIssue { issue_type } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("code_issue".to_string()));
+            info.insert("issue_type".to_string(), serde_json::Value::String(issue_type.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::Security { vulnerability_type } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("security".to_string()));
+            info.insert("vulnerability_type".to_string(), serde_json::Value::String(vulnerability_type.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::DataLeakage { leak_type } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("data_leakage".to_string()));
+            info.insert("leak_type".to_string(), serde_json::Value::String(leak_type.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::LowConfidence { confidence_marker } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("low_confidence".to_string()));
+            info.insert("confidence_marker".to_string(), serde_json::Value::String(confidence_marker.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::Secret { secret_type } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("secret".to_string()));
+            info.insert("secret_type".to_string(), serde_json::Value::String(secret_type.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::PromptInjection { technique } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("prompt_injection".to_string()));
+            info.insert("technique".to_string(), serde_json::Value::String(technique.clone()));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::Memorization { source_file, source_offset } => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("memorization".to_string()));
+            info.insert("source_file".to_string(), serde_json::Value::String(source_file.clone()));
+            info.insert("source_offset".to_string(), serde_json::Value::Number(serde_json::Number::from(*source_offset)));
+            serde_json::Value::Object(info)
+        }
+        AnomalyType::Custom => {
+            let mut info = serde_json::Map::new();
+            info.insert("type".to_string(), serde_json::Value::String("custom".to_string()));
+            serde_json::Value::Object(info)
+        }
+    };
+    
+    match_obj.insert("anomaly".to_string(), anomaly_info);
+    serde_json::Value::Object(match_obj)
 }
 
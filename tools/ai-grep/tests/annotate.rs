@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A tab-indented line with two findings, a line with a tab between its findings, CRLF
+/// endings, and a last line without a terminator.
+const INPUT: &str = "Intro line\n\
+                     \tAs an AI model, I cannot browse.\n\
+                     // TODO: fix\tthis FIXME\r\n\
+                     last TODO";
+
+fn annotate(style: &str) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("annotate");
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("review.md");
+    fs::write(&input, INPUT).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--code-issues", "--annotate", "--annotate-style", style])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn carets_line_up_under_each_match() {
+    assert_eq!(annotate("caret"), "Intro line\n\
+                                   \tAs an AI model, I cannot browse.\n\
+                                   \t^^^^^^^^ [CRITICAL hallucination/capability_disclaimer]\n\
+                                   \t                ^^^^^^^^ [CRITICAL hallucination/capability_disclaimer]\n\
+                                   // TODO: fix\tthis FIXME\r\n\
+                                   ^^^^^^^ [MEDIUM code_issue/comment_marker]\r\n            \t     ^^^^^ [MEDIUM code_issue/development_marker]\r\n\
+                                   last TODO\n     ^^^^ [MEDIUM code_issue/development_marker]");
+}
+
+#[test]
+fn comment_style_hides_markers_in_markdown() {
+    let stdout = annotate("comment");
+    assert!(stdout.contains("// TODO: fix\tthis FIXME\r\n<!-- ai-grep: MEDIUM code_issue/comment_marker at column 1 -->\r\n"));
+    assert!(stdout.ends_with("last TODO\n<!-- ai-grep: MEDIUM code_issue/development_marker at column 6 -->"));
+}
+
+#[test]
+fn dropping_the_markers_gives_back_the_input() {
+    for style in ["caret", "comment"] {
+        let stdout = annotate(style);
+        let original: String = stdout.split_inclusive('\n')
+            .filter(|line| !line.contains(" [CRITICAL ") && !line.contains(" [MEDIUM ") && !line.starts_with("<!-- ai-grep:"))
+            .collect();
+        // The last line's terminator was only added to separate its markers
+        assert_eq!(original.strip_suffix('\n').unwrap(), INPUT);
+    }
+}
+
+#[test]
+fn unreadable_inputs_fail_with_two() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("annotate_missing");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), "TODO: ship\n").unwrap();
+    
+    for quiet in [false, true] {
+        let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+            .current_dir(&dir)
+            .args(["--code-issues", "--annotate", "missing.txt", "notes.txt"])
+            .args(quiet.then_some("-s"))
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stderr.contains("missing.txt"), !quiet, "{}", stderr);
+        // The inputs that could be read are still annotated
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("TODO: ship\n^^^^ "), "{}", String::from_utf8_lossy(&output.stdout));
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A fresh directory for one test, holding `src.py` with `source`.
+fn workspace(name: &str, source: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("baseline").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("src.py"), source).unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(dir)
+        .args(["--security", "-o"])
+        .args(args)
+        .arg("src.py")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn write_baseline_records_every_finding() {
+    let dir = workspace("write", "result = eval(user_input)\nos.system(cmd)\n");
+    let output = run(&dir, &["--baseline", "baseline.json", "--write-baseline", "--fail-on", "high"]);
+    // Recording a baseline still fails on what it records
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("wrote 2 fingerprint(s) to baseline.json"));
+    
+    let baseline: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("baseline.json")).unwrap()).unwrap();
+    assert_eq!(baseline["version"], 1);
+    let findings = baseline["findings"].as_array().unwrap();
+    let texts: Vec<&str> = findings.iter().map(|f| f["matched_text"].as_str().unwrap()).collect();
+    assert_eq!(texts, vec!["eval(", "system("]);
+    for finding in findings {
+        assert_eq!(finding["file"], "src.py");
+        assert_eq!(finding["category"], "security");
+        assert_eq!(finding["fingerprint"].as_str().unwrap().len(), 16);
+    }
+}
+
+#[test]
+fn baseline_suppresses_known_findings_and_fails_on_new_ones() {
+    let dir = workspace("suppress", "result = eval(user_input)\n");
+    assert_eq!(run(&dir, &["--fail-on", "high"]).status.code(), Some(1));
+    assert!(run(&dir, &["--baseline", "baseline.json", "--write-baseline"]).status.success());
+    
+    let output = run(&dir, &["--baseline", "baseline.json", "--fail-on", "high"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 known finding(s) suppressed by baseline"));
+    
+    // Moving the known finding to another line keeps it suppressed; a new one is reported
+    // and fails the run
+    fs::write(dir.join("src.py"), "import os\nresult = eval(user_input)\nos.system(cmd)\n").unwrap();
+    let output = run(&dir, &["--baseline", "baseline.json", "--fail-on", "high"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "system(\n");
+    
+    // Only findings at or above the --fail-on severity count
+    assert_eq!(run(&dir, &["--baseline", "baseline.json", "--fail-on", "critical"]).status.code(), Some(0));
+    
+    // The same text on a changed line is a new finding
+    fs::write(dir.join("src.py"), "value = eval(other_input)\n").unwrap();
+    assert_eq!(run(&dir, &["--baseline", "baseline.json", "--fail-on", "high"]).status.code(), Some(1));
+}
+
+#[test]
+fn unreadable_baselines_are_errors() {
+    let dir = workspace("errors", "result = eval(user_input)\n");
+    let output = run(&dir, &["--baseline", "missing.json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot read baseline missing.json"));
+    
+    fs::write(dir.join("empty.json"), "{}").unwrap();
+    let output = run(&dir, &["--baseline", "empty.json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no \"findings\" array"));
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn write_inputs() -> (PathBuf, PathBuf) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("binary_files");
+    fs::create_dir_all(&dir).unwrap();
+    
+    let binary = dir.join("artifact.o");
+    fs::write(&binary, b"\x7fELF\x00\x00\x01 password = \"hunter2\" \xff\n").unwrap();
+    // Invalid UTF-8 but no NUL: still text
+    let latin1 = dir.join("notes.txt");
+    fs::write(&latin1, b"caf\xe9 config: password = \"s3cret\"\n").unwrap();
+    (binary, latin1)
+}
+
+fn run(extra: &[&str]) -> String {
+    let (binary, latin1) = write_inputs();
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .arg("--security")
+        .args(extra)
+        .args([&binary, &latin1])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn binary_files_report_only_that_they_match() {
+    let output = run(&[]);
+    assert!(output.lines().next().unwrap().starts_with("Binary file ") && output.contains("artifact.o matches"));
+    assert!(!output.contains("hunter2"));
+    // Lossy decoding keeps the rest of a text file with bad bytes
+    assert!(output.contains("caf\u{FFFD} config: password = \"s3cret\""));
+}
+
+#[test]
+fn binary_files_modes() {
+    let as_text = run(&["--binary-files", "text"]);
+    assert!(as_text.contains("password = \"hunter2\""));
+    assert!(!as_text.contains("Binary file"));
+    
+    let skipped = run(&["--binary-files", "without-match", "-c"]);
+    let counts: Vec<&str> = skipped.lines().map(|line| line.rsplit(':').next().unwrap()).collect();
+    assert_eq!(counts, ["0", "1"]);
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const PATTERN: &str = r#"(?P<key>api_key|token)\s*=\s*(?P<quote>")?(?P<value>\w+)"#;
+
+fn run(extra: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("captures");
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("config.txt");
+    fs::write(&input, "api_key = \"abc123\"\nretry: token = xyz\n").unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["-E", PATTERN])
+        .args(extra)
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn ndjson_matches_carry_named_captures() {
+    let stdout = run(&["--format", "ndjson"]);
+    let matches: Vec<serde_json::Value> = stdout.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(matches.len(), 2);
+    
+    let first = &matches[0]["captures"];
+    assert_eq!(first["key"], serde_json::json!({"text": "api_key", "start": 0, "end": 7}));
+    assert_eq!(first["quote"]["text"], "\"");
+    assert_eq!(first["value"]["text"], "abc123");
+    
+    let second = &matches[1]["captures"];
+    assert_eq!(second["key"], serde_json::json!({"text": "token", "start": 7, "end": 12}));
+    assert!(second["quote"].is_null());
+    assert_eq!(second["value"]["text"], "xyz");
+}
+
+#[test]
+fn show_captures_lists_groups_in_text_output() {
+    let stdout = run(&["--show-captures"]);
+    assert!(stdout.contains(r#"Captures: key="api_key" quote="\"" value="abc123""#), "{stdout}");
+    assert!(stdout.contains(r#"Captures: key="token" quote=null value="xyz""#), "{stdout}");
+    
+    assert!(!run(&[]).contains("Captures:"));
+}
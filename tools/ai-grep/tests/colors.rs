@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/colors")
+}
+
+/// Run on the mixed-severity fixture with ESC written as `\e`, as in the snapshots. Overlapping
+/// hits are kept so every severity shows up.
+fn run(args: &[&str], envs: &[(&str, &str)]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(fixtures())
+        .args(["--hallucinations", "--low-confidence", "-n", "--no-dedupe"])
+        .args(args)
+        .arg("mixed.txt")
+        .env_remove("NO_COLOR")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().replace('\x1b', "\\e")
+}
+
+fn snapshot(name: &str) -> String {
+    fs::read_to_string(fixtures().join(name)).unwrap()
+}
+
+#[test]
+fn always_colors_each_severity_even_when_piped() {
+    let stdout = run(&["--code-issues", "--data-leakage", "--severity", "--color=always"], &[]);
+    assert_eq!(stdout, snapshot("default.snap"));
+    
+    // Forcing color wins over NO_COLOR
+    let forced = run(&["--code-issues", "--data-leakage", "--severity", "--color=always"], &[("NO_COLOR", "1")]);
+    assert_eq!(forced, stdout);
+}
+
+#[test]
+fn colors_spec_overrides_defaults() {
+    let stdout = run(&["--data-leakage", "--color=always", "--colors", "critical=35;1:low=:line_no=1;32"], &[]);
+    assert_eq!(stdout, snapshot("custom.snap"));
+}
+
+#[test]
+fn auto_is_plain_when_piped() {
+    assert!(!run(&["--color=auto"], &[]).contains("\\e["));
+    assert!(!run(&["--color=auto"], &[("NO_COLOR", "1")]).contains("\\e["));
+}
+
+#[test]
+fn severities_are_text_tags_without_color() {
+    let stdout = run(&["--severity", "--stats", "--color=never"], &[]);
+    assert!(stdout.contains("  [CRITICAL] Severity: Critical (1.00)\n"), "{}", stdout);
+    assert!(stdout.contains("  [CRITICAL]           "), "{}", stdout);
+    assert!(stdout.is_ascii(), "{}", stdout);
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// "é" and "ü" are two bytes each, and the first line ends in CRLF.
+const INPUT: &str = "héllo TODO x\r\nplain\nünï FIXME\n";
+
+fn run(extra: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("column");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), INPUT).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .args(["-E", "TODO|FIXME"])
+        .args(extra)
+        .arg("notes.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn column_counts_characters_not_bytes() {
+    assert_eq!(run(&["--column"]), "1:7:héllo TODO x\n3:5:ünï FIXME\n");
+}
+
+#[test]
+fn byte_offset_is_absolute_in_the_file() {
+    let todo = INPUT.find("TODO").unwrap();
+    let fixme = INPUT.find("FIXME").unwrap();
+    assert_eq!(run(&["-b"]), format!("{}:héllo TODO x\n{}:ünï FIXME\n", todo, fixme));
+    assert_eq!(run(&["-n", "--column", "-b"]), format!("1:7:{}:héllo TODO x\n3:5:{}:ünï FIXME\n", todo, fixme));
+    
+    let stdout = run(&["--format", "ndjson"]);
+    let offsets: Vec<u64> = stdout.lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["absolute_offset"].as_u64().unwrap())
+        .collect();
+    assert_eq!(offsets, [todo as u64, fixme as u64]);
+}
+
+#[test]
+fn prefix_is_colored_like_line_numbers_and_match_is_highlighted() {
+    let stdout = run(&["--column", "-b", "--color=always"]);
+    let first = stdout.lines().next().unwrap();
+    assert_eq!(first, "\x1b[32m1\x1b[0m:\x1b[32m7\x1b[0m:\x1b[32m7\x1b[0m:héllo \x1b[33mTODO\x1b[0m x");
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use flate2::write::GzEncoder;
+
+const LOG: &str = "starting up\nTODO: rotate these logs\nshutting down\nFIXME: and compress them\n";
+
+fn gzip(text: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(text.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// A directory holding gzip and zstd copies of LOG, a copy with no extension, and a
+/// corrupt archive.
+fn logs() -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compressed");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("app.log.gz"), gzip(LOG)).unwrap();
+    fs::write(dir.join("app.log.zst"), zstd::encode_all(LOG.as_bytes(), 0).unwrap()).unwrap();
+    fs::write(dir.join("rotated"), gzip(LOG)).unwrap();
+    fs::write(dir.join("broken.log.gz"), b"\x1f\x8b\x08\x00 not really gzip").unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(dir)
+        .arg("--code-issues")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn archives_are_scanned_under_their_own_names() {
+    let dir = logs();
+    let output = run(&dir, &["-n", "-o", "app.log.gz", "app.log.zst"], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "app.log.gz:2:TODO\napp.log.gz:4:FIXME\napp.log.zst:2:TODO\napp.log.zst:4:FIXME\n"
+    );
+}
+
+#[test]
+fn magic_bytes_are_only_trusted_with_decompress_auto() {
+    let dir = logs();
+    assert_eq!(String::from_utf8_lossy(&run(&dir, &["-c", "rotated"], b"").stdout), "0\n");
+    assert_eq!(String::from_utf8_lossy(&run(&dir, &["-c", "--decompress", "auto", "rotated"], b"").stdout), "2\n");
+    assert_eq!(String::from_utf8_lossy(&run(&dir, &["-c", "--decompress", "auto"], &gzip(LOG)).stdout), "2\n");
+    assert_eq!(String::from_utf8_lossy(&run(&dir, &["-c", "--decompress", "never", "app.log.gz"], b"").stdout), "0\n");
+}
+
+#[test]
+fn a_corrupt_archive_does_not_stop_the_others() {
+    let dir = logs();
+    let output = run(&dir, &["-c", "broken.log.gz", "app.log.gz"], b"");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with("app.log.gz:2\n"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("broken.log.gz"));
+    
+    let output = run(&dir, &["-c", "-s", "broken.log.gz", "app.log.gz"], b"");
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
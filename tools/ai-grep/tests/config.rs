@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const INPUT: &str = "password = \"hunter22\"\n// TODO fix\nmail bob@example.com\n";
+
+const CONFIG: &str = "min-severity = \"high\"\n\
+                      line-number = true\n\
+                      with-filename = true\n\
+                      severity-override = [\"email=critical\"]\n\
+                      annotate = true\n";
+
+/// Run ai-grep in `project/src` of a fresh directory, with `local` as project/ai-grep.toml
+/// and `user` as the config under a private HOME.
+fn run(name: &str, local: Option<&str>, user: Option<&str>, args: &[&str]) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("config").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("project/src")).unwrap();
+    fs::create_dir_all(dir.join("home/.config/ai-toolkit")).unwrap();
+    fs::write(dir.join("project/src/input.txt"), INPUT).unwrap();
+    if let Some(local) = local {
+        fs::write(dir.join("project/ai-grep.toml"), local).unwrap();
+    }
+    if let Some(user) = user {
+        fs::write(dir.join("home/.config/ai-toolkit/ai-grep.toml"), user).unwrap();
+    }
+    
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(dir.join("project/src"))
+        .env("HOME", dir.join("home"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn config_in_a_parent_directory_sets_defaults() {
+    let local = "min-severity = \"high\"\nline-number = true\nwith-filename = true\nseverity-override = [\"email=critical\"]\n";
+    assert_eq!(stdout(run("parent", Some(local), None, &["--security", "--code-issues", "--data-leakage", "input.txt"])),
+               "input.txt:1:password = \"hunter22\"\n  \
+                Security concern: hardcoded_password\n\
+                \n\
+                input.txt:3:mail bob@example.com\n  \
+                Data leakage: email\n");
+}
+
+#[test]
+fn command_line_flags_win() {
+    let args = ["--print-config", "--min-severity", "low", "-h", "--severity-override", "email=low", "--redact"];
+    let output = stdout(run("precedence", Some(CONFIG), None, &args));
+    let settings: Vec<&str> = output.lines().skip(1).collect();
+    // The flags replace their config values, -h overrides with-filename, the repeated
+    // option replaces the whole list, and --redact drops the conflicting annotate
+    assert_eq!(settings, [
+        "severity-override = [\"email=low\"]  # command line",
+        "min-severity = \"low\"  # command line",
+        "line-number = true  # config file",
+        "no-filename = true  # command line",
+        "redact = true  # command line",
+    ]);
+    
+    // Without that, annotate from the config would make a --redact run fail
+    let redacted = stdout(run("precedence", Some(CONFIG), None, &["--security", "--redact", "input.txt"]));
+    assert!(redacted.contains("mail [REDACTED:email]"), "{}", redacted);
+}
+
+#[test]
+fn false_leaves_a_flag_unset() {
+    let config = "line-number = false\nwith-filename = true\n";
+    let output = stdout(run("false", Some(config), None, &["--code-issues", "input.txt"]));
+    assert!(output.starts_with("input.txt:// TODO fix\n"), "{}", output);
+}
+
+#[test]
+fn user_config_is_the_fallback_and_no_config_skips_both() {
+    let user = "line-number = true\n";
+    let output = stdout(run("user", None, Some(user), &["--code-issues", "input.txt"]));
+    assert!(output.starts_with("2:// TODO fix\n"), "{}", output);
+    
+    // A project config shadows the user's
+    let output = stdout(run("user", Some("with-filename = true\n"), Some(user), &["--code-issues", "input.txt"]));
+    assert!(output.starts_with("input.txt:// TODO fix\n"), "{}", output);
+    
+    let output = stdout(run("user", Some("with-filename = true\n"), Some(user), &["--no-config", "--code-issues", "input.txt"]));
+    assert!(output.starts_with("// TODO fix\n"), "{}", output);
+    assert_eq!(stdout(run("user", None, Some(user), &["--no-config", "--print-config"])), "# config file: disabled by --no-config\n");
+}
+
+#[test]
+fn unknown_keys_warn_and_bad_values_fail() {
+    let output = run("unknown", Some("colour = \"always\"\nline-number = true\n"), None, &["--code-issues", "input.txt"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown option `colour`"));
+    assert!(stdout(output).starts_with("2:// TODO fix\n"));
+    
+    let output = run("bad", Some("line-number = \"yes\"\n"), None, &["--code-issues", "input.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`line-number`: expected true or false"));
+    
+    let output = run("bad", Some("min-severity = \"hgh\"\n"), None, &["--code-issues", "input.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid value 'hgh' for '--min-severity"));
+}
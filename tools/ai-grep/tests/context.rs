@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/context")
+}
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(fixtures())
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Each `.out` file was recorded with GNU grep 3.8 from the same flags, e.g.
+/// `grep -n -A 2 -B 1 -E TODO notes.txt todo.txt > n_A2_B1_files.out`.
+fn golden(name: &str) -> String {
+    fs::read_to_string(fixtures().join(name)).unwrap()
+}
+
+#[test]
+fn after_context_matches_grep() {
+    assert_eq!(run(&["-A", "1", "-E", "TODO", "notes.txt"]), golden("A1.out"));
+}
+
+#[test]
+fn before_context_matches_grep() {
+    assert_eq!(run(&["-B", "2", "-E", "TODO", "notes.txt"]), golden("B2.out"));
+}
+
+#[test]
+fn overlapping_windows_merge_into_one_hunk() {
+    assert_eq!(run(&["-n", "-C", "3", "-E", "TODO", "notes.txt"]), golden("n_C3.out"));
+}
+
+#[test]
+fn zero_context_still_separates_hunks() {
+    assert_eq!(run(&["-C", "0", "-E", "TODO", "notes.txt"]), golden("C0.out"));
+}
+
+#[test]
+fn touching_windows_merge_and_files_are_separated() {
+    let stdout = run(&["-n", "-A", "2", "-B", "1", "-E", "TODO", "notes.txt", "todo.txt"]);
+    assert_eq!(stdout, golden("n_A2_B1_files.out"));
+}
+
+#[test]
+fn inverted_matches_take_matching_lines_as_context() {
+    assert_eq!(run(&["-v", "-C", "1", "-E", "TODO", "todo.txt"]), golden("v_C1.out"));
+}
+
+#[test]
+fn json_carries_context_and_hunk_ids() {
+    let stdout = run(&["--format", "ndjson", "-n", "-C", "1", "-E", "TODO", "todo.txt"]);
+    let matches: Vec<serde_json::Value> = stdout.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    
+    // "TODO two TODO" is one line with two matches, both in the second hunk
+    let hunks: Vec<u64> = matches.iter().map(|m| m["hunk_id"].as_u64().unwrap()).collect();
+    assert_eq!(hunks, vec![0, 1, 1]);
+    assert_eq!(matches[0]["context_before"], serde_json::json!([{ "line_number": 1, "content": "alpha" }]));
+    assert_eq!(matches[0]["context_after"], serde_json::json!([{ "line_number": 3, "content": "beta" }]));
+    assert_eq!(matches[2]["context_before"], serde_json::json!([{ "line_number": 5, "content": "delta" }]));
+    assert_eq!(matches[2]["context_after"], serde_json::json!([{ "line_number": 7, "content": "epsilon" }]));
+    
+    // Without -A/-B/-C the fields are left out
+    let plain: serde_json::Value = serde_json::from_str(run(&["--format", "ndjson", "-E", "TODO", "todo.txt"]).lines().next().unwrap()).unwrap();
+    assert!(plain.get("hunk_id").is_none());
+    assert!(plain.get("context_before").is_none());
+}
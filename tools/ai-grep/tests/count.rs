@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+fn count(args: &[&str], fixture: &str) -> usize {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .arg("--code-issues")
+        .args(args)
+        .arg(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/count").join(fixture))
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap().trim().parse().unwrap()
+}
+
+#[test]
+fn count_is_matching_lines_like_grep() {
+    // Line 2 holds three TODOs, line 4 a FIXME
+    assert_eq!(count(&["-c"], "todos.rs"), 2);
+    assert_eq!(count(&["--count-matches"], "todos.rs"), 4);
+    assert_eq!(count(&["-c", "-v"], "todos.rs"), 3);
+}
+
+#[test]
+fn empty_input_counts_zero() {
+    for args in [&["-c"][..], &["-c", "-v"], &["--count-matches"]] {
+        assert_eq!(count(args, "empty.txt"), 0, "{:?}", args);
+        
+        let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+            .arg("--code-issues")
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\n", "{:?} on stdin", args);
+    }
+}
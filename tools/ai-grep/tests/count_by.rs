@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/count").join(name)
+}
+
+fn run(args: &[&str], files: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--security", "--low-confidence"])
+        .args(args)
+        .args(files.iter().map(|name| fixture(name)))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn breakdown_rows_and_totals() {
+    let output = run(&["--count-by", "category"], &["answer.txt", "notes.txt"]);
+    let lines: Vec<&str> = output.lines().collect();
+    
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("answer.txt: hallucination:2 low_confidence:1 security:1"), "{}", lines[0]);
+    assert!(lines[1].ends_with("notes.txt: low_confidence:1"), "{}", lines[1]);
+    assert_eq!(lines[2], "total: hallucination:2 low_confidence:2 security:1");
+}
+
+#[test]
+fn breakdown_agrees_with_count() {
+    for extra in [&[][..], &["--count-matches"], &["--min-severity", "medium"], &["-v"]] {
+        let count: usize = run(&[extra, &["-c"][..]].concat(), &["answer.txt"]).trim().parse().unwrap();
+        let by_file = run(&[extra, &["--count-by", "file"][..]].concat(), &["answer.txt"]);
+        assert!(by_file.trim().ends_with(&format!("answer.txt:{}", count)), "{:?}: {}", extra, by_file);
+    }
+}
+
+#[test]
+fn json_breakdown_is_one_document() {
+    let output = run(&["--count-by", "severity", "--format", "json"], &["answer.txt", "notes.txt"]);
+    let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+    
+    assert_eq!(json["count_by"], "severity");
+    assert_eq!(json["files"].as_array().unwrap().len(), 2);
+    assert_eq!(json["totals"]["critical"], 2);
+    assert_eq!(json["files"][1]["counts"]["low"], 1);
+}
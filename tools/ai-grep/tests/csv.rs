@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/csv").join(name)
+}
+
+fn run(extra: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--data-leakage", "--format", "csv"])
+        .args(extra)
+        .arg(fixture("contacts.txt"))
+        .arg(fixture("notes.txt"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn csv_round_trips_through_reader() {
+    let output = run(&[]);
+    let mut reader = csv::Reader::from_reader(output.as_bytes());
+    
+    let header: Vec<String> = reader.headers().unwrap().iter().map(str::to_string).collect();
+    assert_eq!(header, ["file", "line", "column", "category", "subtype", "severity", "severity_score", "matched_text", "content"]);
+    
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    let emails: Vec<&str> = rows.iter().filter(|row| &row[4] == "email").map(|row| &row[7]).collect();
+    assert_eq!(emails, ["jane.smith@example.com", "ops@example.org", "bob@example.com"]);
+    
+    let contacts = std::fs::read_to_string(fixture("contacts.txt")).unwrap();
+    let first_line = contacts.lines().next().unwrap();
+    let ssn = rows.iter().find(|row| &row[4] == "ssn").unwrap();
+    assert_eq!(&ssn[1], "3");
+    assert_eq!(&ssn[8], contacts.lines().nth(2).unwrap());
+    assert!(ssn[8].contains('\r'));
+    
+    let jane = rows.iter().find(|row| &row[7] == "jane.smith@example.com").unwrap();
+    assert_eq!(&jane[2], "25");
+    assert_eq!(&jane[8], first_line);
+}
+
+#[test]
+fn multi_file_run_is_one_stream() {
+    let output = run(&[]);
+    assert_eq!(output.lines().filter(|line| line.starts_with("file,")).count(), 1);
+    
+    let headerless = run(&["--no-header"]);
+    assert_eq!(output.strip_prefix("file,line,column,category,subtype,severity,severity_score,matched_text,content\n"), Some(headerless.as_str()));
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// One line per overlap case:
+/// 1. same category, different severity: "I cannot" (critical) inside "I cannot browse" (high)
+///    and "cannot browse" (high)
+/// 2. same category and severity: "TODO" inside the longer comment marker "// TODO"
+/// 3. different categories: "unconfirmed" is both hallucination uncertainty and low-confidence
+///    verification_issue
+/// 4. same category without overlap: "TODO" and "remove" are both kept
+const INPUT: &str = "As an AI model I cannot browse the web.\n\
+                     // TODO tidy up\n\
+                     The figures are unconfirmed.\n\
+                     TODO: remove the flag\n";
+
+/// (line, subtype, matched text, overlapping categories) of every NDJSON match.
+fn run(extra: &[&str]) -> Vec<(u64, String, String, Vec<String>)> {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("dedupe");
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("overlaps.txt");
+    fs::write(&input, INPUT).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--code-issues", "--low-confidence", "--format", "ndjson"])
+        .args(extra)
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    
+    String::from_utf8(output.stdout).unwrap().lines()
+        .map(|line| {
+            let m: serde_json::Value = serde_json::from_str(line).unwrap();
+            let anomaly = &m["anomaly"];
+            let subtype = ["marker_type", "issue_type", "confidence_marker"].iter()
+                .find_map(|key| anomaly[key].as_str())
+                .unwrap()
+                .to_string();
+            let overlaps = m["overlaps"].as_array()
+                .map(|categories| categories.iter().map(|c| c.as_str().unwrap().to_string()).collect())
+                .unwrap_or_default();
+            (m["line_number"].as_u64().unwrap(), subtype, m["matched_text"].as_str().unwrap().to_string(), overlaps)
+        })
+        .collect()
+}
+
+fn finding(line: u64, subtype: &str, text: &str, overlaps: &[&str]) -> (u64, String, String, Vec<String>) {
+    (line, subtype.to_string(), text.to_string(), overlaps.iter().map(|c| c.to_string()).collect())
+}
+
+#[test]
+fn overlapping_matches_are_deduplicated_per_category() {
+    assert_eq!(run(&[]), vec![
+        finding(1, "capability_disclaimer", "As an AI", &[]),
+        finding(1, "capability_disclaimer", "I cannot", &[]),
+        finding(2, "comment_marker", "// TODO", &[]),
+        finding(3, "uncertainty", "unconfirmed", &["low_confidence"]),
+        finding(3, "verification_issue", "unconfirmed", &["hallucination"]),
+        finding(4, "development_marker", "TODO", &[]),
+        finding(4, "deprecation", "remove", &[]),
+    ]);
+}
+
+#[test]
+fn no_dedupe_reports_every_hit() {
+    let raw = run(&["--no-dedupe"]);
+    assert_eq!(raw.len(), 10);
+    assert!(raw.contains(&finding(1, "capability_disclaimer", "I cannot browse", &[])));
+    assert!(raw.contains(&finding(1, "browsing_limitation", "cannot browse", &[])));
+    assert!(raw.contains(&finding(2, "development_marker", "TODO", &[])));
+    assert!(raw.iter().all(|(_, _, _, overlaps)| overlaps.is_empty()));
+}
+
+#[test]
+fn counts_follow_deduplication() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("dedupe");
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("count.txt");
+    fs::write(&input, INPUT).unwrap();
+    
+    let count = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+            .args(["--hallucinations", "--code-issues", "--low-confidence", "--count-matches", "-c"])
+            .args(extra)
+            .arg(&input)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    assert_eq!(count(&[]), "7");
+    assert_eq!(count(&["--no-dedupe"]), "10");
+}
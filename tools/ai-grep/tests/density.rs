@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A confident paragraph, a hedge-heavy one over two lines, and one with a single hedge
+/// in 20 words (5 per 100).
+const INPUT: &str = "The capital of France is Paris. It has been the capital for centuries\n\
+                     and hosts many government institutions and museums of world renown.\n\
+                     \n\
+                     I think this might possibly work, but it may be wrong.\n\
+                     Perhaps we should probably check. It might not.\n\
+                     \n\
+                     \n\
+                     The old bridge opened in 1932 and it is perhaps the best known landmark of the busy harbour city today.\n";
+
+fn run(extra: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("density");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("answer.txt"), INPUT).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .args(["--low-confidence", "--density"])
+        .args(extra)
+        .arg("answer.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn only_dense_paragraphs_are_reported() {
+    assert_eq!(run(&[]), "4-5: 31.6 hedges/100 words: I think this might possibly work, but it may be wrong.\n  \
+                          Hedges: I think, might (2), possibly, Perhaps, probably\n\
+                          \n\
+                          8: 5.0 hedges/100 words: The old bridge opened in 1932 and it is perhaps the best known landmark of the busy harbour city today.\n  \
+                          Hedges: perhaps\n");
+}
+
+#[test]
+fn threshold_drops_sparse_paragraphs() {
+    let stdout = run(&["--density-threshold", "5"]);
+    assert!(stdout.starts_with("4-5: "));
+    assert!(!stdout.contains("\n8: "));
+}
+
+#[test]
+fn json_has_one_record_per_paragraph() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--format", "json", "--density-threshold", "10"])).unwrap();
+    assert_eq!(doc["paragraph_count"], 1);
+    let paragraph = &doc["paragraphs"][0];
+    assert_eq!((paragraph["start_line"].as_u64(), paragraph["end_line"].as_u64()), (Some(4), Some(5)));
+    assert_eq!(paragraph["words"], 19);
+    assert_eq!(paragraph["hedge_count"], 6);
+    assert_eq!(paragraph["markers"][5], serde_json::json!({
+        "text": "might",
+        "confidence_marker": "uncertainty",
+        "line_number": 5,
+        "column": 38,
+    }));
+}
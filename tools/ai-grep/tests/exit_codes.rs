@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(extra: &[&str], files: &[&str]) -> Output {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/severity");
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(fixtures)
+        .args(["--code-issues", "--hallucinations"])
+        .args(extra)
+        .args(files)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn status_is_the_highest_severity_found() {
+    for (fixture, code) in [("none.txt", 0), ("low.txt", 10), ("medium.txt", 11), ("high.txt", 12), ("critical.txt", 13)] {
+        let output = run(&["--severity-exit-codes"], &[fixture]);
+        assert_eq!(output.status.code(), Some(code), "{}", fixture);
+    }
+    
+    // Across several inputs the most severe finding of any of them counts
+    assert_eq!(run(&["--severity-exit-codes"], &["low.txt", "high.txt", "none.txt"]).status.code(), Some(12));
+    // --min-severity drops findings before the status is worked out
+    assert_eq!(run(&["--severity-exit-codes", "--min-severity", "high"], &["medium.txt"]).status.code(), Some(0));
+}
+
+#[test]
+fn min_severity_applies_to_counts_and_fail_on() {
+    let output = run(&["-c", "--min-severity", "high"], &["low.txt", "medium.txt", "high.txt", "critical.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "low.txt:0\nmedium.txt:0\nhigh.txt:1\ncritical.txt:1\n");
+    
+    assert_eq!(run(&["--fail-on", "high"], &["high.txt"]).status.code(), Some(1));
+    assert_eq!(run(&["--fail-on", "high", "--min-severity", "critical"], &["high.txt"]).status.code(), Some(0));
+}
+
+#[test]
+fn errors_exit_with_two() {
+    let output = run(&["--severity-exit-codes"], &["critical.txt", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.txt"));
+    
+    assert_eq!(run(&["--severity-exit-codes", "--json-input=choices["], &["low.txt"]).status.code(), Some(2));
+    assert_eq!(run(&["--json-input=choices["], &["low.txt"]).status.code(), Some(1));
+}
+
+#[test]
+fn default_status_is_unchanged() {
+    for fixture in ["none.txt", "critical.txt"] {
+        assert_eq!(run(&[], &[fixture]).status.code(), Some(0));
+    }
+}
+
+#[test]
+fn quiet_prints_nothing() {
+    let output = run(&["-q", "--severity-exit-codes", "--stats"], &["medium.txt"]);
+    assert_eq!(output.status.code(), Some(11));
+    assert!(output.stdout.is_empty());
+    
+    let output = run(&["--quiet", "--fail-on", "critical", "--format", "csv"], &["critical.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run(args: &[&str], stdin: &[u8]) -> std::process::Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn nul_separated_list_matches_positional_files() {
+    let dir = scratch_dir("files_from_nul");
+    let spaced = dir.join("with space.txt");
+    let newline = dir.join("new\nline.txt");
+    fs::write(&spaced, "As an AI, I cannot browse.\n").unwrap();
+    fs::write(&newline, "My last update was in 2021.\n").unwrap();
+    
+    let list = [&spaced, &newline].iter()
+        .map(|path| path.to_str().unwrap())
+        .collect::<Vec<_>>()
+        .join("\0");
+    let from_list = run(&["--hallucinations", "--stats", "-0", "--files-from", "-"], list.as_bytes());
+    assert!(from_list.status.success(), "{}", String::from_utf8_lossy(&from_list.stderr));
+    
+    let positional = run(&["--hallucinations", "--stats", spaced.to_str().unwrap(), newline.to_str().unwrap()], b"");
+    assert_eq!(String::from_utf8_lossy(&from_list.stdout), String::from_utf8_lossy(&positional.stdout));
+    assert!(String::from_utf8_lossy(&from_list.stdout).contains("Files scanned: 2"));
+}
+
+#[test]
+fn list_and_positional_files_conflict() {
+    let dir = scratch_dir("files_from_conflict");
+    let file = dir.join("a.txt");
+    fs::write(&file, "as an ai\n").unwrap();
+    
+    let output = run(&["--hallucinations", "--files-from", "-", file.to_str().unwrap()], file.to_str().unwrap().as_bytes());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--files-from cannot be combined"));
+}
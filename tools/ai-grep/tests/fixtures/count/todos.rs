@@ -0,0 +1,5 @@
+fn parse() {
+    // TODO: handle errors TODO: trim input TODO: support tabs
+    let x = 1;
+    // FIXME: overflow
+}
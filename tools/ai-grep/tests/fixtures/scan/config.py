@@ -0,0 +1,4 @@
+# TODO: move these out of source control
+password = "hunter2"
+api_key = "sk-ant-REDACTED"
+result = eval(user_input)
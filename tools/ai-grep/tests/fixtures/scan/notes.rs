@@ -0,0 +1,5 @@
+// FIXME: this is a temporary workaround
+fn copy(dst: &mut [u8], src: &[u8]) {
+    // HACK: unsafe memcpy until the legacy path is removed
+    dst.copy_from_slice(src);
+}
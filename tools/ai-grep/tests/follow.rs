@@ -0,0 +1,125 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// ai-grep following app.log in a directory, with its output lines delivered as they are
+/// printed.
+struct Follower {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Follower {
+    /// Start following, and wait until app.log has been opened: ai-grep also follows a file
+    /// that is never created, and reports it missing only after opening the ones before it.
+    fn start(dir: &Path) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+            .current_dir(dir)
+            .args(["--code-issues", "-o", "--follow", "app.log", "started.log"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let lines = forward(BufReader::new(child.stdout.take().unwrap()));
+        let messages = forward(BufReader::new(child.stderr.take().unwrap()));
+        while !messages.recv_timeout(Duration::from_secs(10)).expect("--follow did not start").starts_with("ai-grep: started.log:") {}
+        // Keep reading stderr so later messages cannot fill the pipe
+        thread::spawn(move || messages.into_iter().for_each(drop));
+        Follower { child, lines }
+    }
+    
+    fn next_line(&self) -> String {
+        self.lines.recv_timeout(Duration::from_secs(10)).expect("no output from --follow")
+    }
+}
+
+/// The lines of `reader`, sent as they are read.
+fn forward(reader: impl BufRead + Send + 'static) -> Receiver<String> {
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    lines
+}
+
+impl Drop for Follower {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn workspace(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("follow").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn append(path: &Path, text: &str) {
+    OpenOptions::new().create(true).append(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+}
+
+#[test]
+fn appended_lines_are_reported_once_complete() {
+    let dir = workspace("append");
+    // Not there yet, so it is read from its start once it appears
+    let follower = Follower::start(&dir);
+    
+    append(&dir.join("app.log"), "starting\nFIXME: first\n");
+    assert_eq!(follower.next_line(), "app.log:FIXME");
+    
+    append(&dir.join("app.log"), "TO");
+    thread::sleep(Duration::from_millis(500));
+    append(&dir.join("app.log"), "DO: second\nHACK: third\n");
+    assert_eq!(follower.next_line(), "app.log:TODO");
+    assert_eq!(follower.next_line(), "app.log:HACK");
+}
+
+#[test]
+fn existing_content_is_skipped() {
+    let dir = workspace("existing");
+    fs::write(dir.join("app.log"), "TODO: already there\n").unwrap();
+    let follower = Follower::start(&dir);
+    
+    append(&dir.join("app.log"), "FIXME: new\n");
+    assert_eq!(follower.next_line(), "app.log:FIXME");
+}
+
+#[test]
+fn truncated_files_are_read_again_from_the_start() {
+    let dir = workspace("truncate");
+    let follower = Follower::start(&dir);
+    append(&dir.join("app.log"), "a long first line before the marker\nTODO: before truncation\n");
+    assert_eq!(follower.next_line(), "app.log:TODO");
+    
+    fs::write(dir.join("app.log"), "HACK: after\n").unwrap();
+    assert_eq!(follower.next_line(), "app.log:HACK");
+}
+
+#[test]
+fn rotated_files_are_drained_then_replaced() {
+    let dir = workspace("rotate");
+    let follower = Follower::start(&dir);
+    append(&dir.join("app.log"), "TODO: before rotation\n");
+    assert_eq!(follower.next_line(), "app.log:TODO");
+    
+    // The last lines of the old file come before anything in the new one
+    append(&dir.join("app.log"), "FIXME: last of the old file\n");
+    fs::rename(dir.join("app.log"), dir.join("app.log.1")).unwrap();
+    fs::write(dir.join("app.log"), "HACK: first of the new file\n").unwrap();
+    assert_eq!(follower.next_line(), "app.log:FIXME");
+    assert_eq!(follower.next_line(), "app.log:HACK");
+    
+    append(&dir.join("app.log.1"), "TODO: written to the old file too late\n");
+    append(&dir.join("app.log"), "XXX: still following the new file\n");
+    assert_eq!(follower.next_line(), "app.log:XXX");
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Seven generated files leaking the same key, and the same address in different case.
+fn run(extra: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("group_duplicates");
+    fs::create_dir_all(dir.join("out")).unwrap();
+    for i in 1..=7 {
+        let email = if i % 2 == 0 { "Bob@Example.com" } else { "bob@example.com" };
+        fs::write(dir.join(format!("out/{}.txt", i)), format!("Use sk-ant-REDACTED here.\nWrite to {}\n", email)).unwrap();
+    }
+    fs::write(dir.join("out/8.txt"), "\n\nwrite to BOB@EXAMPLE.COM or alice@example.com\n").unwrap();
+    
+    let files: Vec<String> = (1..=8).map(|i| format!("out/{}.txt", i)).collect();
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .args(["--no-config", "--secrets", "--data-leakage", "--group-duplicates"])
+        .args(extra)
+        .args(&files)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn each_distinct_finding_is_printed_once() {
+    assert_eq!(run(&[]), "[MEDIUM data_leakage/email] bob@example.com (8 occurrences)\n  \
+                          out/1.txt:2, out/2.txt:2, out/3.txt:2, out/4.txt:2, out/5.txt:2, +3 more\n\
+                          \n\
+                          [CRITICAL secret/llm_api_key] sk-ant-REDACTED (7 occurrences)\n  \
+                          out/1.txt:1, out/2.txt:1, out/3.txt:1, out/4.txt:1, out/5.txt:1, +2 more\n\
+                          \n\
+                          [MEDIUM data_leakage/email] alice@example.com (1 occurrence)\n  \
+                          out/8.txt:3\n");
+}
+
+#[test]
+fn parallel_scans_group_the_same_way() {
+    assert_eq!(run(&["-j", "4", "--unordered"]), run(&[]));
+}
+
+#[test]
+fn json_lists_every_location() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--format", "json"])).unwrap();
+    assert_eq!(doc["group_count"], 3);
+    let email = &doc["duplicates"][0];
+    assert_eq!(email["anomaly"], serde_json::json!({ "type": "data_leakage", "leak_type": "email" }));
+    assert_eq!(email["count"], 8);
+    assert_eq!(email["locations"].as_array().unwrap().len(), 8);
+    assert_eq!(email["locations"][7], serde_json::json!({ "file": "out/8.txt", "line_number": 3, "column": 10 }));
+    
+    let ndjson = run(&["--format", "ndjson"]);
+    assert_eq!(ndjson.lines().count(), 3);
+}
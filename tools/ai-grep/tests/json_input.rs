@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const RESPONSE: &str = r#"{
+  "id": "TODO-not-a-field-to-scan",
+  "choices": [
+    {"message": {"role": "assistant", "content": "Sure.\nTODO: fix this later"}},
+    {"message": {"role": "assistant", "content": "All good"}}
+  ],
+  "note": "FIXME is not in a content field",
+  "text": "FIXME here"
+}
+"#;
+
+const RECORDS: &str = "{\"messages\": [{\"content\": \"ok\"}, {\"content\": \"a TODO\"}]}\n\
+                       not json\n\
+                       {\"messages\": [{\"content\": \"HACK it\"}], \"text\": \"TODO elsewhere\"}\n";
+
+fn run(args: &[&str]) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("json_input");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("response.json"), RESPONSE).unwrap();
+    fs::write(dir.join("records.jsonl"), RECORDS).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .arg("--code-issues")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// The record, JSON pointer and matched text of each finding.
+fn findings(args: &[&str]) -> Vec<(u64, String, String)> {
+    let output = run(&[args, &["--format", "ndjson"]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(|line| {
+            let m: serde_json::Value = serde_json::from_str(line).unwrap();
+            (
+                m["record"].as_u64().unwrap(),
+                m["json_pointer"].as_str().unwrap().to_string(),
+                m["matched_text"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn content_and_text_fields_are_scanned_decoded() {
+    let found = findings(&["--json-input", "response.json"]);
+    assert_eq!(found, vec![
+        (0, "/choices/0/message/content".to_string(), "TODO".to_string()),
+        (0, "/text".to_string(), "FIXME".to_string()),
+    ]);
+    
+    // Lines and columns are within the decoded field
+    let output = run(&["--json-input", "-o", "response.json"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0:/choices/0/message/content:2:1:TODO\n0:/text:1:1:FIXME\n");
+}
+
+#[test]
+fn a_path_selects_fields_record_by_record() {
+    let output = run(&["--json-input=messages[*].content", "--format", "ndjson", "records.jsonl"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("records.jsonl: record 1: invalid JSON"), "{}", stderr);
+    
+    let found = findings(&["--json-input=messages[*].content", "records.jsonl"]);
+    assert_eq!(found, vec![
+        (0, "/messages/1/content".to_string(), "TODO".to_string()),
+        (2, "/messages/0/content".to_string(), "HACK".to_string()),
+    ]);
+    
+    let found = findings(&["--json-input=text", "records.jsonl"]);
+    assert_eq!(found, vec![(2, "/text".to_string(), "TODO".to_string())]);
+}
@@ -0,0 +1,39 @@
+use std::process::Command;
+
+fn list(extra: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .arg("--list-presets")
+        .args(extra)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn json_listing_groups_rules_by_category() {
+    let document: serde_json::Value = serde_json::from_str(&list(&["--format", "json"])).unwrap();
+    let categories = document.as_object().unwrap();
+    assert_eq!(categories.len(), 7);
+    
+    let secrets = document["secret"].as_array().unwrap();
+    let generic = secrets.iter().find(|rule| rule["subtype"] == "high_entropy_string").unwrap();
+    assert_eq!(generic["check"], "entropy");
+    
+    let markers = document["code_issue"].as_array().unwrap();
+    assert_eq!(markers[0]["subtype"], "development_marker");
+    assert_eq!(markers[0]["severity"], "Medium");
+    assert!(markers[0]["check"].is_null());
+    assert!(markers[0]["pattern"].as_str().unwrap().starts_with("(?i)"));
+}
+
+#[test]
+fn listing_shows_effective_regex() {
+    let document: serde_json::Value =
+        serde_json::from_str(&list(&["--format", "json", "--case-sensitive-presets"])).unwrap();
+    assert!(!document["code_issue"][0]["pattern"].as_str().unwrap().starts_with("(?i)"));
+    
+    let text = list(&[]);
+    assert!(text.contains("  --secrets"));
+    assert!(text.contains("development_marker"));
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/markdown").join(name)
+}
+
+fn run(extra: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--format", "markdown"])
+        .args(extra)
+        .arg(fixture("response.md"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn table_cells_escape_pipes_and_backticks() {
+    let report = run(&[]);
+    
+    assert!(report.contains("| Category | [CRITICAL] | [HIGH] | [MEDIUM] | [LOW] | Total |"));
+    assert!(report.contains("| hallucination | 3 | 0 | 0 | 0 | 3 |"));
+    assert!(report.contains("| 1 | [CRITICAL] | hallucination / capability_disclaimer | ``As an AI, I cannot \\| pipe `ticks` here`` |"));
+    // Every table row has the same number of unescaped cell separators
+    for row in report.lines().filter(|line| line.starts_with("| ") && line[2..].starts_with(|c: char| c.is_ascii_digit())) {
+        assert_eq!(row.replace("\\|", "").matches('|').count(), 5, "{}", row);
+    }
+}
+
+#[test]
+fn long_lines_are_truncated_around_the_match() {
+    let report = run(&["--report-width", "40"]);
+    let row = report.lines().find(|line| line.starts_with("| 3 |")).unwrap();
+    let span = row.rsplit(" | ").next().unwrap().trim_end_matches(" |");
+    
+    assert_eq!(span, "`… padding as an ai language model paddi…`");
+    let command = report.lines().rev().nth(1).unwrap();
+    assert!(command.ends_with(&format!("--hallucinations --format markdown --report-width 40 {}", fixture("response.md").display())));
+}
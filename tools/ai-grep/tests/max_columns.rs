@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A 1,500-character line with a disclaimer followed by multibyte text and a TODO, then
+/// a short line that is never cut.
+fn input() -> String {
+    format!("{} As an AI model I think {} TODO {}\nshort TODO line\n", "x".repeat(1500), "é".repeat(40), "y".repeat(200))
+}
+
+fn run(args: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("max_columns");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("long.txt"), input()).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .args(["-n", "-M", "40"])
+        .args(args)
+        .arg("long.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn long_lines_are_cut_around_the_match() {
+    assert_eq!(run(&["--hallucinations"]), format!(
+        "1:… [1491 chars omitted] …{} As an AI model I think {}… [239 chars omitted] …\n  \
+         AI hallucination marker: capability_disclaimer\n",
+        "x".repeat(9), "é".repeat(7),
+    ));
+    
+    // A match near the start keeps the start of the line
+    assert_eq!(run(&["--code-issues"]).lines().nth(3), Some("2:short TODO line"));
+}
+
+#[test]
+fn preview_shows_the_start_of_the_line() {
+    let stdout = run(&["--code-issues", "--max-columns-preview"]);
+    assert!(stdout.starts_with(&format!("1:{}… [1730 chars omitted] …\n", "x".repeat(40))));
+}
+
+#[test]
+fn highlights_stay_balanced() {
+    let stdout = run(&["--code-issues", "--color=always", "--colors", "line_no="]);
+    let first = stdout.lines().next().unwrap();
+    assert_eq!(first, format!("1:… [1555 chars omitted] …{} \x1b[33mTODO\x1b[0m {}… [175 chars omitted] …", "é".repeat(9), "y".repeat(25)));
+    
+    // A match cut off by the window is not painted at all
+    let preview = run(&["--code-issues", "--color=always", "--colors", "line_no=", "--max-columns-preview", "-C", "0"]);
+    assert_eq!(preview.lines().next().unwrap(), format!("1:{}… [1730 chars omitted] …", "x".repeat(40)));
+}
+
+#[test]
+fn json_keeps_whole_lines() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--hallucinations", "--format", "json"])).unwrap();
+    assert_eq!(doc["matches"][0]["content"], input().lines().next().unwrap());
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const LICENSE: &str = "Permission is hereby granted, free of charge, to any person obtaining a copy\n\
+                       of this software and associated documentation files (the \"Software\"), to deal\n\
+                       in the Software without restriction, including without limitation the rights\n\
+                       to use, copy, modify, merge, publish, distribute, sublicense, and/or sell\n";
+
+/// Line by line: a 13-word copy with different case and punctuation, only 7 shared words,
+/// a 16-word copy spanning several corpus lines, and text from a nested corpus file.
+const OUTPUT: &str = "Sure: permission is HEREBY granted -- free of charge -- to any person obtaining a copy!\n\
+                      Free of charge, to any person obtaining nothing.\n\
+                      We may deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge and so on\n\
+                      It was the best of times, it was the worst of times, he wrote.\n";
+
+fn run(extra: &[&str]) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("memorization");
+    fs::create_dir_all(dir.join("corpus/books")).unwrap();
+    fs::write(dir.join("corpus/LICENSE"), LICENSE).unwrap();
+    fs::write(dir.join("corpus/books/novel.txt"), "Chapter 1\n\nIt was the best of times, it was the worst of times\n").unwrap();
+    fs::write(dir.join("output.txt"), OUTPUT).unwrap();
+    
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .args(["--memorization-check", "corpus", "--format", "ndjson"])
+        .args(extra)
+        .arg("output.txt")
+        .output()
+        .unwrap()
+}
+
+/// (line, matched text, source file, source offset, severity) of every finding.
+fn findings(extra: &[&str]) -> Vec<(u64, String, String, u64, String)> {
+    let output = run(extra);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines()
+        .map(|line| {
+            let m: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(m["anomaly"]["type"], "memorization");
+            (
+                m["line_number"].as_u64().unwrap(),
+                m["matched_text"].as_str().unwrap().to_string(),
+                m["anomaly"]["source_file"].as_str().unwrap().to_string(),
+                m["anomaly"]["source_offset"].as_u64().unwrap(),
+                m["severity"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn shared_ngrams_are_traced_to_their_source() {
+    let found = findings(&[]);
+    assert_eq!(found.len(), 3, "{:?}", found);
+    
+    assert_eq!(found[0].0, 1);
+    assert_eq!(found[0].1, "permission is HEREBY granted -- free of charge -- to any person obtaining a copy");
+    assert_eq!((found[0].2.as_str(), found[0].3, found[0].4.as_str()), ("corpus/LICENSE", 0, "Low"));
+    
+    // Twice the minimum overlap raises the severity
+    assert_eq!(found[1].0, 3);
+    assert_eq!(found[1].1, "deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge");
+    assert_eq!((found[1].3, found[1].4.as_str()), (LICENSE.find("deal").unwrap() as u64, "Medium"));
+    
+    assert_eq!(found[2].0, 4);
+    assert_eq!((found[2].2.as_str(), found[2].3), ("corpus/books/novel.txt", 11));
+}
+
+#[test]
+fn min_overlap_raises_the_bar() {
+    let found = findings(&["--min-overlap", "14"]);
+    let lines: Vec<u64> = found.iter().map(|f| f.0).collect();
+    assert_eq!(lines, vec![3]);
+    assert_eq!(found[0].4, "Low");
+    
+    let short = findings(&["--ngram-size", "4", "--min-overlap", "6"]);
+    assert!(short.iter().any(|f| f.0 == 2 && f.1 == "Free of charge, to any person obtaining"));
+}
+
+#[test]
+fn min_overlap_below_ngram_size_is_rejected() {
+    let output = run(&["--min-overlap", "3"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--min-overlap"));
+}
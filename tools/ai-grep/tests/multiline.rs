@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(name: &str, input: &str, extra: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("multiline");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, input).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--format", "ndjson"])
+        .args(extra)
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn matches(stdout: &str) -> Vec<serde_json::Value> {
+    stdout.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+#[test]
+fn disclaimers_wrapped_across_lines_are_found() {
+    let input = "Sure.\nSorry, I don't have\naccess to the internet.\n";
+    assert!(run("wrapped.txt", input, &[]).is_empty());
+    
+    let found = matches(&run("wrapped.txt", input, &["--multiline"]));
+    assert_eq!(found.len(), 1);
+    let m = &found[0];
+    assert_eq!(m["matched_text"], "I don't have\naccess");
+    assert_eq!((m["start_line"].as_u64(), m["start_column"].as_u64()), (Some(2), Some(8)));
+    assert_eq!((m["end_line"].as_u64(), m["end_column"].as_u64()), (Some(3), Some(7)));
+    assert_eq!(m["content"], "Sorry, I don't have\naccess to the internet.");
+}
+
+#[test]
+fn sliding_window_finds_the_same_matches_as_the_whole_input() {
+    let mut input = String::new();
+    for i in 0..400 {
+        input.push_str(&format!("line {} of filler text\n", i));
+        if i % 9 == 0 {
+            input.push_str("as an\nAI model, I don't\nhave access\n");
+        }
+    }
+    
+    let whole = run("long.txt", &input, &["--multiline"]);
+    let windowed = run("long.txt", &input, &["--multiline", "--multiline-window", "1024"]);
+    assert_eq!(matches(&whole).len(), 45 * 2);
+    assert_eq!(windowed, whole);
+}
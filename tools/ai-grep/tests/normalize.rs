@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(name: &str, input: &str, args: &[&str]) -> Vec<serde_json::Value> {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("normalize");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, input).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(args)
+        .args(["--format", "ndjson"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// The match's byte span and text as reported, checked against the original line.
+fn span(m: &serde_json::Value) -> (usize, usize, String) {
+    let (start, end) = (m["start_pos"].as_u64().unwrap() as usize, m["end_pos"].as_u64().unwrap() as usize);
+    let content = m["content"].as_str().unwrap();
+    assert_eq!(&content[start..end], m["matched_text"].as_str().unwrap());
+    (start, end, m["matched_text"].as_str().unwrap().to_string())
+}
+
+#[test]
+fn curly_quotes_match_presets_at_original_offsets() {
+    // "ﬁ" (3 bytes) becomes "fi" (2) and "…" (3) becomes "..." (3) before the match
+    let line = "ﬁle… I’m not sure";
+    let matches = run("quotes.txt", &format!("{}\n", line), &["--hallucinations"]);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(span(&matches[0]), (line.find('I').unwrap(), line.len(), "I’m not sure".to_string()));
+}
+
+#[test]
+fn spans_that_shrink_or_grow_map_back() {
+    // Fullwidth letters are 3 bytes each and fold to ASCII; the NBSP inside the second
+    // match shrinks from 2 bytes to 1
+    let input = "ＴＯＤＯ: ship it\nwell, I\u{a0}cannot say\n";
+    let matches = run("widths.txt", input, &["--code-issues", "--hallucinations"]);
+    let spans: Vec<_> = matches.iter().map(span).collect();
+    assert_eq!(spans, vec![
+        (0, 12, "ＴＯＤＯ".to_string()),
+        (6, 15, "I\u{a0}cannot".to_string()),
+    ]);
+    assert_eq!(matches[1]["absolute_offset"], "ＴＯＤＯ: ship it\n".len() + 6);
+}
+
+#[test]
+fn combining_marks_and_ligatures_map_to_whole_characters() {
+    // "e" + U+0301 composes to "é"; a match ending inside the "ﬁ" ligature covers all of it
+    let line = "cafe\u{301} ﬁx";
+    let matches = run("marks.txt", &format!("{}\n", line), &["--normalize", "-E", "café f"]);
+    assert_eq!(span(&matches[0]), (0, line.len() - 1, "cafe\u{301} ﬁ".to_string()));
+}
+
+#[test]
+fn no_normalize_matches_raw_text() {
+    let input = "I’m not sure\n";
+    assert!(run("raw.txt", input, &["--hallucinations", "--no-normalize"]).is_empty());
+    // Custom patterns match raw text unless --normalize is given
+    assert!(run("custom.txt", input, &["-E", "I'm"]).is_empty());
+    assert_eq!(run("custom.txt", input, &["--normalize", "-E", "I'm"]).len(), 1);
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const INPUT: &str = "mail bob@example.com or amy@example.org\nnothing here\nTODO: fix\n";
+
+fn run(args: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("only_matching");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("input.txt"), INPUT).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .args(args)
+        .arg("input.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_each_match_on_its_own_line() {
+    assert_eq!(run(&["--data-leakage", "-o"]), "bob@example.com\namy@example.org\n");
+    assert_eq!(run(&["--data-leakage", "-o", "-n", "-b", "-H"]), "input.txt:1:5:bob@example.com\ninput.txt:1:24:amy@example.org\n");
+    
+    // Context is ignored, as in grep
+    assert_eq!(run(&["-E", "TODO", "--only-matching", "-n", "-C", "1"]), "3:TODO\n");
+}
+
+#[test]
+fn colors_the_whole_match() {
+    assert_eq!(run(&["-E", "TODO", "-o", "-n", "--color=always"]).replace('\x1b', "\\e"), "\\e[32m3\\e[0m:\\e[33mTODO\\e[0m\n");
+}
+
+#[test]
+fn json_output_is_unchanged() {
+    assert_eq!(run(&["--data-leakage", "-o", "--format", "json"]), run(&["--data-leakage", "--format", "json"]));
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixture_files() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scan");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    files
+}
+
+fn run(threads: usize, extra: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--code-issues", "--security", "--data-leakage"])
+        .args(["--low-confidence", "--secrets", "--prompt-injection", "--color=never"])
+        .arg(format!("--threads={}", threads))
+        .args(extra)
+        .args(fixture_files())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn parallel_output_matches_single_threaded() {
+    for extra in [&["-n"][..], &["-c"], &["-l"], &["--format", "json", "--stats"]] {
+        let sequential = run(1, extra);
+        assert!(!sequential.is_empty());
+        for threads in [2, 4, 16] {
+            assert_eq!(run(threads, extra), sequential, "--threads={} {:?}", threads, extra);
+        }
+    }
+}
+
+#[test]
+fn unordered_output_has_same_lines() {
+    let mut sequential: Vec<String> = run(1, &["-c"]).lines().map(str::to_string).collect();
+    let mut unordered: Vec<String> = run(4, &["-c", "--unordered"]).lines().map(str::to_string).collect();
+    sequential.sort();
+    unordered.sort();
+    assert_eq!(unordered, sequential);
+}
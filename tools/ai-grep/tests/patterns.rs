@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn fixture(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/patterns").join(name).to_str().unwrap().to_string()
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ai-grep")).args(args).args(["--color", "never"]).output().unwrap()
+}
+
+/// (line, matched text) of every match in notes.txt.
+fn matches(args: &[&str]) -> Vec<(u64, String)> {
+    let output = run(&[args, &["--format", "ndjson", &fixture("notes.txt")]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .map(|m| (m["line_number"].as_u64().unwrap(), m["matched_text"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[test]
+fn every_expression_matches() {
+    let found = matches(&["-e", "TODO", "-e", "FIXME"]);
+    assert_eq!(found, [(1, "TODO".into()), (2, "FIXME".into()), (4, "TODO".into()), (4, "FIXME".into())]);
+    
+    // The first positional argument is a file once -e is given
+    let output = run(&["-e", "nothing", &fixture("notes.txt")]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "nothing to see\n");
+}
+
+#[test]
+fn pattern_files_hold_one_pattern_per_line() {
+    let found = matches(&["--file", &fixture("list.txt"), "-e", "parser"]);
+    assert_eq!(found, [(1, "parser".into()), (2, "FIXME".into()), (2, "flaky".into()), (4, "FIXME".into())]);
+}
+
+#[test]
+fn a_span_several_patterns_match_is_reported_once() {
+    let found = matches(&["-E", "-e", "TO.O", "-e", "TODO", "--no-dedupe"]);
+    assert_eq!(found, [(1, "TODO".into()), (4, "TODO".into())]);
+    
+    // Overlapping but different spans are both kept without dedupe
+    let found = matches(&["-E", "-e", "FIX", "-e", "FIXME", "--no-dedupe"]);
+    assert_eq!(found, [(2, "FIX".into()), (2, "FIXME".into()), (4, "FIX".into()), (4, "FIXME".into())]);
+}
+
+#[test]
+fn presets_take_no_extra_patterns() {
+    let output = run(&["--code-issues", "-e", "TODO", &fixture("notes.txt")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be combined with preset flags"));
+}
@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn redact(files: &[&str]) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("redact");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("r1.txt"), "mail alice@example.com\n").unwrap();
+    // Invalid UTF-8 around a leak that must still be found
+    fs::write(dir.join("r2.txt"), b"caf\xe9 SSN 123-45-6789\n").unwrap();
+    
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .arg("--redact")
+        .args(files)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn invalid_utf8_is_redacted_too() {
+    let output = redact(&["r1.txt", "r2.txt"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "mail [REDACTED:email]\ncaf\u{FFFD} SSN [REDACTED:ssn]\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("  ssn: 1\n"));
+}
+
+#[test]
+fn unreadable_inputs_fail_with_two() {
+    let output = redact(&["r1.txt", "r2.txt", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing.txt"), "{}", stderr);
+    // The inputs that could be read are still redacted and counted
+    assert!(stderr.contains("ai-grep: 2 redaction(s)"), "{}", stderr);
+}
@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn inputs() -> (PathBuf, PathBuf) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("score");
+    fs::create_dir_all(&dir).unwrap();
+    
+    let risky = dir.join("risky.txt");
+    // Two critical matches and one medium, in two categories
+    fs::write(&risky, "As an AI model I have no opinion.\npassword = \"hunter2\"\nI'm not sure.\n").unwrap();
+    let clean = dir.join("clean.txt");
+    fs::write(&clean, "The build passed.\n").unwrap();
+    (risky, clean)
+}
+
+fn run(extra: &[&str]) -> Output {
+    let (risky, clean) = inputs();
+    Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(["--hallucinations", "--security", "--score"])
+        .args(extra)
+        .args([&risky, &clean])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn prints_one_score_per_file() {
+    let output = run(&[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<(&str, f64)> = stdout.lines()
+        .map(|line| {
+            let (file, score) = line.split_once('\t').unwrap();
+            (file, score.parse().unwrap())
+        })
+        .collect();
+    
+    assert_eq!(rows.len(), 2);
+    assert!(rows[0].0.ends_with("risky.txt"));
+    assert!(rows[0].1 > 50.0 && rows[0].1 < 100.0, "{stdout}");
+    assert!(rows[1].0.ends_with("clean.txt"));
+    assert_eq!(rows[1].1, 0.0);
+}
+
+#[test]
+fn json_reports_components_and_weights_apply() {
+    let output = run(&["--format", "json", "--score-weights", "severity=1,density=0,diversity=0"]);
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let risky = &document["files"][0];
+    
+    assert_eq!(risky["by_severity"], serde_json::json!({"Critical": 2, "Medium": 1}));
+    assert_eq!(risky["weighted_severity"], 2.5);
+    assert_eq!(risky["categories"], 2);
+    // Only the severity component counts: 100 * (1 - e^(-2.5 / 5))
+    let expected = 100.0 * (1.0 - (-0.5f64).exp());
+    assert!((risky["score"].as_f64().unwrap() - expected).abs() < 1e-9);
+    assert_eq!(document["files"][1]["score"], 0.0);
+}
+
+#[test]
+fn fail_on_score_sets_exit_status() {
+    assert_eq!(run(&["--fail-on-score", "50"]).status.code(), Some(1));
+    assert_eq!(run(&["--fail-on-score", "99"]).status.code(), Some(0));
+}
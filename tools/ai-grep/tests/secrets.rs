@@ -0,0 +1,62 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The text and anomaly of each finding in `input`, in order.
+fn findings(args: &[&str], input: &str) -> Vec<(String, serde_json::Value)> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .args(args)
+        .args(["--format", "ndjson"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(|line| {
+            let m: serde_json::Value = serde_json::from_str(line).unwrap();
+            (m["matched_text"].as_str().unwrap().to_string(), m["anomaly"].clone())
+        })
+        .collect()
+}
+
+#[test]
+fn generic_secrets_must_be_high_entropy() {
+    let token = "Zx8Qp2Lm9Rt4Vb7Nc1Kd5Hf3Jg6Ws0YaE";
+    let input = format!(
+        "secret: {token}\n\
+         padding: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\n\
+         repeat: xyzxyzxyzxyzxyzxyzxyzxyzxyzxyzxyzxyz\n\
+         commit: 3f786850e387550fdab836ed7e6dc881de23001b\n"
+    );
+    let found = findings(&["--secrets"], &input);
+    assert_eq!(found.len(), 1, "{:?}", found);
+    assert_eq!(found[0].0, token);
+    assert_eq!(found[0].1, serde_json::json!({ "type": "secret", "secret_type": "high_entropy_string" }));
+    
+    // About 5 bits per character, under a stricter threshold
+    assert!(findings(&["--secrets", "--entropy-threshold", "5.5"], &input).is_empty());
+    // The repeated pattern has log2(3) bits per character; the hex hash is never reported
+    let found = findings(&["--secrets", "--entropy-threshold", "1.5"], &input);
+    let texts: Vec<&str> = found.iter().map(|(text, _)| text.as_str()).collect();
+    assert_eq!(texts, vec![token, "xyzxyzxyzxyzxyzxyzxyzxyzxyzxyzxyzxyz"]);
+}
+
+#[test]
+fn provider_keys_are_not_repeated_as_generic_secrets() {
+    let key = format!("ghp_{}", "Zx8Qp2Lm9Rt4Vb7Nc1Kd5Hf3Jg6Ws0YaEuTo");
+    let found = findings(&["--secrets"], &format!("token {key}\n"));
+    assert_eq!(found, vec![(key, serde_json::json!({ "type": "secret", "secret_type": "github_token" }))]);
+}
+
+#[test]
+fn card_numbers_must_pass_luhn() {
+    let input = "paid with 4111 1111 1111 1111\n\
+                 order 4111-1111-1111-1112\n\
+                 ref 1234567812345678\n\
+                 card 5500005555555559\n";
+    let found: Vec<String> = findings(&["--data-leakage"], input).into_iter().map(|(text, _)| text).collect();
+    assert_eq!(found, vec!["4111 1111 1111 1111", "5500005555555559"]);
+}
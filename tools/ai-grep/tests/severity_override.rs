@@ -0,0 +1,61 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const INPUT: &str = "Reach me at jane@example.com\nThe server is at 10.0.0.12\n";
+
+fn run(args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .arg("--data-leakage")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(INPUT.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// The leak type and severity of each finding, in order.
+fn severities(args: &[&str]) -> Vec<(String, String)> {
+    let output = run(&[args, &["--format", "ndjson"]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(|line| {
+            let m: serde_json::Value = serde_json::from_str(line).unwrap();
+            (m["anomaly"]["leak_type"].as_str().unwrap().to_string(), m["severity"].as_str().unwrap().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn overrides_set_the_reported_severity() {
+    let pairs = |email: &str, ip: &str| vec![("email".to_string(), email.to_string()), ("ip_address".to_string(), ip.to_string())];
+    let defaults = severities(&[]);
+    assert_eq!(defaults, pairs("Medium", "Low"));
+    
+    assert_eq!(severities(&["--severity-override", "email=critical"]), pairs("Critical", "Low"));
+    // A whole category, written with dashes, and a subtype that wins over it
+    assert_eq!(
+        severities(&["--severity-override", "data-leakage=low", "--severity-override", "ip-address=high"]),
+        pairs("Low", "High")
+    );
+    // The last override of the same name wins
+    assert_eq!(
+        severities(&["--severity-override", "email=low", "--severity-override", "email=medium"])[0].1,
+        "Medium"
+    );
+}
+
+#[test]
+fn unknown_names_are_rejected_with_the_valid_ones() {
+    let output = run(&["--severity-override", "phone=low"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown category or type 'phone'"), "{}", stderr);
+    assert!(stderr.contains("data_leakage, deprecation"), "{}", stderr);
+    assert!(stderr.contains("ip_address"), "{}", stderr);
+    
+    assert!(!run(&["--severity-override", "email=urgent"]).status.success());
+}
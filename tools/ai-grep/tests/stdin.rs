@@ -0,0 +1,45 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+fn run(args: &[&str], stdin: &str) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("stdin");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), "TODO: from a file\n").unwrap();
+    
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(&dir)
+        .arg("--code-issues")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn dash_reads_stdin_among_files() {
+    let stdout = run(&["-c", "notes.txt", "-", "--label", "pipe"], "FIXME one\nFIXME two\n");
+    assert_eq!(stdout, "notes.txt:1\npipe:2\n");
+}
+
+#[test]
+fn label_names_stdin_in_reports() {
+    let stdout = run(&["--format", "ndjson", "--label", "chat.log"], "TODO later\n");
+    let m: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(m["file"], "chat.log");
+}
+
+#[test]
+fn with_and_without_filename_override_the_file_count() {
+    assert_eq!(run(&["-c", "-H", "--label", "pipe"], "TODO\n"), "pipe:1\n");
+    assert_eq!(run(&["-c", "-H", "notes.txt"], ""), "notes.txt:1\n");
+    assert_eq!(run(&["-c", "-h", "notes.txt", "-"], "TODO\n"), "1\n1\n");
+    // The last of -H and -h wins
+    assert_eq!(run(&["-c", "-h", "-H", "notes.txt"], ""), "notes.txt:1\n");
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(extra: &[&str], files: &[&str]) -> String {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/severity");
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-grep"))
+        .current_dir(fixtures)
+        .args(["--code-issues", "--hallucinations"])
+        .args(extra)
+        .args(files)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn summary_is_an_alias_of_stats() {
+    let text = run(&["--summary"], &["medium.txt", "none.txt"]);
+    assert_eq!(text, run(&["--stats"], &["medium.txt", "none.txt"]));
+    assert!(text.contains("Files scanned: 2 (1 with matches)\nLines scanned: 3\nTotal matches: 2\n"), "{}", text);
+}
+
+#[test]
+fn the_score_is_also_given_per_100_lines() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--summary", "--format", "json"], &["medium.txt", "none.txt"])).unwrap();
+    let summary = &doc["summary"];
+    assert_eq!(summary["lines_scanned"], 3);
+    let (total, per_100_lines) = (summary["weighted_score"].as_f64().unwrap(), summary["score_per_100_lines"].as_f64().unwrap());
+    assert!((per_100_lines - total * 100.0 / 3.0).abs() < 1e-9, "{}", summary);
+    assert_eq!(summary["by_severity"], serde_json::json!({ "Low": 1, "Medium": 1 }));
+    
+    let empty: serde_json::Value = serde_json::from_str(&run(&["--summary", "--format", "json"], &["none.txt"])).unwrap();
+    assert_eq!(empty["summary"]["score_per_100_lines"], 0.0);
+}
@@ -0,0 +1,1168 @@
+//! Anomaly detection shared by the ai-toolkit tools: the preset pattern tables behind
+//! `ai-grep --hallucinations`, `--secrets` and the rest, and the engine that runs them.
+//!
+//! ```
+//! use ai_toolkit_core::{Categories, Scanner, Severity};
+//!
+//! let matches = Scanner::new(Categories::all()).scan("As an AI model, I cannot browse.");
+//! assert_eq!(matches[0].matched_text, "As an AI");
+//! assert_eq!(matches[0].anomaly_type.category(), "hallucination");
+//! assert_eq!(matches[0].severity, Severity::Critical);
+//! ```
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Unknown severity level '{0}' (expected low, medium, high or critical)")]
+    UnknownSeverity(String),
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+    #[error("cannot read corpus {}: {source}", dir.display())]
+    CorpusDir { dir: PathBuf, source: io::Error },
+    #[error("{}: {source}", path.display())]
+    CorpusFile { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Match {
+    pub line_number: usize,
+    pub content: String,
+    pub matched_text: String,
+    pub start_pos: usize,
+    pub end_pos: usize,
+    pub anomaly_type: AnomalyType,
+    pub severity: Severity,
+    /// Where the scanned text came from when it was a string inside a JSON document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<JsonField>,
+    /// Named groups of the pattern, in pattern order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub captures: Vec<Capture>,
+    /// Last line of a multiline match; `content` then holds every line it spans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// Byte offset in the input of the start of the match's (first) line
+    pub line_offset: u64,
+    /// Categories of other findings whose spans overlap this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlaps: Vec<String>,
+    /// Lines shown before the match, leaving out lines with findings of their own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_before: Vec<ContextLine>,
+    /// Lines shown after the match, leaving out lines with findings of their own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_after: Vec<ContextLine>,
+}
+
+/// A line of input printed around a match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextLine {
+    pub line_number: usize,
+    /// Byte offset in the input of the start of the line
+    pub offset: u64,
+    pub content: String,
+}
+
+/// A named capture group; `span` is the group's byte range in the line, or `None` when the
+/// group did not take part in the match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capture {
+    pub name: String,
+    pub span: Option<(usize, usize)>,
+}
+
+impl Match {
+    /// 1-based character column of the match within its line.
+    pub fn column(&self) -> usize {
+        self.content[..self.start_pos].chars().count() + 1
+    }
+    
+    /// Byte offset in the input of the start of the match.
+    pub fn absolute_offset(&self) -> u64 {
+        self.line_offset + self.start_pos as u64
+    }
+    
+    /// 1-based character column just past the match on its last line.
+    pub fn end_column(&self) -> usize {
+        let line_start = self.content[..self.end_pos].rfind('\n').map_or(0, |i| i + 1);
+        self.content[line_start..self.end_pos].chars().count() + 1
+    }
+}
+
+/// A string in a JSON document: the index of the record it is in and its JSON pointer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonField {
+    pub record: usize,
+    pub pointer: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnomalyType {
+    Hallucination { marker_type: String },
+    CodeIssue { issue_type: String },
+    Security { vulnerability_type: String },
+    DataLeakage { leak_type: String },
+    LowConfidence { confidence_marker: String },
+    Secret { secret_type: String },
+    PromptInjection { technique: String },
+    /// Text shared with a file of a `CorpusIndex`, starting at `source_offset` in it
+    Memorization { source_file: String, source_offset: u64 },
+    Custom,
+}
+
+impl AnomalyType {
+    /// Every name `category` returns.
+    pub const CATEGORIES: [&'static str; 9] = [
+        "hallucination",
+        "code_issue",
+        "security",
+        "data_leakage",
+        "low_confidence",
+        "secret",
+        "prompt_injection",
+        "memorization",
+        "custom",
+    ];
+    
+    pub fn category(&self) -> &'static str {
+        match self {
+            AnomalyType::Hallucination { .. } => "hallucination",
+            AnomalyType::CodeIssue { .. } => "code_issue",
+            AnomalyType::Security { .. } => "security",
+            AnomalyType::DataLeakage { .. } => "data_leakage",
+            AnomalyType::LowConfidence { .. } => "low_confidence",
+            AnomalyType::Secret { .. } => "secret",
+            AnomalyType::PromptInjection { .. } => "prompt_injection",
+            AnomalyType::Memorization { .. } => "memorization",
+            AnomalyType::Custom => "custom",
+        }
+    }
+    
+    pub fn subtype(&self) -> Option<&str> {
+        match self {
+            AnomalyType::Hallucination { marker_type } => Some(marker_type),
+            AnomalyType::CodeIssue { issue_type } => Some(issue_type),
+            AnomalyType::Security { vulnerability_type } => Some(vulnerability_type),
+            AnomalyType::DataLeakage { leak_type } => Some(leak_type),
+            AnomalyType::LowConfidence { confidence_marker } => Some(confidence_marker),
+            AnomalyType::Secret { secret_type } => Some(secret_type),
+            AnomalyType::PromptInjection { technique } => Some(technique),
+            AnomalyType::Memorization { source_file, .. } => Some(source_file),
+            AnomalyType::Custom => None,
+        }
+    }
+}
+
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn to_score(self) -> f64 {
+        match self {
+            Severity::Low => 0.25,
+            Severity::Medium => 0.5,
+            Severity::High => 0.75,
+            Severity::Critical => 1.0,
+        }
+    }
+    
+    pub fn to_emoji(self) -> &'static str {
+        match self {
+            Severity::Low => "ℹ️",
+            Severity::Medium => "⚠️",
+            Severity::High => "🚨",
+            Severity::Critical => "💀",
+        }
+    }
+    
+    /// The plain-text stand-in for [`Severity::to_emoji`], e.g. `[CRITICAL]`.
+    pub fn to_tag(self) -> &'static str {
+        match self {
+            Severity::Low => "[LOW]",
+            Severity::Medium => "[MEDIUM]",
+            Severity::High => "[HIGH]",
+            Severity::Critical => "[CRITICAL]",
+        }
+    }
+    
+    /// The emoji when `emoji` is set, the text tag otherwise, so redirected output stays clean.
+    pub fn marker(self, emoji: bool) -> &'static str {
+        if emoji { self.to_emoji() } else { self.to_tag() }
+    }
+    
+    /// How alarming it is to see the same item `count` times: 5, 10 and 20 repeats each
+    /// raise it a level.
+    pub fn from_count(count: usize) -> Severity {
+        match count {
+            20.. => Severity::Critical,
+            10.. => Severity::High,
+            5.. => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = Error;
+    
+    fn from_str(level: &str) -> Result<Self, Error> {
+        match level.to_lowercase().as_str() {
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(Error::UnknownSeverity(level.to_string())),
+        }
+    }
+}
+
+/// When to color output (`--color`); emoji follow the same setting.
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    Always,
+    Never,
+    /// Only when stdout is a terminal and NO_COLOR is unset.
+    #[default]
+    Auto,
+}
+
+impl ColorMode {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => io::stdout().is_terminal() && !no_color(),
+        }
+    }
+}
+
+/// Whether the user asked for plain output: https://no-color.org treats any non-empty
+/// NO_COLOR as a request to drop color, which `--color always` still overrides.
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// A preset pattern table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    Hallucinations,
+    CodeIssues,
+    Security,
+    DataLeakage,
+    LowConfidence,
+    Secrets,
+    PromptInjection,
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Category::Hallucinations,
+        Category::CodeIssues,
+        Category::Security,
+        Category::DataLeakage,
+        Category::LowConfidence,
+        Category::Secrets,
+        Category::PromptInjection,
+    ];
+    
+    /// Kebab-case name, as in the ai-grep flag that enables the preset.
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Hallucinations => "hallucinations",
+            Category::CodeIssues => "code-issues",
+            Category::Security => "security",
+            Category::DataLeakage => "data-leakage",
+            Category::LowConfidence => "low-confidence",
+            Category::Secrets => "secrets",
+            Category::PromptInjection => "prompt-injection",
+        }
+    }
+    
+    pub fn description(self) -> &'static str {
+        match self {
+            Category::Hallucinations => "AI model limitations and disclaimers",
+            Category::CodeIssues => "Development markers (TODO, FIXME, HACK, etc.)",
+            Category::Security => "Security vulnerability patterns",
+            Category::DataLeakage => "Training data or PII leakage indicators",
+            Category::LowConfidence => "Uncertainty and hedging language",
+            Category::Secrets => "API keys, tokens and high-entropy secrets",
+            Category::PromptInjection => "Prompt-injection and jailbreak attempts",
+        }
+    }
+}
+
+/// The presets a `Scanner` runs, in the order their rules are tried.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Categories(Vec<Category>);
+
+impl Categories {
+    pub fn all() -> Self {
+        Categories(Category::ALL.to_vec())
+    }
+    
+    pub fn none() -> Self {
+        Categories(Vec::new())
+    }
+    
+    /// Add `category`, unless it is already included.
+    pub fn with(mut self, category: Category) -> Self {
+        if !self.contains(category) {
+            self.0.push(category);
+        }
+        self
+    }
+    
+    pub fn contains(&self, category: Category) -> bool {
+        self.0.contains(&category)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = Category> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl From<Category> for Categories {
+    fn from(category: Category) -> Self {
+        Categories(vec![category])
+    }
+}
+
+impl FromIterator<Category> for Categories {
+    fn from_iter<I: IntoIterator<Item = Category>>(iter: I) -> Self {
+        iter.into_iter().fold(Categories::none(), Categories::with)
+    }
+}
+
+/// Which rules match against NFKC-normalized text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Normalize {
+    /// Preset rules only; custom patterns see the text exactly as written
+    #[default]
+    Presets,
+    Always,
+    Never,
+}
+
+/// How a `Scanner` compiles and applies its rules.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Match case-insensitively, preset patterns included
+    pub ignore_case: bool,
+    /// Match every preset with exact case, even those written case-insensitive
+    pub case_sensitive_presets: bool,
+    /// Let `.` match newlines and spaces in presets and literals match any whitespace, for
+    /// text scanned as a whole rather than line by line
+    pub multiline: bool,
+    pub normalize: Normalize,
+    /// Minimum Shannon entropy (bits/char) for generic secret candidates
+    pub entropy_threshold: f64,
+    /// Keep only the strongest of overlapping hits of the same category
+    pub dedupe: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            ignore_case: false,
+            case_sensitive_presets: false,
+            multiline: false,
+            normalize: Normalize::default(),
+            entropy_threshold: 3.5,
+            dedupe: true,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Whether `rule` matches against normalized text.
+    fn normalizes(&self, rule: &Rule) -> bool {
+        match self.normalize {
+            Normalize::Presets => rule.category.is_some(),
+            Normalize::Always => true,
+            Normalize::Never => false,
+        }
+    }
+}
+
+/// Extra condition a regex hit must satisfy before it is reported.
+#[derive(Debug, Clone, Copy)]
+pub enum RuleCheck {
+    None,
+    /// Card numbers must pass the Luhn checksum
+    Luhn,
+    /// Generic secrets must be high-entropy, non-hex and not already reported
+    Entropy,
+}
+
+impl RuleCheck {
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            RuleCheck::None => None,
+            RuleCheck::Luhn => Some("luhn"),
+            RuleCheck::Entropy => Some("entropy"),
+        }
+    }
+}
+
+/// A regex compiled once, together with the anomaly it reports.
+#[derive(Debug)]
+pub struct Rule {
+    pub regex: Regex,
+    /// The preset the rule belongs to, or `None` for a custom pattern
+    pub category: Option<Category>,
+    pub subtype: &'static str,
+    pub severity: Severity,
+    pub check: RuleCheck,
+}
+
+impl Rule {
+    pub fn anomaly_type(&self) -> AnomalyType {
+        let subtype = self.subtype.to_string();
+        match self.category {
+            Some(Category::Hallucinations) => AnomalyType::Hallucination { marker_type: subtype },
+            Some(Category::CodeIssues) => AnomalyType::CodeIssue { issue_type: subtype },
+            Some(Category::Security) => AnomalyType::Security { vulnerability_type: subtype },
+            Some(Category::DataLeakage) => AnomalyType::DataLeakage { leak_type: subtype },
+            Some(Category::LowConfidence) => AnomalyType::LowConfidence { confidence_marker: subtype },
+            Some(Category::Secrets) => AnomalyType::Secret { secret_type: subtype },
+            Some(Category::PromptInjection) => AnomalyType::PromptInjection { technique: subtype },
+            None => AnomalyType::Custom,
+        }
+    }
+}
+
+/// An accepted regex hit; offsets are bytes into the scanned text.
+#[derive(Debug)]
+pub struct Hit {
+    /// Index of the rule in `Scanner::rules`
+    pub rule: usize,
+    pub start: usize,
+    pub end: usize,
+    pub captures: Vec<Capture>,
+    /// Categories of other hits overlapping this one, when hits are deduplicated
+    pub overlaps: Vec<String>,
+}
+
+/// Compiled rules and the options to run them with. Compiling is the expensive part, so
+/// build one scanner and reuse it for every input (it can be shared between threads).
+#[derive(Debug)]
+pub struct Scanner {
+    rules: Vec<Rule>,
+    options: ScanOptions,
+    corpus: Option<CorpusIndex>,
+}
+
+impl Scanner {
+    /// Scanner for the presets in `categories`, with default options.
+    pub fn new(categories: Categories) -> Self {
+        Scanner::with_options(categories, ScanOptions::default()).expect("preset patterns are valid regexes")
+    }
+    
+    pub fn with_options(categories: Categories, options: ScanOptions) -> Result<Self, Error> {
+        let mut rules = Vec::new();
+        for category in categories.iter() {
+            for (pattern_str, subtype, severity, check) in preset_rules(category) {
+                rules.push(Rule {
+                    regex: build_preset_regex(pattern_str, &options)?,
+                    category: Some(category),
+                    subtype,
+                    severity,
+                    check,
+                });
+            }
+        }
+        Ok(Scanner { rules, options, corpus: None })
+    }
+    
+    /// Scanner for a user-written regex, reported as `AnomalyType::Custom`.
+    pub fn regex(pattern: &str, options: ScanOptions) -> Result<Self, Error> {
+        Scanner::regexes(&[pattern], options)
+    }
+    
+    /// Scanner for a fixed string, reported as `AnomalyType::Custom`.
+    pub fn literal(text: &str, options: ScanOptions) -> Result<Self, Error> {
+        Scanner::literals(&[text], options)
+    }
+    
+    /// Scanner for several user-written regexes, like `grep -e A -e B`: each is reported as
+    /// `AnomalyType::Custom`, and text matched by more than one is reported once.
+    pub fn regexes(patterns: &[impl AsRef<str>], options: ScanOptions) -> Result<Self, Error> {
+        let rules = patterns.iter()
+            .map(|pattern| custom_rule(pattern.as_ref(), false, &options))
+            .collect::<Result<_, _>>()?;
+        Ok(Scanner { rules, options, corpus: None })
+    }
+    
+    /// Scanner for several fixed strings, as `regexes`.
+    pub fn literals(texts: &[impl AsRef<str>], options: ScanOptions) -> Result<Self, Error> {
+        let rules = texts.iter()
+            .map(|text| custom_rule(&regex::escape(text.as_ref()), true, &options))
+            .collect::<Result<_, _>>()?;
+        Ok(Scanner { rules, options, corpus: None })
+    }
+    
+    /// Also report text copied from the files of `corpus`, as `AnomalyType::Memorization`.
+    pub fn with_corpus(mut self, corpus: CorpusIndex) -> Self {
+        self.corpus = Some(corpus);
+        self
+    }
+    
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+    
+    /// Run every rule over `text` line by line, returning the accepted hits in input order.
+    pub fn scan(&self, text: &str) -> Vec<Match> {
+        let mut line_offset = 0;
+        text.split_inclusive('\n')
+            .enumerate()
+            .flat_map(|(i, terminated)| {
+                let line = terminated.strip_suffix('\n')
+                    .map_or(terminated, |line| line.strip_suffix('\r').unwrap_or(line));
+                let matches = self.scan_line(i + 1, line_offset, line);
+                line_offset += terminated.len() as u64;
+                matches
+            })
+            .collect()
+    }
+    
+    /// Run every rule over one line, without its terminator, returning the accepted hits in
+    /// column order.
+    pub fn scan_line(&self, line_number: usize, line_offset: u64, line: &str) -> Vec<Match> {
+        let mut matches: Vec<Match> = self.hits(line).into_iter()
+            .map(|hit| {
+                let rule = &self.rules[hit.rule];
+                Match {
+                    line_number,
+                    content: line.to_string(),
+                    matched_text: line[hit.start..hit.end].to_string(),
+                    start_pos: hit.start,
+                    end_pos: hit.end,
+                    anomaly_type: rule.anomaly_type(),
+                    severity: rule.severity,
+                    field: None,
+                    captures: hit.captures,
+                    end_line: None,
+                    line_offset,
+                    overlaps: hit.overlaps,
+                    context_before: Vec::new(),
+                    context_after: Vec::new(),
+                }
+            })
+            .collect();
+        
+        if let Some(corpus) = &self.corpus {
+            for passage in corpus.find(line) {
+                matches.push(Match {
+                    line_number,
+                    content: line.to_string(),
+                    matched_text: line[passage.start..passage.end].to_string(),
+                    start_pos: passage.start,
+                    end_pos: passage.end,
+                    anomaly_type: AnomalyType::Memorization {
+                        source_file: corpus.files[passage.source.file as usize].clone(),
+                        source_offset: passage.source.offset,
+                    },
+                    severity: corpus.severity(passage.words),
+                    field: None,
+                    captures: Vec::new(),
+                    end_line: None,
+                    line_offset,
+                    overlaps: Vec::new(),
+                    context_before: Vec::new(),
+                    context_after: Vec::new(),
+                });
+            }
+            matches.sort_by_key(|m| m.start_pos);
+        }
+        matches
+    }
+    
+    /// Run every rule over `text` as one string, applying the rules' extra checks, and return
+    /// hits in text order. Corpus passages are not included.
+    pub fn hits(&self, text: &str) -> Vec<Hit> {
+        let (rules, options) = (&self.rules, &self.options);
+        let mut hits: Vec<Hit> = Vec::new();
+        // ASCII is already in normal form, so most lines skip this
+        let normalized = (!text.is_ascii() && rules.iter().any(|rule| options.normalizes(rule)))
+            .then(|| NormalizedText::new(text));
+        
+        for (index, rule) in rules.iter().enumerate() {
+            let normalized = normalized.as_ref().filter(|_| options.normalizes(rule));
+            let haystack = normalized.map_or(text, |normalized| normalized.text.as_str());
+            let original_span = |start: usize, end: usize| normalized.map_or((start, end), |normalized| normalized.original_span(start, end));
+            
+            // Resolving groups is slower than finding the match, so only do it when they are named
+            let found: Vec<(regex::Match, Vec<Capture>)> = if rule.regex.capture_names().flatten().next().is_some() {
+                rule.regex.captures_iter(haystack)
+                    .map(|caps| (caps.get(0).expect("group 0 always participates"), named_captures(&rule.regex, &caps)))
+                    .collect()
+            } else {
+                rule.regex.find_iter(haystack).map(|mat| (mat, Vec::new())).collect()
+            };
+            
+            for (mat, captures) in found {
+                let (start, end) = original_span(mat.start(), mat.end());
+                let candidate = mat.as_str();
+                let accepted = match rule.check {
+                    RuleCheck::None => true,
+                    // Random 16-digit numbers are not card numbers unless they pass the checksum
+                    RuleCheck::Luhn => luhn_valid(candidate),
+                    RuleCheck::Entropy => {
+                        // Hex IDs (hashes, UUIDs) are long but not secrets, and anything a
+                        // provider-specific pattern already reported should not repeat
+                        !candidate.chars().all(|c| c.is_ascii_hexdigit())
+                            && shannon_entropy(candidate) >= options.entropy_threshold
+                            && !hits.iter().any(|hit| {
+                                matches!(rules[hit.rule].category, Some(Category::Secrets))
+                                    && hit.start < end
+                                    && start < hit.end
+                            })
+                    }
+                };
+                if !accepted {
+                    continue;
+                }
+                
+                let captures = captures.into_iter()
+                    .map(|capture| Capture {
+                        span: capture.span.map(|(start, end)| original_span(start, end)),
+                        ..capture
+                    })
+                    .collect();
+                hits.push(Hit { rule: index, start, end, captures, overlaps: Vec::new() });
+            }
+        }
+        
+        // Report findings in input order rather than grouped by pattern
+        hits.sort_by_key(|hit| hit.start);
+        
+        // Custom patterns that match the same text report it once, even with dedupe off
+        let mut custom_spans = HashSet::new();
+        hits.retain(|hit| rules[hit.rule].category.is_some() || custom_spans.insert((hit.start, hit.end)));
+        
+        if options.dedupe {
+            dedupe_hits(hits, rules)
+        } else {
+            hits
+        }
+    }
+}
+
+/// The rule for one custom pattern, already escaped if it is a fixed string.
+fn custom_rule(pattern: &str, flexible_spaces: bool, options: &ScanOptions) -> Result<Rule, Error> {
+    let regex_flags = if options.ignore_case { "(?i)" } else { "" };
+    let pattern = format!("{}{}", regex_flags, pattern);
+    // Spaces in user-written regexes are left alone; `\s` is available there
+    let regex = if options.multiline {
+        Regex::new(&multiline_pattern(&pattern, flexible_spaces))?
+    } else {
+        Regex::new(&pattern)?
+    };
+    
+    Ok(Rule {
+        regex,
+        category: None,
+        subtype: "",
+        severity: Severity::Medium,
+        check: RuleCheck::None,
+    })
+}
+
+/// The names `apply_severity_overrides` can match, sorted: every category and the subtype of
+/// every preset rule.
+pub fn severity_override_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = Category::ALL.into_iter()
+        .flat_map(preset_rules)
+        .map(|(_, subtype, _, _)| subtype)
+        .chain(AnomalyType::CATEGORIES)
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Set the severity of matches by category or subtype name, e.g. `("email", Severity::Low)`.
+/// Later overrides win over earlier ones. Names not in `severity_override_names` match
+/// nothing, so callers should check them first.
+pub fn apply_severity_overrides(matches: &mut [Match], overrides: &[(String, Severity)]) {
+    if overrides.is_empty() {
+        return;
+    }
+    
+    let lookup = |name: &str| overrides.iter().rev().find(|(category, _)| category == name).map(|(_, level)| *level);
+    
+    for m in matches.iter_mut() {
+        // A subtype override (e.g. "email") wins over its category ("data_leakage")
+        let level = m.anomaly_type.subtype()
+            .and_then(lookup)
+            .or_else(|| lookup(m.anomaly_type.category()));
+        
+        if let Some(level) = level {
+            m.severity = level;
+        }
+    }
+}
+
+/// Hash index of the word n-grams of a reference corpus, for finding text copied from it.
+///
+/// Only a 64-bit hash of each n-gram is stored, with where it first occurs, so memory grows
+/// with the number of distinct n-grams (a few dozen bytes each) rather than with the text,
+/// and stops growing at the `max_ngrams` given to `build`. The tradeoffs: n-grams past that
+/// limit are not indexed, so text copied from the rest of a very large corpus goes unnoticed
+/// (`is_full` says when this happens); a passage repeated within the corpus is traced to its
+/// first occurrence only; and a hash collision could, with negligible probability, report
+/// text that is not in the corpus.
+#[derive(Debug, Default)]
+pub struct CorpusIndex {
+    ngram_size: usize,
+    min_overlap: usize,
+    /// Reference file paths, as shown in findings
+    files: Vec<String>,
+    ngrams: HashMap<u64, CorpusPosition>,
+    full: bool,
+}
+
+/// Where an n-gram first occurs in the corpus.
+#[derive(Debug, Clone, Copy)]
+struct CorpusPosition {
+    /// Index into `CorpusIndex::files`
+    file: u32,
+    /// Index of the n-gram's first word among the file's words
+    word: u32,
+    /// Byte offset of that word in the file (invalid UTF-8 before it can shift this)
+    offset: u64,
+}
+
+/// A run of words in a scanned line that also appears, in order, in the corpus.
+struct CorpusPassage {
+    start: usize,
+    end: usize,
+    /// Where the passage's first n-gram occurs in the corpus
+    source: CorpusPosition,
+    words: usize,
+}
+
+impl CorpusIndex {
+    /// Index every file under `dir`, recursively, in path order. Passages of at least
+    /// `min_overlap` words that follow the corpus word for word are reported.
+    ///
+    /// # Panics
+    ///
+    /// If `ngram_size` is 0 or `min_overlap` is smaller than `ngram_size`.
+    pub fn build(dir: &Path, ngram_size: usize, min_overlap: usize, max_ngrams: usize) -> Result<Self, Error> {
+        assert!(ngram_size > 0, "n-grams need at least one word");
+        assert!(min_overlap >= ngram_size, "the minimum overlap cannot be shorter than an n-gram");
+        
+        let mut paths = Vec::new();
+        corpus_files(dir, &mut paths)
+            .map_err(|source| Error::CorpusDir { dir: dir.to_path_buf(), source })?;
+        paths.sort();
+        
+        let mut index = CorpusIndex { ngram_size, min_overlap, ..CorpusIndex::default() };
+        for path in paths {
+            let bytes = fs::read(&path).map_err(|source| Error::CorpusFile { path: path.clone(), source })?;
+            let text = String::from_utf8_lossy(&bytes);
+            let file = index.files.len() as u32;
+            index.files.push(path.display().to_string());
+            
+            for (word, window) in corpus_words(&text).windows(index.ngram_size).enumerate() {
+                let hash = ngram_hash(&text, window);
+                if index.ngrams.len() >= max_ngrams && !index.ngrams.contains_key(&hash) {
+                    index.full = true;
+                    continue;
+                }
+                index.ngrams.entry(hash)
+                    .or_insert(CorpusPosition { file, word: word as u32, offset: window[0].0 as u64 });
+            }
+        }
+        Ok(index)
+    }
+    
+    /// Whether n-grams were left out of the index for going over `max_ngrams`.
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+    
+    /// Passages of `line` of at least `min_overlap` words that follow the corpus word for
+    /// word, in line order.
+    fn find(&self, line: &str) -> Vec<CorpusPassage> {
+        let words = corpus_words(line);
+        let mut passages = Vec::new();
+        let mut finish = |first: usize, source: CorpusPosition, ngrams: usize| {
+            let count = ngrams + self.ngram_size - 1;
+            if count >= self.min_overlap {
+                passages.push(CorpusPassage { start: words[first].0, end: words[first + count - 1].1, source, words: count });
+            }
+        };
+        
+        // The run being extended: its first n-gram in `words`, that n-gram's corpus position,
+        // the position of its last n-gram, and how many n-grams it has
+        let mut run: Option<(usize, CorpusPosition, CorpusPosition, usize)> = None;
+        for (i, window) in words.windows(self.ngram_size).enumerate() {
+            let position = self.ngrams.get(&ngram_hash(line, window)).copied();
+            run = match (run, position) {
+                (Some((first, source, last, ngrams)), Some(next)) if next.file == last.file && next.word == last.word + 1 => {
+                    Some((first, source, next, ngrams + 1))
+                }
+                (previous, position) => {
+                    if let Some((first, source, _, ngrams)) = previous {
+                        finish(first, source, ngrams);
+                    }
+                    position.map(|position| (i, position, position, 1))
+                }
+            };
+        }
+        if let Some((first, source, _, ngrams)) = run {
+            finish(first, source, ngrams);
+        }
+        passages
+    }
+    
+    /// Each further `min_overlap` words copied raises the severity by one level.
+    fn severity(&self, words: usize) -> Severity {
+        match words / self.min_overlap {
+            0 | 1 => Severity::Low,
+            2 => Severity::Medium,
+            3 => Severity::High,
+            _ => Severity::Critical,
+        }
+    }
+}
+
+/// Every file under `dir`, following subdirectories.
+fn corpus_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            corpus_files(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Byte spans of the words in `text`: runs of letters and digits, so punctuation and
+/// spacing do not affect matching.
+fn corpus_words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(word_start)) => {
+                words.push((word_start, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push((word_start, text.len()));
+    }
+    words
+}
+
+/// Case-insensitive hash of the words at `spans` in `text`.
+fn ngram_hash(text: &str, spans: &[(usize, usize)]) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    for &(start, end) in spans {
+        for c in text[start..end].chars().flat_map(char::to_lowercase) {
+            c.hash(&mut hasher);
+        }
+        ' '.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Text rewritten for matching by normalization, remembering where each byte came from so
+/// hits can be reported at their place in the original.
+struct NormalizedText {
+    text: String,
+    /// For every byte of `text`, the byte range of the original it was produced from
+    origins: Vec<(usize, usize)>,
+    original_len: usize,
+}
+
+impl NormalizedText {
+    /// NFKC-normalize `original` and fold typographic punctuation to ASCII. Each character is
+    /// normalized together with the combining marks that follow it, which is the unit an
+    /// offset can be mapped back to.
+    fn new(original: &str) -> Self {
+        let mut text = String::with_capacity(original.len());
+        let mut origins = Vec::with_capacity(original.len());
+        let mut chars = original.char_indices().peekable();
+        
+        while let Some((start, c)) = chars.next() {
+            let mut end = start + c.len_utf8();
+            while let Some(&(next, mark)) = chars.peek().filter(|(_, mark)| unicode_normalization::char::is_combining_mark(*mark)) {
+                end = next + mark.len_utf8();
+                chars.next();
+            }
+            
+            let unit = &original[start..end];
+            if unit.is_ascii() {
+                text.push_str(unit);
+            } else {
+                text.extend(unit.nfkc().map(fold_punctuation));
+            }
+            origins.resize(text.len(), (start, end));
+        }
+        
+        NormalizedText { text, origins, original_len: original.len() }
+    }
+    
+    /// Map a byte range of the normalized text to the original. A range that starts or ends
+    /// inside the expansion of one original character (a ligature, say) grows to cover it.
+    fn original_span(&self, start: usize, end: usize) -> (usize, usize) {
+        let from = self.origins.get(start).map_or(self.original_len, |&(from, _)| from);
+        let to = if end > start { self.origins[end - 1].1 } else { from };
+        (from, to)
+    }
+}
+
+/// Fold typographic quotes, dashes and spaces that NFKC leaves alone to their ASCII forms.
+fn fold_punctuation(c: char) -> char {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+        '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => ' ',
+        _ => c,
+    }
+}
+
+/// Collapse hits of the same category with overlapping spans into the one with the highest
+/// severity, then the longest span, then the earliest rule. Overlapping hits of different
+/// categories are all kept, each noting the others' categories. `hits` must be sorted by start.
+fn dedupe_hits(hits: Vec<Hit>, rules: &[Rule]) -> Vec<Hit> {
+    let outranks = |a: &Hit, b: &Hit| {
+        (rules[a.rule].severity, a.end - a.start) > (rules[b.rule].severity, b.end - b.start)
+    };
+    
+    let mut kept: Vec<Option<Hit>> = Vec::with_capacity(hits.len());
+    // Kept hits that may still overlap the next one, i.e. that end after it starts
+    let mut active: Vec<usize> = Vec::new();
+    for hit in hits {
+        active.retain(|&i| kept[i].as_ref().is_some_and(|other| other.end > hit.start));
+        let rivals: Vec<usize> = active.iter().copied()
+            .filter(|&i| kept[i].as_ref().is_some_and(|other| rules[other.rule].category == rules[hit.rule].category))
+            .collect();
+        if rivals.iter().all(|&i| kept[i].as_ref().is_some_and(|other| outranks(&hit, other))) {
+            for i in rivals {
+                kept[i] = None;
+            }
+            active.push(kept.len());
+            kept.push(Some(hit));
+        }
+    }
+    
+    let mut kept: Vec<Hit> = kept.into_iter().flatten().collect();
+    for i in 0..kept.len() {
+        for j in i + 1..kept.len() {
+            if kept[j].start >= kept[i].end {
+                break;
+            }
+            let (first, second) = (rules[kept[i].rule].anomaly_type().category(), rules[kept[j].rule].anomaly_type().category());
+            if first != second {
+                if !kept[i].overlaps.iter().any(|category| category == second) {
+                    kept[i].overlaps.push(second.to_string());
+                }
+                if !kept[j].overlaps.iter().any(|category| category == first) {
+                    kept[j].overlaps.push(first.to_string());
+                }
+            }
+        }
+    }
+    kept
+}
+
+fn named_captures(regex: &Regex, caps: &regex::Captures) -> Vec<Capture> {
+    regex.capture_names()
+        .flatten()
+        .map(|name| Capture {
+            name: name.to_string(),
+            span: caps.name(name).map(|group| (group.start(), group.end())),
+        })
+        .collect()
+}
+
+/// Compile a preset regex with the requested case folding. Patterns keep their authored
+/// case behaviour unless `ignore_case` or `case_sensitive_presets` is set.
+fn build_preset_regex(pattern_str: &str, options: &ScanOptions) -> Result<Regex, Error> {
+    let bare = pattern_str.strip_prefix("(?i)").unwrap_or(pattern_str);
+    
+    let pattern = if options.case_sensitive_presets {
+        bare.to_string()
+    } else if options.ignore_case {
+        format!("(?i){}", bare)
+    } else {
+        pattern_str.to_string()
+    };
+    
+    if options.multiline {
+        return Ok(Regex::new(&multiline_pattern(&pattern, true))?);
+    }
+    Ok(Regex::new(&pattern)?)
+}
+
+/// Rewrite a pattern for multiline matching: `.` matches newlines, and with `flexible_spaces`
+/// each space outside a character class matches any run of whitespace, line breaks included.
+fn multiline_pattern(pattern: &str, flexible_spaces: bool) -> String {
+    let mut rewritten = String::from("(?s)");
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+    
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                rewritten.push(c);
+                rewritten.extend(chars.next());
+            }
+            '[' => {
+                in_class = true;
+                rewritten.push(c);
+            }
+            ']' => {
+                in_class = false;
+                rewritten.push(c);
+            }
+            ' ' if flexible_spaces && !in_class => rewritten.push_str(r"\s+"),
+            _ => rewritten.push(c),
+        }
+    }
+    rewritten
+}
+
+/// Pattern table for a preset: (regex, subtype, severity, extra check).
+fn preset_rules(preset: Category) -> Vec<(&'static str, &'static str, Severity, RuleCheck)> {
+    use RuleCheck::{Entropy, Luhn, None};
+    
+    match preset {
+        Category::Hallucinations => vec![
+            (r"(?i)\b(september 2021|knowledge cutoff|training data|cutoff date)\b", "knowledge_cutoff", Severity::High, None),
+            (r"(?i)\b(my last update|as of my last training)\b", "knowledge_cutoff", Severity::High, None),
+            (r"(?i)\b(as an ai|i cannot|i don't have access|i'm not able to)\b", "capability_disclaimer", Severity::Critical, None),
+            (r"(?i)\b(i don't have the ability|i cannot browse|cannot access the internet)\b", "capability_disclaimer", Severity::High, None),
+            (r"(?i)\b(i'm not sure|i cannot verify|unconfirmed|i don't know)\b", "uncertainty", Severity::Medium, None),
+            (r"(?i)\b(i cannot confirm|i'm uncertain|unclear)\b", "uncertainty", Severity::Medium, None),
+            (r"(?i)\b(real-time|cannot browse|cannot access websites)\b", "browsing_limitation", Severity::High, None),
+            (r"(?i)\b(i don't have internet access|cannot search the web)\b", "browsing_limitation", Severity::High, None),
+        ],
+        Category::CodeIssues => vec![
+            (r"(?i)\b(todo|fixme|hack|bug|xxx|note|warn|warning)\b", "development_marker", Severity::Medium, None),
+            (r"(?i)\b(deprecated|obsolete|legacy|remove|delete)\b", "deprecation", Severity::Low, None),
+            (r"(?i)\b(temporary|temp|quick.?fix|workaround)\b", "temporary_code", Severity::Medium, None),
+            (r"(?i)\b(unsafe|danger|critical|urgent)\b", "safety_concern", Severity::High, None),
+            (r"(?i)//\s*(TODO|FIXME|HACK|BUG|XXX)", "comment_marker", Severity::Medium, None),
+            (r"#\s*(TODO|FIXME|HACK|BUG|XXX)", "comment_marker", Severity::Medium, None),
+        ],
+        Category::Security => vec![
+            (r"(?i)\b(password|passwd|pwd)\s*=\s*[\x22\x27][^\x22\x27]*[\x22\x27]", "hardcoded_password", Severity::Critical, None),
+            (r"(?i)\b(api.?key|secret.?key|private.?key)\s*[=:]\s*[\x22\x27][^\x22\x27]+[\x22\x27]", "hardcoded_secret", Severity::Critical, None),
+            (r"(?i)\b(eval|exec|system|shell_exec)\s*\(", "code_injection", Severity::High, None),
+            (r"(?i)\b(sql\s+injection|xss|csrf|rce|lfi|rfi)\b", "vulnerability_mention", Severity::High, None),
+            (r"(?i)\b(unsafe|memcpy|strcpy|gets|scanf)\b", "unsafe_function", Severity::High, None),
+            (r"(?i)\b(admin|root|administrator)\s*[/:]\s*\w+", "privileged_access", Severity::Medium, None),
+        ],
+        Category::DataLeakage => vec![
+            (r"\b\d{3}-\d{2}-\d{4}\b", "ssn", Severity::Critical, None),
+            (r"\b\d{4}[\s-]?\d{4}[\s-]?\d{4}[\s-]?\d{4}\b", "credit_card", Severity::Critical, Luhn),
+            (r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b", "email", Severity::Medium, None),
+            (r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b", "ip_address", Severity::Low, None),
+            (r"(?i)\b(training.?data|dataset|corpus|model.?weights)\b", "training_reference", Severity::Medium, None),
+            (r"(?i)\b(openai|anthropic|google|microsoft|meta)\s+(internal|confidential)", "internal_reference", Severity::High, None),
+        ],
+        Category::LowConfidence => vec![
+            (r"(?i)\b(might|maybe|perhaps|possibly|potentially|could be)\b", "uncertainty", Severity::Low, None),
+            (r"(?i)\b(likely|probably|seems|appears|suggests)\b", "hedging", Severity::Low, None),
+            (r"(?i)\b(i think|i believe|i guess|i assume)\b", "opinion", Severity::Medium, None),
+            (r"(?i)\b(allegedly|supposedly|reportedly|apparently)\b", "hearsay", Severity::Medium, None),
+            (r"(?i)\b(unverified|unconfirmed|uncertain|unclear)\b", "verification_issue", Severity::High, None),
+        ],
+        Category::Secrets => vec![
+            (r"\b(AKIA|ASIA)[0-9A-Z]{16}\b", "aws_access_key", Severity::Critical, None),
+            (r"\bgh[pousr]_[A-Za-z0-9]{36,}\b", "github_token", Severity::Critical, None),
+            (r"\bgithub_pat_[A-Za-z0-9_]{22,}\b", "github_token", Severity::Critical, None),
+            (r"\bxox[abposr]-[A-Za-z0-9-]{10,}\b", "slack_token", Severity::Critical, None),
+            (r"https://hooks\.slack\.com/services/[A-Za-z0-9/]+", "slack_webhook", Severity::Critical, None),
+            (r"\bAIza[0-9A-Za-z_-]{35}\b", "google_api_key", Severity::Critical, None),
+            (r"\b[sr]k_live_[A-Za-z0-9]{24,}\b", "stripe_key", Severity::Critical, None),
+            (r"\bsk-(ant-)?[A-Za-z0-9_-]{20,}\b", "llm_api_key", Severity::Critical, None),
+            (r"-----BEGIN ([A-Z]+ )?PRIVATE KEY-----", "private_key", Severity::Critical, None),
+            (r"[A-Za-z0-9+/_-]{32,}={0,2}", "high_entropy_string", Severity::Critical, Entropy),
+        ],
+        Category::PromptInjection => vec![
+            (r"(?i)\b(ignore|disregard|forget|override)\s+(all\s+)?(of\s+)?(the\s+|your\s+|any\s+)?(previous|prior|above|earlier|preceding)\s+(instructions?|prompts?|directions|rules|context)\b", "instruction_override", Severity::Critical, None),
+            (r"(?i)\b(ignore|disregard|forget|override|reveal|print|leak)\s+(the\s+|your\s+)?(system|developer)\s+(prompt|message|instructions)\b", "system_prompt_override", Severity::Critical, None),
+            (r"(?i)\b(you are now|you're now|act as|pretend to be)\s+(DAN|an?\s+unrestricted|in developer mode|jailbroken)\b|\bdo anything now\b", "jailbreak_persona", Severity::High, None),
+            (r"(?i)\b(pretend|imagine|role-?play|act as if)\b.{0,40}\b(no|without)\s+(restrictions|rules|filters|guidelines|limits)\b", "role_play", Severity::High, None),
+            (r"(?i)[A-Za-z0-9+/]{20,}={0,2}.{0,40}\bdecode\s+(and|then)\s+(execute|run|follow)\b|\bdecode\s+(and|then)\s+(execute|run|follow)\b.{0,40}[A-Za-z0-9+/]{20,}={0,2}", "encoded_payload", Severity::High, None),
+            (r"(?i)<!--.{0,200}?\b(ignore|instructions?|system|assistant|prompt)\b.{0,200}?-->", "hidden_html_comment", Severity::High, None),
+            (r"[\u{200B}\u{200C}\u{200D}\u{2060}\u{FEFF}]", "zero_width_characters", Severity::Medium, None),
+            (r"(?i)\b(new|updated|real)\s+instructions\s*:", "injected_instructions", Severity::Medium, None),
+        ],
+    }
+}
+
+/// Shannon entropy of a string in bits per character.
+fn shannon_entropy(s: &str) -> f64 {
+    let mut frequencies: HashMap<char, usize> = HashMap::new();
+    for c in s.chars() {
+        *frequencies.entry(c).or_insert(0) += 1;
+    }
+    
+    let len = s.chars().count() as f64;
+    frequencies.values()
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Luhn checksum over the digits of a candidate card number.
+fn luhn_valid(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() < 13 {
+        return false;
+    }
+    
+    let sum: u32 = digits.iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+    
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,79 @@
+use ai_toolkit_core::{
+    apply_severity_overrides, severity_override_names, AnomalyType, Categories, Category, Match, Normalize, ScanOptions,
+    Scanner, Severity,
+};
+
+#[test]
+fn findings_are_reported_per_line_in_input_order() {
+    let text = "Sure, here you go.\r\n// TODO: check the key sk-ant-REDACTED\nI think it might work.";
+    let matches = Scanner::new(Categories::all()).scan(text);
+    
+    let found: Vec<(usize, &str, &str)> = matches.iter()
+        .map(|m| (m.line_number, m.anomaly_type.category(), m.matched_text.as_str()))
+        .collect();
+    assert_eq!(found, vec![
+        (2, "code_issue", "// TODO"),
+        (2, "secret", "sk-ant-REDACTED"),
+        (3, "low_confidence", "I think"),
+        (3, "low_confidence", "might"),
+    ]);
+    // Offsets count the CRLF of the first line
+    assert_eq!(matches[0].line_offset, 20);
+    assert_eq!(matches[1].column(), 24);
+}
+
+#[test]
+fn options_change_how_rules_match() {
+    // Presets match NFKC-normalized text but report the original
+    let text = "It is ｐｏｓｓｉｂｌｙ the “final” result.";
+    let hedges = Scanner::new(Category::LowConfidence.into());
+    assert_eq!(hedges.scan(text)[0].matched_text, "ｐｏｓｓｉｂｌｙ");
+    
+    let exact = ScanOptions { case_sensitive_presets: true, ..ScanOptions::default() };
+    assert!(Scanner::with_options(Category::LowConfidence.into(), exact).unwrap().scan("POSSIBLY").is_empty());
+    
+    // Literal patterns are escaped, and only normalized when asked to
+    let literal = |pattern: &str, normalize| {
+        let options = ScanOptions { ignore_case: true, normalize, ..ScanOptions::default() };
+        Scanner::literal(pattern, options).unwrap().scan(text).len()
+    };
+    assert_eq!(literal("\"final\" result.", Normalize::Presets), 0);
+    assert_eq!(literal("\"FINAL\" result.", Normalize::Always), 1);
+    assert_eq!(literal("result?", Normalize::Always), 0);
+    
+    assert!(Scanner::regex("(unclosed", ScanOptions::default()).is_err());
+}
+
+#[test]
+fn matches_round_trip_through_json() {
+    let matches = Scanner::new(Categories::none().with(Category::DataLeakage)).scan("mail bob@example.com");
+    let json = serde_json::to_value(&matches).unwrap();
+    assert_eq!(json[0]["anomaly_type"], serde_json::json!({ "type": "data_leakage", "leak_type": "email" }));
+    assert_eq!(json[0]["severity"], "Medium");
+    assert!(json[0].get("context_before").is_none());
+    
+    let parsed: Vec<Match> = serde_json::from_value(json).unwrap();
+    assert!(matches!(&parsed[0].anomaly_type, AnomalyType::DataLeakage { leak_type } if leak_type == "email"));
+    assert_eq!(parsed[0].severity, Severity::Medium);
+    assert_eq!("critical".parse::<Severity>().unwrap(), Severity::Critical);
+    assert_eq!((Severity::from_count(4), Severity::from_count(10)), (Severity::Low, Severity::High));
+    assert_eq!((Severity::Critical.marker(true), Severity::Critical.marker(false)), ("💀", "[CRITICAL]"));
+}
+
+#[test]
+fn severity_overrides_by_category_and_subtype() {
+    let mut matches = Scanner::new(Categories::all()).scan("mail bob@example.com\n// TODO");
+    let overrides = [("data_leakage".to_string(), Severity::Critical), ("comment_marker".to_string(), Severity::Low)];
+    apply_severity_overrides(&mut matches, &overrides);
+    let severities: Vec<Severity> = matches.iter().map(|m| m.severity).collect();
+    assert_eq!(severities, vec![Severity::Critical, Severity::Low]);
+    
+    // Every name a match can report is one that can be overridden
+    let names = severity_override_names();
+    assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+    for m in &matches {
+        assert!(names.contains(&m.anomaly_type.category()));
+        assert!(names.contains(&m.anomaly_type.subtype().unwrap()));
+    }
+    assert!(!names.contains(&"phone"));
+}
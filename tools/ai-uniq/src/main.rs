@@ -1,15 +1,23 @@
+use ai_toolkit_core::{ColorMode, Severity};
 use clap::Parser;
 use regex::Regex;
-use serde_json;
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Parser, Debug)]
 #[command(name = "ai-uniq")]
 #[command(about = "Statistical verification and deduplication for AI outputs with repetition detection")]
+#[command(group(clap::ArgGroup::new("with_chars").multiple(true)))]
+#[command(group(clap::ArgGroup::new("with_loops").multiple(true)))]
 struct Args {
     /// Input files (reads from stdin if none provided)
     files: Vec<PathBuf>,
@@ -34,6 +42,10 @@ struct Args {
     #[arg(short = 'f', long)]
     skip_fields: Option<usize>,
     
+    /// Field separator for -f (default: runs of whitespace)
+    #[arg(short = 't', long, value_name = "CHAR")]
+    field_separator: Option<char>,
+    
     /// Skip first N characters like uniq -s
     #[arg(short = 's', long)]
     skip_chars: Option<usize>,
@@ -42,6 +54,54 @@ struct Args {
     #[arg(short = 'w', long)]
     check_chars: Option<usize>,
     
+    /// Count -s and -w in bytes rather than characters
+    #[arg(long)]
+    bytes: bool,
+    
+    /// Collapse only consecutive duplicate lines like uniq (the default for plain line input)
+    #[arg(long, conflicts_with_all = ["global", "sort_freq", "numeric_sort", "reverse", "max_tracked", "show_positions", "fuzzy"])]
+    adjacent: bool,
+    
+    /// Count duplicate lines across the whole input, printed in order of first appearance
+    #[arg(long)]
+    global: bool,
+    
+    /// Merge near-duplicate lines whose similarity, ignoring case and punctuation, is at least
+    /// THRESHOLD (default 0.85); each cluster prints its first line and example variants.
+    /// Give the value as --fuzzy=0.9: after a space it would be read as a file
+    #[arg(long, value_name = "THRESHOLD", num_args = 0..=1, require_equals = true, default_missing_value = "0.85",
+          conflicts_with_all = ["word_analysis", "phrase_analysis", "char_analysis", "detect_loops", "stats", "all", "approximate"])]
+    fuzzy: Option<f64>,
+    
+    /// How --fuzzy measures similarity
+    #[arg(long, value_enum, default_value = "jaccard", requires = "fuzzy")]
+    fuzzy_metric: FuzzyMetric,
+    
+    /// Treat each input file (or record, with --doc-separator) as a document and cluster
+    /// near-duplicate documents by MinHash-estimated Jaccard similarity of word shingles
+    #[arg(long, conflicts_with_all = ["word_analysis", "phrase_analysis", "char_analysis", "detect_loops", "stats", "all", "approximate", "fuzzy", "adjacent"])]
+    docs: bool,
+    
+    /// With --docs, split each input into records at lines equal to SEP
+    #[arg(long, value_name = "SEP", allow_hyphen_values = true, requires = "docs")]
+    doc_separator: Option<String>,
+    
+    /// With --docs, cluster documents whose estimated similarity is at least THRESHOLD
+    #[arg(long, value_name = "THRESHOLD", default_value = "0.7", requires = "docs")]
+    doc_threshold: f64,
+    
+    /// With --docs, words per shingle
+    #[arg(long, value_name = "N", default_value = "3", requires = "docs")]
+    shingle_size: usize,
+    
+    /// With --docs, hash functions in each document's MinHash signature
+    #[arg(long, value_name = "N", default_value = "128", requires = "docs")]
+    num_hashes: usize,
+    
+    /// With --docs, write the first document of every cluster, and every unique one, to DIR
+    #[arg(long, value_name = "DIR", requires = "docs")]
+    keep_first: Option<PathBuf>,
+    
     /// AI-specific: Analyze word repetition patterns
     #[arg(long)]
     word_analysis: bool,
@@ -50,31 +110,107 @@ struct Args {
     #[arg(long)]
     phrase_analysis: bool,
     
-    /// AI-specific: N-gram size for phrase analysis
-    #[arg(long, default_value = "3")]
-    ngram_size: usize,
+    /// AI-specific: Apply NFKC normalization before counting words, so "ﬁle" and "file"
+    /// count together
+    #[arg(long)]
+    unicode_normalize: bool,
+    
+    /// AI-specific: Strip diacritics so "naïve" and "naive" count as one word
+    #[arg(long)]
+    strip_diacritics: bool,
     
-    /// AI-specific: Flag repetitions above threshold
-    #[arg(long, default_value = "5")]
-    repetition_threshold: usize,
+    /// AI-specific: Leave these words out of --word-analysis and the word-loop check: "en"
+    /// for the built-in English list, or a file with one word per line
+    #[arg(long, value_name = "LANG|FILE", default_value = "en")]
+    stopwords: String,
     
-    /// AI-specific: Show top N most repeated items
-    #[arg(long, default_value = "20")]
-    top_n: usize,
+    /// AI-specific: Count stopwords too
+    #[arg(long, conflicts_with = "stopwords")]
+    no_stopwords: bool,
     
-    /// AI-specific: Detect AI loop patterns
+    /// AI-specific: Leave words shorter than N characters out of --word-analysis and the
+    /// word-loop check
+    #[arg(long, value_name = "N", default_value = "1")]
+    min_word_length: usize,
+    
+    /// AI-specific: N-gram size for phrase analysis; a range (2-5) or repeated flags count
+    /// several sizes in one pass
+    #[arg(long, value_name = "N|MIN-MAX", default_value = "3", value_parser = parse_ngram_sizes)]
+    ngram_size: Vec<(usize, usize)>,
+    
+    /// AI-specific: With several n-gram sizes, keep n-grams that only occur inside a longer
+    /// repeated one (same count) instead of folding them into it
     #[arg(long)]
+    no_collapse: bool,
+    
+    /// AI-specific: Analyze character n-grams and the longest repeated substrings, which
+    /// catch loops inside words ("abababab") that word and line analysis can't see
+    #[arg(long, group = "with_chars")]
+    char_analysis: bool,
+    
+    /// AI-specific: Characters per n-gram for --char-analysis
+    #[arg(long, value_name = "N", default_value = "4", requires = "with_chars")]
+    char_ngram_size: usize,
+    
+    /// AI-specific: Shortest repeated substring --char-analysis reports and the character
+    /// loop check of --detect-loops looks for
+    #[arg(long, value_name = "N", default_value = "20")]
+    min_repeat_len: usize,
+    
+    /// AI-specific: Flag repetitions above threshold (default 5; without it, --threshold-ratio
+    /// alone decides what --above-threshold flags)
+    #[arg(long, value_name = "N")]
+    repetition_threshold: Option<usize>,
+    
+    /// AI-specific: Flag items making up more than this fraction of all the words, phrases or
+    /// lines counted (defaults 0.05, 0.02 and 0.1), so the threshold scales with the input.
+    /// Give the value as --threshold-ratio=0.1: after a space it would be read as a file
+    #[arg(long, value_name = "RATIO", num_args = 0..=1, require_equals = true)]
+    threshold_ratio: Option<Option<f64>>,
+    
+    /// AI-specific: With both thresholds, flag items that pass all of them (the stricter
+    /// wins) or any of them
+    #[arg(long, value_enum, default_value = "all", requires = "threshold_ratio")]
+    threshold_mode: ThresholdMode,
+    
+    /// AI-specific: Show top N most repeated items (default 20) and most severe loops; given
+    /// with --stats, also list the N most repeated lines and words. 0 shows everything
+    #[arg(long, value_name = "N")]
+    top_n: Option<usize>,
+    
+    /// AI-specific: Detect AI loop patterns
+    #[arg(long, group = "with_loops")]
     detect_loops: bool,
     
+    /// AI-specific: Look for loops only in the last N lines, where decoding loops usually
+    /// appear; reported lines still count from the start of the input
+    #[arg(long, value_name = "N", requires = "with_loops")]
+    tail_window: Option<usize>,
+    
     /// AI-specific: Statistical analysis of repetition
     #[arg(long)]
     stats: bool,
     
-    /// Sort output by frequency (descending)
+    /// AI-specific: Run every analysis (words, phrases, characters, loops and statistics)
+    /// over one reading of the input
+    #[arg(long, groups = ["with_chars", "with_loops"], conflicts_with = "approximate")]
+    all: bool,
+    
+    /// AI-specific: Run the analyses on each input file on its own, under a "==> FILE <=="
+    /// header, then on all of them together
+    #[arg(long, conflicts_with = "approximate")]
+    per_file: bool,
+    
+    /// AI-specific: Show how many input files each counted word, phrase or n-gram occurs in:
+    /// repeated within one file it is a loop, across many a template
+    #[arg(long, conflicts_with = "approximate")]
+    show_file_spread: bool,
+    
+    /// Sort output by frequency (descending) instead of by first appearance
     #[arg(long)]
     sort_freq: bool,
     
-    /// Sort output numerically
+    /// Sort output numerically instead of by first appearance
     #[arg(long)]
     numeric_sort: bool,
     
@@ -82,10 +218,15 @@ struct Args {
     #[arg(short = 'r', long)]
     reverse: bool,
     
-    /// Output format: text, json
+    /// Output format: text, json, or ndjson (one record per item, loop or stat)
     #[arg(long, default_value = "text")]
     format: String,
     
+    /// Use emoji in text output (--color); auto only on a terminal with NO_COLOR unset,
+    /// otherwise severities print as [CRITICAL] and the like
+    #[arg(long, value_enum, default_value = "auto")]
+    color: ColorMode,
+    
     /// Show only items above threshold
     #[arg(long)]
     above_threshold: bool,
@@ -93,6 +234,268 @@ struct Args {
     /// Minimum repetition count to display
     #[arg(long, default_value = "1")]
     min_count: usize,
+    
+    /// Cap on distinct lines tracked by plain uniq, or words and phrases with --approximate;
+    /// least frequent are evicted beyond it
+    #[arg(long, value_name = "N")]
+    max_tracked: Option<usize>,
+    
+    /// Show where each counted line, word or phrase occurs: its line numbers, and word offsets
+    /// for phrases (plain lines are then counted across the whole input)
+    #[arg(long)]
+    show_positions: bool,
+    
+    /// Occurrences recorded per item for --show-positions and --detect-loops; later ones
+    /// are only counted
+    #[arg(long, value_name = "N", default_value = "1000")]
+    max_positions: usize,
+    
+    /// Stream --word-analysis and --phrase-analysis input, keeping at most --max-tracked
+    /// (default 100000) distinct items in memory; counts may then be approximate
+    #[arg(long)]
+    approximate: bool,
+}
+
+impl Args {
+    fn json_output(&self) -> bool {
+        self.format == "json" || self.format == "ndjson"
+    }
+    
+    /// Plain line input is deduplicated adjacently unless --global, or an option that orders
+    /// the whole input, asks for every line to be counted together.
+    fn counts_globally(&self) -> bool {
+        self.global || self.sort_freq || self.numeric_sort || self.reverse || self.max_tracked.is_some()
+            || self.show_positions || self.fuzzy.is_some()
+    }
+    
+    fn emoji(&self) -> bool {
+        self.color.enabled()
+    }
+    
+    /// Every requested n-gram size, ascending and without repeats.
+    fn ngram_sizes(&self) -> Vec<usize> {
+        let sizes: BTreeSet<usize> = self.ngram_size.iter().flat_map(|&(min, max)| min..=max).collect();
+        sizes.into_iter().collect()
+    }
+    
+    /// How many items or loops to show: --top-n, 20 without it, or all of them for 0.
+    fn top_n_limit(&self) -> usize {
+        match self.top_n {
+            Some(0) => usize::MAX,
+            Some(n) => n,
+            None => DEFAULT_TOP_N,
+        }
+    }
+    
+    /// --repetition-threshold, or its default.
+    fn repetition_threshold(&self) -> usize {
+        self.repetition_threshold.unwrap_or(DEFAULT_REPETITION_THRESHOLD)
+    }
+    
+    /// Flagged lists print with --above-threshold or --threshold-ratio.
+    fn flags_items(&self) -> bool {
+        self.above_threshold || self.threshold_ratio.is_some()
+    }
+    
+    /// What flags an item of `kind` when `total` of them were counted. The absolute
+    /// threshold only joins a ratio when --repetition-threshold is given.
+    fn threshold(&self, kind: ItemKind, total: usize) -> Threshold {
+        let ratio = self.threshold_ratio.map(|ratio| ratio.unwrap_or(kind.default_ratio()));
+        let count = match ratio {
+            Some(_) => self.repetition_threshold,
+            None => Some(self.repetition_threshold()),
+        };
+        Threshold { count, ratio, total, mode: self.threshold_mode, kind }
+    }
+    
+    /// `icon` and a space to lead a status line, or nothing when emoji are off.
+    fn icon(&self, icon: &str) -> String {
+        if self.emoji() { format!("{} ", icon) } else { String::new() }
+    }
+}
+
+const DEFAULT_TOP_N: usize = 20;
+const DEFAULT_REPETITION_THRESHOLD: usize = 5;
+
+/// `--ngram-size` as an inclusive range: "3" or "2-5".
+fn parse_ngram_sizes(value: &str) -> Result<(usize, usize), String> {
+    let parse = |n: &str| n.trim().parse::<usize>().map_err(|_| format!("expected N or MIN-MAX, got {:?}", value));
+    let (min, max) = match value.split_once('-') {
+        Some((min, max)) => (parse(min)?, parse(max)?),
+        None => (parse(value)?, parse(value)?),
+    };
+    if min == 0 {
+        return Err("n-grams have at least 1 word".to_string());
+    }
+    if min > max {
+        return Err(format!("range {} is empty", value));
+    }
+    Ok((min, max))
+}
+
+/// Function words that dominate any word count, after punctuation is stripped (so "dont"
+/// as well as "don't", which survives with --unicode-normalize). "again" is left out on
+/// purpose: "let me try again" is what a loop looks like.
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but",
+    "by", "can", "could", "did", "didnt", "didn't", "do", "does", "doesnt", "doesn't", "doing",
+    "dont", "don't", "down", "during", "each", "few", "for", "from", "further", "had", "has",
+    "have", "having", "he", "her", "here", "hers", "herself", "him", "himself", "his", "how",
+    "i", "if", "im", "i'm", "in", "into", "is", "isnt", "isn't", "it", "its", "it's", "itself",
+    "just", "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on",
+    "once", "only", "or", "other", "our", "ours", "ourselves", "out", "over", "own", "same",
+    "she", "should", "so", "some", "such", "than", "that", "thats", "that's", "the", "their",
+    "theirs", "them", "themselves", "then", "there", "these", "they", "this", "those",
+    "through", "to", "too", "under", "until", "up", "very", "was", "we", "were", "what",
+    "when", "where", "which", "while", "who", "whom", "why", "will", "with", "would", "you",
+    "your", "yours", "yourself", "yourselves",
+];
+
+/// The words --word-analysis and the word-loop check count: not a stopword (compared
+/// without case) and at least --min-word-length characters long.
+struct WordFilter {
+    stopwords: HashSet<String>,
+    min_length: usize,
+}
+
+impl WordFilter {
+    fn new(args: &Args) -> Result<Self> {
+        let stopwords = if args.no_stopwords {
+            HashSet::new()
+        } else if matches!(args.stopwords.as_str(), "en" | "english") {
+            ENGLISH_STOPWORDS.iter().map(|word| word.to_string()).collect()
+        } else {
+            let list = fs::read_to_string(&args.stopwords)
+                .map_err(|e| anyhow::anyhow!("--stopwords {}: {} (give en or a file)", args.stopwords, e))?;
+            list.lines()
+                .map(str::trim)
+                .filter(|word| !word.is_empty() && !word.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect()
+        };
+        Ok(WordFilter { stopwords, min_length: args.min_word_length })
+    }
+    
+    fn keeps(&self, word: &str) -> bool {
+        word.chars().count() >= self.min_length && !self.stopwords.contains(&word.to_lowercase())
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum FuzzyMetric {
+    /// Shared words over all words of the two lines
+    Jaccard,
+    /// One minus the character edit distance over the longer line's length
+    Levenshtein,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ThresholdMode {
+    /// Flag items that pass both --repetition-threshold and --threshold-ratio
+    All,
+    /// Flag items that pass either
+    Any,
+}
+
+/// What --threshold-ratio takes a fraction of.
+#[derive(Clone, Copy, Debug)]
+enum ItemKind {
+    Lines,
+    Words,
+    Phrases,
+}
+
+impl ItemKind {
+    /// A repeated line stands out less than a repeated word, and any one phrase is rarer still.
+    fn default_ratio(self) -> f64 {
+        match self {
+            ItemKind::Lines => 0.1,
+            ItemKind::Words => 0.05,
+            ItemKind::Phrases => 0.02,
+        }
+    }
+    
+    fn name(self) -> &'static str {
+        match self {
+            ItemKind::Lines => "lines",
+            ItemKind::Words => "words",
+            ItemKind::Phrases => "phrases",
+        }
+    }
+}
+
+/// Count at which an item right at --threshold-ratio is graded, so twice the ratio grades
+/// like 10 repeats and four times like 20 (see `Severity::from_count`).
+const RATIO_SEVERITY_COUNT: f64 = 5.0;
+
+/// The repetition thresholds flagging items of one kind, out of `total` counted.
+#[derive(Clone, Copy, Debug)]
+struct Threshold {
+    count: Option<usize>,
+    ratio: Option<f64>,
+    total: usize,
+    mode: ThresholdMode,
+    kind: ItemKind,
+}
+
+impl Threshold {
+    /// Items seen once are never flagged, however short the input.
+    fn flags(&self, count: usize) -> bool {
+        self.combine(
+            self.count.map(|threshold| count >= threshold),
+            self.ratio.map(|ratio| count > 1 && count as f64 > ratio * self.total as f64),
+            |a, b| a && b,
+            |a, b| a || b,
+        ).unwrap_or(false)
+    }
+    
+    /// The fraction of all items of this kind that `count` makes up.
+    fn share(&self, count: usize) -> f64 {
+        if self.total > 0 { count as f64 / self.total as f64 } else { 0.0 }
+    }
+    
+    /// Severity by count, and by how far past the ratio `count` goes, so the same count
+    /// grades lower in a longer input. With both thresholds, the mode picks the lower or
+    /// higher grade.
+    fn severity(&self, count: usize) -> Severity {
+        self.combine(
+            self.count.map(|_| Severity::from_count(count)),
+            self.ratio.map(|ratio| Severity::from_count((self.share(count) / ratio * RATIO_SEVERITY_COUNT) as usize)),
+            Severity::min,
+            Severity::max,
+        ).unwrap_or(Severity::Low)
+    }
+    
+    fn combine<T>(&self, by_count: Option<T>, by_ratio: Option<T>, all: fn(T, T) -> T, any: fn(T, T) -> T) -> Option<T> {
+        match (by_count, by_ratio) {
+            (Some(a), Some(b)) => Some(match self.mode {
+                ThresholdMode::All => all(a, b),
+                ThresholdMode::Any => any(a, b),
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+    
+    /// The thresholds in words: "appearing ≥5 times and making up over 5.0% of words".
+    fn describe(&self) -> String {
+        let parts: Vec<String> = [
+            self.count.map(|count| format!("appearing ≥{} times", count)),
+            self.ratio.map(|ratio| format!("making up over {:.1}% of {}", ratio * 100.0, self.kind.name())),
+        ].into_iter().flatten().collect();
+        parts.join(match self.mode {
+            ThresholdMode::All => " and ",
+            ThresholdMode::Any => " or ",
+        })
+    }
+    
+    /// How often a flagged item occurs, with its share once a ratio is in play.
+    fn tally(&self, count: usize) -> String {
+        match self.ratio {
+            Some(_) => format!("{}x, {:.1}%", count, self.share(count) * 100.0),
+            None => format!("{}x", count),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -107,12 +510,69 @@ struct RepetitionStats {
     loop_indicators: Vec<LoopIndicator>,
 }
 
+/// How far the input deflates, overall and over its last TAIL_FRACTION. Text that repeats
+/// itself compresses well even when no line or word repeats exactly ("Step 14: check value
+/// 14 again"), and a tail that compresses far better than the rest is degenerating.
+#[derive(Debug, Clone)]
+struct CompressionStats {
+    original_bytes: usize,
+    compressed_bytes: usize,
+    tail_bytes: usize,
+    tail_compressed_bytes: usize,
+}
+
+impl CompressionStats {
+    fn new(input: &str) -> Result<Self> {
+        let mut tail_start = input.len() - (input.len() as f64 * TAIL_FRACTION) as usize;
+        while !input.is_char_boundary(tail_start) {
+            tail_start += 1;
+        }
+        let tail = &input[tail_start..];
+        Ok(CompressionStats {
+            original_bytes: input.len(),
+            compressed_bytes: deflated_size(input.as_bytes())?,
+            tail_bytes: tail.len(),
+            tail_compressed_bytes: deflated_size(tail.as_bytes())?,
+        })
+    }
+    
+    /// Compressed over original size; 1 for empty input, which has nothing to repeat.
+    fn ratio(&self) -> f64 {
+        compression_ratio(self.compressed_bytes, self.original_bytes)
+    }
+    
+    fn tail_ratio(&self) -> f64 {
+        compression_ratio(self.tail_compressed_bytes, self.tail_bytes)
+    }
+}
+
+/// The end of the input whose compression ratio --stats reports on its own.
+const TAIL_FRACTION: f64 = 0.2;
+
+fn compression_ratio(compressed: usize, original: usize) -> f64 {
+    if original == 0 { 1.0 } else { compressed as f64 / original as f64 }
+}
+
+fn deflated_size(bytes: &[u8]) -> Result<usize> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?.len())
+}
+
 #[derive(Debug, Clone)]
 struct LoopIndicator {
     pattern: String,
     count: usize,
     pattern_type: LoopType,
     severity: Severity,
+    /// 1-based line numbers (exact/pattern loops) or token offsets (word/phrase loops),
+    /// up to --max-positions of them
+    positions: Vec<usize>,
+    /// 1-based lines of the first and last occurrences
+    first_line: usize,
+    last_line: usize,
+    /// Lines (LineRun) or words (PhraseRun) from one repetition of a run to the next
+    period: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -121,248 +581,1836 @@ enum LoopType {
     PhraseLoop,
     WordLoop,
     PatternLoop,
+    /// A line, or a block of up to MAX_LINE_PERIOD lines, repeated back-to-back
+    LineRun,
+    /// A word or phrase of up to MAX_PHRASE_PERIOD words repeated back-to-back
+    PhraseRun,
+    /// At least --min-repeat-len characters repeated back-to-back, even inside a word
+    CharLoop,
 }
 
-#[derive(Debug, Clone)]
-enum Severity {
-    Low,
-    Medium,
-    High,
-    Critical,
-}
-
-impl Severity {
-    fn to_emoji(&self) -> &'static str {
+impl LoopType {
+    const ALL: [LoopType; 7] = [
+        LoopType::ExactRepeat, LoopType::PhraseLoop, LoopType::WordLoop, LoopType::PatternLoop,
+        LoopType::LineRun, LoopType::PhraseRun, LoopType::CharLoop,
+    ];
+    
+    /// How many repeats flag a loop of this type; single words repeat naturally, so they
+    /// need twice --repetition-threshold.
+    fn threshold(&self, args: &Args) -> usize {
         match self {
-            Severity::Low => "ℹ️",
-            Severity::Medium => "⚠️", 
-            Severity::High => "🚨",
-            Severity::Critical => "💀",
+            LoopType::WordLoop => args.repetition_threshold() * 2,
+            LoopType::ExactRepeat | LoopType::PhraseLoop | LoopType::PatternLoop
+                | LoopType::LineRun | LoopType::PhraseRun | LoopType::CharLoop => args.repetition_threshold(),
         }
     }
     
-    fn threshold(&self) -> usize {
+    fn position_unit(&self) -> &'static str {
         match self {
-            Severity::Low => 3,
-            Severity::Medium => 5,
-            Severity::High => 10,
-            Severity::Critical => 20,
+            LoopType::ExactRepeat | LoopType::PatternLoop | LoopType::LineRun | LoopType::PhraseRun => "line",
+            LoopType::PhraseLoop | LoopType::WordLoop => "token",
+            LoopType::CharLoop => "char",
+        }
+    }
+}
+
+/// Longest block of lines --detect-loops looks for repeating back-to-back.
+const MAX_LINE_PERIOD: usize = 8;
+
+/// Longest phrase, in words, --detect-loops looks for repeating back-to-back.
+const MAX_PHRASE_PERIOD: usize = 16;
+
+/// A maximal stretch of a sequence that repeats itself every `period` items.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    start: usize,
+    /// Items covered, at least two periods
+    len: usize,
+    period: usize,
+}
+
+impl Run {
+    fn repetitions(&self) -> usize {
+        self.len / self.period
+    }
+    
+    fn end(&self) -> usize {
+        self.start + self.len
+    }
+}
+
+/// How many distinct words or phrases --approximate tracks without --max-tracked.
+const APPROXIMATE_TRACKED: usize = 100_000;
+
+/// Where one occurrence of an item starts.
+#[derive(Debug, Clone, Copy)]
+struct Occurrence {
+    /// 1-based input line
+    line: usize,
+    /// 1-based index in the counted sequence of lines, words or phrases; a phrase's index is
+    /// the word offset of its first word
+    offset: usize,
+}
+
+/// Every occurrence of an item that compares equal, kept as its first form.
+struct Tally {
+    item: String,
+    count: usize,
+    first: Occurrence,
+    last: Occurrence,
+    /// The first --max-positions occurrences
+    positions: Vec<Occurrence>,
+    /// Distinct input files it occurs in, and the last of them
+    files: usize,
+    last_file: Option<usize>,
+}
+
+/// Streaming counts of distinct items, keyed by their normalized form. Past `max_tracked`
+/// distinct items the least frequent are evicted, and the counts become approximate.
+struct Tallies {
+    counts: HashMap<String, Tally>,
+    max_tracked: Option<usize>,
+    max_positions: usize,
+    evicted: bool,
+    /// First line of each input file, to tell which file an occurrence is in
+    file_starts: Vec<usize>,
+}
+
+impl Tallies {
+    fn new(max_tracked: Option<usize>, max_positions: usize) -> Self {
+        Tallies { counts: HashMap::new(), max_tracked, max_positions, evicted: false, file_starts: Vec::new() }
+    }
+    
+    /// Count the files each item occurs in, given the first line of each (see `file_starts`);
+    /// occurrences must then be added in input order.
+    fn across_files(mut self, file_starts: &[usize]) -> Self {
+        self.file_starts = file_starts.to_vec();
+        self
+    }
+    
+    fn add(&mut self, key: String, item: &str, occurrence: Occurrence) {
+        let tally = self.counts.entry(key)
+            .or_insert_with(|| Tally {
+                item: item.to_string(),
+                count: 0,
+                first: occurrence,
+                last: occurrence,
+                positions: Vec::new(),
+                files: 0,
+                last_file: None,
+            });
+        tally.count += 1;
+        tally.last = occurrence;
+        if tally.positions.len() < self.max_positions {
+            tally.positions.push(occurrence);
+        }
+        let file = self.file_starts.partition_point(|&start| start <= occurrence.line);
+        if tally.last_file != Some(file) {
+            tally.files += 1;
+            tally.last_file = Some(file);
+        }
+        
+        if let Some(max_tracked) = self.max_tracked {
+            if self.counts.len() > max_tracked {
+                // Evict in batches so we are not re-sorting on every new item
+                evict_least_frequent(&mut self.counts, max_tracked - max_tracked / 10);
+                self.evicted = true;
+            }
+        }
+    }
+    
+    /// The counted items, with a warning on stderr if any `kind` of item was evicted.
+    fn into_items(self, kind: &str) -> Vec<CountedItem> {
+        if self.evicted {
+            eprintln!("ai-uniq: warning: more than {} distinct {}, least frequent were evicted; counts are approximate",
+                     self.max_tracked.unwrap_or_default(), kind);
         }
+        
+        self.counts.into_iter()
+            .map(|(normalized, tally)| CountedItem {
+                content: tally.item,
+                count: tally.count,
+                normalized,
+                first: tally.first,
+                last: tally.last,
+                positions: tally.positions,
+                files: tally.files,
+                variants: Vec::new(),
+            })
+            .collect()
     }
 }
 
+/// A run of consecutive lines that compare equal, kept as its first line.
+struct AdjacentRun {
+    line: String,
+    key: String,
+    count: usize,
+    /// 1-based line number of the first line in the run
+    position: usize,
+}
+
 #[derive(Debug, Clone)]
 struct CountedItem {
     content: String,
     count: usize,
     normalized: String,
+    /// The first occurrence, whose offset orders output unless a sort is asked for
+    first: Occurrence,
+    last: Occurrence,
+    /// The first --max-positions occurrences
+    positions: Vec<Occurrence>,
+    /// How many input files it occurs in
+    files: usize,
+    /// Other lines merged into this one by --fuzzy, in order of appearance
+    variants: Vec<String>,
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    
+    if args.all {
+        args.word_analysis = true;
+        args.phrase_analysis = true;
+        args.char_analysis = true;
+        args.detect_loops = true;
+        args.stats = true;
+    }
     
-    let input = if args.files.is_empty() {
-        read_stdin()?
-    } else {
-        args.files.iter()
-            .map(|f| fs::read_to_string(f))
-            .collect::<Result<Vec<_>, _>>()?
-            .join("\n")
-    };
+    let analyses = args.word_analysis || args.phrase_analysis || args.char_analysis || args.detect_loops || args.stats;
+    if (args.per_file || args.show_file_spread) && !analyses {
+        bail!("--per-file and --show-file-spread apply to --word-analysis, --phrase-analysis, --char-analysis, --detect-loops, --stats and --all");
+    }
+    
+    if args.approximate && !(args.word_analysis || args.phrase_analysis) {
+        bail!("--approximate only applies to --word-analysis and --phrase-analysis");
+    }
+    
+    if args.approximate && (args.word_analysis as usize + args.phrase_analysis as usize + args.char_analysis as usize
+        + args.detect_loops as usize + args.stats as usize) > 1
+    {
+        bail!("--approximate streams a single --word-analysis or --phrase-analysis");
+    }
+    
+    if args.docs {
+        return process_documents(&args);
+    }
+    
+    if let Some(Some(ratio)) = args.threshold_ratio {
+        if !(ratio > 0.0 && ratio <= 1.0) {
+            bail!("--threshold-ratio must be above 0 and at most 1, got {}", ratio);
+        }
+    }
     
+    if args.char_ngram_size == 0 || args.min_repeat_len == 0 {
+        bail!("--char-ngram-size and --min-repeat-len must be at least 1");
+    }
+    
+    if !analyses {
+        // Traditional uniq functionality, streamed so large inputs never sit in memory
+        return if let Some(threshold) = args.fuzzy {
+            process_fuzzy_lines(&args, threshold)
+        } else if args.counts_globally() {
+            process_lines(&args)
+        } else {
+            process_adjacent_lines(&args)
+        };
+    }
+    
+    let mut report = Report::default();
+    
+    if args.approximate {
+        if args.word_analysis {
+            report_words(&stream_word_counts(&args)?, &args, &mut report)?;
+        } else {
+            report_phrases(stream_phrase_counts(&args)?, &args, &mut report)?;
+        }
+        return report.finish(&args);
+    }
+    
+    let inputs = read_inputs(&args)?;
+    let input: String = inputs.iter().map(|(_, text)| text.as_str()).collect();
+    let tokens = Tokens::new(&input, &args, file_starts(&inputs));
+    
+    if args.per_file {
+        return report_per_file(&inputs, &tokens, &args);
+    }
+    
+    run_analyses(&tokens, &args, &mut report)?;
+    report.finish(&args)
+}
+
+/// Run every analysis asked for, always in this order, over the one tokenization.
+fn run_analyses(tokens: &Tokens, args: &Args, report: &mut Report) -> Result<()> {
     if args.word_analysis {
-        analyze_words(&input, &args)?;
-    } else if args.phrase_analysis {
-        analyze_phrases(&input, &args)?;
-    } else if args.detect_loops {
-        detect_ai_loops(&input, &args)?;
-    } else if args.stats {
-        show_statistics(&input, &args)?;
-    } else {
-        // Traditional uniq functionality
-        process_lines(&input, &args)?;
+        report_words(tokens.word_counts()?, args, report)?;
+    }
+    if args.phrase_analysis {
+        report_phrases(count_phrases(tokens.words(), &tokens.file_starts, args), args, report)?;
+    }
+    if args.char_analysis {
+        report_chars(tokens, args, report)?;
+    }
+    if args.detect_loops {
+        detect_ai_loops(tokens, args, report)?;
+    }
+    if args.stats {
+        show_statistics(tokens, args, report)?;
+    }
+    Ok(())
+}
+
+/// --per-file: the analyses of each input on its own, each under a header, then of all of
+/// them together. As JSON, the per-file documents (each naming its `file`) are listed under
+/// `files` and the combined one is the `aggregate`; NDJSON records carry their `file`, null
+/// for the aggregate.
+fn report_per_file(inputs: &[(String, String)], tokens: &Tokens, args: &Args) -> Result<()> {
+    let mut sections = Vec::new();
+    for (i, (name, text)) in inputs.iter().enumerate() {
+        if !args.json_output() {
+            if i > 0 {
+                println!();
+            }
+            println!("==> {} <==", name);
+        }
+        let mut report = Report::default();
+        run_analyses(&Tokens::new(text, args, Vec::new()), args, &mut report)?;
+        sections.push((serde_json::Value::String(name.clone()), report.into_section()));
+    }
+    
+    if !args.json_output() {
+        println!();
+        println!("==> All files <==");
+    }
+    let mut report = Report::default();
+    run_analyses(tokens, args, &mut report)?;
+    if !args.json_output() {
+        return Ok(());
+    }
+    sections.push((serde_json::Value::Null, report.into_section()));
+    
+    if args.format == "ndjson" {
+        for (file, section) in &sections {
+            for record in section.iter().flat_map(|section| &section.records) {
+                let mut record = record.clone();
+                record["file"] = file.clone();
+                println!("{}", serde_json::to_string(&record)?);
+            }
+        }
+        return Ok(());
     }
     
+    let mut documents: Vec<serde_json::Value> = sections.into_iter()
+        .map(|(file, section)| {
+            let mut document = section.map(|section| section.document).unwrap_or_default();
+            document.insert("file".to_string(), file);
+            serde_json::Value::Object(document)
+        })
+        .collect();
+    let mut aggregate = documents.pop().unwrap_or_default();
+    if let Some(aggregate) = aggregate.as_object_mut() {
+        aggregate.remove("file");
+    }
+    let output = serde_json::json!({
+        "analysis_type": "per_file",
+        "files": documents,
+        "aggregate": aggregate,
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
 
+/// The input split up for the analyses: each tokenization and count is made the first
+/// time an analysis needs it, then shared with the ones after it.
+struct Tokens<'a> {
+    input: &'a str,
+    args: &'a Args,
+    /// First line of each input file (see `file_starts`), or empty for a single input
+    file_starts: Vec<usize>,
+    lines: OnceCell<Vec<(usize, String)>>,
+    words: OnceCell<Vec<(usize, String)>>,
+    line_counts: OnceCell<Vec<CountedItem>>,
+    /// Words the stopword and length filters keep
+    word_counts: OnceCell<Vec<CountedItem>>,
+    chars: OnceCell<CharText>,
+    char_repeats: OnceCell<Vec<LoopIndicator>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(input: &'a str, args: &'a Args, file_starts: Vec<usize>) -> Self {
+        Tokens {
+            input,
+            args,
+            file_starts,
+            lines: OnceCell::new(),
+            words: OnceCell::new(),
+            line_counts: OnceCell::new(),
+            word_counts: OnceCell::new(),
+            chars: OnceCell::new(),
+            char_repeats: OnceCell::new(),
+        }
+    }
+    
+    /// Which input file `line` is in, so loops are never seen running from one into the next.
+    fn file_of(&self, line: usize) -> usize {
+        self.file_starts.partition_point(|&start| start <= line)
+    }
+    
+    fn lines(&self) -> &[(usize, String)] {
+        self.lines.get_or_init(|| numbered_lines(self.input))
+    }
+    
+    fn words(&self) -> &[(usize, String)] {
+        self.words.get_or_init(|| split_words(self.input, self.args))
+    }
+    
+    fn line_counts(&self) -> &[CountedItem] {
+        self.line_counts.get_or_init(|| count_items(self.lines(), &self.file_starts, self.args))
+    }
+    
+    fn word_counts(&self) -> Result<&[CountedItem]> {
+        if let Some(counted) = self.word_counts.get() {
+            return Ok(counted);
+        }
+        let counted = count_words(self.words(), &self.file_starts, &WordFilter::new(self.args)?, self.args);
+        Ok(self.word_counts.get_or_init(|| counted))
+    }
+    
+    fn chars(&self) -> &CharText {
+        self.chars.get_or_init(|| CharText::new(self.input))
+    }
+    
+    /// The `char_loops` of the input.
+    fn char_repeats(&self) -> &[LoopIndicator] {
+        self.char_repeats.get_or_init(|| char_loops(self.chars(), self.args))
+    }
+}
+
+/// Collects what the analyses report. Text prints as each analysis runs, a blank line
+/// apart; JSON waits for them all, so several can print as sibling keys of one document.
+#[derive(Default)]
+struct Report {
+    sections: Vec<(&'static str, JsonSection)>,
+    printed_text: bool,
+}
+
+impl Report {
+    /// Called before an analysis prints its text.
+    fn begin_text(&mut self) {
+        if self.printed_text {
+            println!();
+        }
+        self.printed_text = true;
+    }
+    
+    fn add_json(&mut self, name: &'static str, section: JsonSection) {
+        self.sections.push((name, section));
+    }
+    
+    fn finish(self, args: &Args) -> Result<()> {
+        match self.into_section() {
+            Some(section) => section.print(args),
+            None => Ok(()),
+        }
+    }
+    
+    /// The JSON sections as one: a single one as it is, several as a "combined" document
+    /// keyed by analysis, whose records name their `section`.
+    fn into_section(mut self) -> Option<JsonSection> {
+        if self.sections.len() <= 1 {
+            return self.sections.pop().map(|(_, section)| section);
+        }
+        
+        let mut output = serde_json::Map::new();
+        output.insert("analysis_type".to_string(), serde_json::Value::String("combined".to_string()));
+        output.insert("analyses".to_string(), serde_json::json!(self.sections.iter().map(|(name, _)| name).collect::<Vec<_>>()));
+        let mut records = Vec::new();
+        for (name, section) in self.sections {
+            output.insert(name.to_string(), serde_json::Value::Object(section.document));
+            records.extend(section.records.into_iter().map(|mut record| {
+                record["section"] = serde_json::Value::String(name.to_string());
+                record
+            }));
+        }
+        Some(JsonSection { document: output, records })
+    }
+}
+
 fn read_stdin() -> Result<String> {
     let mut buffer = String::new();
     io::stdin().read_to_string(&mut buffer)?;
     Ok(buffer)
 }
 
-fn process_lines(input: &str, args: &Args) -> Result<()> {
-    let lines: Vec<String> = input.lines().map(|s| s.to_string()).collect();
-    let counted_items = count_items(&lines, args);
+/// Every input as its name and text: the files in order, each ending with a newline so the
+/// last line of one never runs into the first line of the next, or stdin.
+fn read_inputs(args: &Args) -> Result<Vec<(String, String)>> {
+    if args.files.is_empty() {
+        return Ok(vec![("standard input".to_string(), read_stdin()?)]);
+    }
     
-    let filtered_items = filter_items(&counted_items, args);
-    let sorted_items = sort_items(filtered_items, args);
+    args.files.iter()
+        .map(|file| {
+            let mut text = fs::read_to_string(file).with_context(|| format!("cannot read {}", file.display()))?;
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            Ok((file.display().to_string(), text))
+        })
+        .collect()
+}
+
+/// The 1-based line each of `inputs` starts on once they are read as one.
+fn file_starts(inputs: &[(String, String)]) -> Vec<usize> {
+    let mut next = 1;
+    inputs.iter()
+        .map(|(_, text)| {
+            let start = next;
+            next += text.lines().count();
+            start
+        })
+        .collect()
+}
+
+/// Feed every input line to `f` one at a time, reading files in order (or stdin).
+fn for_each_line(args: &Args, mut f: impl FnMut(&str)) -> Result<()> {
+    let mut readers: Vec<Box<dyn BufRead>> = Vec::new();
+    if args.files.is_empty() {
+        readers.push(Box::new(io::stdin().lock()));
+    } else {
+        for file in &args.files {
+            let reader = fs::File::open(file).with_context(|| format!("cannot read {}", file.display()))?;
+            readers.push(Box::new(BufReader::new(reader)));
+        }
+    }
+    
+    let mut line = String::new();
+    for mut reader in readers {
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            f(line.trim_end_matches(['\n', '\r']));
+        }
+    }
+    
+    Ok(())
+}
+
+fn process_lines(args: &Args) -> Result<()> {
+    let mut tallies = Tallies::new(args.max_tracked, args.max_positions);
+    let mut line_number = 0;
+    
+    for_each_line(args, |line| {
+        line_number += 1;
+        tallies.add(normalize_item(line, args), line, Occurrence { line: line_number, offset: line_number });
+    })?;
+    
+    let counted_items = tallies.into_items("lines");
+    let filtered_items = filter_items(&counted_items, args);
+    let sorted_items = sort_items(filtered_items, args);
+    
+    output_items(&sorted_items, None, args)?;
+    Ok(())
+}
+
+/// Count lines globally, then merge the distinct ones that --fuzzy finds similar enough.
+fn process_fuzzy_lines(args: &Args, threshold: f64) -> Result<()> {
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        bail!("--fuzzy threshold must be above 0 and at most 1, got {}", threshold);
+    }
+    
+    let mut tallies = Tallies::new(args.max_tracked, args.max_positions);
+    let mut line_number = 0;
+    for_each_line(args, |line| {
+        line_number += 1;
+        tallies.add(normalize_item(line, args), line, Occurrence { line: line_number, offset: line_number });
+    })?;
+    
+    let mut counted_items = tallies.into_items("lines");
+    counted_items.sort_by_key(|item| item.first.offset);
+    let clustered = cluster_items(counted_items, threshold, args);
+    let filtered_items = filter_items(&clustered, args);
+    let sorted_items = sort_items(filtered_items, args);
+    
+    output_items(&sorted_items, None, args)
+}
+
+/// Group `items` (in order of first appearance) into clusters of near-duplicates, each led
+/// by its earliest line. A line joins the first cluster whose leader is at least `threshold`
+/// similar; leaders are found through a prefix filter, so lines are not compared pairwise.
+///
+/// Every feature (a word for Jaccard, a character trigram for Levenshtein) is ranked by how
+/// few lines have it. Two lines that are similar enough must share at least `overlap`
+/// features, which means they share one of their rarest `len - overlap + 1`. Only leaders
+/// holding one of those near the same place are compared, after length and shared-feature
+/// checks. Edits can break most trigrams of a long
+/// line, so a long Levenshtein leader is indexed by only its rarest FUZZY_PROBES and must keep
+/// FUZZY_KEPT of them in a line: variants within FUZZY_SURE_EDITS edits are always found, and
+/// one with more is missed if its edits break too many of them.
+fn cluster_items(items: Vec<CountedItem>, threshold: f64, args: &Args) -> Vec<CountedItem> {
+    let metric = args.fuzzy_metric;
+    let keys: Vec<Vec<char>> = items.iter().map(|item| fuzzy_key(&item.normalized).chars().collect()).collect();
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    // Each line's features as ids, sorted so shared ones can be counted by merging, and the
+    // first and last position of each with how often it occurs
+    let (features, spans): (Vec<Vec<u32>>, Vec<Vec<_>>) = keys.iter()
+        .map(|key| {
+            let mut occurrences: Vec<(u32, u32)> = fuzzy_features(key, metric).into_iter()
+                .enumerate()
+                .map(|(position, feature)| {
+                    let next = ids.len() as u32;
+                    (*ids.entry(feature).or_insert(next), position as u32)
+                })
+                .collect();
+            occurrences.sort_unstable();
+            let mut set: Vec<u32> = Vec::new();
+            let mut span: Vec<(u32, u32, u32)> = Vec::new();
+            for (feature, position) in occurrences {
+                if set.last() == Some(&feature) {
+                    let (_, last, count) = span.last_mut().unwrap();
+                    (*last, *count) = (position, *count + 1);
+                } else {
+                    set.push(feature);
+                    span.push((position, position, 1));
+                }
+            }
+            (set, span)
+        })
+        .unzip();
+    let span = |i: usize, feature: u32| spans[i][features[i].binary_search(&feature).unwrap()];
+    let mut frequency = vec![0usize; ids.len()];
+    for &feature in features.iter().flatten() {
+        frequency[feature as usize] += 1;
+    }
+    // Rarest first, ties broken by id so the order is total
+    let ranked: Vec<Vec<u32>> = features.iter()
+        .map(|set| {
+            let mut set = set.clone();
+            set.sort_by_key(|&feature| (frequency[feature as usize], feature));
+            set
+        })
+        .collect();
+    
+    let size = |i: usize| match metric {
+        FuzzyMetric::Jaccard => features[i].len(),
+        FuzzyMetric::Levenshtein => keys[i].len(),
+    };
+    let comparable_sizes = |a: usize, b: usize| {
+        let (short, long) = (size(a).min(size(b)), size(a).max(size(b)));
+        short as f64 >= threshold * long as f64 - 1e-9
+    };
+    // Most edits between a line and any line similar enough to it, which is at most
+    // len / threshold long
+    let max_edits = |i: usize| ((1.0 - threshold) / threshold * keys[i].len() as f64 + 1e-9).floor() as usize;
+    // How many of its rarest features a line is indexed by as a leader; None when a similar line
+    // could share none of them
+    let prefix_len = |i: usize| {
+        let n = ranked[i].len();
+        match metric {
+            // |A ∩ B| >= threshold * |A ∪ B| >= threshold * |A|
+            FuzzyMetric::Jaccard => {
+                let overlap = (threshold * n as f64 - 1e-9).ceil() as usize;
+                (overlap > 0).then(|| n - overlap + 1)
+            }
+            // Each edit breaks at most FUZZY_QGRAM trigrams
+            FuzzyMetric::Levenshtein => (n > FUZZY_QGRAM * max_edits(i)).then_some(FUZZY_QGRAM * max_edits(i) + 1),
+        }
+    };
+    let similar = |a: usize, b: usize| {
+        if !comparable_sizes(a, b) {
+            return false;
+        }
+        let long = size(a).max(size(b));
+        match metric {
+            FuzzyMetric::Jaccard => {
+                let (shared, _) = shared_features(&features[a], &spans[a], &features[b], &spans[b], u32::MAX);
+                let union = features[a].len() + features[b].len() - shared;
+                union == 0 || shared as f64 / union as f64 >= threshold
+            }
+            // Each edit breaks at most FUZZY_QGRAM of a line's trigrams, and only ones within
+            // max_edits places of each other can be the same one unedited
+            FuzzyMetric::Levenshtein => {
+                let max_edits = ((1.0 - threshold) * long as f64 + 1e-9).floor() as usize;
+                let (_, shared) = shared_features(&features[a], &spans[a], &features[b], &spans[b], max_edits as u32);
+                let trigrams = |i: usize| (keys[i].len() + 1).saturating_sub(FUZZY_QGRAM);
+                long == 0 || (shared + FUZZY_QGRAM * max_edits >= trigrams(a).max(trigrams(b))
+                    && bounded_levenshtein(&keys[a], &keys[b], max_edits)
+                        .is_some_and(|distance| 1.0 - distance as f64 / long as f64 >= threshold))
+            }
+        }
+    };
+    
+    let mut cluster_of: Vec<usize> = Vec::with_capacity(items.len());
+    let mut leaders: Vec<usize> = Vec::new();
+    let mut by_key: HashMap<&[char], usize> = HashMap::new();
+    // The leaders indexed by each feature and size bucket, with where it lies in them and their
+    // size
+    let bucket = |size: u32| ((size as f64 + 1.0).ln() * FUZZY_SIZE_BUCKETS) as u32;
+    let mut index: Vec<Vec<Vec<_>>> = vec![Vec::new(); ids.len()];
+    let mut unfiltered: Vec<usize> = Vec::new();
+    let mut capped: Vec<bool> = Vec::new();
+    // Probe hits per cluster for the current line, and which clusters have any
+    let mut hits: Vec<u32> = Vec::new();
+    let mut touched: Vec<usize> = Vec::new();
+    
+    for i in 0..items.len() {
+        if let Some(&cluster) = by_key.get(keys[i].as_slice()) {
+            cluster_of.push(cluster);
+            continue;
+        }
+        
+        let prefix = prefix_len(i);
+        // The nth of a leader's trigrams to survive in this line is outranked here only by the
+        // leader's earlier survivors and by trigrams the edits introduced, so the first
+        // FUZZY_KEPT survivors are among this line's rarest FUZZY_QGRAM * max_edits + FUZZY_KEPT
+        let probes = match metric {
+            FuzzyMetric::Jaccard => prefix,
+            FuzzyMetric::Levenshtein => prefix.map(|len| (len - 1 + FUZZY_KEPT).min(ranked[i].len())),
+        };
+        // An untouched trigram moves by at most the number of edits
+        let reach = match metric {
+            FuzzyMetric::Jaccard => u32::MAX,
+            FuzzyMetric::Levenshtein => max_edits(i) as u32,
+        };
+        let smallest = (threshold * size(i) as f64 - 1e-9).ceil() as u32;
+        let largest = ((size(i) as f64 + 1e-9) / threshold).floor() as u32;
+        let mut hit = |cluster: usize| {
+            if hits[cluster] == 0 {
+                touched.push(cluster);
+            }
+            hits[cluster] += 1;
+        };
+        unfiltered.iter().for_each(|&cluster| hit(cluster));
+        match probes {
+            Some(len) => {
+                for &feature in &ranked[i][..len] {
+                    let (first, last, _) = span(i, feature);
+                    let buckets = &index[feature as usize];
+                    let (low, high) = (bucket(smallest) as usize, buckets.len().min(bucket(largest) as usize + 1));
+                    for leaders in buckets.get(low..high).unwrap_or_default() {
+                        for &(cluster, leader_first, leader_last, leader_size) in leaders {
+                            if (smallest..=largest).contains(&leader_size) && spans_near((first, last), (leader_first, leader_last), reach) {
+                                hit(cluster as usize);
+                            }
+                        }
+                    }
+                }
+            }
+            None => (0..leaders.len()).for_each(hit),
+        }
+        // A line within FUZZY_SURE_EDITS edits of a capped leader keeps FUZZY_KEPT of its
+        // indexed trigrams in place, so one that keeps fewer is not worth comparing
+        let mut candidates: Vec<usize> = touched.iter().copied()
+            .filter(|&cluster| !capped[cluster] || hits[cluster] as usize >= FUZZY_KEPT)
+            .collect();
+        candidates.sort_unstable();
+        
+        let joined = candidates.into_iter()
+            .find(|&cluster| similar(i, leaders[cluster]));
+        for cluster in touched.drain(..) {
+            hits[cluster] = 0;
+        }
+        let cluster = joined.unwrap_or_else(|| {
+            let cluster = leaders.len();
+            leaders.push(i);
+            hits.push(0);
+            let is_capped = matches!(metric, FuzzyMetric::Levenshtein) && prefix.is_some_and(|len| len > FUZZY_PROBES);
+            capped.push(is_capped);
+            match prefix {
+                Some(len) => ranked[i][..len.min(FUZZY_PROBES)].iter().for_each(|&feature| {
+                    let (first, last, _) = span(i, feature);
+                    let (buckets, b) = (&mut index[feature as usize], bucket(size(i) as u32) as usize);
+                    if buckets.len() <= b {
+                        buckets.resize(b + 1, Vec::new());
+                    }
+                    buckets[b].push((cluster as u32, first, last, size(i) as u32));
+                }),
+                None => unfiltered.push(cluster),
+            }
+            cluster
+        });
+        by_key.insert(&keys[i], cluster);
+        cluster_of.push(cluster);
+    }
+    
+    let mut clusters: Vec<Option<CountedItem>> = vec![None; leaders.len()];
+    for (item, cluster) in items.into_iter().zip(cluster_of) {
+        match &mut clusters[cluster] {
+            None => clusters[cluster] = Some(item),
+            Some(merged) => {
+                merged.count += item.count;
+                if item.last.offset > merged.last.offset {
+                    merged.last = item.last;
+                }
+                merged.positions.extend(item.positions);
+                if merged.variants.len() < args.max_positions {
+                    merged.variants.push(item.content);
+                }
+            }
+        }
+    }
+    
+    clusters.into_iter().flatten()
+        .map(|mut item| {
+            item.positions.sort_by_key(|p| p.offset);
+            item.positions.truncate(args.max_positions);
+            item
+        })
+        .collect()
+}
+
+/// Length of the character n-grams --fuzzy-metric levenshtein indexes lines by.
+const FUZZY_QGRAM: usize = 3;
+
+/// Most trigrams a leader is indexed by for --fuzzy-metric levenshtein.
+const FUZZY_PROBES: usize = 14;
+
+/// Size buckets per factor of e that fuzzy leaders are indexed in, so lines are only checked
+/// against leaders of comparable size.
+const FUZZY_SIZE_BUCKETS: f64 = 8.0;
+
+/// Edits to a long line that --fuzzy-metric levenshtein is sure to see through: each breaks
+/// at most FUZZY_QGRAM of the leader's indexed trigrams.
+const FUZZY_SURE_EDITS: usize = 2;
+
+/// Indexed trigrams a line must keep to be compared with a long leader.
+const FUZZY_KEPT: usize = FUZZY_PROBES - FUZZY_QGRAM * FUZZY_SURE_EDITS;
+
+/// What --fuzzy compares: lowercase words with punctuation dropped, single-spaced.
+fn fuzzy_key(line: &str) -> String {
+    line.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The words (Jaccard) or character trigrams (Levenshtein) of a fuzzy key, repeats included.
+fn fuzzy_features(key: &[char], metric: FuzzyMetric) -> Vec<String> {
+    match metric {
+        FuzzyMetric::Jaccard => key.split(|c| *c == ' ')
+            .filter(|word| !word.is_empty())
+            .map(|word| word.iter().collect())
+            .collect(),
+        FuzzyMetric::Levenshtein => key.windows(FUZZY_QGRAM).map(|gram| gram.iter().collect()).collect(),
+    }
+}
+
+/// How many ids two sorted, deduplicated feature lists have in common, and how many
+/// occurrences of them the two lines share, counting only those whose first-to-last positions
+/// come within `reach` of each other.
+fn shared_features(a: &[u32], a_spans: &[(u32, u32, u32)], b: &[u32], b_spans: &[(u32, u32, u32)], reach: u32) -> (usize, usize) {
+    let (mut i, mut j, mut shared, mut occurrences) = (0, 0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                let ((first_a, last_a, count_a), (first_b, last_b, count_b)) = (a_spans[i], b_spans[j]);
+                if spans_near((first_a, last_a), (first_b, last_b), reach) {
+                    shared += 1;
+                    occurrences += count_a.min(count_b) as usize;
+                }
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    (shared, occurrences)
+}
+
+/// Whether two first-to-last position ranges come within `reach` of each other.
+fn spans_near((first_a, last_a): (u32, u32), (first_b, last_b): (u32, u32), reach: u32) -> bool {
+    first_a <= last_b.saturating_add(reach) && first_b <= last_a.saturating_add(reach)
+}
+
+/// The edit distance between `a` and `b`, or None once it is sure to exceed `limit`.
+fn bounded_levenshtein(a: &[char], b: &[char], limit: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    // Only cells within `limit` of the diagonal can be on a path within the limit, so the
+    // rest of each row is left at `over`
+    let over = limit + 1;
+    let mut previous: Vec<usize> = (0..=b.len()).map(|j| j.min(over)).collect();
+    let mut current = vec![over; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        let row = i + 1;
+        let (first, last) = (row.saturating_sub(limit), (row + limit).min(b.len()));
+        let mut best = over;
+        if first == 0 {
+            current[0] = row;
+            best = row;
+        } else {
+            current[first - 1] = over;
+        }
+        for j in first.max(1)..=last {
+            let substitution = previous[j - 1] + usize::from(*ca != b[j - 1]);
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1).min(over);
+            best = best.min(current[j]);
+        }
+        if last < b.len() {
+            current[last + 1] = over;
+        }
+        // Distances never shrink down the table, so a row above the limit ends the search
+        if best > limit {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= limit)
+}
+
+/// A --docs document: a whole input, or one record of it.
+struct Document {
+    /// The input's path ("-" for stdin), with `#N` for its Nth record under --doc-separator
+    name: String,
+    /// Where --keep-first writes the document, relative to its directory
+    file_name: String,
+    text: String,
+}
+
+/// A --docs cluster: its first document and the later ones estimated similar to it.
+struct DocumentCluster {
+    /// Indexes into the documents, the first one leading
+    members: Vec<usize>,
+    /// Each member's estimated similarity to the first (1.0 for the first itself)
+    similarities: Vec<f64>,
+}
+
+/// Cluster near-duplicate documents and report every cluster with more than one member.
+fn process_documents(args: &Args) -> Result<()> {
+    if !(args.doc_threshold > 0.0 && args.doc_threshold <= 1.0) {
+        bail!("--doc-threshold must be above 0 and at most 1, got {}", args.doc_threshold);
+    }
+    if args.shingle_size == 0 || args.num_hashes == 0 {
+        bail!("--shingle-size and --num-hashes must be at least 1");
+    }
+    
+    let documents = read_documents(args)?;
+    let signatures: Vec<Vec<u64>> = documents.iter()
+        .map(|document| minhash(&shingles(&document.text, args.shingle_size), args.num_hashes))
+        .collect();
+    let clusters = cluster_documents(&signatures, args.doc_threshold);
+    
+    if let Some(dir) = &args.keep_first {
+        fs::create_dir_all(dir)?;
+        let mut written = HashSet::new();
+        for cluster in &clusters {
+            let document = &documents[cluster.members[0]];
+            if !written.insert(&document.file_name) {
+                bail!("--keep-first: more than one document would be written to {}", dir.join(&document.file_name).display());
+            }
+            fs::write(dir.join(&document.file_name), &document.text)?;
+        }
+    }
+    
+    let duplicated: Vec<&DocumentCluster> = clusters.iter().filter(|cluster| cluster.members.len() > 1).collect();
+    if args.json_output() {
+        return output_json_documents(&documents, &duplicated, clusters.len(), args);
+    }
+    
+    if duplicated.is_empty() {
+        println!("{}No near-duplicate documents among {} (threshold: {})", args.icon("✅"), documents.len(), args.doc_threshold);
+    } else {
+        println!("=== Near-Duplicate Documents ===");
+        for (number, cluster) in duplicated.iter().enumerate() {
+            println!("Cluster {} ({} documents):", number + 1, cluster.members.len());
+            for (&member, similarity) in cluster.members.iter().zip(&cluster.similarities) {
+                println!("  {:.2}  {}", similarity, documents[member].name);
+            }
+        }
+        println!();
+        println!("{} documents, {} after removing {} near-duplicates (threshold: {})",
+                documents.len(), clusters.len(), documents.len() - clusters.len(), args.doc_threshold);
+    }
+    if let Some(dir) = &args.keep_first {
+        println!("Kept {} documents in {}", clusters.len(), dir.display());
+    }
+    
+    Ok(())
+}
+
+/// Read every input as one document, or as records split at --doc-separator lines; records
+/// holding nothing but whitespace are skipped.
+fn read_documents(args: &Args) -> Result<Vec<Document>> {
+    let inputs = if args.files.is_empty() {
+        vec![("-".to_string(), "stdin.txt".to_string(), read_stdin()?)]
+    } else {
+        args.files.iter()
+            .map(|file| {
+                let file_name = file.file_name().map_or_else(|| "document.txt".to_string(), |name| name.to_string_lossy().into_owned());
+                let text = fs::read_to_string(file).with_context(|| format!("cannot read {}", file.display()))?;
+                Ok((file.display().to_string(), file_name, text))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    
+    let mut documents = Vec::new();
+    for (name, file_name, text) in inputs {
+        let Some(separator) = &args.doc_separator else {
+            documents.push(Document { name, file_name, text });
+            continue;
+        };
+        
+        let (stem, extension) = match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem.to_string(), extension.to_string()),
+            _ => (file_name.clone(), "txt".to_string()),
+        };
+        let mut record = String::new();
+        let mut number = 0;
+        for line in text.lines().chain(std::iter::once(separator.as_str())) {
+            if line != separator {
+                record.push_str(line);
+                record.push('\n');
+                continue;
+            }
+            if !record.trim().is_empty() {
+                number += 1;
+                documents.push(Document {
+                    name: format!("{}#{}", name, number),
+                    file_name: format!("{}-{}.{}", stem, number, extension),
+                    text: std::mem::take(&mut record),
+                });
+            }
+            record.clear();
+        }
+    }
+    
+    Ok(documents)
+}
+
+/// Hashes of every run of `size` consecutive words, compared the way --fuzzy compares lines;
+/// a document shorter than that is one shingle.
+fn shingles(text: &str, size: usize) -> Vec<u64> {
+    let key = fuzzy_key(text);
+    let words: Vec<&str> = key.split(' ').filter(|word| !word.is_empty()).collect();
+    let hash = |shingle: &[&str]| {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        hasher.finish()
+    };
+    if words.len() <= size {
+        return if words.is_empty() { Vec::new() } else { vec![hash(&words)] };
+    }
+    words.windows(size).map(hash).collect()
+}
+
+/// The MinHash signature of a set of shingle hashes: for each of `num_hashes` seeded hash
+/// functions, the smallest value any shingle takes. The share of positions two signatures
+/// agree on estimates the Jaccard similarity of their shingle sets.
+fn minhash(shingles: &[u64], num_hashes: usize) -> Vec<u64> {
+    (0..num_hashes as u64)
+        .map(|seed| {
+            let seed = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            shingles.iter().map(|&shingle| mix(shingle ^ seed)).min().unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// The splitmix64 finalizer, a cheap bijective scrambling of 64 bits.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn estimated_similarity(a: &[u64], b: &[u64]) -> f64 {
+    a.iter().zip(b).filter(|(x, y)| x == y).count() as f64 / a.len() as f64
+}
+
+/// Group documents, in input order, into clusters led by their first document: each joins
+/// the first cluster whose leader it is estimated at least `threshold` similar to.
+///
+/// Leaders are found by locality-sensitive hashing rather than comparing every pair. The
+/// signature is cut into bands of `rows` hashes, and only leaders agreeing with a document on
+/// a whole band are compared. Rows are as many as still make a pair at `threshold` share a
+/// band with 99% probability, so few truly similar documents are missed.
+fn cluster_documents(signatures: &[Vec<u64>], threshold: f64) -> Vec<DocumentCluster> {
+    let num_hashes = signatures.first().map_or(1, Vec::len);
+    let rows = (1..=num_hashes).rev()
+        .find(|&rows| 1.0 - (1.0 - threshold.powi(rows as i32)).powi((num_hashes / rows) as i32) >= 0.99)
+        .unwrap_or(1);
+    let bands = num_hashes / rows;
+    
+    let mut clusters: Vec<DocumentCluster> = Vec::new();
+    let mut buckets: Vec<HashMap<&[u64], Vec<usize>>> = vec![HashMap::new(); bands];
+    for (i, signature) in signatures.iter().enumerate() {
+        let mut candidates: Vec<usize> = (0..bands)
+            .filter_map(|band| buckets[band].get(&signature[band * rows..(band + 1) * rows]))
+            .flatten()
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        
+        let joined = candidates.into_iter()
+            .map(|cluster| (cluster, estimated_similarity(signature, &signatures[clusters[cluster].members[0]])))
+            .find(|&(_, similarity)| similarity >= threshold - 1e-9);
+        match joined {
+            Some((cluster, similarity)) => {
+                clusters[cluster].members.push(i);
+                clusters[cluster].similarities.push(similarity);
+            }
+            None => {
+                for (band, bucket) in buckets.iter_mut().enumerate() {
+                    bucket.entry(&signature[band * rows..(band + 1) * rows]).or_default().push(clusters.len());
+                }
+                clusters.push(DocumentCluster { members: vec![i], similarities: vec![1.0] });
+            }
+        }
+    }
+    clusters
+}
+
+/// Collapse consecutive lines that compare equal the way coreutils uniq does, printing the
+/// first line of each run as soon as the run ends.
+fn process_adjacent_lines(args: &Args) -> Result<()> {
+    let mut runs: Vec<AdjacentRun> = Vec::new();
+    let mut current: Option<AdjacentRun> = None;
+    let mut line_number = 0;
+    
+    let mut finish = |run: AdjacentRun| {
+        if !shows_run(run.count, args) {
+            return;
+        }
+        if args.format == "ndjson" {
+            // Streamed like the text output, so huge logs never pile up in memory
+            println!("{}", line_record(&run.line, run.count, run.position, run.position + run.count - 1));
+        } else if args.json_output() {
+            runs.push(run);
+        } else if args.count {
+            println!("{:7} {}", run.count, run.line);
+        } else {
+            println!("{}", run.line);
+        }
+    };
+    
+    for_each_line(args, |line| {
+        line_number += 1;
+        let key = normalize_item(line, args);
+        match &mut current {
+            Some(run) if run.key == key => run.count += 1,
+            _ => {
+                let next = AdjacentRun { line: line.to_string(), key, count: 1, position: line_number };
+                if let Some(run) = current.replace(next) {
+                    finish(run);
+                }
+            }
+        }
+    })?;
+    
+    if let Some(run) = current {
+        finish(run);
+    }
+    
+    if args.format == "json" {
+        output_json_runs(&runs)?;
+    }
+    Ok(())
+}
+
+/// Which runs -d, -u and --min-count let through; -d with -u prints nothing, as in uniq.
+fn shows_run(count: usize, args: &Args) -> bool {
+    (!args.duplicates || count > 1) && (!args.unique || count == 1) && count >= args.min_count
+}
+
+fn evict_least_frequent(counts: &mut HashMap<String, Tally>, keep: usize) {
+    let mut entries: Vec<(String, Tally)> = counts.drain().collect();
+    // Among equally frequent lines the ones seen first are kept, so eviction is deterministic
+    entries.sort_unstable_by(|a, b| b.1.count.cmp(&a.1.count).then(a.1.first.offset.cmp(&b.1.first.offset)));
+    entries.truncate(keep.max(1));
+    counts.extend(entries);
+}
+
+/// Split into words, similar to: tr ' ' '\n', each with its 1-based line number.
+fn split_words(input: &str, args: &Args) -> Vec<(usize, String)> {
+    input
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| line.split_whitespace().map(move |w| (i + 1, normalize_word(w, args))))
+        .collect()
+}
+
+/// Count the n-grams of every --ngram-size in one pass over `words`, each size on its own,
+/// smallest first. A phrase's offset is the word offset of its first word.
+fn count_phrases(words: &[(usize, String)], file_starts: &[usize], args: &Args) -> Vec<(usize, Vec<CountedItem>)> {
+    let sizes = args.ngram_sizes();
+    let mut tallies: Vec<Tallies> = sizes.iter().map(|_| Tallies::new(None, args.max_positions).across_files(file_starts)).collect();
+    let longest = sizes.last().copied().unwrap_or_default();
+    
+    for (i, &(line, _)) in words.iter().enumerate() {
+        // Grow the phrase starting here a word at a time, counting it at each requested size
+        let mut phrase = String::new();
+        for (n, (_, word)) in words[i..].iter().take(longest).enumerate() {
+            if n > 0 {
+                phrase.push(' ');
+            }
+            phrase.push_str(word);
+            if let Ok(index) = sizes.binary_search(&(n + 1)) {
+                let normalized = normalize_item(&phrase, args);
+                tallies[index].add(normalized.clone(), &normalized, Occurrence { line, offset: i + 1 });
+            }
+        }
+    }
+    
+    sizes.into_iter()
+        .zip(tallies)
+        .map(|(n, tallies)| {
+            let mut counted = tallies.into_items("phrases");
+            counted.sort_by_key(|item| item.first.offset);
+            (n, counted)
+        })
+        .collect()
+}
+
+/// Every run of `n` consecutive words, on the line of its first word.
+fn ngrams(words: &[(usize, String)], n: usize) -> Vec<(usize, String)> {
+    words.windows(n)
+        .map(|window| {
+            let phrase: Vec<&str> = window.iter().map(|(_, word)| word.as_str()).collect();
+            (window[0].0, phrase.join(" "))
+        })
+        .collect()
+}
+
+/// The input's lines, each with its 1-based line number.
+fn numbered_lines(input: &str) -> Vec<(usize, String)> {
+    input.lines().enumerate().map(|(i, line)| (i + 1, line.to_string())).collect()
+}
+
+/// Count words as the input streams in, so memory stays bounded by --max-tracked however
+/// long it is.
+fn stream_word_counts(args: &Args) -> Result<Vec<CountedItem>> {
+    let filter = WordFilter::new(args)?;
+    let mut tallies = Tallies::new(Some(args.max_tracked.unwrap_or(APPROXIMATE_TRACKED)), args.max_positions);
+    let mut line_number = 0;
+    let mut position = 0;
+    
+    for_each_line(args, |line| {
+        line_number += 1;
+        for word in line.split_whitespace() {
+            position += 1;
+            let word = normalize_word(word, args);
+            if !filter.keeps(&word) {
+                continue;
+            }
+            let key = normalize_item(&word, args);
+            tallies.add(key.clone(), &key, Occurrence { line: line_number, offset: position });
+        }
+    })?;
+    
+    Ok(tallies.into_items("words"))
+}
+
+/// Count the n-grams of every --ngram-size (which run across line breaks) as the input
+/// streams in, keeping at most --max-tracked phrases of each size.
+fn stream_phrase_counts(args: &Args) -> Result<Vec<(usize, Vec<CountedItem>)>> {
+    let sizes = args.ngram_sizes();
+    let max_tracked = Some(args.max_tracked.unwrap_or(APPROXIMATE_TRACKED));
+    let mut tallies: Vec<Tallies> = sizes.iter().map(|_| Tallies::new(max_tracked, args.max_positions)).collect();
+    let longest = sizes.last().copied().unwrap_or_default();
+    let mut window: VecDeque<(usize, String)> = VecDeque::with_capacity(longest);
+    let mut line_number = 0;
+    let mut position = 0;
+    
+    for_each_line(args, |line| {
+        line_number += 1;
+        for word in line.split_whitespace() {
+            position += 1;
+            window.push_back((line_number, normalize_word(word, args)));
+            if window.len() > longest {
+                window.pop_front();
+            }
+            // Each size's phrase ending at this word
+            for (&n, tallies) in sizes.iter().zip(&mut tallies) {
+                if window.len() >= n {
+                    let start = window.len() - n;
+                    let phrase = window.range(start..).map(|(_, word)| word.as_str()).collect::<Vec<_>>().join(" ");
+                    let key = normalize_item(&phrase, args);
+                    tallies.add(key.clone(), &key, Occurrence { line: window[start].0, offset: position + 1 - n });
+                }
+            }
+        }
+    })?;
+    
+    Ok(sizes.into_iter().zip(tallies).map(|(n, tallies)| (n, tallies.into_items("phrases"))).collect())
+}
+
+fn report_words(counted_words: &[CountedItem], args: &Args, report: &mut Report) -> Result<()> {
+    let filtered_words = filter_items(counted_words, args);
+    let sorted_words = sort_items(filtered_words, args);
+    
+    if args.json_output() {
+        report.add_json("words", json_analysis(&sorted_words, "words", args));
+    } else {
+        report.begin_text();
+        println!("=== Word Frequency Analysis ===");
+        output_items(&sorted_words, None, args)?;
+        
+        if args.flags_items() {
+            let threshold = args.threshold(ItemKind::Words, counted_words.iter().map(|item| item.count).sum());
+            let flagged: Vec<_> = sorted_words.iter()
+                .filter(|item| threshold.flags(item.count))
+                .collect();
+            
+            if !flagged.is_empty() {
+                println!("\n{}FLAGGED: Words {}:", args.icon("🚨"), threshold.describe());
+                for item in flagged {
+                    println!("  {} {} ({})", 
+                            threshold.severity(item.count).marker(args.emoji()),
+                            item.content, 
+                            threshold.tally(item.count));
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Report the counted phrases of each n-gram size. With several sizes, each gets its own
+/// list, and a combined list ranks the repeated phrases of every size by how many words
+/// their repetition wastes, so a longer phrase outranks a shorter one seen as often.
+fn report_phrases(mut counted_phrases: Vec<(usize, Vec<CountedItem>)>, args: &Args, report: &mut Report) -> Result<()> {
+    // Taken before collapsing, which drops phrases but not the text they were counted in
+    let thresholds: Vec<Threshold> = counted_phrases.iter()
+        .map(|(_, items)| args.threshold(ItemKind::Phrases, items.iter().map(|item| item.count).sum()))
+        .collect();
+    if counted_phrases.len() > 1 && !args.no_collapse {
+        collapse_subsumed(&mut counted_phrases);
+    }
+    let suspicious = most_suspicious(&counted_phrases, args);
+    let sorted_phrases: Vec<(usize, Vec<CountedItem>)> = counted_phrases.iter()
+        .map(|(n, items)| (*n, sort_items(filter_items(items, args), args)))
+        .collect();
+    
+    if args.json_output() {
+        report.add_json("phrases", match sorted_phrases.as_slice() {
+            [(_, items)] => json_analysis(items, "phrases", args),
+            _ => json_phrase_sizes(&sorted_phrases, &suspicious, args),
+        });
+        return Ok(());
+    }
+    
+    report.begin_text();
+    for ((n, sorted_phrases), threshold) in sorted_phrases.iter().zip(&thresholds) {
+        println!("=== {}-gram Phrase Analysis ===", n);
+        output_items(sorted_phrases, Some("word"), args)?;
+        
+        if args.flags_items() {
+            let flagged: Vec<_> = sorted_phrases.iter()
+                .filter(|item| threshold.flags(item.count))
+                .collect();
+            
+            if !flagged.is_empty() {
+                println!("\n{}FLAGGED: Phrases {}:", args.icon("🚨"), threshold.describe());
+                for item in flagged {
+                    println!("  {} \"{}\" ({})", 
+                            threshold.severity(item.count).marker(args.emoji()),
+                            item.content, 
+                            threshold.tally(item.count));
+                }
+            }
+        }
+    }
+    
+    if sorted_phrases.len() > 1 && !suspicious.is_empty() {
+        println!("\n{}Most suspicious phrases:", args.icon("🔍"));
+        for (n, item) in &suspicious {
+            println!("  {} \"{}\" ({}-gram, {}x)",
+                     Severity::from_count(item.count).marker(args.emoji()),
+                     item.content,
+                     n,
+                     item.count);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Drop each phrase that only ever occurs inside a longer repeated phrase of another
+/// requested size: one contained in it with the same count adds nothing. `counted_phrases`
+/// is ordered by size, smallest first.
+fn collapse_subsumed(counted_phrases: &mut [(usize, Vec<CountedItem>)]) {
+    let sizes: Vec<usize> = counted_phrases.iter().map(|(n, _)| *n).collect();
+    // Phrases inside a longer repeated phrase, with that phrase's count
+    let mut contained: HashSet<(String, usize)> = HashSet::new();
+    
+    for (n, items) in counted_phrases.iter_mut().rev() {
+        items.retain(|item| !contained.contains(&(item.content.clone(), item.count)));
+        for item in items.iter().filter(|item| item.count > 1) {
+            let words: Vec<&str> = item.content.split(' ').collect();
+            for &shorter in sizes.iter().filter(|&&size| size < *n) {
+                for window in words.windows(shorter) {
+                    contained.insert((window.join(" "), item.count));
+                }
+            }
+        }
+    }
+}
+
+/// The --top-n repeated phrases of any size, ranked by the words spent repeating them:
+/// (count - 1) x n, then by length and first appearance.
+fn most_suspicious<'a>(counted_phrases: &'a [(usize, Vec<CountedItem>)], args: &Args) -> Vec<(usize, &'a CountedItem)> {
+    let mut suspicious: Vec<(usize, &CountedItem)> = counted_phrases.iter()
+        .flat_map(|(n, items)| items.iter().map(move |item| (*n, item)))
+        .filter(|(_, item)| item.count > 1 && item.count >= args.min_count)
+        .collect();
+    suspicious.sort_by(|(n1, a), (n2, b)| {
+        ((b.count - 1) * n2).cmp(&((a.count - 1) * n1))
+            .then(n2.cmp(n1))
+            .then(a.first.offset.cmp(&b.first.offset))
+    });
+    suspicious.truncate(args.top_n_limit());
+    suspicious
+}
+
+/// The input as characters, for --char-analysis and the character loop check, so offsets
+/// count Unicode scalar values rather than bytes.
+struct CharText {
+    chars: Vec<char>,
+    /// Offset of the first character of each line
+    line_starts: Vec<usize>,
+}
+
+impl CharText {
+    fn new(input: &str) -> Self {
+        let chars: Vec<char> = input.chars().collect();
+        let line_starts = std::iter::once(0)
+            .chain(chars.iter().enumerate().filter(|(_, &c)| c == '\n').map(|(i, _)| i + 1))
+            .collect();
+        CharText { chars, line_starts }
+    }
+    
+    /// 1-based line of the character at `offset`.
+    fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+    
+    fn text(&self, start: usize, len: usize) -> String {
+        self.chars[start..start + len].iter().collect()
+    }
+}
+
+/// Count the character n-grams of --char-ngram-size within each line, leaving out those that
+/// are only whitespace. An n-gram's offset is the 1-based input character it starts at.
+fn count_char_ngrams(text: &CharText, file_starts: &[usize], args: &Args) -> Vec<CountedItem> {
+    let mut tallies = Tallies::new(None, args.max_positions).across_files(file_starts);
+    for (i, &start) in text.line_starts.iter().enumerate() {
+        let end = text.line_starts.get(i + 1).map_or(text.chars.len(), |next| next - 1);
+        let line = &text.chars[start..end];
+        let line = line.strip_suffix(&['\r'][..]).unwrap_or(line);
+        for (j, window) in line.windows(args.char_ngram_size).enumerate() {
+            if window.iter().all(|c| c.is_whitespace()) {
+                continue;
+            }
+            let gram: String = window.iter().collect();
+            let key = if args.ignore_case { gram.to_lowercase() } else { gram.clone() };
+            tallies.add(key, &gram, Occurrence { line: i + 1, offset: start + j + 1 });
+        }
+    }
+    
+    let mut counted = tallies.into_items("n-grams");
+    counted.sort_by_key(|item| item.first.offset);
+    counted
+}
+
+fn report_chars(tokens: &Tokens, args: &Args, report: &mut Report) -> Result<()> {
+    let text = tokens.chars();
+    let counted_ngrams = count_char_ngrams(text, &tokens.file_starts, args);
+    let sorted_ngrams = sort_items(filter_items(&counted_ngrams, args), args);
+    let repeats = tokens.char_repeats();
+    
+    if args.json_output() {
+        report.add_json("chars", json_chars(&sorted_ngrams, repeats, args));
+        return Ok(());
+    }
+    
+    report.begin_text();
+    println!("=== {}-char N-gram Analysis ===", args.char_ngram_size);
+    output_items(&sorted_ngrams, Some("char"), args)?;
+    println!();
+    
+    if repeats.is_empty() {
+        println!("{}No substrings of {}+ characters repeated {}+ times", args.icon("✅"),
+                 args.min_repeat_len, args.repetition_threshold().max(2));
+        return Ok(());
+    }
+    
+    println!("=== Repeated Substrings ({}+ chars) ===", args.min_repeat_len);
+    for repeat in repeats {
+        println!("{} \"{}\" ({}x)", repeat.severity.marker(args.emoji()), repeat.pattern, repeat.count);
+        if let Some(period) = repeat.period {
+            println!("    back-to-back every {} char{}", period, if period == 1 { "" } else { "s" });
+        } else {
+            let positions = repeat.positions.iter().map(|&offset| format!("{}:{}", text.line(offset - 1), offset));
+            println!("    at line:char {}", position_list(positions, repeat.count));
+        }
+        println!("    first on line {}, last on line {}", repeat.first_line, repeat.last_line);
+    }
     
-    output_items(&sorted_items, args)?;
     Ok(())
 }
 
-fn analyze_words(input: &str, args: &Args) -> Result<()> {
-    // Split into words, similar to: tr ' ' '\n'
-    let words: Vec<String> = input
-        .split_whitespace()
-        .map(|w| normalize_word(w, args))
+/// The longest substrings of at least --min-repeat-len characters that occur at least
+/// --repetition-threshold times, as CharLoop indicators in order of first appearance.
+/// Occurrences that overlap or touch make a back-to-back run, reported as its repeating
+/// unit and the repetitions of its longest stretch; others count their occurrences.
+///
+/// Repeats are the deepest qualifying intervals of a suffix array, so the whole check takes
+/// O(n log n). Matches never cross a line break, and a substring whose every occurrence
+/// follows the same character is left out as part of a longer one.
+fn char_loops(text: &CharText, args: &Args) -> Vec<LoopIndicator> {
+    let min_count = args.repetition_threshold().max(2);
+    // Every line break is a symbol of its own, so no two suffixes match through one
+    let mut line_breaks = 0;
+    let symbols: Vec<u32> = text.chars.iter()
+        .map(|&c| if c == '\n' {
+            line_breaks += 1;
+            char::MAX as u32 + line_breaks
+        } else if args.ignore_case {
+            c.to_lowercase().next().unwrap_or(c) as u32
+        } else {
+            c as u32
+        })
         .collect();
-    
-    let counted_words = count_items(&words, args);
-    let filtered_words = filter_items(&counted_words, args);
-    let sorted_words = sort_items(filtered_words, args);
-    
-    if args.format == "json" {
-        output_json_analysis(&sorted_words, "words", args)?;
-    } else {
-        println!("=== Word Frequency Analysis ===");
-        output_items(&sorted_words, args)?;
-        
-        if args.above_threshold {
-            let flagged: Vec<_> = sorted_words.iter()
-                .filter(|item| item.count >= args.repetition_threshold)
-                .collect();
-                
-            if !flagged.is_empty() {
-                println!("\n🚨 FLAGGED: Words appearing ≥{} times:", args.repetition_threshold);
-                for item in flagged {
-                    println!("  {} {} ({}x)", 
-                            get_severity_for_count(item.count).to_emoji(),
-                            item.content, 
-                            item.count);
+    let sa = suffix_array(&symbols);
+    let lcp = lcp_array(&symbols, &sa);
+    
+    // Walk the LCP intervals bottom-up; each entry is (shared prefix length, first suffix,
+    // whether an interval nested in it already qualified)
+    let mut repeats: Vec<(Vec<usize>, usize)> = Vec::new();
+    let mut stack: Vec<(usize, usize, bool)> = vec![(0, 0, false)];
+    for i in 1..=sa.len() {
+        let shared = lcp.get(i).copied().unwrap_or(0);
+        let mut first = i - 1;
+        let mut nested = false;
+        while stack.last().is_some_and(|top| shared < top.0) {
+            let (len, interval_first, has_nested) = stack.pop().unwrap_or_default();
+            let qualifies = len >= args.min_repeat_len && i - interval_first >= min_count;
+            if qualifies && !has_nested {
+                let mut starts = sa[interval_first..i].to_vec();
+                starts.sort_unstable();
+                let before = |start: usize| symbols[start - 1];
+                if starts[0] == 0 || starts.iter().any(|&start| before(start) != before(starts[0])) {
+                    repeats.push((starts, len));
                 }
             }
+            first = interval_first;
+            match stack.last_mut() {
+                Some(top) if shared <= top.0 => top.2 |= qualifies || has_nested,
+                _ => nested = qualifies || has_nested,
+            }
+        }
+        if stack.last().is_some_and(|top| shared > top.0) {
+            stack.push((shared, first, nested));
         }
     }
-    
-    Ok(())
+    repeats.sort_by_key(|(starts, _)| starts[0]);
+    
+    repeats.into_iter()
+        .map(|(starts, len)| {
+            // The closest occurrences are a period apart when they overlap or touch
+            let period = starts.windows(2).map(|pair| pair[1] - pair[0]).min().filter(|&gap| gap <= len);
+            match period {
+                Some(period) => {
+                    let mut longest = Run { start: starts[0], len, period };
+                    let mut stretch_start = starts[0];
+                    for pair in starts.windows(2) {
+                        if pair[1] - pair[0] != period {
+                            stretch_start = pair[1];
+                        }
+                        if pair[1] + len - stretch_start > longest.len {
+                            longest = Run { start: stretch_start, len: pair[1] + len - stretch_start, period };
+                        }
+                    }
+                    run_loop(text.text(longest.start, period), LoopType::CharLoop, &longest,
+                             text.line(longest.start), text.line(longest.end() - 1))
+                }
+                None => LoopIndicator {
+                    pattern: text.text(starts[0], len),
+                    count: starts.len(),
+                    pattern_type: LoopType::CharLoop,
+                    severity: Severity::from_count(1),
+                    positions: starts.iter().take(args.max_positions).map(|start| start + 1).collect(),
+                    first_line: text.line(starts[0]),
+                    last_line: text.line(starts[starts.len() - 1] + len - 1),
+                    period: None,
+                },
+            }
+        })
+        .collect()
 }
 
-fn analyze_phrases(input: &str, args: &Args) -> Result<()> {
-    let words: Vec<String> = input
-        .split_whitespace()
-        .map(|w| normalize_word(w, args))
-        .collect();
-    
-    let mut phrases = Vec::new();
-    
-    // Generate N-grams
-    for window in words.windows(args.ngram_size) {
-        let phrase = window.join(" ");
-        phrases.push(phrase);
+/// The suffixes of `symbols` in sorted order, by prefix doubling: each round orders them by
+/// twice as many symbols with two counting sorts, until every suffix has a rank of its own.
+fn suffix_array(symbols: &[u32]) -> Vec<usize> {
+    let n = symbols.len();
+    let mut sa: Vec<usize> = (0..n).collect();
+    sa.sort_unstable_by_key(|&i| symbols[i]);
+    let mut rank = vec![0; n];
+    for j in 1..n {
+        rank[sa[j]] = rank[sa[j - 1]] + usize::from(symbols[sa[j]] != symbols[sa[j - 1]]);
     }
     
-    let counted_phrases = count_items(&phrases, args);
-    let filtered_phrases = filter_items(&counted_phrases, args);
-    let sorted_phrases = sort_items(filtered_phrases, args);
-    
-    if args.format == "json" {
-        output_json_analysis(&sorted_phrases, "phrases", args)?;
-    } else {
-        println!("=== {}-gram Phrase Analysis ===", args.ngram_size);
-        output_items(&sorted_phrases, args)?;
+    let mut next_rank = vec![0; n];
+    let mut by_second = Vec::with_capacity(n);
+    let mut counts = vec![0; n + 1];
+    let mut k = 1;
+    while n > 0 && rank[sa[n - 1]] < n - 1 {
+        // Suffixes in order of the k symbols after their first k, those with none first...
+        by_second.clear();
+        by_second.extend(n.saturating_sub(k)..n);
+        by_second.extend(sa.iter().filter(|&&i| i >= k).map(|&i| i - k));
+        // ...then stably by their first k
+        counts.fill(0);
+        for &i in &by_second {
+            counts[rank[i] + 1] += 1;
+        }
+        for r in 1..=n {
+            counts[r] += counts[r - 1];
+        }
+        for &i in &by_second {
+            sa[counts[rank[i]]] = i;
+            counts[rank[i]] += 1;
+        }
         
-        if args.above_threshold {
-            let flagged: Vec<_> = sorted_phrases.iter()
-                .filter(|item| item.count >= args.repetition_threshold)
-                .collect();
-                
-            if !flagged.is_empty() {
-                println!("\n🚨 FLAGGED: Phrases appearing ≥{} times:", args.repetition_threshold);
-                for item in flagged {
-                    println!("  {} \"{}\" ({}x)", 
-                            get_severity_for_count(item.count).to_emoji(),
-                            item.content, 
-                            item.count);
-                }
-            }
+        let second = |i: usize| rank.get(i + k).map_or(0, |&r| r + 1);
+        next_rank[sa[0]] = 0;
+        for j in 1..n {
+            let (a, b) = (sa[j - 1], sa[j]);
+            next_rank[b] = next_rank[a] + usize::from((rank[a], second(a)) != (rank[b], second(b)));
         }
+        std::mem::swap(&mut rank, &mut next_rank);
+        k *= 2;
+    }
+    sa
+}
+
+/// How many leading symbols each suffix in `sa` shares with the one before it (Kasai's
+/// algorithm); the first entry is 0.
+fn lcp_array(symbols: &[u32], sa: &[usize]) -> Vec<usize> {
+    let n = symbols.len();
+    let mut rank = vec![0; n];
+    for (i, &start) in sa.iter().enumerate() {
+        rank[start] = i;
     }
     
-    Ok(())
+    let mut lcp = vec![0; n];
+    let mut shared = 0;
+    for start in 0..n {
+        if rank[start] == 0 {
+            shared = 0;
+            continue;
+        }
+        let previous = sa[rank[start] - 1];
+        while start + shared < n && previous + shared < n && symbols[start + shared] == symbols[previous + shared] {
+            shared += 1;
+        }
+        lcp[rank[start]] = shared;
+        shared = shared.saturating_sub(1);
+    }
+    lcp
 }
 
-fn detect_ai_loops(input: &str, args: &Args) -> Result<()> {
+/// Report repetition that looks like a decoding loop. Lines, words, phrases and stock
+/// transitions repeated often enough anywhere are listed, but their severity follows their
+/// longest back-to-back run, so repetition spread through a long text stays low; runs of a
+/// line, block or phrase repeating with a short fixed period are listed on their own.
+fn detect_ai_loops(tokens: &Tokens, args: &Args, report: &mut Report) -> Result<()> {
+    // With --tail-window only the last lines are scanned, then line numbers are shifted back
+    let skipped_lines = args.tail_window.map_or(0, |window| tokens.lines().len().saturating_sub(window));
+    let window;
+    let tokens = match skipped_lines.checked_sub(1).and_then(|last| tokens.input.match_indices('\n').nth(last)) {
+        Some((newline, _)) => {
+            // Shifted so lines in the window still fall in their files
+            let file_starts = tokens.file_starts.iter().map(|start| start.saturating_sub(skipped_lines)).collect();
+            window = Tokens::new(&tokens.input[newline + 1..], args, file_starts);
+            &window
+        }
+        None => tokens,
+    };
     let mut loop_indicators = Vec::new();
     
     // Check for exact line repetitions
-    let lines: Vec<String> = input.lines().map(|s| s.to_string()).collect();
-    let line_counts = count_items(&lines, args);
-    
-    for item in &line_counts {
-        if item.count >= args.repetition_threshold {
-            loop_indicators.push(LoopIndicator {
-                pattern: item.content.clone(),
-                count: item.count,
-                pattern_type: LoopType::ExactRepeat,
-                severity: get_severity_for_count(item.count),
-            });
+    let lines = tokens.lines();
+    let line_runs = longest_runs(lines, 1, tokens);
+    
+    for item in tokens.line_counts() {
+        if item.count >= LoopType::ExactRepeat.threshold(args) {
+            loop_indicators.push(counted_loop(item, LoopType::ExactRepeat, &line_runs));
         }
     }
     
     // Check for word loops
-    let words: Vec<String> = input
-        .split_whitespace()
-        .map(|w| normalize_word(w, args))
-        .collect();
-    let word_counts = count_items(&words, args);
-    
-    for item in &word_counts {
-        if item.count >= args.repetition_threshold * 2 { // Higher threshold for words
-            loop_indicators.push(LoopIndicator {
-                pattern: item.content.clone(),
-                count: item.count,
-                pattern_type: LoopType::WordLoop,
-                severity: get_severity_for_count(item.count),
-            });
+    let words = tokens.words();
+    let word_runs = longest_runs(words, 1, tokens);
+    
+    for item in tokens.word_counts()? {
+        if item.count >= LoopType::WordLoop.threshold(args) {
+            loop_indicators.push(counted_loop(item, LoopType::WordLoop, &word_runs));
         }
     }
     
     // Check for phrase loops (3-grams)
-    let mut phrases = Vec::new();
-    for window in words.windows(3) {
-        phrases.push(window.join(" "));
-    }
-    let phrase_counts = count_items(&phrases, args);
+    let phrases = ngrams(words, 3);
+    let phrase_counts = count_items(&phrases, &tokens.file_starts, args);
+    let phrase_runs = longest_runs(&phrases, 3, tokens);
     
     for item in &phrase_counts {
-        if item.count >= args.repetition_threshold {
-            loop_indicators.push(LoopIndicator {
-                pattern: item.content.clone(),
-                count: item.count,
-                pattern_type: LoopType::PhraseLoop,
-                severity: get_severity_for_count(item.count),
-            });
+        if item.count >= LoopType::PhraseLoop.threshold(args) {
+            loop_indicators.push(counted_loop(item, LoopType::PhraseLoop, &phrase_runs));
         }
     }
     
     // Check for pattern loops (regex-based)
-    detect_pattern_loops(input, &mut loop_indicators, args);
-    
-    // Sort by severity and count
-    loop_indicators.sort_by(|a, b| {
-        b.count.cmp(&a.count)
-            .then_with(|| match (&a.severity, &b.severity) {
-                (Severity::Critical, _) => std::cmp::Ordering::Less,
-                (_, Severity::Critical) => std::cmp::Ordering::Greater,
-                (Severity::High, _) => std::cmp::Ordering::Less,
-                (_, Severity::High) => std::cmp::Ordering::Greater,
-                _ => std::cmp::Ordering::Equal,
-            })
-    });
+    detect_pattern_loops(tokens, &mut loop_indicators, args);
+    
+    // Check for lines and phrases repeating back-to-back; a phrase run inside a line run
+    // is the same loop seen word by word. Keys name their file, so no run crosses into the next
+    let line_keys: Vec<(usize, String)> = lines.iter().map(|(number, line)| (tokens.file_of(*number), normalize_item(line, args))).collect();
+    let repeated_lines: Vec<Run> = periodic_runs(&line_keys, MAX_LINE_PERIOD, LoopType::LineRun.threshold(args))
+        .into_iter()
+        .filter(|run| line_keys[run.start..run.start + run.period].iter().any(|(_, key)| !key.trim().is_empty()))
+        .collect();
+    for run in &repeated_lines {
+        let unit: Vec<&str> = lines[run.start..run.start + run.period].iter().map(|(_, line)| line.as_str()).collect();
+        loop_indicators.push(run_loop(unit.join(" / "), LoopType::LineRun, run, lines[run.start].0, lines[run.end() - 1].0));
+    }
     
-    if args.format == "json" {
-        output_json_loops(&loop_indicators, args)?;
+    let word_keys: Vec<(usize, &str)> = words.iter().map(|(line, word)| (tokens.file_of(*line), word.as_str())).collect();
+    for run in periodic_runs(&word_keys, MAX_PHRASE_PERIOD, LoopType::PhraseRun.threshold(args)) {
+        let (first_line, last_line) = (words[run.start].0, words[run.end() - 1].0);
+        if repeated_lines.iter().any(|line_run| lines[line_run.start].0 <= first_line && last_line <= lines[line_run.end() - 1].0) {
+            continue;
+        }
+        let unit: Vec<&str> = word_keys[run.start..run.start + run.period].iter().map(|(_, word)| *word).collect();
+        let unit = unit.join(" ");
+        loop_indicators.push(run_loop(unit, LoopType::PhraseRun, &run, first_line, last_line));
+    }
+    
+    // Check for text repeating back-to-back inside words, which word runs can't see; one
+    // within the lines of a line or phrase run is that run again
+    for indicator in tokens.char_repeats().iter().cloned() {
+        let within_run = |run: &LoopIndicator| {
+            run.period.is_some() && run.first_line <= indicator.first_line && indicator.last_line <= run.last_line
+        };
+        if indicator.period.is_some() && indicator.count >= LoopType::CharLoop.threshold(args)
+            && !loop_indicators.iter().any(within_run)
+        {
+            loop_indicators.push(indicator);
+        }
+    }
+    
+    if skipped_lines > 0 {
+        for indicator in &mut loop_indicators {
+            indicator.first_line += skipped_lines;
+            indicator.last_line += skipped_lines;
+            if indicator.pattern_type.position_unit() == "line" {
+                indicator.positions.iter_mut().for_each(|line| *line += skipped_lines);
+            }
+        }
+    }
+    
+    // Most severe first, then by count
+    loop_indicators.sort_by(|a, b| b.severity.cmp(&a.severity).then(b.count.cmp(&a.count)));
+    
+    if args.json_output() {
+        report.add_json("loops", json_loops(&loop_indicators, args));
     } else {
+        report.begin_text();
         output_loop_analysis(&loop_indicators, args)?;
     }
     
     Ok(())
 }
 
-fn detect_pattern_loops(input: &str, loop_indicators: &mut Vec<LoopIndicator>, args: &Args) {
+/// A loop for an item counted across the input, as severe as its longest back-to-back run
+/// in `runs` (from `longest_runs`).
+fn counted_loop(item: &CountedItem, pattern_type: LoopType, runs: &HashMap<String, usize>) -> LoopIndicator {
+    LoopIndicator {
+        pattern: item.content.clone(),
+        count: item.count,
+        pattern_type,
+        severity: Severity::from_count(runs.get(&item.normalized).copied().unwrap_or(1)),
+        positions: item.positions.iter().map(|p| p.offset).collect(),
+        first_line: item.first.line,
+        last_line: item.last.line,
+        period: None,
+    }
+}
+
+fn run_loop(pattern: String, pattern_type: LoopType, run: &Run, first_line: usize, last_line: usize) -> LoopIndicator {
+    LoopIndicator {
+        pattern,
+        count: run.repetitions(),
+        pattern_type,
+        severity: Severity::from_count(run.repetitions()),
+        positions: Vec::new(),
+        first_line,
+        last_line,
+        period: Some(run.period),
+    }
+}
+
+/// For every item (compared as `count_items` compares them), the most times it occurs in a
+/// row with each occurrence `step` items after the one before: 1 for adjacent words or lines,
+/// the phrase length for phrases repeated back-to-back.
+fn longest_runs(items: &[(usize, String)], step: usize, tokens: &Tokens) -> HashMap<String, usize> {
+    let keys: Vec<String> = items.iter().map(|(_, item)| normalize_item(item, tokens.args)).collect();
+    let mut run_ending_at = vec![1; keys.len()];
+    let mut longest: HashMap<String, usize> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        if i >= step && keys[i - step] == *key && tokens.file_of(items[i - step].0) == tokens.file_of(items[i].0) {
+            run_ending_at[i] = run_ending_at[i - step] + 1;
+        }
+        let best = longest.entry(key.clone()).or_insert(0);
+        *best = (*best).max(run_ending_at[i]);
+    }
+    longest
+}
+
+/// The most of the ascending, deduplicated `lines` that follow one another in one file.
+fn consecutive_lines(lines: &[usize], tokens: &Tokens) -> usize {
+    let (mut longest, mut run) = (0, 0);
+    for (i, &line) in lines.iter().enumerate() {
+        let follows = i > 0 && lines[i - 1] + 1 == line && tokens.file_of(lines[i - 1]) == tokens.file_of(line);
+        run = if follows { run + 1 } else { 1 };
+        longest = longest.max(run);
+    }
+    longest
+}
+
+/// Maximal stretches of `items` where each item equals the one `period` before it, for
+/// every period up to `max_period`, that repeat their first period at least `min_repetitions`
+/// times. Shorter periods are tried first, and a run inside one already found is dropped,
+/// so "a a a a" is one run of period 1 rather than also one of period 2.
+fn periodic_runs<T: PartialEq>(items: &[T], max_period: usize, min_repetitions: usize) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for period in 1..=max_period.min(items.len() / 2) {
+        let mut start = 0;
+        for i in period..=items.len() {
+            if i < items.len() && items[i] == items[i - period] {
+                continue;
+            }
+            let run = Run { start, len: i - start, period };
+            if run.repetitions() >= min_repetitions.max(2)
+                && !runs.iter().any(|found| found.start <= run.start && run.end() <= found.end())
+            {
+                runs.push(run);
+            }
+            start = i + 1 - period;
+        }
+    }
+    runs.sort_by_key(|run| run.start);
+    runs
+}
+
+fn detect_pattern_loops(tokens: &Tokens, loop_indicators: &mut Vec<LoopIndicator>, args: &Args) {
+    let input = tokens.input;
     // Common AI loop patterns
     let patterns = [
         (r"(?i)\b(the same|similar|likewise|similarly|in the same way)\b", "similarity_loop"),
@@ -377,103 +2425,227 @@ fn detect_pattern_loops(input: &str, loop_indicators: &mut Vec<LoopIndicator>, a
             let matches: Vec<_> = regex.find_iter(input).collect();
             let count = matches.len();
             
-            if count >= args.repetition_threshold {
+            if count >= LoopType::PatternLoop.threshold(args) {
+                // Matches come in order, so each line number picks up where the last left off
+                let (mut line, mut scanned) = (1, 0);
+                let mut lines: Vec<usize> = matches.iter()
+                    .map(|m| {
+                        line += input[scanned..m.start()].matches('\n').count();
+                        scanned = m.start();
+                        line
+                    })
+                    .collect();
+                let positions = lines.iter().copied().take(args.max_positions).collect();
+                let (first_line, last_line) = (lines[0], lines[count - 1]);
+                // Several matches on one line still make a run of one line
+                lines.dedup();
+                
                 loop_indicators.push(LoopIndicator {
                     pattern: format!("{} pattern", pattern_name),
                     count,
                     pattern_type: LoopType::PatternLoop,
-                    severity: get_severity_for_count(count),
+                    severity: Severity::from_count(consecutive_lines(&lines, tokens)),
+                    positions,
+                    first_line,
+                    last_line,
+                    period: None,
                 });
             }
         }
     }
 }
 
-fn show_statistics(input: &str, args: &Args) -> Result<()> {
-    let lines: Vec<String> = input.lines().map(|s| s.to_string()).collect();
-    let words: Vec<String> = input.split_whitespace().map(|s| s.to_string()).collect();
-    
-    let line_counts = count_items(&lines, args);
-    let word_counts = count_items(&words, args);
+fn show_statistics(tokens: &Tokens, args: &Args, report: &mut Report) -> Result<()> {
+    // Every word counts here, as it appears, unlike the words --word-analysis keeps
+    let words: Vec<(usize, String)> = tokens.lines().iter()
+        .flat_map(|(line_number, line)| line.split_whitespace().map(|word| (*line_number, word.to_string())))
+        .collect();
+    let word_counts = count_items(&words, &tokens.file_starts, args);
     
-    let line_stats = calculate_stats(&line_counts);
+    let line_stats = calculate_stats(tokens.line_counts());
     let word_stats = calculate_stats(&word_counts);
+    let compression = CompressionStats::new(tokens.input)?;
+    let line_lists = StatLists::new(tokens.line_counts(), args.threshold(ItemKind::Lines, line_stats.total_items), args);
+    let word_lists = StatLists::new(&word_counts, args.threshold(ItemKind::Words, word_stats.total_items), args);
     
-    if args.format == "json" {
-        output_json_stats(&line_stats, &word_stats, args)?;
+    if args.json_output() {
+        report.add_json("stats", json_stats(&line_stats, &word_stats, &compression, [&line_lists, &word_lists]));
     } else {
+        report.begin_text();
         println!("=== Statistical Analysis ===");
         println!();
         println!("Lines:");
         print_stats(&line_stats, "line");
+        line_lists.print(args);
         println!();
         println!("Words:");
         print_stats(&word_stats, "word");
+        word_lists.print(args);
+        println!();
+        println!("Compression:");
+        println!("  Compression ratio: {:.3} ({} of {} bytes)", compression.ratio(), compression.compressed_bytes, compression.original_bytes);
+        println!("  Tail compression ratio: {:.3} (last {:.0}%, {} of {} bytes)", compression.tail_ratio(),
+                 TAIL_FRACTION * 100.0, compression.tail_compressed_bytes, compression.tail_bytes);
         
         // Overall assessment
-        let overall_risk = assess_repetition_risk(&line_stats, &word_stats);
+        let overall_risk = assess_repetition_risk(&line_stats, &word_stats, &compression);
         println!();
         println!("=== Overall Assessment ===");
-        println!("{} Repetition Risk: {:?}", overall_risk.to_emoji(), overall_risk);
+        println!("{} Repetition Risk: {:?}", overall_risk.marker(args.emoji()), overall_risk);
         
         if matches!(overall_risk, Severity::High | Severity::Critical) {
-            println!("⚠️  High repetition detected - possible AI loop or training data memorization");
+            println!("{}High repetition detected - possible AI loop or training data memorization", args.icon("⚠️ "));
         }
     }
     
     Ok(())
 }
 
-fn count_items(items: &[String], args: &Args) -> Vec<CountedItem> {
-    let mut counts: HashMap<String, usize> = HashMap::new();
-    
-    for item in items {
-        let normalized = normalize_item(item, args);
-        *counts.entry(normalized.clone()).or_insert(0) += 1;
-    }
-    
-    counts.into_iter()
-        .map(|(normalized, count)| CountedItem {
-            content: normalized.clone(),
-            count,
-            normalized,
-        })
-        .collect()
+/// The lines or words --stats lists under its figures: the most repeated for an explicit
+/// --top-n, and those a threshold flags for --above-threshold or --threshold-ratio.
+struct StatLists<'a> {
+    most_repeated: Option<Vec<&'a CountedItem>>,
+    flagged: Option<Vec<&'a CountedItem>>,
+    threshold: Threshold,
 }
 
-fn normalize_item(item: &str, args: &Args) -> String {
-    let mut result = item.to_string();
-    
-    if args.ignore_case {
-        result = result.to_lowercase();
+impl<'a> StatLists<'a> {
+    fn new(counted: &'a [CountedItem], threshold: Threshold, args: &Args) -> Self {
+        StatLists {
+            most_repeated: args.top_n.map(|_| most_repeated(counted, |item| item.count > 1, args)),
+            flagged: args.flags_items().then(|| most_repeated(counted, |item| threshold.flags(item.count), args)),
+            threshold,
+        }
     }
     
-    if let Some(skip_chars) = args.skip_chars {
-        if skip_chars < result.len() {
-            result = result[skip_chars..].to_string();
+    fn print(&self, args: &Args) {
+        if let Some(items) = self.most_repeated.as_ref().filter(|items| !items.is_empty()) {
+            println!("  Most repeated:");
+            for item in items {
+                println!("  {:8} {}", item.count, item.content);
+            }
+        }
+        if let Some(items) = self.flagged.as_ref().filter(|items| !items.is_empty()) {
+            println!("  Flagged ({}):", self.threshold.describe());
+            for item in items {
+                println!("  {} {} ({}x, {:.1}%)", self.threshold.severity(item.count).marker(args.emoji()),
+                         item.content, item.count, self.threshold.share(item.count) * 100.0);
+            }
         }
     }
     
-    if let Some(check_chars) = args.check_chars {
-        if check_chars < result.len() {
-            result = result[..check_chars].to_string();
+    fn insert_json(&self, stats_obj: &mut serde_json::Map<String, serde_json::Value>) {
+        if let Some(items) = &self.most_repeated {
+            stats_obj.insert("most_repeated".to_string(), serde_json::json!(
+                items.iter().map(|item| serde_json::json!({ "content": item.content, "count": item.count })).collect::<Vec<_>>()
+            ));
+        }
+        if let Some(items) = &self.flagged {
+            stats_obj.insert("flagged".to_string(), serde_json::json!(
+                items.iter().map(|item| serde_json::json!({
+                    "content": item.content,
+                    "count": item.count,
+                    "ratio": self.threshold.share(item.count),
+                    "severity": format!("{:?}", self.threshold.severity(item.count)),
+                })).collect::<Vec<_>>()
+            ));
+            stats_obj.insert("flag_threshold".to_string(), serde_json::json!({
+                "count": self.threshold.count,
+                "ratio": self.threshold.ratio,
+                "mode": format!("{:?}", self.threshold.mode).to_lowercase(),
+            }));
         }
     }
+}
+
+/// The first --top-n items `keep` accepts, most often first, ties in order of first
+/// appearance.
+fn most_repeated<'a>(counted: &'a [CountedItem], keep: impl Fn(&CountedItem) -> bool, args: &Args) -> Vec<&'a CountedItem> {
+    let mut repeated: Vec<&CountedItem> = counted.iter().filter(|item| keep(item)).collect();
+    repeated.sort_by(|a, b| b.count.cmp(&a.count).then(a.first.offset.cmp(&b.first.offset)));
+    repeated.truncate(args.top_n_limit());
+    repeated
+}
+
+/// Count `items`, each given with the 1-based line it starts on.
+fn count_items(items: &[(usize, String)], file_starts: &[usize], args: &Args) -> Vec<CountedItem> {
+    let mut tallies = Tallies::new(None, args.max_positions).across_files(file_starts);
+    for (i, (line, item)) in items.iter().enumerate() {
+        let normalized = normalize_item(item, args);
+        tallies.add(normalized.clone(), &normalized, Occurrence { line: *line, offset: i + 1 });
+    }
     
-    if let Some(skip_fields) = args.skip_fields {
-        let fields: Vec<&str> = result.split_whitespace().collect();
-        if skip_fields < fields.len() {
-            result = fields[skip_fields..].join(" ");
+    let mut counted = tallies.into_items("items");
+    // First-appearance order, so later stable sorts break ties the same way every run
+    counted.sort_by_key(|item| item.first.offset);
+    counted
+}
+
+/// Count the words `filter` keeps. The others still take up their offset, so offsets
+/// remain word positions in the input.
+fn count_words(words: &[(usize, String)], file_starts: &[usize], filter: &WordFilter, args: &Args) -> Vec<CountedItem> {
+    let mut tallies = Tallies::new(None, args.max_positions).across_files(file_starts);
+    for (i, (line, word)) in words.iter().enumerate() {
+        if filter.keeps(word) {
+            let normalized = normalize_item(word, args);
+            tallies.add(normalized.clone(), &normalized, Occurrence { line: *line, offset: i + 1 });
         }
     }
     
-    result
+    let mut counted = tallies.into_items("words");
+    counted.sort_by_key(|item| item.first.offset);
+    counted
+}
+
+/// The part of an item uniq compares: skip -f fields (each a run of blanks then
+/// non-blanks, or up to the next -t separator), then -s characters, keep at most -w
+/// characters, and fold case for -i. Characters are Unicode scalar values, or bytes with
+/// --bytes, where a cut through a multibyte character leaves U+FFFD.
+fn normalize_item(item: &str, args: &Args) -> String {
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    
+    let mut rest = item;
+    for _ in 0..args.skip_fields.unwrap_or(0) {
+        rest = match args.field_separator {
+            Some(separator) => rest.split_once(separator).map_or("", |(_, after)| after),
+            None => rest.trim_start_matches(is_blank).trim_start_matches(|c| !is_blank(c)),
+        };
+    }
+    
+    let skip_chars = args.skip_chars.unwrap_or(0);
+    let key = if args.bytes {
+        let bytes = &rest.as_bytes()[skip_chars.min(rest.len())..];
+        let end = args.check_chars.map_or(bytes.len(), |n| n.min(bytes.len()));
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    } else {
+        let start = rest.char_indices().nth(skip_chars).map_or(rest.len(), |(i, _)| i);
+        let rest = &rest[start..];
+        let end = args.check_chars
+            .and_then(|n| rest.char_indices().nth(n))
+            .map_or(rest.len(), |(i, _)| i);
+        rest[..end].to_string()
+    };
+    
+    if args.ignore_case {
+        key.to_lowercase()
+    } else {
+        key
+    }
 }
 
 fn normalize_word(word: &str, args: &Args) -> String {
     let mut result = word.to_string();
     
-    // Remove punctuation for word analysis
+    if args.unicode_normalize {
+        result = result.nfkc().collect();
+    }
+    
+    if args.strip_diacritics {
+        result = result.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect();
+    }
+    
+    // Remove punctuation for word analysis. Apostrophes go too, straight or curly, with or
+    // without --unicode-normalize, so "don't" is always "dont", as the stopwords expect
     result = result.chars()
         .filter(|c| c.is_alphanumeric() || c.is_whitespace())
         .collect();
@@ -500,54 +2672,93 @@ fn filter_items(items: &[CountedItem], args: &Args) -> Vec<CountedItem> {
         .collect()
 }
 
+/// Orders items by first appearance, or by --sort-freq or --numeric-sort when given (with
+/// -r reversing that order). Ties go to the item seen first, then the lexicographically
+/// smaller one, so the same input always gives the same output.
 fn sort_items(mut items: Vec<CountedItem>, args: &Args) -> Vec<CountedItem> {
-    if args.sort_freq {
-        items.sort_by(|a, b| {
-            if args.reverse {
-                a.count.cmp(&b.count)
-            } else {
-                b.count.cmp(&a.count)
-            }
-        });
-    } else if args.numeric_sort {
-        items.sort_by(|a, b| {
+    items.sort_by(|a, b| {
+        let order = if args.sort_freq {
+            b.count.cmp(&a.count)
+        } else if args.numeric_sort {
             let a_num = a.content.parse::<i64>().unwrap_or(0);
             let b_num = b.content.parse::<i64>().unwrap_or(0);
-            if args.reverse {
-                b_num.cmp(&a_num)
-            } else {
-                a_num.cmp(&b_num)
-            }
-        });
-    } else {
-        items.sort_by(|a, b| {
-            if args.reverse {
-                b.content.cmp(&a.content)
-            } else {
-                a.content.cmp(&b.content)
-            }
-        });
-    }
+            a_num.cmp(&b_num)
+        } else {
+            a.first.offset.cmp(&b.first.offset)
+        };
+        let order = if args.reverse { order.reverse() } else { order };
+        
+        order
+            .then(a.first.offset.cmp(&b.first.offset))
+            .then(a.content.cmp(&b.content))
+    });
     
     // Apply top_n limit
-    if args.word_analysis || args.phrase_analysis {
-        items.truncate(args.top_n);
+    if args.word_analysis || args.phrase_analysis || args.char_analysis {
+        items.truncate(args.top_n_limit());
     }
     
     items
 }
 
-fn output_items(items: &[CountedItem], args: &Args) -> Result<()> {
+/// Print counted items; `offset_unit` names what --show-positions counts offsets in
+/// ("word" or "char"), or is None to list only lines.
+fn output_items(items: &[CountedItem], offset_unit: Option<&str>, args: &Args) -> Result<()> {
+    if args.json_output() {
+        return json_analysis(items, "lines", args).print(args);
+    }
+    
     for item in items {
         if args.count {
             println!("{:8} {}", item.count, item.content);
         } else {
             println!("{}", item.content);
         }
+        if args.show_positions {
+            println!("    at {}", item_positions(item, offset_unit));
+        }
+        if args.show_file_spread {
+            println!("    in {} file{}", item.files, if item.files == 1 { "" } else { "s" });
+        }
+        for variant in item.variants.iter().take(SHOWN_VARIANTS) {
+            println!("    ~ {}", variant);
+        }
+        if item.variants.len() > SHOWN_VARIANTS {
+            println!("    ~ (+{} more variants)", item.variants.len() - SHOWN_VARIANTS);
+        }
     }
     Ok(())
 }
 
+/// How many --fuzzy variants text output lists under a cluster.
+const SHOWN_VARIANTS: usize = 3;
+
+/// Where an item occurs, for --show-positions: "line 2, 7" or, for phrases, "line:word 2:5, 7:31"
+/// (and "line:char" for character n-grams).
+fn item_positions(item: &CountedItem, offset_unit: Option<&str>) -> String {
+    match offset_unit {
+        Some(unit) => {
+            let positions = item.positions.iter().map(|p| format!("{}:{}", p.line, p.offset));
+            format!("line:{} {}", unit, position_list(positions, item.count))
+        }
+        None => format!("line {}", position_list(item.positions.iter().map(|p| p.line.to_string()), item.count)),
+    }
+}
+
+/// How many positions text output lists before summing up the rest.
+const SHOWN_POSITIONS: usize = 5;
+
+/// The first few of `total` positions, with "(+N more)" for the others.
+fn position_list(positions: impl Iterator<Item = String>, total: usize) -> String {
+    let shown: Vec<String> = positions.take(SHOWN_POSITIONS).collect();
+    let more = total.saturating_sub(shown.len());
+    if more > 0 {
+        format!("{} (+{} more)", shown.join(", "), more)
+    } else {
+        shown.join(", ")
+    }
+}
+
 fn calculate_stats(items: &[CountedItem]) -> RepetitionStats {
     let total_items: usize = items.iter().map(|i| i.count).sum();
     let unique_items = items.len();
@@ -592,39 +2803,46 @@ fn calculate_stats(items: &[CountedItem]) -> RepetitionStats {
     }
 }
 
-fn get_severity_for_count(count: usize) -> Severity {
-    if count >= 20 {
-        Severity::Critical
-    } else if count >= 10 {
-        Severity::High
-    } else if count >= 5 {
-        Severity::Medium
-    } else {
-        Severity::Low
-    }
+/// Line max repetitions at or above which the risk score gains 1, 2 and 3 points.
+const LINE_RISK_THRESHOLDS: [usize; 3] = [3, 5, 10];
+/// Word max repetitions at or above which the risk score gains 1, 2 and 3 points.
+const WORD_RISK_THRESHOLDS: [usize; 3] = [10, 20, 50];
+/// Line entropy below which the risk score gains 1 and 2 points.
+const ENTROPY_RISK_THRESHOLDS: [f64; 2] = [3.0, 2.0];
+/// Compression ratios below which the risk score gains 1 and 2 points, for the whole input
+/// and again for its tail; prose deflates to around half its size.
+const COMPRESSION_RISK_THRESHOLDS: [f64; 2] = [0.3, 0.15];
+/// Fewer bytes than this deflate poorly whatever they say, so their ratio scores nothing.
+const MIN_COMPRESSION_BYTES: usize = 256;
+/// Risk scores from which the overall risk is Medium, High and Critical.
+const RISK_SCORE_BANDS: [usize; 3] = [3, 5, 7];
+
+fn assess_repetition_risk(line_stats: &RepetitionStats, word_stats: &RepetitionStats, compression: &CompressionStats) -> Severity {
+    risk_from_score(repetition_risk_score(line_stats, word_stats, compression))
 }
 
-fn assess_repetition_risk(line_stats: &RepetitionStats, word_stats: &RepetitionStats) -> Severity {
-    let line_risk = if line_stats.max_repetition >= 10 { 3 } 
-                   else if line_stats.max_repetition >= 5 { 2 }
-                   else if line_stats.max_repetition >= 3 { 1 } 
-                   else { 0 };
-    
-    let word_risk = if word_stats.max_repetition >= 50 { 3 }
-                   else if word_stats.max_repetition >= 20 { 2 }
-                   else if word_stats.max_repetition >= 10 { 1 }
-                   else { 0 };
-    
-    let entropy_risk = if line_stats.entropy < 2.0 { 2 }
-                      else if line_stats.entropy < 3.0 { 1 }
-                      else { 0 };
-    
-    let total_risk = line_risk + word_risk + entropy_risk;
+fn repetition_risk_score(line_stats: &RepetitionStats, word_stats: &RepetitionStats, compression: &CompressionStats) -> usize {
+    let line_risk = LINE_RISK_THRESHOLDS.iter().filter(|&&t| line_stats.max_repetition >= t).count();
+    let word_risk = WORD_RISK_THRESHOLDS.iter().filter(|&&t| word_stats.max_repetition >= t).count();
+    let entropy_risk = ENTROPY_RISK_THRESHOLDS.iter().filter(|&&t| line_stats.entropy < t).count();
+    let compression_risk = |ratio: f64, bytes: usize| {
+        if bytes < MIN_COMPRESSION_BYTES {
+            0
+        } else {
+            COMPRESSION_RISK_THRESHOLDS.iter().filter(|&&t| ratio < t).count()
+        }
+    };
     
-    match total_risk {
-        0..=2 => Severity::Low,
-        3..=4 => Severity::Medium,
-        5..=6 => Severity::High,
+    line_risk + word_risk + entropy_risk
+        + compression_risk(compression.ratio(), compression.original_bytes)
+        + compression_risk(compression.tail_ratio(), compression.tail_bytes)
+}
+
+fn risk_from_score(score: usize) -> Severity {
+    match RISK_SCORE_BANDS.iter().filter(|&&band| score >= band).count() {
+        0 => Severity::Low,
+        1 => Severity::Medium,
+        2 => Severity::High,
         _ => Severity::Critical,
     }
 }
@@ -639,22 +2857,43 @@ fn print_stats(stats: &RepetitionStats, item_type: &str) {
     println!("  Shannon entropy: {:.2}", stats.entropy);
 }
 
+/// Print the loops found, the --top-n most severe in full; `loop_indicators` is sorted most
+/// severe first.
 fn output_loop_analysis(loop_indicators: &[LoopIndicator], args: &Args) -> Result<()> {
     if loop_indicators.is_empty() {
-        println!("✅ No AI loops detected (threshold: {})", args.repetition_threshold);
+        println!("{}No AI loops detected (threshold: {})", args.icon("✅"), args.repetition_threshold());
         return Ok(());
     }
     
     println!("=== AI Loop Detection Results ===");
-    println!("🚨 {} potential loops detected:", loop_indicators.len());
+    println!("{}{} potential loops detected:", args.icon("🚨"), loop_indicators.len());
     println!();
     
-    for indicator in loop_indicators {
+    let shown = &loop_indicators[..loop_indicators.len().min(args.top_n_limit())];
+    for indicator in shown {
         println!("{} {:?} Loop: \"{}\" ({}x)", 
-                indicator.severity.to_emoji(),
+                indicator.severity.marker(args.emoji()),
                 indicator.pattern_type,
                 indicator.pattern,
                 indicator.count);
+        
+        if !indicator.positions.is_empty() {
+            println!("    at {} {}",
+                    indicator.pattern_type.position_unit(),
+                    position_list(indicator.positions.iter().map(|p| p.to_string()), indicator.count));
+        }
+        if let Some(period) = indicator.period {
+            let unit = match indicator.pattern_type {
+            LoopType::LineRun => "line",
+            LoopType::CharLoop => "char",
+            _ => "word",
+        };
+            println!("    back-to-back every {} {}{}", period, unit, if period == 1 { "" } else { "s" });
+        }
+        println!("    first on line {}, last on line {}", indicator.first_line, indicator.last_line);
+    }
+    if shown.len() < loop_indicators.len() {
+        println!("(+{} more loops; --top-n 0 shows all)", loop_indicators.len() - shown.len());
     }
     
     let critical_count = loop_indicators.iter()
@@ -663,75 +2902,300 @@ fn output_loop_analysis(loop_indicators: &[LoopIndicator], args: &Args) -> Resul
     
     if critical_count > 0 {
         println!();
-        println!("💀 CRITICAL: {} severe loops detected - likely AI malfunction", critical_count);
+        println!("{}CRITICAL: {} severe loops detected - likely AI malfunction", args.icon("💀"), critical_count);
     }
     
     Ok(())
 }
 
-fn output_json_analysis(items: &[CountedItem], analysis_type: &str, _args: &Args) -> Result<()> {
+/// One analysis in JSON: the document --format json prints, and the records --format ndjson
+/// prints a line each instead.
+struct JsonSection {
+    document: serde_json::Map<String, serde_json::Value>,
+    records: Vec<serde_json::Value>,
+}
+
+impl JsonSection {
+    /// A document whose `key` holds `records`.
+    fn with_records(mut document: serde_json::Map<String, serde_json::Value>, key: &str, records: Vec<serde_json::Value>) -> Self {
+        document.insert(key.to_string(), serde_json::Value::Array(records.clone()));
+        JsonSection { document, records }
+    }
+    
+    fn print(&self, args: &Args) -> Result<()> {
+        if args.format == "ndjson" {
+            for record in &self.records {
+                println!("{}", serde_json::to_string(record)?);
+            }
+        } else {
+            println!("{}", serde_json::to_string_pretty(&self.document)?);
+        }
+        Ok(())
+    }
+}
+
+fn json_analysis(items: &[CountedItem], analysis_type: &str, args: &Args) -> JsonSection {
     let mut output = serde_json::Map::new();
     output.insert("analysis_type".to_string(), serde_json::Value::String(analysis_type.to_string()));
     output.insert("total_items".to_string(), serde_json::Value::Number(serde_json::Number::from(items.len())));
     
     let items_json: Vec<serde_json::Value> = items.iter().map(|item| {
-        let mut item_obj = serde_json::Map::new();
-        item_obj.insert("content".to_string(), serde_json::Value::String(item.content.clone()));
-        item_obj.insert("count".to_string(), serde_json::Value::Number(serde_json::Number::from(item.count)));
-        serde_json::Value::Object(item_obj)
+        let mut record = if analysis_type == "lines" {
+            line_record(&item.content, item.count, item.first.line, item.last.line)
+        } else {
+            let mut item_obj = serde_json::Map::new();
+            item_obj.insert("content".to_string(), serde_json::Value::String(item.content.clone()));
+            item_obj.insert("count".to_string(), serde_json::Value::Number(serde_json::Number::from(item.count)));
+            serde_json::Value::Object(item_obj)
+        };
+        if args.fuzzy.is_some() {
+            record["variants"] = serde_json::json!(item.variants);
+        }
+        if args.show_positions {
+            record["lines"] = serde_json::json!(item.positions.iter().map(|p| p.line).collect::<Vec<_>>());
+            if analysis_type == "phrases" {
+                record["word_offsets"] = serde_json::json!(item.positions.iter().map(|p| p.offset).collect::<Vec<_>>());
+            }
+        }
+        if args.show_file_spread {
+            record["files"] = serde_json::json!(item.files);
+        }
+        record
+    }).collect();
+    
+    JsonSection::with_records(output, "items", items_json)
+}
+
+/// Phrase analysis over several n-gram sizes: each size's items under `sizes`, keyed by n,
+/// and the combined `most_suspicious` ranking. With --format ndjson each item prints as a
+/// record of its own that names its `ngram_size`.
+fn json_phrase_sizes(sorted_phrases: &[(usize, Vec<CountedItem>)], suspicious: &[(usize, &CountedItem)], args: &Args) -> JsonSection {
+    let record = |n: usize, item: &CountedItem| {
+        let mut record = serde_json::json!({ "content": item.content, "count": item.count, "ngram_size": n });
+        if args.show_positions {
+            record["lines"] = serde_json::json!(item.positions.iter().map(|p| p.line).collect::<Vec<_>>());
+            record["word_offsets"] = serde_json::json!(item.positions.iter().map(|p| p.offset).collect::<Vec<_>>());
+        }
+        if args.show_file_spread {
+            record["files"] = serde_json::json!(item.files);
+        }
+        record
+    };
+    
+    let records = sorted_phrases.iter()
+        .flat_map(|(n, items)| items.iter().map(|item| record(*n, item)))
+        .collect();
+    
+    let sizes: serde_json::Map<String, serde_json::Value> = sorted_phrases.iter()
+        .map(|(n, items)| {
+            let items: Vec<serde_json::Value> = items.iter().map(|item| record(*n, item)).collect();
+            (n.to_string(), serde_json::json!({ "total_items": items.len(), "items": items }))
+        })
+        .collect();
+    let output = serde_json::json!({
+        "analysis_type": "phrases",
+        "ngram_sizes": sorted_phrases.iter().map(|(n, _)| n).collect::<Vec<_>>(),
+        "sizes": sizes,
+        "most_suspicious": suspicious.iter().map(|(n, item)| record(*n, item)).collect::<Vec<_>>(),
+    });
+    JsonSection { document: json_object(output), records }
+}
+
+/// Character analysis: the n-grams under `items` and the repeated substrings, as loop
+/// records, under `repeated_substrings`. With --format ndjson the n-grams print first, then
+/// the substrings.
+fn json_chars(ngrams: &[CountedItem], repeats: &[LoopIndicator], args: &Args) -> JsonSection {
+    let items: Vec<serde_json::Value> = ngrams.iter().map(|item| {
+        let mut record = serde_json::json!({ "content": item.content, "count": item.count });
+        if args.show_positions {
+            record["lines"] = serde_json::json!(item.positions.iter().map(|p| p.line).collect::<Vec<_>>());
+            record["char_offsets"] = serde_json::json!(item.positions.iter().map(|p| p.offset).collect::<Vec<_>>());
+        }
+        if args.show_file_spread {
+            record["files"] = serde_json::json!(item.files);
+        }
+        record
     }).collect();
+    let substrings: Vec<serde_json::Value> = repeats.iter().map(|repeat| loop_record(repeat, args)).collect();
+    
+    let output = serde_json::json!({
+        "analysis_type": "chars",
+        "ngram_size": args.char_ngram_size,
+        "min_repeat_len": args.min_repeat_len,
+        "total_items": items.len(),
+        "items": items,
+        "repeated_substrings": substrings,
+    });
+    JsonSection { document: json_object(output), records: items.into_iter().chain(substrings).collect() }
+}
+
+/// The map inside a `json!` object.
+fn json_object(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    match value {
+        serde_json::Value::Object(map) => map,
+        _ => unreachable!("not a JSON object"),
+    }
+}
+
+/// A line and where it was seen: `first_line` and `last_line` are 1-based across all inputs.
+fn line_record(content: &str, count: usize, first_line: usize, last_line: usize) -> serde_json::Value {
+    let mut item_obj = serde_json::Map::new();
+    item_obj.insert("content".to_string(), serde_json::Value::String(content.to_string()));
+    item_obj.insert("count".to_string(), serde_json::Value::Number(serde_json::Number::from(count)));
+    item_obj.insert("first_line".to_string(), serde_json::Value::Number(serde_json::Number::from(first_line)));
+    item_obj.insert("last_line".to_string(), serde_json::Value::Number(serde_json::Number::from(last_line)));
+    serde_json::Value::Object(item_obj)
+}
+
+/// The --docs clusters with more than one member, each listing its documents by name and
+/// 1-based position among all documents, with their estimated similarity to the first.
+fn output_json_documents(documents: &[Document], clusters: &[&DocumentCluster], unique: usize, args: &Args) -> Result<()> {
+    let mut output = serde_json::Map::new();
+    output.insert("analysis_type".to_string(), serde_json::Value::String("documents".to_string()));
+    output.insert("total_documents".to_string(), serde_json::json!(documents.len()));
+    output.insert("unique_documents".to_string(), serde_json::json!(unique));
+    output.insert("threshold".to_string(), serde_json::json!(args.doc_threshold));
+    output.insert("shingle_size".to_string(), serde_json::json!(args.shingle_size));
+    output.insert("num_hashes".to_string(), serde_json::json!(args.num_hashes));
+    
+    let records = clusters.iter()
+        .map(|cluster| {
+            let members: Vec<serde_json::Value> = cluster.members.iter().zip(&cluster.similarities)
+                .map(|(&member, similarity)| serde_json::json!({
+                    "document": documents[member].name,
+                    "index": member + 1,
+                    "similarity": similarity,
+                }))
+                .collect();
+            serde_json::json!({ "size": members.len(), "members": members })
+        })
+        .collect();
+    
+    JsonSection::with_records(output, "clusters", records).print(args)
+}
+
+fn output_json_runs(runs: &[AdjacentRun]) -> Result<()> {
+    let mut output = serde_json::Map::new();
+    output.insert("analysis_type".to_string(), serde_json::Value::String("adjacent_lines".to_string()));
+    output.insert("total_items".to_string(), serde_json::Value::Number(serde_json::Number::from(runs.len())));
+    
+    let items_json: Vec<serde_json::Value> = runs.iter()
+        .map(|run| line_record(&run.line, run.count, run.position, run.position + run.count - 1))
+        .collect();
     
     output.insert("items".to_string(), serde_json::Value::Array(items_json));
     println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
 
-fn output_json_loops(loop_indicators: &[LoopIndicator], _args: &Args) -> Result<()> {
+fn json_loops(loop_indicators: &[LoopIndicator], args: &Args) -> JsonSection {
     let mut output = serde_json::Map::new();
     output.insert("analysis_type".to_string(), serde_json::Value::String("loop_detection".to_string()));
     output.insert("loops_detected".to_string(), serde_json::Value::Number(serde_json::Number::from(loop_indicators.len())));
     
-    let loops_json: Vec<serde_json::Value> = loop_indicators.iter().map(|indicator| {
-        let mut loop_obj = serde_json::Map::new();
-        loop_obj.insert("pattern".to_string(), serde_json::Value::String(indicator.pattern.clone()));
-        loop_obj.insert("count".to_string(), serde_json::Value::Number(serde_json::Number::from(indicator.count)));
-        loop_obj.insert("type".to_string(), serde_json::Value::String(format!("{:?}", indicator.pattern_type)));
-        loop_obj.insert("severity".to_string(), serde_json::Value::String(format!("{:?}", indicator.severity)));
-        serde_json::Value::Object(loop_obj)
-    }).collect();
+    let thresholds: serde_json::Map<String, serde_json::Value> = LoopType::ALL.iter()
+        .map(|loop_type| (format!("{:?}", loop_type), serde_json::Value::Number(serde_json::Number::from(loop_type.threshold(args)))))
+        .collect();
+    output.insert("thresholds".to_string(), serde_json::Value::Object(thresholds));
     
-    output.insert("loops".to_string(), serde_json::Value::Array(loops_json));
-    println!("{}", serde_json::to_string_pretty(&output)?);
-    Ok(())
+    // loops_detected still counts the ones past --top-n
+    let loops_json: Vec<serde_json::Value> = loop_indicators.iter()
+        .take(args.top_n_limit())
+        .map(|indicator| loop_record(indicator, args))
+        .collect();
+    
+    JsonSection::with_records(output, "loops", loops_json)
 }
 
-fn output_json_stats(line_stats: &RepetitionStats, word_stats: &RepetitionStats, _args: &Args) -> Result<()> {
-    let mut output = serde_json::Map::new();
+/// One loop as a JSON record; --char-analysis lists its repeated substrings the same way.
+fn loop_record(indicator: &LoopIndicator, args: &Args) -> serde_json::Value {
+    let mut loop_obj = serde_json::Map::new();
+    loop_obj.insert("pattern".to_string(), serde_json::Value::String(indicator.pattern.clone()));
+    loop_obj.insert("count".to_string(), serde_json::Value::Number(serde_json::Number::from(indicator.count)));
+    loop_obj.insert("first_line".to_string(), serde_json::Value::Number(serde_json::Number::from(indicator.first_line)));
+    loop_obj.insert("last_line".to_string(), serde_json::Value::Number(serde_json::Number::from(indicator.last_line)));
+    loop_obj.insert("threshold".to_string(), serde_json::Value::Number(serde_json::Number::from(indicator.pattern_type.threshold(args))));
+    loop_obj.insert("type".to_string(), serde_json::Value::String(format!("{:?}", indicator.pattern_type)));
+    loop_obj.insert("severity".to_string(), serde_json::Value::String(format!("{:?}", indicator.severity)));
+    loop_obj.insert("position_unit".to_string(), serde_json::Value::String(indicator.pattern_type.position_unit().to_string()));
+    if let Some(period) = indicator.period {
+        loop_obj.insert("period".to_string(), serde_json::Value::Number(serde_json::Number::from(period)));
+    }
+    loop_obj.insert("positions".to_string(), serde_json::Value::Array(
+        indicator.positions.iter().map(|p| serde_json::Value::Number(serde_json::Number::from(*p))).collect()
+    ));
+    serde_json::Value::Object(loop_obj)
+}
+
+/// Repetition statistics, with the lines and words listed in `lists`.
+fn json_stats(line_stats: &RepetitionStats, word_stats: &RepetitionStats, compression: &CompressionStats,
+              lists: [&StatLists; 2]) -> JsonSection {
+    let risk_score = repetition_risk_score(line_stats, word_stats, compression);
+    
+    let mut thresholds = serde_json::Map::new();
+    thresholds.insert("line_max_repetition".to_string(), serde_json::json!(LINE_RISK_THRESHOLDS));
+    thresholds.insert("word_max_repetition".to_string(), serde_json::json!(WORD_RISK_THRESHOLDS));
+    thresholds.insert("line_entropy_below".to_string(), serde_json::json!(ENTROPY_RISK_THRESHOLDS));
+    thresholds.insert("compression_ratio_below".to_string(), serde_json::json!(COMPRESSION_RISK_THRESHOLDS));
+    thresholds.insert("compression_min_bytes".to_string(), serde_json::json!(MIN_COMPRESSION_BYTES));
+    thresholds.insert("risk_score".to_string(), serde_json::json!({
+        "Medium": RISK_SCORE_BANDS[0],
+        "High": RISK_SCORE_BANDS[1],
+        "Critical": RISK_SCORE_BANDS[2],
+    }));
+    
+    let mut overall = serde_json::Map::new();
+    overall.insert("overall_risk".to_string(), serde_json::Value::String(format!("{:?}", risk_from_score(risk_score))));
+    overall.insert("risk_score".to_string(), serde_json::Value::Number(serde_json::Number::from(risk_score)));
+    overall.insert("thresholds".to_string(), serde_json::Value::Object(thresholds));
+    
+    let mut line_json = stats_json(line_stats);
+    let mut word_json = stats_json(word_stats);
+    lists[0].insert_json(&mut line_json);
+    lists[1].insert_json(&mut word_json);
     
-    let mut line_stats_obj = serde_json::Map::new();
-    line_stats_obj.insert("total_items".to_string(), serde_json::Value::Number(serde_json::Number::from(line_stats.total_items)));
-    line_stats_obj.insert("unique_items".to_string(), serde_json::Value::Number(serde_json::Number::from(line_stats.unique_items)));
-    line_stats_obj.insert("duplicate_items".to_string(), serde_json::Value::Number(serde_json::Number::from(line_stats.duplicate_items)));
-    line_stats_obj.insert("max_repetition".to_string(), serde_json::Value::Number(serde_json::Number::from(line_stats.max_repetition)));
-    line_stats_obj.insert("avg_repetition".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(line_stats.avg_repetition).unwrap()));
-    line_stats_obj.insert("repetition_ratio".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(line_stats.repetition_ratio).unwrap()));
-    line_stats_obj.insert("entropy".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(line_stats.entropy).unwrap()));
-    
-    let mut word_stats_obj = serde_json::Map::new();
-    word_stats_obj.insert("total_items".to_string(), serde_json::Value::Number(serde_json::Number::from(word_stats.total_items)));
-    word_stats_obj.insert("unique_items".to_string(), serde_json::Value::Number(serde_json::Number::from(word_stats.unique_items)));
-    word_stats_obj.insert("duplicate_items".to_string(), serde_json::Value::Number(serde_json::Number::from(word_stats.duplicate_items)));
-    word_stats_obj.insert("max_repetition".to_string(), serde_json::Value::Number(serde_json::Number::from(word_stats.max_repetition)));
-    word_stats_obj.insert("avg_repetition".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(word_stats.avg_repetition).unwrap()));
-    word_stats_obj.insert("repetition_ratio".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(word_stats.repetition_ratio).unwrap()));
-    word_stats_obj.insert("entropy".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(word_stats.entropy).unwrap()));
-    
-    output.insert("line_stats".to_string(), serde_json::Value::Object(line_stats_obj));
-    output.insert("word_stats".to_string(), serde_json::Value::Object(word_stats_obj));
-    
-    let overall_risk = assess_repetition_risk(line_stats, word_stats);
-    output.insert("overall_risk".to_string(), serde_json::Value::String(format!("{:?}", overall_risk)));
+    let mut output = serde_json::Map::new();
+    output.insert("line_stats".to_string(), serde_json::Value::Object(line_json.clone()));
+    output.insert("word_stats".to_string(), serde_json::Value::Object(word_json.clone()));
+    output.insert("compression".to_string(), serde_json::Value::Object(compression_json(compression)));
+    output.extend(overall.clone());
+    
+    let records = [
+        ("lines", line_json),
+        ("words", word_json),
+        ("compression", compression_json(compression)),
+        ("overall", overall),
+    ];
+    let records = records.into_iter()
+        .map(|(scope, mut record)| {
+            record.insert("scope".to_string(), serde_json::Value::String(scope.to_string()));
+            serde_json::Value::Object(record)
+        })
+        .collect();
     
-    println!("{}", serde_json::to_string_pretty(&output)?);
-    Ok(())
-}
\ No newline at end of file
+    JsonSection { document: output, records }
+}
+
+fn stats_json(stats: &RepetitionStats) -> serde_json::Map<String, serde_json::Value> {
+    let mut stats_obj = serde_json::Map::new();
+    stats_obj.insert("total_items".to_string(), serde_json::Value::Number(serde_json::Number::from(stats.total_items)));
+    stats_obj.insert("unique_items".to_string(), serde_json::Value::Number(serde_json::Number::from(stats.unique_items)));
+    stats_obj.insert("duplicate_items".to_string(), serde_json::Value::Number(serde_json::Number::from(stats.duplicate_items)));
+    stats_obj.insert("max_repetition".to_string(), serde_json::Value::Number(serde_json::Number::from(stats.max_repetition)));
+    stats_obj.insert("avg_repetition".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(stats.avg_repetition).unwrap()));
+    stats_obj.insert("repetition_ratio".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(stats.repetition_ratio).unwrap()));
+    stats_obj.insert("entropy".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(stats.entropy).unwrap()));
+    stats_obj
+}
+
+fn compression_json(compression: &CompressionStats) -> serde_json::Map<String, serde_json::Value> {
+    let mut compression_obj = serde_json::Map::new();
+    compression_obj.insert("original_bytes".to_string(), serde_json::json!(compression.original_bytes));
+    compression_obj.insert("compressed_bytes".to_string(), serde_json::json!(compression.compressed_bytes));
+    compression_obj.insert("ratio".to_string(), serde_json::json!(compression.ratio()));
+    compression_obj.insert("tail_fraction".to_string(), serde_json::json!(TAIL_FRACTION));
+    compression_obj.insert("tail_bytes".to_string(), serde_json::json!(compression.tail_bytes));
+    compression_obj.insert("tail_compressed_bytes".to_string(), serde_json::json!(compression.tail_compressed_bytes));
+    compression_obj.insert("tail_ratio".to_string(), serde_json::json!(compression.tail_ratio()));
+    compression_obj
+}
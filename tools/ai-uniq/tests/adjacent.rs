@@ -0,0 +1,66 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run ai-uniq over `input` on stdin; expected outputs below were taken from GNU uniq.
+fn uniq(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn only_consecutive_duplicates_collapse() {
+    let input = "b\nb\na\nb\n";
+    assert_eq!(uniq(&[], input), "b\na\nb\n");
+    assert_eq!(uniq(&["-c"], input), "      2 b\n      1 a\n      1 b\n");
+    assert_eq!(uniq(&["--global", "-c"], input), "       3 b\n       1 a\n");
+}
+
+#[test]
+fn line_endings_and_empty_input_match_uniq() {
+    // A missing final newline still ends the last line, and is added on output
+    assert_eq!(uniq(&[], "a\na"), "a\n");
+    assert_eq!(uniq(&[], "only"), "only\n");
+    assert_eq!(uniq(&["-c"], "\n\n\nx\n\n"), "      3 \n      1 x\n      1 \n");
+    assert_eq!(uniq(&[], ""), "");
+}
+
+#[test]
+fn repeated_and_unique_filters_match_uniq() {
+    let input = "a\na\nb\n\n\nc\n";
+    assert_eq!(uniq(&["-d"], input), "a\n\n");
+    assert_eq!(uniq(&["-u"], input), "b\nc\n");
+    assert_eq!(uniq(&["-d", "-u"], input), "");
+}
+
+#[test]
+fn comparison_options_keep_the_first_line_of_each_run() {
+    assert_eq!(uniq(&["-i", "-c"], "Apple\napple\nAPPLE\nbanana\n"), "      3 Apple\n      1 banana\n");
+    
+    // Fields are blanks then non-blanks, so the blanks before the next field still count
+    let fields = "x  one\ny  one\nz one\n\tfoo bar\n foo bar\n";
+    assert_eq!(uniq(&["-f", "1"], fields), "x  one\nz one\n\tfoo bar\n");
+    
+    let chars = "abcdef\nabcxyz\nxbcdef\nABCdef\nabc\n";
+    assert_eq!(uniq(&["-w", "3"], chars), "abcdef\nxbcdef\nABCdef\nabc\n");
+    assert_eq!(uniq(&["-s", "1", "-w", "2"], chars), "abcdef\nABCdef\nabc\n");
+    assert_eq!(uniq(&["-c", "-i", "-s", "3"], chars), "      1 abcdef\n      1 abcxyz\n      2 xbcdef\n      1 abc\n");
+}
+
+#[test]
+fn json_reports_where_each_run_starts() {
+    let doc: serde_json::Value = serde_json::from_str(&uniq(&["--format", "json", "-d"], "a\nb\nb\nb\nc\nc\n")).unwrap();
+    assert_eq!(doc["analysis_type"], "adjacent_lines");
+    assert_eq!(doc["items"], serde_json::json!([
+        { "content": "b", "count": 3, "first_line": 2, "last_line": 4 },
+        { "content": "c", "count": 2, "first_line": 5, "last_line": 6 },
+    ]));
+}
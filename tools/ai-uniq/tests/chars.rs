@@ -0,0 +1,82 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn document(args: &[&str], input: &str) -> serde_json::Value {
+    serde_json::from_str(&run(&[args, &["--format", "json"]].concat(), input)).unwrap()
+}
+
+#[test]
+fn ngrams_stay_within_lines() {
+    let doc = document(&["--char-analysis", "--char-ngram-size", "3", "--sort-freq", "--show-positions"], "abcabc\nbca\n");
+    assert_eq!(doc["items"][0], serde_json::json!({ "content": "abc", "count": 2, "lines": [1, 1], "char_offsets": [1, 4] }));
+    assert_eq!(doc["items"][1], serde_json::json!({ "content": "bca", "count": 2, "lines": [1, 2], "char_offsets": [2, 8] }));
+    // "bc\nb" and the like never count
+    assert_eq!(doc["total_items"], 3);
+}
+
+#[test]
+fn a_run_inside_a_word_is_one_repeated_unit() {
+    let input = format!("The answer is {}.\nDone\n", "ab".repeat(40));
+    let doc = document(&["--char-analysis"], &input);
+    assert_eq!(doc["repeated_substrings"].as_array().unwrap().len(), 1);
+    let run = &doc["repeated_substrings"][0];
+    assert_eq!((run["pattern"].as_str(), run["count"].as_u64(), run["period"].as_u64()), (Some("ab"), Some(40), Some(2)));
+    assert_eq!(run["severity"], "Critical");
+}
+
+#[test]
+fn spread_repeats_list_where_they_occur() {
+    let input: String = (1..=5).map(|i| format!("{i}. see the configuration manual\n")).collect();
+    let text = run(&["--char-analysis", "--char-ngram-size", "40"], &input);
+    assert_eq!(
+        text,
+        "=== 40-char N-gram Analysis ===\n\n=== Repeated Substrings (20+ chars) ===\n\
+         [LOW] \". see the configuration manual\" (5x)\n    at line:char 1:2, 2:34, 3:66, 4:98, 5:130\n    \
+         first on line 1, last on line 5\n"
+    );
+    
+    // Shorter than --min-repeat-len
+    assert!(run(&["--char-analysis", "--min-repeat-len", "40"], &input).contains("No substrings of 40+ characters repeated 5+ times"));
+}
+
+#[test]
+fn multibyte_text_counts_characters() {
+    let input = format!("héllo {}\n", "日本語テキスト".repeat(10));
+    let doc = document(&["--char-analysis", "--sort-freq", "--show-positions"], &input);
+    assert_eq!(doc["items"][0]["content"], "日本語テ");
+    assert_eq!(doc["items"][0]["char_offsets"][0], 7);
+    assert_eq!(doc["repeated_substrings"][0]["pattern"], "日本語テキスト");
+    assert_eq!(doc["repeated_substrings"][0]["count"], 10);
+}
+
+#[test]
+fn detect_loops_reports_runs_words_miss() {
+    let input = format!("intro\nOutput: {}\noutro\n", "xyz".repeat(12));
+    let doc = document(&["--detect-loops"], &input);
+    assert_eq!(doc["loops_detected"], 1);
+    assert_eq!(
+        doc["loops"][0],
+        serde_json::json!({
+            "type": "CharLoop", "severity": "High", "count": 12, "period": 3, "pattern": "xyz",
+            "first_line": 2, "last_line": 2, "threshold": 5, "position_unit": "char", "positions": [],
+        })
+    );
+    
+    // Word runs already cover the same text repeated with spaces
+    let doc = document(&["--detect-loops"], &format!("{}\n", "again and ".repeat(12)));
+    assert!(doc["loops"].as_array().unwrap().iter().all(|found| found["type"] != "CharLoop"), "{}", doc);
+}
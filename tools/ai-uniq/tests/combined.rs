@@ -0,0 +1,64 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = "go on\ngo on\ngo on\ngo on\ngo on\ngo on\ndone\n";
+
+fn run(args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(INPUT.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn document(args: &[&str]) -> serde_json::Value {
+    serde_json::from_str(&run(&[args, &["--format", "json"]].concat())).unwrap()
+}
+
+#[test]
+fn sections_print_in_a_fixed_order() {
+    let text = run(&["--stats", "--word-analysis", "--color", "never"]);
+    assert!(text.starts_with("=== Word Frequency Analysis ===\ngo\ndone\n\n=== Statistical Analysis ===\n"), "{}", text);
+    
+    // Each section is what the analysis prints on its own
+    let words = run(&["--word-analysis"]);
+    let loops = run(&["--detect-loops", "--color", "never"]);
+    assert_eq!(run(&["--detect-loops", "--word-analysis", "--color", "never"]), format!("{words}\n{loops}"));
+}
+
+#[test]
+fn json_nests_each_analysis_under_its_name() {
+    let doc = document(&["--word-analysis", "--detect-loops", "--stats"]);
+    assert_eq!(doc["analysis_type"], "combined");
+    assert_eq!(doc["analyses"], serde_json::json!(["words", "loops", "stats"]));
+    assert_eq!(doc["words"], document(&["--word-analysis"]));
+    assert_eq!(doc["loops"], document(&["--detect-loops"]));
+    assert_eq!(doc["stats"], document(&["--stats"]));
+    assert!(doc.get("phrases").is_none());
+}
+
+#[test]
+fn ndjson_records_name_their_section() {
+    let records: Vec<serde_json::Value> = run(&["--phrase-analysis", "--stats", "--format", "ndjson"]).lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let sections: Vec<&str> = records.iter().map(|record| record["section"].as_str().unwrap()).collect();
+    assert_eq!(sections, ["phrases", "phrases", "phrases", "stats", "stats", "stats", "stats"]);
+    assert_eq!(records[0], serde_json::json!({ "content": "go on go", "count": 5, "section": "phrases" }));
+    assert_eq!(records[6]["scope"], "overall");
+}
+
+#[test]
+fn all_runs_every_analysis() {
+    // --tail-window and --char-ngram-size accept --all in place of the flag they tune
+    let doc = document(&["--all", "--tail-window", "3", "--char-ngram-size", "3"]);
+    assert_eq!(doc["analyses"], serde_json::json!(["words", "phrases", "chars", "loops", "stats"]));
+    assert_eq!(doc["chars"]["ngram_size"], 3);
+    assert_eq!(doc["loops"]["loops_detected"], 0);
+}
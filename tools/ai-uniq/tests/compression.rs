@@ -0,0 +1,73 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/stats").join(name)).unwrap()
+}
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .arg("--stats")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn stats(input: &str) -> serde_json::Value {
+    serde_json::from_str(&run(&["--format", "json"], input)).unwrap()
+}
+
+fn ratio(doc: &serde_json::Value, key: &str) -> f64 {
+    doc["compression"][key].as_f64().unwrap()
+}
+
+#[test]
+fn repetitive_text_compresses_far_better_than_prose() {
+    let prose = stats(&fixture("prose.txt"));
+    let degenerate = stats(&fixture("degenerate.txt"));
+    
+    assert!(ratio(&prose, "ratio") > 0.45, "{}", prose["compression"]);
+    assert!(ratio(&degenerate, "ratio") < 0.2, "{}", degenerate["compression"]);
+    assert!(ratio(&degenerate, "tail_ratio") < 0.2, "{}", degenerate["compression"]);
+    
+    // No line of the degenerate fixture repeats, yet it rates High once compression counts
+    assert_eq!(degenerate["line_stats"]["max_repetition"], 1);
+    assert_eq!(degenerate["overall_risk"], "High");
+    assert_ne!(prose["overall_risk"], "High");
+}
+
+#[test]
+fn tail_ratio_catches_a_degenerating_end() {
+    let steps: String = (1..=12).map(|i| format!("Step {i}: check the value {i} again before you continue.\n")).collect();
+    let doc = stats(&format!("{}{}", fixture("prose.txt"), steps));
+    assert!(ratio(&doc, "ratio") > 0.4, "{}", doc["compression"]);
+    assert!(ratio(&doc, "tail_ratio") < ratio(&doc, "ratio") / 2.0, "{}", doc["compression"]);
+}
+
+#[test]
+fn text_output_shows_both_ratios() {
+    let text = run(&[], &fixture("degenerate.txt"));
+    assert!(text.contains("Compression:\n  Compression ratio: 0.1"), "{}", text);
+    assert!(text.contains("  Tail compression ratio: 0.1"), "{}", text);
+    assert!(text.contains("(last 20%, "), "{}", text);
+}
+
+#[test]
+fn short_inputs_do_not_score() {
+    // Nine distinct lines that deflate well, but are too short for the ratio to count
+    let input: String = (0..9).map(|i| format!("{i} {}\n", "xy".repeat(9))).collect();
+    let doc = stats(&input);
+    assert!(ratio(&doc, "ratio") < 0.3, "{}", doc["compression"]);
+    assert_eq!(doc["risk_score"], 0);
+    
+    assert_eq!(stats("")["compression"]["ratio"], 1.0);
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/docs");
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    files.sort();
+    files
+}
+
+fn run(args: &[&str], files: &[PathBuf], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .arg("--docs")
+        .args(args)
+        .args(files)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn json(args: &[&str], files: &[PathBuf], input: &str) -> serde_json::Value {
+    let output = run(&[args, &["--format", "json"]].concat(), files, input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// Each reported cluster as the set of its members' file names.
+fn clusters(doc: &serde_json::Value) -> BTreeSet<BTreeSet<String>> {
+    doc["clusters"].as_array().unwrap().iter()
+        .map(|cluster| cluster["members"].as_array().unwrap().iter()
+            .map(|member| {
+                let name = member["document"].as_str().unwrap();
+                Path::new(name).file_name().map_or(name, |file| file.to_str().unwrap()).to_string()
+            })
+            .collect())
+        .collect()
+}
+
+fn set(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn planted_near_duplicates_are_found_and_nothing_else() {
+    // The corpus plants a retried and an extended copy of one answer and a lightly edited
+    // copy of another, next to answers that share their boilerplate or their topic
+    let doc = json(&[], &corpus(), "");
+    assert_eq!(
+        clusters(&doc),
+        BTreeSet::from([
+            set(&["binary-search-copy.txt", "binary-search.txt"]),
+            set(&["rust-setup-extended.txt", "rust-setup-retry.txt", "rust-setup.txt"]),
+        ])
+    );
+    assert_eq!((doc["total_documents"].as_u64(), doc["unique_documents"].as_u64()), (Some(8), Some(5)));
+    
+    let member = &doc["clusters"][0]["members"][1];
+    assert_eq!(member["index"], 3);
+    let similarity = member["similarity"].as_f64().unwrap();
+    assert!(similarity > 0.9 && similarity < 1.0, "{}", similarity);
+}
+
+#[test]
+fn a_higher_threshold_keeps_only_the_closest_copies() {
+    let doc = json(&["--doc-threshold", "0.95"], &corpus(), "");
+    assert_eq!(clusters(&doc), BTreeSet::from([set(&["binary-search-copy.txt", "binary-search.txt"])]));
+}
+
+#[test]
+fn records_split_at_the_separator() {
+    let answer = "The quick brown fox jumps over the lazy dog near the river bank today.\n";
+    let input = format!("{answer}---\nSomething else entirely, about compilers and parsers.\n---\n{}---\n", answer.to_uppercase());
+    let doc = json(&["--doc-separator", "---"], &[], &input);
+    assert_eq!(clusters(&doc), BTreeSet::from([set(&["-#1", "-#3"])]));
+    assert_eq!(doc["total_documents"], 3);
+    
+    let output = run(&["--doc-separator", "---"], &[], &input);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "=== Near-Duplicate Documents ===\nCluster 1 (2 documents):\n  1.00  -#1\n  1.00  -#3\n\n\
+         3 documents, 2 after removing 1 near-duplicates (threshold: 0.7)\n"
+    );
+}
+
+#[test]
+fn keep_first_writes_the_deduplicated_set() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("keep-first");
+    let _ = std::fs::remove_dir_all(&dir);
+    let output = run(&["--keep-first", dir.to_str().unwrap()], &corpus(), "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    
+    let kept: BTreeSet<String> = std::fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(kept, set(&["binary-search-copy.txt", "binary-search-explained.txt", "python-setup.txt", "refusal.txt", "rust-setup-extended.txt"]));
+    assert_eq!(std::fs::read(dir.join("refusal.txt")).unwrap(), std::fs::read(&corpus()[4]).unwrap());
+}
+
+#[test]
+fn distinct_documents_report_no_clusters() {
+    let output = run(&[], &corpus()[..2], "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "No near-duplicate documents among 2 (threshold: 0.7)\n");
+}
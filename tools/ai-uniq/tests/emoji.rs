@@ -0,0 +1,50 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run(args: &[&str], input: &str) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .env_remove("NO_COLOR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    output.stdout
+}
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+}
+
+/// Spelled out as escapes so a re-encoding of the source can't change both sides at once.
+const INFO: &str = "\u{2139}\u{FE0F}";
+const SIREN: &str = "\u{1F6A8}";
+const SKULL: &str = "\u{1F480}";
+const CHECK: &str = "\u{2705}";
+
+#[test]
+fn severity_emoji_are_intact_utf8() {
+    assert_eq!(INFO.as_bytes(), b"\xE2\x84\xB9\xEF\xB8\x8F");
+    assert_eq!(SIREN.as_bytes(), b"\xF0\x9F\x9A\xA8");
+    
+    let flagged = run(&["--word-analysis", "--above-threshold", "--repetition-threshold", "3", "--color", "always"], "again again again\n");
+    assert!(contains(&flagged, &format!("\n{} FLAGGED: Words", SIREN)));
+    assert!(contains(&flagged, &format!("  {} again (3x)", INFO)));
+    
+    let loops = run(&["--detect-loops", "--color", "always"], &"stuck\n".repeat(20));
+    assert!(contains(&loops, &format!("{} CRITICAL:", SKULL)));
+    assert!(contains(&run(&["--detect-loops", "--color", "always"], "fine\n"), &format!("{} No AI loops detected", CHECK)));
+}
+
+#[test]
+fn piped_output_uses_text_tags() {
+    let loops = String::from_utf8(run(&["--detect-loops"], &"stuck\n".repeat(20))).unwrap();
+    assert!(loops.contains("\n[CRITICAL] ExactRepeat Loop: \"stuck\" (20x)\n"), "{}", loops);
+    assert!(loops.contains("\nCRITICAL: 3 severe loops detected"), "{}", loops);
+    assert!(loops.is_ascii(), "{}", loops);
+    
+    assert_eq!(run(&["--detect-loops", "--color", "never"], "fine\n"), run(&["--detect-loops"], "fine\n"));
+}
@@ -0,0 +1,94 @@
+use std::path::Path;
+use std::process::Command;
+
+/// tail-loop.txt ends with three "stuck again" lines and head-loop.txt starts with three more.
+fn run(args: &[&str]) -> String {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/files");
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .args(["--color", "never"])
+        .arg(fixtures.join("tail-loop.txt"))
+        .arg(fixtures.join("head-loop.txt"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn document(args: &[&str]) -> serde_json::Value {
+    serde_json::from_str(&run(&[args, &["--format", "json"]].concat())).unwrap()
+}
+
+#[test]
+fn each_file_gets_its_own_section_then_the_total() {
+    let text = run(&["--per-file", "--word-analysis", "--top-n", "1"]);
+    let headers: Vec<&str> = text.lines().filter(|line| line.starts_with("==> ")).collect();
+    assert_eq!(headers.len(), 3);
+    assert!(headers[0].ends_with("tail-loop.txt <==") && headers[1].ends_with("head-loop.txt <=="), "{}", text);
+    assert_eq!(headers[2], "==> All files <==");
+    assert!(text.ends_with("\n\n==> All files <==\n=== Word Frequency Analysis ===\nintro\n"), "{}", text);
+}
+
+#[test]
+fn json_lists_files_and_the_aggregate() {
+    let doc = document(&["--per-file", "--word-analysis", "--sort-freq", "--top-n", "1"]);
+    assert_eq!(doc["analysis_type"], "per_file");
+    let files = doc["files"].as_array().unwrap();
+    assert!(files[0]["file"].as_str().unwrap().ends_with("tail-loop.txt"));
+    assert_eq!(files[1]["items"][0], serde_json::json!({ "content": "stuck", "count": 3 }));
+    assert_eq!(doc["aggregate"]["items"][0], serde_json::json!({ "content": "stuck", "count": 6 }));
+    assert!(doc["aggregate"].get("file").is_none());
+    
+    let records: Vec<serde_json::Value> = run(&["--per-file", "--word-analysis", "--top-n", "1", "--format", "ndjson"]).lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 3);
+    assert!(records[1]["file"].as_str().unwrap().ends_with("head-loop.txt"));
+    assert_eq!(records[2]["file"], serde_json::Value::Null);
+}
+
+#[test]
+fn file_spread_counts_distinct_files() {
+    let doc = document(&["--word-analysis", "--show-file-spread"]);
+    let spread: Vec<(&str, u64)> = doc["items"].as_array().unwrap().iter()
+        .map(|item| (item["content"].as_str().unwrap(), item["files"].as_u64().unwrap()))
+        .collect();
+    // "here" is a stopword
+    assert_eq!(spread, [("intro", 1), ("stuck", 2), ("again", 2), ("outro", 1)]);
+    
+    assert!(run(&["--phrase-analysis", "--ngram-size", "2", "--show-file-spread"]).starts_with(
+        "=== 2-gram Phrase Analysis ===\nintro stuck\n    in 1 file\nstuck again\n    in 2 files\n"
+    ));
+}
+
+#[test]
+fn loops_never_run_across_files() {
+    let doc = document(&["--detect-loops", "--repetition-threshold", "3"]);
+    let runs: Vec<(u64, u64)> = doc["loops"].as_array().unwrap().iter()
+        .filter(|found| found["type"] == "LineRun")
+        .map(|found| (found["first_line"].as_u64().unwrap(), found["last_line"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(runs, [(2, 4), (5, 7)]);
+    // Six in total, but no more than three in a row in either file
+    let exact = doc["loops"].as_array().unwrap().iter().find(|found| found["type"] == "ExactRepeat").unwrap();
+    assert_eq!((exact["count"].as_u64(), exact["severity"].as_str()), (Some(6), Some("Low")));
+}
+
+#[test]
+fn per_file_needs_an_analysis() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-uniq")).args(["--per-file", "-c"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--per-file and --show-file-spread apply to"));
+}
+
+#[test]
+fn unreadable_file_is_named_in_the_error() {
+    let missing = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-such-input.txt");
+    // Streaming lines, reading whole inputs and reading documents
+    for args in [&["-c"][..], &["--word-analysis"], &["--docs"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_ai-uniq")).args(args).arg(&missing).output().unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("cannot read {}", missing.display())), "{:?}: {}", args, stderr);
+    }
+}
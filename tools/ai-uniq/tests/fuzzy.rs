@@ -0,0 +1,136 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const INPUT: &str = "Sure, here is the code:\n\
+                     sure here is the code\n\
+                     The answer is 42.\n\
+                     Sure! Here is the code.\n\
+                     The answer is 43.\n\
+                     the answer is 42\n\
+                     Something else entirely\n";
+
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], input: &str) -> String {
+    let output = run(args, input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn punctuation_and_case_variants_merge() {
+    assert_eq!(
+        stdout(&["--fuzzy", "-c"], INPUT),
+        "       3 Sure, here is the code:\n    ~ sure here is the code\n    ~ Sure! Here is the code.\n       \
+         2 The answer is 42.\n    ~ the answer is 42\n       \
+         1 The answer is 43.\n       \
+         1 Something else entirely\n"
+    );
+}
+
+#[test]
+fn distinct_lines_stay_apart() {
+    let input = "The build failed on Linux\nThe build passed on Linux\nThe tests failed on macOS\n";
+    assert_eq!(stdout(&["--fuzzy"], input), input);
+    
+    let input = "Error: disk full\nWarning: disk full\nError: disk fine\n";
+    assert_eq!(stdout(&["--fuzzy", "--fuzzy-metric", "levenshtein"], input), input);
+}
+
+#[test]
+fn works_with_duplicates_only_and_frequency_order() {
+    let input = format!("one-off line\n{INPUT}The Answer is 42\nthe answer is 42?\n");
+    assert_eq!(
+        stdout(&["--fuzzy", "-d", "--sort-freq", "-c"], &input),
+        "       4 The answer is 42.\n    ~ the answer is 42\n    ~ The Answer is 42\n    ~ the answer is 42?\n       \
+         3 Sure, here is the code:\n    ~ sure here is the code\n    ~ Sure! Here is the code.\n"
+    );
+}
+
+#[test]
+fn levenshtein_counts_character_edits() {
+    let text = stdout(&["--fuzzy=0.95", "--fuzzy-metric", "levenshtein", "-c"], INPUT);
+    assert!(text.contains("       2 The answer is 42.\n    ~ the answer is 42\n       1 The answer is 43.\n"), "{}", text);
+    
+    // One changed character in sixteen is within the default threshold
+    let text = stdout(&["--fuzzy", "--fuzzy-metric", "levenshtein", "-c"], INPUT);
+    assert!(text.contains("       3 The answer is 42.\n    ~ The answer is 43.\n    ~ the answer is 42\n"), "{}", text);
+}
+
+#[test]
+fn json_lists_variants() {
+    let json: serde_json::Value = serde_json::from_str(&stdout(&["--fuzzy", "--format", "json"], INPUT)).unwrap();
+    let first = &json["items"][0];
+    assert_eq!(first["content"], "Sure, here is the code:");
+    assert_eq!(first["count"], 3);
+    assert_eq!(first["variants"], serde_json::json!(["sure here is the code", "Sure! Here is the code."]));
+    assert_eq!(json["items"][3]["variants"], serde_json::json!([]));
+}
+
+#[test]
+fn many_variants_are_summarized() {
+    let input: String = (0..5).map(|i| format!("{}Retrying the request now\n", "!".repeat(i))).collect();
+    assert_eq!(
+        stdout(&["--fuzzy", "-c"], &input),
+        "       5 Retrying the request now\n    ~ !Retrying the request now\n    ~ !!Retrying the request now\n    \
+         ~ !!!Retrying the request now\n    ~ (+1 more variants)\n"
+    );
+}
+
+#[test]
+fn threshold_must_be_a_fraction() {
+    let output = run(&["--fuzzy=1.5"], INPUT);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--fuzzy threshold must be above 0 and at most 1"));
+}
+
+#[test]
+fn levenshtein_scales_to_many_long_lines() {
+    // Long lines from a small vocabulary share most of their trigrams, which is the worst case
+    // for the trigram index; every fifth line is an earlier one with two letters changed
+    let words = ["lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do",
+        "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim", "ad",
+        "minim", "veniam", "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi", "aliquip"];
+    let mut seed: u64 = 42;
+    let mut next = |n: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % n
+    };
+    let (mut lines, mut counts): (Vec<String>, Vec<u64>) = (Vec::new(), Vec::new());
+    let mut input = String::new();
+    for i in 0..5000 {
+        let line = if i % 5 == 4 {
+            let original = next(lines.len());
+            counts[original] += 1;
+            let mut chars: Vec<char> = lines[original].chars().collect();
+            for _ in 0..2 {
+                let position = next(chars.len());
+                chars[position] = if chars[position] == 'x' { 'y' } else { 'x' };
+            }
+            chars.into_iter().collect()
+        } else {
+            let line = (0..20 + next(20)).map(|_| words[next(words.len())]).collect::<Vec<_>>().join(" ");
+            lines.push(line.clone());
+            counts.push(1);
+            line
+        };
+        input.push_str(&line);
+        input.push('\n');
+    }
+    
+    let started = std::time::Instant::now();
+    let json: serde_json::Value = serde_json::from_str(&stdout(&["--fuzzy", "--fuzzy-metric", "levenshtein", "--format", "json"], &input)).unwrap();
+    let found: Vec<u64> = json["items"].as_array().unwrap().iter().map(|item| item["count"].as_u64().unwrap()).collect();
+    assert_eq!(found, counts);
+    assert!(started.elapsed().as_secs() < 60, "took {:?}", started.elapsed());
+}
@@ -0,0 +1,92 @@
+//! The keys of every JSON document and NDJSON record, so changes to them are deliberate.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = "go on\ngo on\ngo on\ngo on\ngo on\ngo on\ndone\n";
+
+fn run(args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(INPUT.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn keys(value: &serde_json::Value) -> Vec<&str> {
+    value.as_object().unwrap().keys().map(String::as_str).collect()
+}
+
+fn document(args: &[&str]) -> serde_json::Value {
+    serde_json::from_str(&run(&[args, &["--format", "json"]].concat())).unwrap()
+}
+
+fn records(args: &[&str]) -> Vec<serde_json::Value> {
+    run(&[args, &["--format", "ndjson"]].concat()).lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn line_modes_report_where_each_line_was_seen() {
+    for (args, analysis_type) in [(&[][..], "adjacent_lines"), (&["--global"], "lines")] {
+        let doc = document(args);
+        assert_eq!(keys(&doc), ["analysis_type", "items", "total_items"]);
+        assert_eq!(doc["analysis_type"], analysis_type);
+        assert_eq!(doc["items"][0], serde_json::json!({ "content": "go on", "count": 6, "first_line": 1, "last_line": 6 }));
+        
+        assert_eq!(records(args), doc["items"].as_array().unwrap().clone());
+    }
+}
+
+#[test]
+fn token_analyses_list_items() {
+    for args in [&["--word-analysis"][..], &["--phrase-analysis"]] {
+        let doc = document(args);
+        assert_eq!(keys(&doc), ["analysis_type", "items", "total_items"]);
+        assert_eq!(keys(&doc["items"][0]), ["content", "count"]);
+        assert_eq!(records(args), doc["items"].as_array().unwrap().clone());
+    }
+}
+
+#[test]
+fn loops_carry_the_thresholds_that_flagged_them() {
+    let doc = document(&["--detect-loops"]);
+    assert_eq!(keys(&doc), ["analysis_type", "loops", "loops_detected", "thresholds"]);
+    assert_eq!(doc["thresholds"], serde_json::json!({ "ExactRepeat": 5, "PhraseLoop": 5, "WordLoop": 10, "PatternLoop": 5, "LineRun": 5, "PhraseRun": 5, "CharLoop": 5 }));
+    assert_eq!(keys(&doc["loops"][0]), ["count", "first_line", "last_line", "pattern", "position_unit", "positions", "severity", "threshold", "type"]);
+    
+    let loops = records(&["--detect-loops"]);
+    assert_eq!(loops.len(), doc["loops_detected"].as_u64().unwrap() as usize);
+    assert_eq!(loops[0], doc["loops"][0]);
+}
+
+#[test]
+fn stats_explain_the_overall_risk() {
+    let doc = document(&["--stats"]);
+    assert_eq!(keys(&doc), ["compression", "line_stats", "overall_risk", "risk_score", "thresholds", "word_stats"]);
+    assert_eq!(keys(&doc["line_stats"]), ["avg_repetition", "duplicate_items", "entropy", "max_repetition", "repetition_ratio", "total_items", "unique_items"]);
+    assert_eq!(keys(&doc["compression"]), ["compressed_bytes", "original_bytes", "ratio", "tail_bytes", "tail_compressed_bytes", "tail_fraction", "tail_ratio"]);
+    assert_eq!(keys(&doc["thresholds"]), ["compression_min_bytes", "compression_ratio_below", "line_entropy_below", "line_max_repetition", "risk_score", "word_max_repetition"]);
+    
+    let stats = records(&["--stats"]);
+    let scopes: Vec<&str> = stats.iter().map(|record| record["scope"].as_str().unwrap()).collect();
+    assert_eq!(scopes, ["lines", "words", "compression", "overall"]);
+    assert_eq!(stats[0]["max_repetition"], doc["line_stats"]["max_repetition"]);
+    assert_eq!(stats[2]["ratio"], doc["compression"]["ratio"]);
+    assert_eq!(stats[3]["thresholds"], doc["thresholds"]);
+}
+
+#[test]
+fn documents_list_clusters_of_near_duplicates() {
+    let doc = document(&["--docs"]);
+    assert_eq!(keys(&doc), ["analysis_type", "clusters", "num_hashes", "shingle_size", "threshold", "total_documents", "unique_documents"]);
+    assert_eq!((doc["total_documents"].as_u64(), doc["unique_documents"].as_u64()), (Some(1), Some(1)));
+    assert!(records(&["--docs"]).is_empty());
+}
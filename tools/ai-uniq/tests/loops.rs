@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/loops").join(name).to_str().unwrap().to_string()
+}
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .arg("--detect-loops")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn loops(args: &[&str], input: &str) -> Vec<serde_json::Value> {
+    let doc: serde_json::Value = serde_json::from_str(&run(&[args, &["--format", "json"]].concat(), input)).unwrap();
+    doc["loops"].as_array().unwrap().clone()
+}
+
+#[test]
+fn a_loop_at_the_end_is_a_critical_run() {
+    // Six lines of answer, then two lines repeated 22 times
+    let found = loops(&[&fixture("loop-tail.txt")], "");
+    assert_eq!(
+        found[0],
+        serde_json::json!({
+            "type": "LineRun", "severity": "Critical", "count": 22, "period": 2,
+            "pattern": "Let me try again. / I apologize for the confusion.",
+            "first_line": 7, "last_line": 50, "threshold": 5, "position_unit": "line", "positions": [],
+        })
+    );
+    // Repeats of the run's lines and words are reported once counted, not again as runs
+    assert_eq!(found.iter().filter(|found| found["period"].is_number()).count(), 1);
+    
+    let text = run(&[&fixture("loop-tail.txt")], "");
+    assert!(text.contains("(22x)\n    back-to-back every 2 lines\n    first on line 7, last on line 50\n"), "{}", text);
+}
+
+#[test]
+fn a_repetitive_list_is_not_critical() {
+    // "the" appears 53 times and "Add the ... to the basket." 25 times, but never back-to-back
+    let found = loops(&[&fixture("benign-list.txt")], "");
+    assert!(!found.is_empty());
+    assert!(found.iter().all(|found| found["severity"] == "Low"), "{:?}", found);
+    assert!(!run(&[&fixture("benign-list.txt")], "").contains("CRITICAL"));
+}
+
+#[test]
+fn severity_follows_the_longest_run_not_the_total() {
+    let spread = "However, one.\nA fact.\nHowever, two.\nA fact.\nHowever, three.\nA fact.\nHowever, four.\nA fact.\nHowever, five.\n";
+    let in_a_row = "However, one.\nHowever, two.\nHowever, three.\nHowever, four.\nHowever, five.\n";
+    let severity = |input: &str| {
+        let found = loops(&[], input);
+        let pattern = found.iter().find(|found| found["type"] == "PatternLoop").unwrap();
+        (pattern["count"].as_u64(), pattern["severity"].as_str().map(str::to_string))
+    };
+    assert_eq!(severity(spread), (Some(5), Some("Low".to_string())));
+    assert_eq!(severity(in_a_row), (Some(5), Some("Medium".to_string())));
+}
+
+#[test]
+fn phrases_repeating_within_a_line_are_runs() {
+    let found = loops(&["--repetition-threshold", "3"], "Sorry. I will fix it. I will fix it. I will fix it. I will fix it.\n");
+    let run = found.iter().find(|found| found["type"] == "PhraseRun").unwrap();
+    assert_eq!((run["pattern"].as_str(), run["count"].as_u64(), run["period"].as_u64()), (Some("I will fix it"), Some(4), Some(4)));
+}
+
+#[test]
+fn tail_window_only_scans_the_last_lines() {
+    let found = loops(&["--tail-window", "10", &fixture("loop-tail.txt")], "");
+    assert_eq!((found[0]["type"].as_str(), found[0]["count"].as_u64()), (Some("LineRun"), Some(5)));
+    assert_eq!((found[0]["first_line"].as_u64(), found[0]["last_line"].as_u64()), (Some(41), Some(50)));
+    
+    let exact = found.iter().find(|found| found["type"] == "ExactRepeat").unwrap();
+    assert_eq!(exact["positions"], serde_json::json!([41, 43, 45, 47, 49]));
+    
+    // The intro's repeated words are out of the window
+    let input = format!("{}stuck\nstuck\nstuck\n", "filler\n".repeat(30));
+    assert!(loops(&["--tail-window", "5", "--repetition-threshold", "3"], &input).iter().all(|found| found["pattern"] != "filler"));
+}
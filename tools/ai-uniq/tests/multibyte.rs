@@ -0,0 +1,51 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn uniq(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn skip_and_check_count_characters_not_bytes() {
+    // Each emoji is four bytes, so byte offsets would land inside one
+    let emoji = "🙂🙂🙂abc\n😀😀😀abc\néé xyz\n";
+    assert_eq!(uniq(&["-c", "-s", "3"], emoji), "      2 🙂🙂🙂abc\n      1 éé xyz\n");
+    assert_eq!(uniq(&["--global", "-c", "-s", "3"], emoji), "       2 🙂🙂🙂abc\n       1 éé xyz\n");
+    assert_eq!(uniq(&["-c", "-w", "4"], "café one\ncafé two\ncafe\n"), "      2 café one\n      1 cafe\n");
+}
+
+#[test]
+fn combining_marks_are_characters_of_their_own() {
+    // "e" + U+0301 and "a" + U+0301 agree once the base letter is skipped
+    assert_eq!(uniq(&["-c", "-s", "1"], "e\u{301}x\na\u{301}x\n\u{e9}x\n"), "      2 e\u{301}x\n      1 \u{e9}x\n");
+    assert_eq!(uniq(&["-w", "1"], "e\u{301}\ne\n"), "e\u{301}\n");
+}
+
+#[test]
+fn bytes_flag_cuts_inside_characters() {
+    // é and è share their first byte
+    assert_eq!(uniq(&["-c", "--bytes", "-w", "3"], "ééa\néèa\n"), "      2 ééa\n");
+    assert_eq!(uniq(&["-c", "-w", "2"], "ééa\néèa\n"), "      1 ééa\n      1 éèa\n");
+}
+
+#[test]
+fn fields_keep_their_leading_blanks_and_tabs_are_blanks() {
+    let input = "a\tx same\nb\t same\nc same\n";
+    assert_eq!(uniq(&["-c", "-f", "2"], input), "      1 a\tx same\n      2 b\t same\n");
+    assert_eq!(uniq(&["--global", "-c", "-f", "1", "-s", "1"], "1\tone\n2 one\n3  one\n"), "       2 1\tone\n       1 3  one\n");
+}
+
+#[test]
+fn ignore_case_folds_after_skipping() {
+    assert_eq!(uniq(&["-i", "-c", "-s", "1"], "Ñandú\nñANDÚ\n"), "      2 Ñandú\n");
+}
@@ -0,0 +1,81 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const INPUT: &str = "I will fix the bug now. Then I will fix the bug now.\nLater I will fix the bug now. The bug is fixed.\n";
+
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A rejected argument can exit before reading its input
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], input: &str) -> String {
+    let output = run(args, input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn a_range_reports_each_size_and_folds_contained_phrases() {
+    assert_eq!(
+        stdout(&["--phrase-analysis", "--ngram-size", "2-5", "-d", "-c"], INPUT),
+        "=== 2-gram Phrase Analysis ===\n\
+         === 3-gram Phrase Analysis ===\n\
+         === 4-gram Phrase Analysis ===\n\
+         === 5-gram Phrase Analysis ===\n       \
+         3 I will fix the bug\n       \
+         3 will fix the bug now\n\
+         \n\
+         Most suspicious phrases:\n  \
+         [LOW] \"I will fix the bug\" (5-gram, 3x)\n  \
+         [LOW] \"will fix the bug now\" (5-gram, 3x)\n"
+    );
+}
+
+#[test]
+fn no_collapse_keeps_contained_phrases() {
+    let text = stdout(&["--phrase-analysis", "--ngram-size", "2", "--ngram-size", "4", "-d", "-c", "--no-collapse"], INPUT);
+    assert!(text.starts_with("=== 2-gram Phrase Analysis ===\n       3 I will\n       3 will fix\n"), "{}", text);
+    assert!(text.contains("=== 4-gram Phrase Analysis ===\n       3 I will fix the\n"), "{}", text);
+}
+
+#[test]
+fn longer_phrases_rank_higher_at_the_same_count() {
+    // "the bug" also ends the text, so it is counted 4 times and survives collapsing
+    let input = format!("{}And the bug.\n", INPUT);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&["--phrase-analysis", "--ngram-size", "2-3", "-d", "--format", "json"], &input)).unwrap();
+    assert_eq!(json["ngram_sizes"], serde_json::json!([2, 3]));
+    assert_eq!(json["sizes"]["2"]["items"], serde_json::json!([{ "content": "the bug", "count": 4, "ngram_size": 2 }]));
+    assert_eq!(json["sizes"]["3"]["total_items"], 4);
+    
+    // Three repeats of a 3-gram waste 6 words, four of a 2-gram also 6: the longer goes first
+    let ranked: Vec<(&str, u64)> = json["most_suspicious"].as_array().unwrap().iter()
+        .map(|item| (item["content"].as_str().unwrap(), item["ngram_size"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(ranked[..2], [("I will fix", 3), ("will fix the", 3)]);
+    assert_eq!(ranked.last(), Some(&("the bug", 2)));
+}
+
+#[test]
+fn streaming_counts_every_size_the_same() {
+    for args in [&["--ngram-size", "1-4"][..], &["--ngram-size", "2", "--ngram-size", "5", "--show-positions"]] {
+        let args = [&["--phrase-analysis", "-c"][..], args].concat();
+        let exact = stdout(&args, INPUT);
+        assert_eq!(stdout(&[&args[..], &["--approximate"]].concat(), INPUT), exact);
+    }
+}
+
+#[test]
+fn sizes_must_be_a_nonempty_range() {
+    for size in ["0", "4-2", "two"] {
+        let output = run(&["--phrase-analysis", "--ngram-size", size], INPUT);
+        assert!(!output.status.success(), "{}", size);
+    }
+}
@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(args: &[&str]) -> Vec<u8> {
+    let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/order/shuffled.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .arg(fixture)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+fn lines(args: &[&str]) -> Vec<String> {
+    String::from_utf8(run(args)).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn repeated_runs_give_identical_output() {
+    for args in [&["--global", "-c"][..], &["--sort-freq"], &["--word-analysis", "--sort-freq"], &["--phrase-analysis", "--ngram-size", "2"]] {
+        assert_eq!(run(args), run(args), "{:?}", args);
+    }
+}
+
+#[test]
+fn lines_come_out_in_order_of_first_appearance() {
+    assert_eq!(lines(&["--global"]), ["delta", "alpha", "charlie", "bravo", "echo", "10", "9"]);
+    assert_eq!(lines(&["--global", "-d"]), ["delta", "alpha", "charlie", "bravo", "echo"]);
+}
+
+#[test]
+fn sorts_break_ties_by_first_appearance() {
+    assert_eq!(lines(&["--sort-freq"]), ["alpha", "delta", "charlie", "bravo", "echo", "10", "9"]);
+    assert_eq!(lines(&["--sort-freq", "-r"]), ["10", "9", "delta", "charlie", "bravo", "echo", "alpha"]);
+    // Non-numeric lines all sort as 0, keeping their first-seen order ahead of 9 and 10
+    assert_eq!(lines(&["--numeric-sort"]), ["delta", "alpha", "charlie", "bravo", "echo", "9", "10"]);
+}
@@ -0,0 +1,54 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Words 1-3, 4-7, 8 and 9-11 on lines 1 to 4.
+const INPUT: &str = "the cat sat\nthe cat sat again\nno\nthe cat sat\n";
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn phrases_show_lines_and_word_offsets() {
+    let text = run(&["--phrase-analysis", "--show-positions", "--min-count", "2"], INPUT);
+    assert_eq!(text, "=== 3-gram Phrase Analysis ===\nthe cat sat\n    at line:word 1:1, 2:4, 4:9\n");
+    
+    let json: serde_json::Value = serde_json::from_str(&run(&["--phrase-analysis", "--show-positions", "--format", "json"], INPUT)).unwrap();
+    assert_eq!(json["items"][0]["lines"], serde_json::json!([1, 2, 4]));
+    assert_eq!(json["items"][0]["word_offsets"], serde_json::json!([1, 4, 9]));
+    
+    // Streaming counts record the same positions
+    assert_eq!(run(&["--phrase-analysis", "--show-positions", "--min-count", "2", "--approximate"], INPUT), text);
+}
+
+#[test]
+fn positions_are_capped_per_item() {
+    let input = "again\n".repeat(8);
+    assert_eq!(run(&["--show-positions", "-c"], &input), "       8 again\n    at line 1, 2, 3, 4, 5 (+3 more)\n");
+    
+    let json: serde_json::Value = serde_json::from_str(&run(&["--word-analysis", "--show-positions", "--max-positions", "3", "--format", "json"], &input)).unwrap();
+    assert_eq!(json["items"][0]["count"], 8);
+    assert_eq!(json["items"][0]["lines"], serde_json::json!([1, 2, 3]));
+}
+
+#[test]
+fn loops_report_their_first_and_last_lines() {
+    let input = format!("intro\n{}outro\n", "stuck again\n".repeat(6));
+    let json: serde_json::Value = serde_json::from_str(&run(&["--detect-loops", "--max-positions", "2", "--format", "json"], &input)).unwrap();
+    let exact = &json["loops"][0];
+    assert_eq!((exact["type"].as_str(), exact["first_line"].as_u64(), exact["last_line"].as_u64()), (Some("ExactRepeat"), Some(2), Some(7)));
+    assert_eq!(exact["positions"], serde_json::json!([2, 3]));
+    
+    let text = run(&["--detect-loops"], &input);
+    assert!(text.contains("\"stuck again\" (6x)\n    at line 2, 3, 4, 5, 6 (+1 more)\n    first on line 2, last on line 7\n"), "{}", text);
+}
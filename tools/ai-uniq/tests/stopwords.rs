@@ -0,0 +1,61 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = "The cache and the queue.\nThe cache is full of the old data.\nClear the cache.\n";
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn english_stopwords_are_left_out_by_default() {
+    let args = ["--word-analysis", "--sort-freq", "-c", "-i"];
+    assert_eq!(
+        run(&args, INPUT),
+        "=== Word Frequency Analysis ===\n       3 cache\n       1 queue\n       1 full\n       1 old\n       1 data\n       1 clear\n"
+    );
+    
+    let all = run(&[&args[..], &["--no-stopwords"]].concat(), INPUT);
+    assert!(all.starts_with("=== Word Frequency Analysis ===\n       5 the\n       3 cache\n       1 and\n"), "{}", all);
+    
+    // Streaming filters the same words
+    assert_eq!(run(&[&args[..], &["--approximate"]].concat(), INPUT), run(&args, INPUT));
+}
+
+#[test]
+fn a_custom_list_and_minimum_length() {
+    let list = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("stopwords.txt");
+    fs::write(&list, "# project words\nCache\n\nthe\n").unwrap();
+    let text = run(&["--word-analysis", "--sort-freq", "-c", "-i", "--stopwords", list.to_str().unwrap(), "--min-word-length", "4"], INPUT);
+    assert_eq!(text, "=== Word Frequency Analysis ===\n       1 queue\n       1 full\n       1 data\n       1 clear\n");
+}
+
+#[test]
+fn common_words_do_not_make_word_loops() {
+    let input: String = (0..30).map(|i| format!("Step {i} updates the index and the cache.\n")).collect();
+    let json: serde_json::Value = serde_json::from_str(&run(&["--detect-loops", "--format", "json"], &input)).unwrap();
+    let words: Vec<&str> = json["loops"].as_array().unwrap().iter()
+        .filter(|found| found["type"] == "WordLoop")
+        .map(|found| found["pattern"].as_str().unwrap())
+        .collect();
+    assert_eq!(words, ["Step", "updates", "index", "cache"]);
+    
+    let json: serde_json::Value = serde_json::from_str(&run(&["--detect-loops", "--no-stopwords", "--format", "json"], &input)).unwrap();
+    let the = json["loops"].as_array().unwrap().iter()
+        .find(|found| found["type"] == "WordLoop" && found["pattern"] == "the")
+        .unwrap();
+    assert_eq!(the["count"], 60);
+    assert_eq!(the["severity"], "Low");
+}
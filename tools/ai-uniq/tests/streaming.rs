@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(files: &[(&str, &str)], args: &[&str]) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("streaming");
+    fs::create_dir_all(&dir).unwrap();
+    for (name, text) in files {
+        fs::write(dir.join(name), text).unwrap();
+    }
+    
+    Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .current_dir(&dir)
+        .args(args)
+        .args(files.iter().map(|(name, _)| name))
+        .output()
+        .unwrap()
+}
+
+fn stdout(files: &[(&str, &str)], args: &[&str]) -> String {
+    let output = run(files, args);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const ANSWER: &[(&str, &str)] = &[("answer.txt", "the cat the dog\nthe end the cat\n")];
+
+#[test]
+fn approximate_counts_match_exact_ones_under_the_cap() {
+    for mode in ["--word-analysis", "--phrase-analysis"] {
+        let exact = stdout(ANSWER, &[mode, "-c", "--ngram-size", "2"]);
+        assert_eq!(stdout(ANSWER, &[mode, "-c", "--ngram-size", "2", "--approximate"]), exact);
+    }
+}
+
+#[test]
+fn approximate_keeps_the_most_frequent_within_the_cap() {
+    let output = run(ANSWER, &["--word-analysis", "-c", "--no-stopwords", "--approximate", "--max-tracked", "2"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("more than 2 distinct words"));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "=== Word Frequency Analysis ===\n       4 the\n       2 cat\n");
+}
+
+#[test]
+fn approximate_needs_a_token_analysis() {
+    let output = run(ANSWER, &["--approximate"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--word-analysis"));
+}
+
+#[test]
+fn files_are_joined_without_gluing_or_blank_lines() {
+    // The middle file has no trailing newline; line numbers must still run 1..=4
+    let files = [("a.txt", "go\n"), ("b.txt", "go\ngo"), ("c.txt", "go\n")];
+    let doc: serde_json::Value = serde_json::from_str(&stdout(&files, &["--detect-loops", "--repetition-threshold", "3", "--format", "json"])).unwrap();
+    assert_eq!(doc["loops"][0]["count"], 4);
+    assert_eq!(doc["loops"][0]["positions"], serde_json::json!([1, 2, 3, 4]));
+}
@@ -0,0 +1,57 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = "go on\ngo on\ngo on\ngo on\ngo on\ngo on\ndone\n";
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .arg("--color=never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn document(args: &[&str], input: &str) -> serde_json::Value {
+    serde_json::from_str(&run(&[args, &["--format", "json"]].concat(), input)).unwrap()
+}
+
+#[test]
+fn ratio_scales_with_the_input() {
+    let text = run(&["--word-analysis", "--threshold-ratio"], INPUT);
+    assert!(text.ends_with("FLAGGED: Words making up over 5.0% of words:\n  [CRITICAL] go (6x, 85.7%)\n"), "{}", text);
+    
+    // The same six repeats are nothing in a longer text
+    let input = format!("{INPUT}{}", (0..200).map(|i| format!("word{i}\n")).collect::<String>());
+    assert!(!run(&["--word-analysis", "--threshold-ratio"], &input).contains("FLAGGED"));
+    assert!(run(&["--word-analysis", "--above-threshold"], &input).contains("FLAGGED: Words appearing ≥5 times:\n  [MEDIUM] go (6x)\n"));
+}
+
+#[test]
+fn mode_combines_both_thresholds() {
+    // 6 of 7 lines passes the ratio but not a count of 7
+    let flagged = |args: &[&str]| document(&[&["--stats", "--threshold-ratio=0.5", "--repetition-threshold", "7"], args].concat(), INPUT)
+        ["line_stats"]["flagged"].as_array().unwrap().len();
+    assert_eq!(flagged(&[]), 0);
+    assert_eq!(flagged(&["--threshold-mode", "any"]), 1);
+}
+
+#[test]
+fn stats_report_counts_and_ratios() {
+    let doc = document(&["--stats", "--threshold-ratio"], INPUT);
+    assert_eq!(doc["line_stats"]["flag_threshold"], serde_json::json!({ "count": null, "ratio": 0.1, "mode": "all" }));
+    let flagged = &doc["line_stats"]["flagged"][0];
+    assert_eq!((flagged["content"].as_str(), flagged["count"].as_u64()), (Some("go on"), Some(6)));
+    assert!((flagged["ratio"].as_f64().unwrap() - 6.0 / 7.0).abs() < 1e-9);
+    // Over four times the ratio grades like 20 repeats
+    assert_eq!(flagged["severity"], "Critical");
+    
+    let text = run(&["--stats", "--above-threshold"], INPUT);
+    assert!(text.contains("  Flagged (appearing ≥5 times):\n  [MEDIUM] go on (6x, 85.7%)\n"), "{}", text);
+}
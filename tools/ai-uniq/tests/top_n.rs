@@ -0,0 +1,57 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = "go on\ngo on\ngo on\ngo on\ngo on\ngo on\ndone\n";
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(args)
+        .arg("--color=never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn document(args: &[&str], input: &str) -> serde_json::Value {
+    serde_json::from_str(&run(&[args, &["--format", "json"]].concat(), input)).unwrap()
+}
+
+#[test]
+fn loops_show_the_most_severe() {
+    let all = document(&["--detect-loops"], INPUT);
+    let doc = document(&["--detect-loops", "--top-n", "2"], INPUT);
+    assert_eq!(doc["loops_detected"], 4);
+    assert_eq!(doc["loops"].as_array().unwrap()[..], all["loops"].as_array().unwrap()[..2]);
+    
+    let text = run(&["--detect-loops", "--top-n", "2"], INPUT);
+    assert!(text.contains("4 potential loops detected:"), "{}", text);
+    assert!(text.ends_with("first on line 1, last on line 6\n(+2 more loops; --top-n 0 shows all)\n"), "{}", text);
+}
+
+#[test]
+fn stats_list_the_most_repeated_when_asked() {
+    let doc = document(&["--stats"], INPUT);
+    assert!(doc["line_stats"].get("most_repeated").is_none());
+    
+    let doc = document(&["--stats", "--top-n", "1"], INPUT);
+    assert_eq!(doc["line_stats"]["most_repeated"], serde_json::json!([{ "content": "go on", "count": 6 }]));
+    assert_eq!(doc["word_stats"]["most_repeated"], serde_json::json!([{ "content": "go", "count": 6 }]));
+    
+    let text = run(&["--stats", "--top-n", "5"], INPUT);
+    assert!(text.contains("  Shannon entropy: 0.59\n  Most repeated:\n         6 go on\n\nWords:"), "{}", text);
+    assert!(text.contains("  Most repeated:\n         6 go\n         6 on\n\nCompression:"), "{}", text);
+}
+
+#[test]
+fn zero_shows_everything() {
+    let input: String = (0..30).map(|i| format!("word{i}\n")).collect();
+    let items = |args: &[&str]| document(args, &input)["items"].as_array().unwrap().len();
+    assert_eq!(items(&["--word-analysis"]), 20);
+    assert_eq!(items(&["--word-analysis", "--top-n", "0"]), 30);
+}
@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Word counts of the fixture, by word.
+fn counts(args: &[&str]) -> BTreeMap<String, u64> {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-uniq"))
+        .args(["--word-analysis", "-i", "--top-n", "0", "--format", "json"])
+        .args(args)
+        .arg(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/unicode/smart.txt"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    json["items"].as_array().unwrap().iter()
+        .map(|item| (item["content"].as_str().unwrap().to_string(), item["count"].as_u64().unwrap()))
+        .collect()
+}
+
+#[test]
+fn curly_and_straight_apostrophes_count_together() {
+    for args in [&["--no-stopwords"][..], &["--no-stopwords", "--unicode-normalize"]] {
+        let counts = counts(args);
+        assert_eq!(counts["dont"], 2, "{:?}", args);
+        assert_eq!(counts["isnt"], 1, "{:?}", args);
+        assert!(!counts.keys().any(|word| word.contains(['\'', '\u{2019}'])), "{:?}: {:?}", args, counts);
+    }
+}
+
+#[test]
+fn ascii_contractions_are_stopwords_either_way() {
+    // "dont" and "isnt" are stopwords; the flag must not let "don't" slip past them
+    for args in [&[][..], &["--unicode-normalize"]] {
+        let counts = counts(args);
+        assert!(!counts.contains_key("dont") && !counts.contains_key("isnt"), "{:?}: {:?}", args, counts);
+    }
+}
+
+#[test]
+fn accented_duplicates_merge_with_strip_diacritics() {
+    let plain = counts(&[]);
+    assert_eq!((plain["naïve"], plain["naive"]), (1, 1));
+    
+    let stripped = counts(&["--strip-diacritics"]);
+    assert_eq!(stripped["naive"], 2);
+    assert!(!stripped.contains_key("naïve"));
+}
+
+#[test]
+fn nfkc_folds_ligatures() {
+    let plain = counts(&[]);
+    assert_eq!((plain["\u{FB01}le"], plain["file"]), (1, 1));
+    
+    let normalized = counts(&["--unicode-normalize"]);
+    assert_eq!(normalized["file"], 2);
+    assert!(!normalized.contains_key("\u{FB01}le"));
+}
@@ -1,9 +1,13 @@
+use ai_toolkit_core::{Categories, Category, Scanner};
 use clap::Parser;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{self, BufRead, Read};
+use std::io::{self, Read};
 use std::path::PathBuf;
+use std::sync::LazyLock;
 use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Parser, Debug)]
 #[command(name = "ai-wc")]
@@ -28,70 +32,475 @@ struct Args {
     #[arg(long)]
     confidence_markers: bool,
     
+    /// Score readability: Flesch Reading Ease and Flesch-Kincaid grade level
+    #[arg(long)]
+    readability: bool,
+    
     /// Show all AI-specific metrics
     #[arg(long)]
     ai_metrics: bool,
     
+    /// Count confidence and hallucination markers per pattern (always included in JSON);
+    /// shows both marker counts unless one of them was asked for
+    #[arg(long, conflicts_with = "traditional")]
+    breakdown: bool,
+    
     /// Traditional word count (lines, words, chars)
     #[arg(short, long)]
     traditional: bool,
+    
+    /// Count bytes, like wc -c
+    #[arg(short = 'c', long)]
+    bytes: bool,
+    
+    /// Count characters as Unicode scalar values, like wc -m (the default count)
+    #[arg(short = 'm', long)]
+    chars: bool,
+    
+    /// Count user-perceived characters (grapheme clusters), so an emoji with a skin tone
+    /// or a letter with combining accents counts once
+    #[arg(long)]
+    graphemes: bool,
+    
+    /// Output format: text, or JSON with every metric regardless of the flags above
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl Args {
+    fn shows_confidence(&self) -> bool {
+        self.confidence_markers || self.ai_metrics || self.breakdown_alone()
+    }
+    
+    fn shows_hallucinations(&self) -> bool {
+        self.hallucination_markers || self.ai_metrics || self.breakdown_alone()
+    }
+    
+    fn breakdown_alone(&self) -> bool {
+        self.breakdown && !(self.confidence_markers || self.hallucination_markers || self.ai_metrics)
+    }
+    
+    fn shows_fact_density(&self) -> bool {
+        self.fact_density || self.ai_metrics
+    }
+    
+    fn shows_repetition(&self) -> bool {
+        self.repetition_score || self.ai_metrics
+    }
+    
+    /// The character counts to show, in wc's order; scalar values when none is asked for.
+    fn char_counts(&self) -> Vec<CharCount> {
+        let mut counts = Vec::new();
+        if self.chars || !(self.bytes || self.graphemes) {
+            counts.push(CharCount::Chars);
+        }
+        if self.bytes {
+            counts.push(CharCount::Bytes);
+        }
+        if self.graphemes {
+            counts.push(CharCount::Graphemes);
+        }
+        counts
+    }
+}
+
+#[derive(Clone, Copy)]
+enum CharCount {
+    Chars,
+    Bytes,
+    Graphemes,
+}
+
+impl CharCount {
+    fn column(self) -> &'static str {
+        match self {
+            CharCount::Chars => "chars",
+            CharCount::Bytes => "bytes",
+            CharCount::Graphemes => "graphemes",
+        }
+    }
+    
+    fn of(self, metrics: &TextMetrics) -> usize {
+        match self {
+            CharCount::Chars => metrics.chars,
+            CharCount::Bytes => metrics.bytes,
+            CharCount::Graphemes => metrics.graphemes,
+        }
+    }
 }
 
 struct TextMetrics {
     lines: usize,
     words: usize,
     chars: usize,
+    bytes: usize,
+    graphemes: usize,
     confidence_markers: usize,
     hallucination_indicators: usize,
+    // How often each pattern matched; patterns that never matched are left out
+    confidence_breakdown: HashMap<String, usize>,
+    hallucination_breakdown: HashMap<String, usize>,
     fact_density: f64,
     repetition_score: f64,
+    reading_ease: f64,
+    grade_level: f64,
+    // Counts behind the ratios and scores, so totals can be computed over all files
+    facts: usize,
+    paragraphs: usize,
+    repeated_phrases: usize,
+    distinct_phrases: usize,
+    sentences: usize,
+    syllables: usize,
+}
+
+impl TextMetrics {
+    /// Sums the counts of several files. The ratios are recomputed from the summed counts,
+    /// so fact density is per paragraph across all files and each file's phrases are
+    /// weighted by how many it has.
+    fn total<'a>(files: impl IntoIterator<Item = &'a TextMetrics>) -> TextMetrics {
+        let mut total = TextMetrics {
+            lines: 0,
+            words: 0,
+            chars: 0,
+            bytes: 0,
+            graphemes: 0,
+            confidence_markers: 0,
+            hallucination_indicators: 0,
+            confidence_breakdown: HashMap::new(),
+            hallucination_breakdown: HashMap::new(),
+            fact_density: 0.0,
+            repetition_score: 0.0,
+            reading_ease: 0.0,
+            grade_level: 0.0,
+            facts: 0,
+            paragraphs: 0,
+            repeated_phrases: 0,
+            distinct_phrases: 0,
+            sentences: 0,
+            syllables: 0,
+        };
+        for metrics in files {
+            total.lines += metrics.lines;
+            total.words += metrics.words;
+            total.chars += metrics.chars;
+            total.bytes += metrics.bytes;
+            total.graphemes += metrics.graphemes;
+            total.confidence_markers += metrics.confidence_markers;
+            total.hallucination_indicators += metrics.hallucination_indicators;
+            for (pattern, &count) in &metrics.confidence_breakdown {
+                *total.confidence_breakdown.entry(pattern.clone()).or_insert(0) += count;
+            }
+            for (pattern, &count) in &metrics.hallucination_breakdown {
+                *total.hallucination_breakdown.entry(pattern.clone()).or_insert(0) += count;
+            }
+            total.facts += metrics.facts;
+            total.paragraphs += metrics.paragraphs;
+            total.repeated_phrases += metrics.repeated_phrases;
+            total.distinct_phrases += metrics.distinct_phrases;
+            total.sentences += metrics.sentences;
+            total.syllables += metrics.syllables;
+        }
+        total.fact_density = ratio(total.facts, total.paragraphs);
+        total.repetition_score = ratio(total.repeated_phrases, total.distinct_phrases);
+        (total.reading_ease, total.grade_level) = readability(total.words, total.sentences, total.syllables);
+        total
+    }
 }
 
 fn main() {
     let args = Args::parse();
     
-    let input_text = if args.files.is_empty() {
-        read_stdin()
-    } else {
-        args.files.iter()
-            .map(|f| fs::read_to_string(f).unwrap_or_else(|_| String::new()))
-            .collect::<Vec<_>>()
-            .join("\n")
-    };
+    if args.files.is_empty() {
+        let metrics = analyze_text(&read_stdin());
+        match args.format {
+            OutputFormat::Text => print_block(&metrics, &args),
+            OutputFormat::Json => print_json(&MetricsJson::new(None, &metrics)),
+        }
+        return;
+    }
     
-    let metrics = analyze_text(&input_text);
+    // Like wc, a file that can't be read is reported and skipped, and the exit status says so
+    let mut failed = false;
+    let mut rows = Vec::new();
+    for path in &args.files {
+        match fs::read_to_string(path) {
+            Ok(text) => rows.push((path.display().to_string(), analyze_text(&text))),
+            Err(e) => {
+                eprintln!("ai-wc: {}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
     
+    let total = (args.files.len() > 1).then(|| TextMetrics::total(rows.iter().map(|(_, metrics)| metrics)));
+    match args.format {
+        OutputFormat::Text => print_table(&rows, total.as_ref(), &args),
+        OutputFormat::Json => print_json(&FilesJson {
+            files: rows.iter().map(|(name, metrics)| MetricsJson::new(Some(name), metrics)).collect(),
+            total: total.as_ref().map(|metrics| MetricsJson::new(None, metrics)),
+        }),
+    }
+    
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Prints the metrics of a single input (stdin) as one labelled block.
+fn print_block(metrics: &TextMetrics, args: &Args) {
     if args.traditional {
-        println!("{:8} {:8} {:8}", metrics.lines, metrics.words, metrics.chars);
+        let counts: String = args.char_counts().iter().map(|count| format!(" {:8}", count.of(metrics))).collect();
+        println!("{:8} {:8}{}", metrics.lines, metrics.words, counts);
         return;
     }
     
     // Enhanced AI-specific output
     println!("Lines: {}", metrics.lines);
     println!("Words: {}", metrics.words);
-    println!("Chars: {}", metrics.chars);
+    for count in args.char_counts() {
+        match count {
+            CharCount::Chars => println!("Chars: {} (Unicode scalar values)", metrics.chars),
+            CharCount::Bytes => println!("Bytes: {}", metrics.bytes),
+            CharCount::Graphemes => println!("Graphemes: {} (user-perceived characters)", metrics.graphemes),
+        }
+    }
     
-    if args.confidence_markers || args.ai_metrics {
+    if args.shows_confidence() {
         println!("AI Confidence markers: {} (\"likely\", \"probably\", \"might\")", metrics.confidence_markers);
+        if args.breakdown {
+            print_breakdown(&metrics.confidence_breakdown);
+        }
     }
     
-    if args.hallucination_markers || args.ai_metrics {
+    if args.shows_hallucinations() {
         println!("Hallucination indicators: {}", metrics.hallucination_indicators);
+        if args.breakdown {
+            print_breakdown(&metrics.hallucination_breakdown);
+        }
     }
     
-    if args.fact_density || args.ai_metrics {
+    if args.shows_fact_density() {
         println!("Fact density: {:.2} facts/paragraph", metrics.fact_density);
     }
     
-    if args.repetition_score || args.ai_metrics {
-        let repetition_level = if metrics.repetition_score > 0.5 { 
-            "high - possible loop" 
-        } else if metrics.repetition_score > 0.3 { 
-            "medium" 
-        } else { 
-            "low" 
-        };
-        println!("Repetition score: {:.2} ({})", metrics.repetition_score, repetition_level);
+    if args.shows_repetition() {
+        println!("Repetition score: {:.2} ({})", metrics.repetition_score, repetition_level(metrics.repetition_score));
+    }
+    
+    if args.readability {
+        println!("Reading ease: {:.2} ({})", metrics.reading_ease, reading_ease_level(metrics.reading_ease));
+        println!("Grade level: {:.2}", metrics.grade_level);
+    }
+}
+
+/// Prints one row per file and the `total` row. Traditional rows are exactly wc's, with the
+/// name after the counts; otherwise the name comes first, under a header naming the columns.
+fn print_table(files: &[(String, TextMetrics)], total: Option<&TextMetrics>, args: &Args) {
+    let rows: Vec<(&str, &TextMetrics)> = files.iter()
+        .map(|(name, metrics)| (name.as_str(), metrics))
+        .chain(total.map(|metrics| ("total", metrics)))
+        .collect();
+    
+    let char_counts = args.char_counts();
+    if args.traditional {
+        for (name, metrics) in rows {
+            let counts: String = char_counts.iter().map(|count| format!(" {:8}", count.of(metrics))).collect();
+            println!("{:8} {:8}{} {}", metrics.lines, metrics.words, counts, name);
+        }
+        return;
+    }
+    
+    let width = rows.iter()
+        .map(|(name, _)| name.chars().count())
+        .fold("file".len(), usize::max);
+    
+    let mut header = format!("{:width$} {:>8} {:>8}", "file", "lines", "words");
+    for count in &char_counts {
+        header += &format!(" {:>8}", count.column());
+    }
+    if args.shows_confidence() {
+        header += &format!(" {:>10}", "confidence");
+    }
+    if args.shows_hallucinations() {
+        header += &format!(" {:>14}", "hallucinations");
+    }
+    if args.shows_fact_density() {
+        header += &format!(" {:>10}", "facts/para");
+    }
+    if args.readability {
+        header += &format!(" {:>8} {:>8}", "ease", "grade");
+    }
+    if args.shows_repetition() {
+        header += &format!(" {:>10}", "repetition");
+    }
+    println!("{}", header);
+    
+    for (name, metrics) in &rows {
+        let mut row = format!("{:width$} {:8} {:8}", name, metrics.lines, metrics.words);
+        for count in &char_counts {
+            row += &format!(" {:>w$}", count.of(metrics), w = count.column().len().max(8));
+        }
+        if args.shows_confidence() {
+            row += &format!(" {:10}", metrics.confidence_markers);
+        }
+        if args.shows_hallucinations() {
+            row += &format!(" {:14}", metrics.hallucination_indicators);
+        }
+        if args.shows_fact_density() {
+            row += &format!(" {:10.2}", metrics.fact_density);
+        }
+        if args.readability {
+            row += &format!(" {:8.2} {:8.2}", metrics.reading_ease, metrics.grade_level);
+        }
+        if args.shows_repetition() {
+            row += &format!(" {:10.2} ({})", metrics.repetition_score, repetition_level(metrics.repetition_score));
+        }
+        println!("{}", row);
+    }
+    
+    if args.breakdown {
+        if args.shows_confidence() {
+            println!("\nConfidence markers by pattern:");
+            for (name, metrics) in &rows {
+                println!("  {}: {}", name, breakdown_summary(&metrics.confidence_breakdown));
+            }
+        }
+        if args.shows_hallucinations() {
+            println!("\nHallucination indicators by pattern:");
+            for (name, metrics) in &rows {
+                println!("  {}: {}", name, breakdown_summary(&metrics.hallucination_breakdown));
+            }
+        }
+    }
+}
+
+/// Prints one indented `pattern: count` line per matched pattern, most frequent first.
+fn print_breakdown(breakdown: &HashMap<String, usize>) {
+    for (pattern, count) in sorted_breakdown(breakdown) {
+        println!("  {}: {}", pattern, count);
+    }
+}
+
+/// The matched patterns on one line, most frequent first, e.g. `as an ai 5, i cannot 2`.
+fn breakdown_summary(breakdown: &HashMap<String, usize>) -> String {
+    let patterns = sorted_breakdown(breakdown);
+    if patterns.is_empty() {
+        return "none".to_string();
+    }
+    patterns.iter()
+        .map(|(pattern, count)| format!("{} {}", pattern, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn sorted_breakdown(breakdown: &HashMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut patterns: Vec<(&str, usize)> = breakdown.iter().map(|(pattern, &count)| (pattern.as_str(), count)).collect();
+    patterns.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    patterns
+}
+
+/// The metrics of one input in --format json.
+#[derive(Serialize)]
+struct MetricsJson<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    lines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+    graphemes: usize,
+    confidence_markers: usize,
+    hallucination_indicators: usize,
+    confidence_breakdown: BTreeMap<&'a str, usize>,
+    hallucination_breakdown: BTreeMap<&'a str, usize>,
+    fact_density: f64,
+    repetition_score: f64,
+    repetition_level: &'static str,
+    sentences: usize,
+    syllables: usize,
+    reading_ease: f64,
+    reading_ease_level: &'static str,
+    grade_level: f64,
+}
+
+impl<'a> MetricsJson<'a> {
+    fn new(file: Option<&'a str>, metrics: &'a TextMetrics) -> Self {
+        MetricsJson {
+            file,
+            lines: metrics.lines,
+            words: metrics.words,
+            chars: metrics.chars,
+            bytes: metrics.bytes,
+            graphemes: metrics.graphemes,
+            confidence_markers: metrics.confidence_markers,
+            hallucination_indicators: metrics.hallucination_indicators,
+            confidence_breakdown: metrics.confidence_breakdown.iter().map(|(pattern, &count)| (pattern.as_str(), count)).collect(),
+            hallucination_breakdown: metrics.hallucination_breakdown.iter().map(|(pattern, &count)| (pattern.as_str(), count)).collect(),
+            fact_density: metrics.fact_density,
+            repetition_score: metrics.repetition_score,
+            repetition_level: repetition_level(metrics.repetition_score),
+            sentences: metrics.sentences,
+            syllables: metrics.syllables,
+            reading_ease: metrics.reading_ease,
+            reading_ease_level: reading_ease_level(metrics.reading_ease),
+            grade_level: metrics.grade_level,
+        }
+    }
+}
+
+/// --format json for file arguments: one entry per readable file, and the total when
+/// several files were given.
+#[derive(Serialize)]
+struct FilesJson<'a> {
+    files: Vec<MetricsJson<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<MetricsJson<'a>>,
+}
+
+fn print_json(value: &impl Serialize) {
+    println!("{}", serde_json::to_string_pretty(value).expect("metrics always serialize"));
+}
+
+fn repetition_level(score: f64) -> &'static str {
+    if score > 0.5 { 
+        "high - possible loop" 
+    } else if score > 0.3 { 
+        "medium" 
+    } else { 
+        "low" 
+    }
+}
+
+/// The usual bands of the Flesch Reading Ease scale.
+fn reading_ease_level(score: f64) -> &'static str {
+    if score >= 90.0 {
+        "very easy"
+    } else if score >= 80.0 {
+        "easy"
+    } else if score >= 70.0 {
+        "fairly easy"
+    } else if score >= 60.0 {
+        "standard"
+    } else if score >= 50.0 {
+        "fairly difficult"
+    } else if score >= 30.0 {
+        "difficult"
+    } else {
+        "very difficult"
+    }
+}
+
+fn ratio(count: usize, of: usize) -> f64 {
+    if of == 0 {
+        0.0
+    } else {
+        count as f64 / of as f64
     }
 }
 
@@ -105,54 +514,80 @@ fn analyze_text(text: &str) -> TextMetrics {
     let lines = text.lines().count();
     let words = text.split_whitespace().count();
     let chars = text.chars().count();
+    let bytes = text.len();
+    let graphemes = text.graphemes(true).count();
     
     // AI-specific analysis
-    let confidence_markers = count_confidence_markers(text);
-    let hallucination_indicators = count_hallucination_indicators(text);
-    let fact_density = calculate_fact_density(text);
-    let repetition_score = calculate_repetition_score(text);
+    let text_lower = text.to_lowercase();
+    let confidence_breakdown = count_markers(&text_lower, &CONFIDENCE_WORDS);
+    let hallucination_breakdown = count_hallucinations(text);
+    let (facts, paragraphs) = count_facts(text);
+    let (repeated_phrases, distinct_phrases) = count_phrases(text);
+    let sentences = count_sentences(text);
+    let syllables: usize = text.split_whitespace().map(count_syllables).sum();
+    let (reading_ease, grade_level) = readability(words, sentences, syllables);
     
     TextMetrics {
         lines,
         words,
         chars,
-        confidence_markers,
-        hallucination_indicators,
-        fact_density,
-        repetition_score,
+        bytes,
+        graphemes,
+        confidence_markers: confidence_breakdown.values().sum(),
+        hallucination_indicators: hallucination_breakdown.values().sum(),
+        confidence_breakdown,
+        hallucination_breakdown,
+        fact_density: ratio(facts, paragraphs),
+        repetition_score: ratio(repeated_phrases, distinct_phrases),
+        reading_ease,
+        grade_level,
+        facts,
+        paragraphs,
+        repeated_phrases,
+        distinct_phrases,
+        sentences,
+        syllables,
     }
 }
 
-fn count_confidence_markers(text: &str) -> usize {
-    let confidence_words = [
-        "likely", "probably", "might", "could", "perhaps", "possibly", 
-        "seems", "appears", "suggests", "indicates", "presumably", 
-        "allegedly", "supposedly", "apparently", "potentially"
-    ];
-    
-    let text_lower = text.to_lowercase();
-    confidence_words.iter()
-        .map(|&word| text_lower.matches(word).count())
-        .sum()
+const CONFIDENCE_WORDS: [&str; 15] = [
+    "likely", "probably", "might", "could", "perhaps", "possibly", 
+    "seems", "appears", "suggests", "indicates", "presumably", 
+    "allegedly", "supposedly", "apparently", "potentially"
+];
+
+/// Counts how often each pattern occurs in already lowercased text, keeping only the
+/// patterns that occur at all.
+fn count_markers(text_lower: &str, patterns: &[&str]) -> HashMap<String, usize> {
+    patterns.iter()
+        .map(|&pattern| (pattern.to_string(), text_lower.matches(pattern).count()))
+        .filter(|&(_, count)| count > 0)
+        .collect()
 }
 
-fn count_hallucination_indicators(text: &str) -> usize {
-    let hallucination_patterns = [
-        "as an ai", "i cannot", "i don't have access", "september 2021",
-        "knowledge cutoff", "i'm not able", "i cannot browse", "real-time",
-        "i don't know", "i'm uncertain", "i can't verify", "unverified"
-    ];
-    
-    let text_lower = text.to_lowercase();
-    hallucination_patterns.iter()
-        .map(|&pattern| text_lower.matches(pattern).count())
-        .sum()
+/// Counts the phrases ai-grep --hallucinations reports, lowercased. A phrase inside a longer
+/// one ("i cannot" in "i cannot browse") is one marker, counted under the phrase matched.
+fn count_hallucinations(text: &str) -> HashMap<String, usize> {
+    static HALLUCINATIONS: LazyLock<Scanner> = LazyLock::new(|| Scanner::new(Categories::none().with(Category::Hallucinations)));
+    let mut counts = HashMap::new();
+    for m in HALLUCINATIONS.scan(text) {
+        *counts.entry(m.matched_text.to_lowercase()).or_insert(0) += 1;
+    }
+    counts
 }
 
-fn calculate_fact_density(text: &str) -> f64 {
-    let paragraphs: Vec<&str> = text.split("\n\n").filter(|p| !p.trim().is_empty()).collect();
+/// Splits text into paragraphs at runs of blank (or whitespace-only) lines, with either
+/// `\n` or `\r\n` line endings, so paragraphs are counted the way they look.
+fn split_paragraphs(text: &str) -> Vec<&str> {
+    let paragraph_break = Regex::new(r"\r?\n\s*\n").unwrap();
+    paragraph_break.split(text).filter(|p| !p.trim().is_empty()).collect()
+}
+
+/// Counts fact indicators and paragraphs; fact density is their ratio.
+fn count_facts(text: &str) -> (usize, usize) {
+    let paragraphs = split_paragraphs(text);
     if paragraphs.is_empty() {
-        return 0.0;
+        return (0, 0);
     }
     
     // Simple heuristic: count sentences with numbers, dates, or proper nouns
@@ -162,13 +597,14 @@ fn calculate_fact_density(text: &str) -> f64 {
         .map(|p| fact_indicators.find_iter(p).count())
         .sum();
     
-    total_facts as f64 / paragraphs.len() as f64
+    (total_facts, paragraphs.len())
 }
 
-fn calculate_repetition_score(text: &str) -> f64 {
+/// Counts the repeated and distinct 3-word phrases; the repetition score is their ratio.
+fn count_phrases(text: &str) -> (usize, usize) {
     let sentences: Vec<&str> = text.split('.').filter(|s| s.trim().len() > 10).collect();
     if sentences.len() < 2 {
-        return 0.0;
+        return (0, 0);
     }
     
     let mut phrase_counts = HashMap::new();
@@ -183,11 +619,68 @@ fn calculate_repetition_score(text: &str) -> f64 {
     }
     
     let repeated_phrases = phrase_counts.values().filter(|&&count| count > 1).count();
-    let total_phrases = phrase_counts.len();
+    (repeated_phrases, phrase_counts.len())
+}
+
+/// Flesch Reading Ease and Flesch-Kincaid grade level; both are 0 for text without words.
+fn readability(words: usize, sentences: usize, syllables: usize) -> (f64, f64) {
+    if words == 0 {
+        return (0.0, 0.0);
+    }
+    let words_per_sentence = ratio(words, sentences.max(1));
+    let syllables_per_word = ratio(syllables, words);
+    (
+        206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word,
+        0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59,
+    )
+}
+
+/// Counts sentences as text ending in `.`, `?` or `!` (or the end of input) followed by
+/// whitespace, so "3.5" and "e.g." mid-sentence don't split; pieces without letters or
+/// digits don't count.
+fn count_sentences(text: &str) -> usize {
+    let sentence_end = Regex::new(r"[.?!]+(\s+|$)").unwrap();
+    sentence_end.split(text)
+        .filter(|sentence| sentence.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// Estimates syllables by counting vowel groups (`y` included), then dropping a silent
+/// final `e` ("make", but not "table") and the silent `e` of `-es`/`-ed` endings ("makes",
+/// "jumped", but not "boxes" or "wanted"). Every word with letters has at least one.
+fn count_syllables(word: &str) -> usize {
+    let letters: Vec<char> = word.chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if letters.is_empty() {
+        return 0;
+    }
+    if letters.len() <= 3 {
+        return 1;
+    }
     
-    if total_phrases == 0 {
-        0.0
-    } else {
-        repeated_phrases as f64 / total_phrases as f64
+    let is_vowel = |c: char| "aeiouy".contains(c);
+    let mut groups = 0;
+    let mut previous_vowel = false;
+    for &c in &letters {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            groups += 1;
+        }
+        previous_vowel = vowel;
     }
-}
\ No newline at end of file
+    
+    let word: String = letters.iter().collect();
+    let n = letters.len();
+    // A consonant before "le" keeps the e voiced, as in "table"
+    let consonant_le = letters[n - 2] == 'l' && !is_vowel(letters[n - 3]);
+    let silent_e = word.ends_with('e') && !is_vowel(letters[n - 2]) && !consonant_le;
+    let silent_ending = (word.ends_with("ed") && !(word.ends_with("ted") || word.ends_with("ded")))
+        || (word.ends_with("es") && !["ses", "xes", "zes", "ces", "ges", "ches", "shes"].iter().any(|&suffix| word.ends_with(suffix)));
+    if (silent_e || silent_ending) && groups > 1 {
+        groups -= 1;
+    }
+    
+    groups
+}
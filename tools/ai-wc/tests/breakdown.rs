@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Three disclaimers, one refusal (counted once, as "i cannot") and two hedges.
+const TEXT: &str = "As an AI, I cannot browse. As an AI I might help.\nAs an ai it is probably fine.\n";
+
+fn run(args: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("breakdown");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("answer.txt"), TEXT).unwrap();
+    fs::write(dir.join("plain.txt"), "Nothing to see here.\n").unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-wc"))
+        .current_dir(&dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn each_pattern_is_counted_most_frequent_first() {
+    let stdout = run(&["--breakdown", "--hallucination-markers", "answer.txt", "plain.txt"]);
+    assert!(stdout.ends_with("\nHallucination indicators by pattern:\n  \
+                              answer.txt: as an ai 3, i cannot 1\n  \
+                              plain.txt: none\n  \
+                              total: as an ai 3, i cannot 1\n"), "{}", stdout);
+    assert!(!stdout.contains("Confidence markers by pattern"));
+}
+
+#[test]
+fn breakdown_alone_shows_both_markers() {
+    let stdout = run(&["--breakdown", "answer.txt"]);
+    assert!(stdout.contains("\nConfidence markers by pattern:\n  answer.txt: might 1, probably 1\n"), "{}", stdout);
+    assert!(stdout.contains("\nHallucination indicators by pattern:\n"));
+}
+
+#[test]
+fn json_always_has_the_breakdown() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--format", "json", "answer.txt", "plain.txt"])).unwrap();
+    assert_eq!(doc["files"][0]["hallucination_breakdown"], serde_json::json!({ "as an ai": 3, "i cannot": 1 }));
+    assert_eq!(doc["files"][0]["confidence_breakdown"], serde_json::json!({ "might": 1, "probably": 1 }));
+    assert_eq!(doc["files"][1]["hallucination_breakdown"], serde_json::json!({}));
+    assert_eq!(doc["total"]["hallucination_indicators"], 4);
+}
+
+#[test]
+fn markers_are_the_ones_ai_grep_reports() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("breakdown_presets");
+    fs::create_dir_all(&dir).unwrap();
+    // Whole phrases only, and the knowledge-cutoff wording ai-grep knows
+    fs::write(dir.join("cutoff.txt"), "As an aide I checked. As of my last training, data ends in September 2021.\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-wc"))
+        .current_dir(&dir)
+        .args(["--format", "json", "cutoff.txt"])
+        .output()
+        .unwrap();
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["files"][0]["hallucination_breakdown"], serde_json::json!({ "as of my last training": 1, "september 2021": 1 }));
+}
@@ -0,0 +1,36 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A thumbs-up with a skin tone (two scalar values, eight bytes) and an e with a
+/// combining acute accent (two scalar values, three bytes).
+const TEXT: &str = "Hi 👍🏽 cafe\u{301}\n";
+
+fn run(args: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("char_counts");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("emoji.txt"), TEXT).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-wc"))
+        .current_dir(&dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn each_count_has_its_own_column() {
+    assert_eq!(run(&["-t", "emoji.txt"]), "       1        3       12 emoji.txt\n");
+    assert_eq!(run(&["-t", "-c", "emoji.txt"]), "       1        3       19 emoji.txt\n");
+    assert_eq!(run(&["-c", "-m", "--graphemes", "emoji.txt"]), "file         lines    words    chars    bytes graphemes\n\
+                                                              emoji.txt        1        3       12       19        10\n");
+}
+
+#[test]
+fn json_has_every_count() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--format", "json", "emoji.txt"])).unwrap();
+    let file = &doc["files"][0];
+    assert_eq!((file["chars"].as_u64(), file["bytes"].as_u64(), file["graphemes"].as_u64()), (Some(12), Some(19), Some(10)));
+}
@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The same three paragraphs with LF endings, with CRLF endings, and separated by several
+/// blank lines, some holding only whitespace.
+const FIXTURES: [&str; 3] = ["lf.txt", "crlf.txt", "blank_runs.txt"];
+
+#[test]
+fn fact_density_ignores_line_endings_and_blank_runs() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-wc"))
+        .current_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/paragraphs"))
+        .args(["--format", "json"])
+        .args(FIXTURES)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    for (i, name) in FIXTURES.iter().enumerate() {
+        let file = &doc["files"][i];
+        assert_eq!(file["file"], *name);
+        // 8 fact indicators over 3 paragraphs
+        assert_eq!(file["fact_density"].as_f64().unwrap(), 8.0 / 3.0, "{}", name);
+        assert_eq!(file["words"], 25);
+    }
+}
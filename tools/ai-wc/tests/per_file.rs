@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Two paragraphs with two fact indicators, a hedge and a disclaimer.
+const ANSWER: &str = "Paris has been the capital since 987.\n\nAs an AI I think it is probably right.\n";
+
+/// One paragraph whose two sentences share every 3-word phrase.
+const LOOP: &str = "the model repeats the same words. the model repeats the same words.\n";
+
+fn run(args: &[&str], stdin: Option<&str>) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("per_file");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("answer.txt"), ANSWER).unwrap();
+    fs::write(dir.join("loop.txt"), LOOP).unwrap();
+    
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai-wc"))
+        .current_dir(&dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.unwrap_or("").as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], stdin: Option<&str>) -> String {
+    let output = run(args, stdin);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn traditional_rows_match_wc() {
+    assert_eq!(stdout(&["-t", "answer.txt", "loop.txt"], None), "       3       16       78 answer.txt\n       \
+                                                                  1       12       68 loop.txt\n       \
+                                                                  4       28      146 total\n");
+    // A single file gets no total row
+    assert_eq!(stdout(&["-t", "loop.txt"], None), "       1       12       68 loop.txt\n");
+}
+
+#[test]
+fn totals_recompute_the_ratios() {
+    let output = stdout(&["--fact-density", "--repetition-score", "answer.txt", "loop.txt"], None);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "file          lines    words    chars facts/para repetition");
+    assert_eq!(lines[1], "answer.txt        3       16       78       1.00       0.00 (low)");
+    assert_eq!(lines[2], "loop.txt          1       12       68       0.00       1.00 (high - possible loop)");
+    // 2 facts over 3 paragraphs, and 4 of the 16 distinct phrases repeat
+    assert_eq!(lines[3], "total             4       28      146       0.67       0.25 (low)");
+}
+
+#[test]
+fn unreadable_files_are_reported_and_skipped() {
+    let output = run(&["-t", "missing.txt", "loop.txt"], None);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("ai-wc: missing.txt: "));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "       1       12       68 loop.txt\n       \
+                                                            1       12       68 total\n");
+}
+
+#[test]
+fn stdin_keeps_the_block() {
+    assert_eq!(stdout(&["--confidence-markers"], Some(ANSWER)), "Lines: 3\nWords: 16\nChars: 78 (Unicode scalar values)\n\
+                                                                AI Confidence markers: 1 (\"likely\", \"probably\", \"might\")\n");
+}
+
+#[test]
+fn json_has_an_entry_per_file_and_the_total() {
+    let doc: serde_json::Value = serde_json::from_str(&stdout(&["--format", "json", "answer.txt", "loop.txt"], None)).unwrap();
+    assert_eq!(doc["files"][0]["file"], "answer.txt");
+    assert_eq!(doc["files"][0]["hallucination_indicators"], 1);
+    assert_eq!(doc["files"][1]["repetition_level"], "high - possible loop");
+    assert_eq!(doc["total"]["words"], 28);
+    assert!(doc["total"].get("file").is_none());
+    
+    // Stdin gives a single object, with every metric whatever the flags
+    let single: serde_json::Value = serde_json::from_str(&stdout(&["--format", "json", "-t"], Some(LOOP))).unwrap();
+    assert_eq!(single["repetition_score"], 1.0);
+    assert!(single.get("file").is_none());
+}
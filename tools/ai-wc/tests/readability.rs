@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Nine words in two sentences with ten syllables ("happy" has two).
+const SIMPLE: &str = "The cat sat on the mat. It was happy!\n";
+
+/// One long sentence of long words.
+const DENSE: &str = "Comprehensive institutional readability evaluation necessitates considerable methodological sophistication.\n";
+
+fn run(args: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("readability");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("simple.txt"), SIMPLE).unwrap();
+    fs::write(dir.join("dense.txt"), DENSE).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_ai-wc"))
+        .current_dir(&dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn scores_follow_the_flesch_formulas() {
+    assert_eq!(run(&["--readability", "simple.txt", "dense.txt"]), "file          lines    words    chars     ease    grade\n\
+                                                                   simple.txt        1        9       38   108.27    -0.72\n\
+                                                                   dense.txt         1        8      108  -203.13    43.58\n\
+                                                                   total             2       17      146   -37.79    19.94\n");
+}
+
+#[test]
+fn json_has_the_counts_behind_the_scores() {
+    let doc: serde_json::Value = serde_json::from_str(&run(&["--format", "json", "simple.txt"])).unwrap();
+    let file = &doc["files"][0];
+    assert_eq!((file["sentences"].as_u64(), file["syllables"].as_u64()), (Some(2), Some(10)));
+    assert_eq!(file["reading_ease_level"], "very easy");
+    assert!((file["grade_level"].as_f64().unwrap() + 0.72).abs() < 0.01);
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A cache directory of its own for each test, emptied first.
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cache").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// The stdout of a verbose stub run with the cache in `dir`.
+fn aicc(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_aicc"))
+        .args(["reverse a string", "-l", "python", "--no-verify", "--verbose", "--backend", "stub"])
+        .arg("--cache-dir")
+        .arg(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// "hit" or "miss" from the verbose cache line, None without one.
+fn lookup(stdout: &str) -> Option<&str> {
+    stdout.lines().find_map(|line| line.strip_prefix("Cache ")).map(|line| line.split(':').next().unwrap())
+}
+
+fn cache_files(dir: &Path) -> usize {
+    fs::read_dir(dir).map_or(0, |entries| entries.count())
+}
+
+#[test]
+fn second_run_is_a_hit() {
+    let dir = cache_dir("hit");
+    let first = aicc(&dir, &[]);
+    assert_eq!(lookup(&first), Some("miss"));
+    assert!(first.contains("Generated 3 candidates"), "{}", first);
+    
+    let second = aicc(&dir, &[]);
+    assert_eq!(lookup(&second), Some("hit"));
+    assert!(!second.contains("Generated 3 candidates"), "{}", second);
+    assert_eq!(cache_files(&dir), 1);
+}
+
+#[test]
+fn options_that_shape_the_result_are_part_of_the_key() {
+    let dir = cache_dir("key");
+    aicc(&dir, &[]);
+    assert_eq!(lookup(&aicc(&dir, &["--seed", "7"])), Some("miss"));
+    assert_eq!(lookup(&aicc(&dir, &["--seed", "7"])), Some("hit"));
+    assert_eq!(lookup(&aicc(&dir, &["--seed", "8"])), Some("miss"));
+    assert_eq!(lookup(&aicc(&dir, &["--candidates", "2"])), Some("miss"));
+    assert_eq!(cache_files(&dir), 4);
+}
+
+#[test]
+fn entry_for_another_key_is_a_miss() {
+    let dir = cache_dir("collision");
+    aicc(&dir, &[]);
+    
+    // Stand in for a hash collision: same file name, different stored key
+    let path = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    let mut entry: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    entry["key"]["prompt"] = serde_json::json!("something else");
+    fs::write(&path, entry.to_string()).unwrap();
+    assert_eq!(lookup(&aicc(&dir, &[])), Some("miss"));
+}
+
+#[test]
+fn no_cache_neither_reads_nor_writes() {
+    let dir = cache_dir("no_cache");
+    let stdout = aicc(&dir, &["--no-cache"]);
+    assert_eq!(lookup(&stdout), None);
+    assert_eq!(cache_files(&dir), 0);
+    
+    aicc(&dir, &[]);
+    let stdout = aicc(&dir, &["--no-cache"]);
+    assert_eq!(lookup(&stdout), None);
+    assert!(stdout.contains("Generated 3 candidates"), "{}", stdout);
+}
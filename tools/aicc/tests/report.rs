@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The `--format json` document from an unverified stub run.
+fn report(args: &[&str]) -> serde_json::Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_aicc"))
+        .args(["reverse a string", "-l", "python", "--no-verify", "--no-cache", "--backend", "stub", "--format", "json"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn json_report_has_the_documented_fields() {
+    let report = report(&[]);
+    assert_eq!(report["candidates_considered"], 3);
+    assert_eq!(report["language"], "python");
+    assert!(report["code"].as_str().unwrap().contains("reverse a string"), "{}", report);
+    assert!(report["confidence"].is_f64(), "{}", report);
+    assert!(report["requirements"].is_array(), "{}", report);
+    assert!(report["test_code"].is_null(), "{}", report);
+    assert!(report["test_results"].is_null(), "{}", report);
+    for check in ["syntax_check", "security_audit", "import_analysis", "test_generation", "style_compliance"] {
+        assert!(report["verification_results"][check].is_boolean(), "{}: {}", check, report);
+    }
+    assert!(report.get("explanation").is_none(), "{}", report);
+    assert!(report.get("output").is_none(), "{}", report);
+}
+
+#[test]
+fn explain_adds_one_entry_per_check() {
+    let report = report(&["--explain", "--candidates", "2"]);
+    assert_eq!(report["candidates_considered"], 2);
+    let explanation = report["explanation"].as_array().unwrap();
+    let checks: Vec<&str> = explanation.iter().map(|entry| entry["check"].as_str().unwrap()).collect();
+    assert_eq!(checks, ["Syntax check", "Security audit", "Import analysis", "Test generation", "Style compliance"]);
+    for entry in explanation {
+        assert!(entry["passed"].is_boolean(), "{}", entry);
+        assert!(entry["description"].is_string(), "{}", entry);
+        assert!(entry.get("reason").is_some(), "{}", entry);
+    }
+}
+
+#[test]
+fn output_file_is_written_and_named_in_the_report() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("report");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("reverse.py");
+    
+    let report = report(&["-o", path.to_str().unwrap()]);
+    assert_eq!(report["output"], path.to_str().unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), report["code"].as_str().unwrap());
+    assert!(report.get("tests_output").is_none(), "{}", report);
+}
@@ -0,0 +1,76 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// A candidate that starts a process of its own, notes its pid, and never finishes importing.
+const LOOPING_CANDIDATE: &str = "import os, subprocess\n\
+                                 child = subprocess.Popen([\"sleep\", \"60\"])\n\
+                                 with open(os.environ[\"AICC_TEST_PID_FILE\"], \"w\") as f:\n    f.write(str(child.pid))\n\
+                                 while True:\n    pass\n";
+
+/// An Ollama endpoint that answers every request with `response`.
+fn serve(response: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            reader.read_exact(&mut vec![0; length]).unwrap();
+            
+            let body = serde_json::json!({ "response": response }).to_string();
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   body.len(), body).unwrap();
+        }
+    });
+    format!("http://{}", address)
+}
+
+/// Whether `pid` is gone, or dead and waiting to be reaped.
+fn exited(pid: &str) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map_or(true, |stat| stat.rsplit(')').next().unwrap().trim_start().starts_with('Z'))
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn looping_candidates_time_out_with_everything_they_started() {
+    let pid_file = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("sandbox-grandchild.pid");
+    let _ = std::fs::remove_file(&pid_file);
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_aicc"))
+        .args(["loop forever", "-l", "python", "--candidates", "1", "--generate-tests", "--no-cache", "--confidence-threshold", "0"])
+        .args(["--verify-timeout", "1", "--format", "json", "--backend", "ollama", "--model", "test"])
+        .args(["--endpoint", &serve(LOOPING_CANDIDATE)])
+        .env("AICC_TEST_PID_FILE", &pid_file)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["test_results"]["failure"], "timeout", "{}", report);
+    assert_eq!(report["verification_results"]["test_generation"], false);
+    
+    // The process the candidate started was killed along with it
+    let grandchild = std::fs::read_to_string(&pid_file).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !exited(&grandchild) {
+        assert!(Instant::now() < deadline, "process {} outlived the timeout", grandchild);
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -1,25 +1,33 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use anyhow::Result;
+use ai_toolkit_core::ColorMode;
 
 #[derive(Parser, Debug)]
 #[command(name = "semdiff")]
 #[command(about = "Semantic diff - compare meaning changes between AI outputs, not just text changes")]
 struct Args {
-    /// First file to compare (use - for stdin)
+    /// First file to compare (use - for stdin), or a directory to compare file by file
     file1: String,
     
-    /// Second file to compare (use - for stdin for second input)
+    /// Second file to compare (use - for stdin for second input), or the second directory
     file2: Option<String>,
     
     /// Show unified diff format
     #[arg(short, long)]
     unified: bool,
     
+    /// Show changed lines with inline word-level markers: plain ({-old-}{+new+}) or color
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "plain")]
+    word_diff: Option<WordDiffMode>,
+    
     /// Context lines for unified diff
     #[arg(short, long, default_value = "3")]
     context: usize,
@@ -32,14 +40,31 @@ struct Args {
     #[arg(short = 'w', long)]
     ignore_whitespace: bool,
     
+    /// Match paragraphs (lines, when both texts are one paragraph) that changed places and
+    /// show them as moved instead of removed and added again
+    #[arg(long)]
+    ignore_order: bool,
+    
     /// Show semantic similarity score (0.0-1.0)
     #[arg(long)]
     similarity_score: bool,
     
+    /// Similarity metric for the vocabulary component of the score
+    #[arg(long, value_enum, default_value = "jaccard")]
+    metric: SimilarityMetric,
+    
     /// Highlight key concept changes
     #[arg(long)]
     concept_diff: bool,
     
+    /// Drop concepts that occur fewer than this many times in a text
+    #[arg(long, default_value = "1")]
+    min_concept_freq: usize,
+    
+    /// Extract concepts with this regex instead of the built-in heuristics (group 1 if present)
+    #[arg(long, value_name = "REGEX")]
+    concept_regex: Option<String>,
+    
     /// Detect factual contradictions
     #[arg(long)]
     contradiction_check: bool,
@@ -48,13 +73,67 @@ struct Args {
     #[arg(long)]
     confidence_diff: bool,
     
+    /// Strip confidence markers ("likely", "might", "clearly") before comparing, so edits
+    /// that only change hedging are not significant; --confidence-diff still shows them
+    #[arg(long)]
+    semantic_only: bool,
+    
+    /// Show the sentiment of each text and how the tone shifted
+    #[arg(long)]
+    sentiment_diff: bool,
+    
     /// Minimum semantic difference threshold (0.0-1.0)
     #[arg(long, default_value = "0.1")]
     threshold: f64,
     
+    /// Split both inputs into aligned chunks and score each pair, reporting a weighted
+    /// overall similarity and the lowest-scoring chunks; for very large inputs
+    #[arg(long, value_enum, value_name = "UNIT")]
+    chunk_by: Option<ChunkBy>,
+    
+    /// Number of lowest-scoring chunks to list with --chunk-by
+    #[arg(long, value_name = "N", default_value = "5", requires = "chunk_by")]
+    lowest_chunks: usize,
+    
     /// Output format: text, json
     #[arg(long, default_value = "text")]
     format: String,
+    
+    /// Color the text diff (--color); auto only on a terminal with NO_COLOR unset.
+    /// --word-diff=color falls back to plain markers when color is off
+    #[arg(long, value_enum, default_value = "auto")]
+    color: ColorMode,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SimilarityMetric {
+    /// Overlap of the concept sets, ignoring how often each occurs
+    Jaccard,
+    /// Cosine of TF-IDF vectors over all word tokens
+    Cosine,
+}
+
+impl SimilarityMetric {
+    fn name(self) -> &'static str {
+        match self {
+            SimilarityMetric::Jaccard => "jaccard",
+            SimilarityMetric::Cosine => "cosine",
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum WordDiffMode {
+    Plain,
+    Color,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ChunkBy {
+    /// Blocks of text separated by blank lines
+    Paragraph,
 }
 
 #[derive(Debug, Clone)]
@@ -66,24 +145,30 @@ struct SemanticChunk {
     sentiment: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct SemanticDiff {
     similarity_score: f64,
+    metric: SimilarityMetric,
     concept_changes: Vec<ConceptChange>,
     fact_changes: Vec<FactChange>,
     confidence_changes: Vec<ConfidenceChange>,
     contradictions: Vec<Contradiction>,
+    sentiment: SentimentChange,
     text_diff: Vec<DiffLine>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    word_changes: Vec<WordChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    moves: Vec<MoveChange>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct ConceptChange {
     concept: String,
     change_type: ChangeType,
     context: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct FactChange {
     fact: String,
     change_type: ChangeType,
@@ -91,7 +176,7 @@ struct FactChange {
     new_value: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct ConfidenceChange {
     marker: String,
     old_confidence: f64,
@@ -99,57 +184,129 @@ struct ConfidenceChange {
     context: String,
 }
 
-#[derive(Debug)]
+/// Tone of each text, from -1.0 (negative) to 1.0 (positive).
+#[derive(Debug, Serialize)]
+struct SentimentChange {
+    old_sentiment: f64,
+    new_sentiment: f64,
+    shift: f64,
+}
+
+#[derive(Debug, Serialize)]
 struct Contradiction {
     statement1: String,
     statement2: String,
     contradiction_type: String,
+    /// The values that differ, for numeric mismatches
+    value1: Option<String>,
+    value2: Option<String>,
+}
+
+/// A run of words that differs within a pair of changed lines.
+#[derive(Debug, Serialize)]
+struct WordChange {
+    change_type: ChangeType,
+    old_value: Option<String>,
+    new_value: Option<String>,
+    old_line: usize,
+    new_line: usize,
+}
+
+/// A paragraph, or line, that --ignore-order found in another place in the second text.
+#[derive(Debug, Serialize)]
+struct MoveChange {
+    change_type: ChangeType,
+    content: String,
+    /// First and last line in each text
+    old_lines: (usize, usize),
+    new_lines: (usize, usize),
+    /// Word overlap of the two versions, 1.0 when it moved unchanged
+    similarity: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 enum ChangeType {
     Added,
     Removed,
     Modified,
+    Moved,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct DiffLine {
     line_type: LineType,
     content: String,
     line_number: Option<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 enum LineType {
     Context,
     Added,
     Removed,
     Modified,
+    Moved,
+    HunkHeader,
 }
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("semdiff: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
     let args = Args::parse();
     
+    let concept_regex = args.concept_regex.as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("invalid --concept-regex: {}", e))?;
+    
+    if Path::new(&args.file1).is_dir() {
+        if args.chunk_by.is_some() {
+            Args::command().error(ErrorKind::ArgumentConflict, "--chunk-by compares two files, not directories").exit();
+        }
+        let Some(dir2) = args.file2.as_deref().filter(|f| Path::new(f).is_dir()) else {
+            anyhow::bail!("{} is a directory; give a second directory to compare it with", args.file1);
+        };
+        return compare_directories(Path::new(&args.file1), Path::new(dir2), &args, concept_regex.as_ref());
+    }
+    
+    // If only one file is specified, the second text comes from stdin, which can only be read once
+    let file2 = args.file2.as_deref().unwrap_or("-");
+    let stdin_inputs = [args.file1.as_str(), file2].iter().filter(|name| **name == "-").count();
+    if stdin_inputs > 1 {
+        Args::command().error(ErrorKind::ArgumentConflict, "stdin can only be used for one of the two inputs").exit();
+    }
+    if stdin_inputs == 1 && io::stdin().is_terminal() {
+        Args::command()
+            .error(ErrorKind::MissingRequiredArgument, "no second input: give two files or pipe text on stdin")
+            .exit();
+    }
+    
     let text1 = read_input(&args.file1)?;
-    let text2 = if let Some(file2) = &args.file2 {
-        read_input(file2)?
-    } else {
-        // If only one file specified, read second from stdin
-        read_stdin()?
-    };
+    let text2 = read_input(file2)?;
+    
+    if args.chunk_by.is_some() {
+        return compare_chunks(&text1, &text2, &args, concept_regex.as_ref());
+    }
     
-    let chunk1 = analyze_text(&text1, &args);
-    let chunk2 = analyze_text(&text2, &args);
+    let chunk1 = analyze_text(&text1, &args, concept_regex.as_ref());
+    let chunk2 = analyze_text(&text2, &args, concept_regex.as_ref());
     
     let semantic_diff = compare_semantics(&chunk1, &chunk2, &args);
     
-    // Filter by threshold
-    if semantic_diff.similarity_score >= (1.0 - args.threshold) {
+    if !is_significant(&semantic_diff, &args) {
         if args.format == "json" {
-            println!("{{\"similarity\": {:.3}, \"changes\": []}}", semantic_diff.similarity_score);
+            println!("{{\"similarity\": {:.3}, \"metric\": \"{}\", \"changes\": []}}",
+                     semantic_diff.similarity_score, semantic_diff.metric.name());
         } else {
-            println!("No significant semantic differences (similarity: {:.3})", semantic_diff.similarity_score);
+            println!("No significant semantic differences (similarity: {:.3}, {})",
+                     semantic_diff.similarity_score, semantic_diff.metric.name());
         }
         return Ok(());
     }
@@ -159,11 +316,414 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Whether a diff passes the --threshold filter. A changed number barely moves the score,
+/// so contradictions are always reported when asked for, and so are confidence changes
+/// when --semantic-only has taken them out of the score. Reordering leaves the score
+/// alone, so moves found by --ignore-order are always reported too.
+fn is_significant(diff: &SemanticDiff, args: &Args) -> bool {
+    let has_contradictions = args.contradiction_check && !diff.contradictions.is_empty();
+    let has_confidence_changes = args.semantic_only && args.confidence_diff && !diff.confidence_changes.is_empty();
+    let has_moves = args.ignore_order && !diff.moves.is_empty();
+    diff.similarity_score < (1.0 - args.threshold) || has_contradictions || has_confidence_changes || has_moves
+}
+
+/// Outcome of comparing one file name across two directories.
+#[derive(Debug, Serialize)]
+struct FileComparison {
+    file: String,
+    /// "similar", "changed", "added" (only in the second directory) or "removed"
+    status: &'static str,
+    similarity_score: f64,
+    /// The full comparison, for files that did not pass the threshold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<SemanticDiff>,
+}
+
+/// Compare files with the same relative path in both directories. A file on only one
+/// side is compared with an empty text, so everything in it shows as added or removed.
+fn compare_directories(dir1: &Path, dir2: &Path, args: &Args, concept_regex: Option<&Regex>) -> Result<()> {
+    let mut files1 = BTreeSet::new();
+    let mut files2 = BTreeSet::new();
+    list_files(dir1, Path::new(""), &mut files1)?;
+    list_files(dir2, Path::new(""), &mut files2)?;
+    
+    let read = |dir: &Path, files: &BTreeSet<PathBuf>, name: &PathBuf| -> Result<Option<String>> {
+        if !files.contains(name) {
+            return Ok(None);
+        }
+        let path = dir.join(name);
+        fs::read_to_string(&path)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    };
+    
+    let mut comparisons = Vec::new();
+    for name in files1.union(&files2) {
+        let old = read(dir1, &files1, name)?;
+        let new = read(dir2, &files2, name)?;
+        
+        let chunk1 = analyze_text(old.as_deref().unwrap_or(""), args, concept_regex);
+        let chunk2 = analyze_text(new.as_deref().unwrap_or(""), args, concept_regex);
+        let mut diff = compare_semantics(&chunk1, &chunk2, args);
+        
+        let status = match (&old, &new) {
+            (None, _) => "added",
+            (_, None) => "removed",
+            _ if is_significant(&diff, args) => "changed",
+            _ => "similar",
+        };
+        if old.is_none() || new.is_none() {
+            diff.similarity_score = 0.0;
+        }
+        
+        comparisons.push(FileComparison {
+            file: name.display().to_string(),
+            status,
+            similarity_score: diff.similarity_score,
+            diff: (status != "similar").then_some(diff),
+        });
+    }
+    
+    if args.format == "json" {
+        let output = serde_json::json!({ "metric": args.metric, "files": comparisons });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+    
+    println!("=== Directory Comparison ({}) ===", args.metric.name());
+    for comparison in &comparisons {
+        println!("{:.3}  {:<8} {}", comparison.similarity_score, comparison.status, comparison.file);
+    }
+    let count = |status: &str| comparisons.iter().filter(|c| c.status == status).count();
+    println!("{} file(s): {} similar, {} changed, {} added, {} removed",
+             comparisons.len(), count("similar"), count("changed"), count("added"), count("removed"));
+    
+    for comparison in &comparisons {
+        if let Some(diff) = &comparison.diff {
+            println!();
+            println!("##### {} ({}) #####", comparison.file, comparison.status);
+            output_diff(diff, args)?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Collect the paths of regular files under `dir`, relative to the directory being compared.
+fn list_files(dir: &Path, relative: &Path, files: &mut BTreeSet<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            list_files(dir, &path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// A blank-line separated block of one input and the lines it spans (1-based, inclusive).
+#[derive(Debug, Clone, Copy)]
+struct Paragraph<'a> {
+    text: &'a str,
+    first_line: usize,
+    last_line: usize,
+}
+
+fn split_paragraphs(text: &str) -> Vec<Paragraph<'_>> {
+    let mut paragraphs = Vec::new();
+    // Byte offset and line number where the open paragraph starts
+    let mut open: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        if line.trim().is_empty() {
+            if let Some((start, first_line)) = open.take() {
+                paragraphs.push(Paragraph { text: text[start..offset].trim_end_matches(['\r', '\n']), first_line, last_line: index });
+            }
+        } else if open.is_none() {
+            open = Some((offset, index + 1));
+        }
+        offset += line.len();
+    }
+    if let Some((start, first_line)) = open {
+        let last_line = text[start..].lines().count() + first_line - 1;
+        paragraphs.push(Paragraph { text: text[start..].trim_end_matches(['\r', '\n']), first_line, last_line });
+    }
+    paragraphs
+}
+
+/// Pair up the chunks of two inputs. Chunks that occur exactly once in each input are
+/// anchors, kept in order by a longest increasing subsequence (as in patience diff); the
+/// chunks between two anchors are paired by position, and any left over on the longer
+/// side are unpaired. Time is O(n log n) and memory linear, however different the inputs.
+fn align_chunks(keys1: &[&str], keys2: &[&str]) -> Vec<(Option<usize>, Option<usize>)> {
+    // Occurrences in each input, and the position in the second
+    let mut counts: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    for key in keys1 {
+        counts.entry(key).or_default().0 += 1;
+    }
+    for (j, key) in keys2.iter().enumerate() {
+        let entry = counts.entry(key).or_default();
+        entry.1 += 1;
+        entry.2 = j;
+    }
+    let unique: Vec<(usize, usize)> = keys1.iter()
+        .enumerate()
+        .filter_map(|(i, key)| {
+            let (count1, count2, j) = counts[key];
+            (count1 == 1 && count2 == 1).then_some((i, j))
+        })
+        .collect();
+    
+    let mut anchors = increasing_pairs(&unique);
+    anchors.push((keys1.len(), keys2.len()));
+    
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (anchor1, anchor2) in anchors {
+        let gap = (anchor1 - i).max(anchor2 - j);
+        for k in 0..gap {
+            pairs.push(((i + k < anchor1).then_some(i + k), (j + k < anchor2).then_some(j + k)));
+        }
+        if anchor1 < keys1.len() {
+            pairs.push((Some(anchor1), Some(anchor2)));
+        }
+        (i, j) = (anchor1 + 1, anchor2 + 1);
+    }
+    pairs
+}
+
+/// The longest run of `pairs`, which are ordered by their first index, whose second index
+/// also increases: the pairs that can stay in place while the others move.
+fn increasing_pairs(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; pairs.len()];
+    for (n, &(_, j)) in pairs.iter().enumerate() {
+        let slot = tails.partition_point(|&t| pairs[t].1 < j);
+        previous[n] = slot.checked_sub(1).map(|s| tails[s]);
+        if slot == tails.len() {
+            tails.push(n);
+        } else {
+            tails[slot] = n;
+        }
+    }
+    let mut run = Vec::new();
+    let mut next = tails.last().copied();
+    while let Some(n) = next {
+        run.push(pairs[n]);
+        next = previous[n];
+    }
+    run.reverse();
+    run
+}
+
+/// Paragraphs of the two texts that --ignore-order pairs up out of order. Each paragraph
+/// pairs with its most similar counterpart, as long as they share enough words to be
+/// `facts_similar`; the longest run of pairs in the same order in both texts stays in
+/// place and the rest moved. Texts of one paragraph each are compared line by line.
+fn find_moves(text1: &str, text2: &str) -> Vec<MoveChange> {
+    let mut units1 = split_paragraphs(text1);
+    let mut units2 = split_paragraphs(text2);
+    if units1.len() <= 1 && units2.len() <= 1 {
+        units1 = split_lines(text1);
+        units2 = split_lines(text2);
+    }
+    
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (i, unit1) in units1.iter().enumerate() {
+        for (j, unit2) in units2.iter().enumerate() {
+            let overlap = word_overlap(unit1.text, unit2.text);
+            if overlap > SIMILAR_OVERLAP {
+                candidates.push((overlap, i, j));
+            }
+        }
+    }
+    // Most similar first, ties in input order
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+    
+    let mut matched1 = vec![None; units1.len()];
+    let mut matched2 = vec![false; units2.len()];
+    for (overlap, i, j) in candidates {
+        if matched1[i].is_none() && !matched2[j] {
+            matched1[i] = Some((j, overlap));
+            matched2[j] = true;
+        }
+    }
+    
+    let pairs: Vec<(usize, usize)> = matched1.iter()
+        .enumerate()
+        .filter_map(|(i, matched)| matched.map(|(j, _)| (i, j)))
+        .collect();
+    let in_place: HashSet<(usize, usize)> = increasing_pairs(&pairs).into_iter().collect();
+    
+    pairs.into_iter()
+        .filter(|pair| !in_place.contains(pair))
+        .map(|(i, j)| MoveChange {
+            change_type: ChangeType::Moved,
+            content: units1[i].text.to_string(),
+            old_lines: (units1[i].first_line, units1[i].last_line),
+            new_lines: (units2[j].first_line, units2[j].last_line),
+            similarity: matched1[i].map_or(0.0, |(_, overlap)| overlap),
+        })
+        .collect()
+}
+
+/// Each non-blank line as a paragraph of its own.
+fn split_lines(text: &str) -> Vec<Paragraph<'_>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| Paragraph { text: line, first_line: index + 1, last_line: index + 1 })
+        .collect()
+}
+
+/// One aligned pair of chunks in a --chunk-by comparison.
+#[derive(Debug, Serialize)]
+struct ChunkComparison {
+    /// First and last line of the chunk in each input, absent on the side it is missing from
+    old_lines: Option<(usize, usize)>,
+    new_lines: Option<(usize, usize)>,
+    /// "similar", "changed", "added" (only in the second input) or "removed"
+    status: &'static str,
+    similarity_score: f64,
+    /// Words in the longer side, which the chunk's score is weighted by
+    weight: usize,
+    /// The full comparison, for chunks that did not pass the threshold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<SemanticDiff>,
+}
+
+/// Compare two large inputs chunk by chunk. Only one pair of chunks is analyzed at a time,
+/// so the similarity math stays proportional to the chunk size rather than the whole text,
+/// and the full diff is built only for the lowest-scoring chunks.
+fn compare_chunks(text1: &str, text2: &str, args: &Args, concept_regex: Option<&Regex>) -> Result<()> {
+    let paragraphs1 = split_paragraphs(text1);
+    let paragraphs2 = split_paragraphs(text2);
+    
+    // Chunks that read the same under -i, -w and --semantic-only are aligned and scored as identical
+    let normalize = |paragraph: &Paragraph| {
+        let text = if args.ignore_case { paragraph.text.to_lowercase() } else { paragraph.text.to_string() };
+        let text = if args.semantic_only { strip_confidence_markers(&text) } else { text };
+        if args.ignore_whitespace { text.split_whitespace().collect::<Vec<_>>().join(" ") } else { text }
+    };
+    let keys1: Vec<String> = paragraphs1.iter().map(normalize).collect();
+    let keys2: Vec<String> = paragraphs2.iter().map(normalize).collect();
+    let pairs = align_chunks(
+        &keys1.iter().map(String::as_str).collect::<Vec<_>>(),
+        &keys2.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+    
+    let status = |old: Option<usize>, new: Option<usize>, score: f64| match (old, new) {
+        (None, _) => "added",
+        (_, None) => "removed",
+        _ if score < 1.0 - args.threshold => "changed",
+        _ => "similar",
+    };
+    
+    let mut scores = Vec::with_capacity(pairs.len());
+    for &(old, new) in &pairs {
+        let score = match (old, new) {
+            (Some(i), Some(j)) if keys1[i] == keys2[j] => 1.0,
+            (Some(i), Some(j)) => {
+                let chunk1 = analyze_text(paragraphs1[i].text, args, concept_regex);
+                let chunk2 = analyze_text(paragraphs2[j].text, args, concept_regex);
+                calculate_similarity_score(&chunk1, &chunk2, args.metric)
+            }
+            _ => 0.0,
+        };
+        let words = |paragraphs: &[Paragraph], index: Option<usize>| {
+            index.map_or(0, |i| paragraphs[i].text.split_whitespace().count())
+        };
+        let weight = words(&paragraphs1, old).max(words(&paragraphs2, new)).max(1);
+        scores.push((score, weight));
+    }
+    
+    let total_weight: usize = scores.iter().map(|&(_, weight)| weight).sum();
+    let similarity_score = if total_weight > 0 {
+        scores.iter().map(|&(score, weight)| score * weight as f64).sum::<f64>() / total_weight as f64
+    } else {
+        1.0
+    };
+    let mut counts: BTreeMap<&str, usize> = ["similar", "changed", "added", "removed"].into_iter().map(|s| (s, 0)).collect();
+    for (&(old, new), &(score, _)) in pairs.iter().zip(&scores) {
+        *counts.get_mut(status(old, new, score)).unwrap() += 1;
+    }
+    
+    // Lowest scores first, ties in input order
+    let mut order: Vec<usize> = (0..pairs.len()).collect();
+    order.sort_by(|&a, &b| scores[a].0.total_cmp(&scores[b].0));
+    let lowest: Vec<ChunkComparison> = order.into_iter()
+        .take(args.lowest_chunks)
+        .map(|index| {
+            let (old, new) = pairs[index];
+            let (score, weight) = scores[index];
+            let status = status(old, new, score);
+            let diff = (status != "similar").then(|| {
+                let chunk1 = analyze_text(old.map_or("", |i| paragraphs1[i].text), args, concept_regex);
+                let chunk2 = analyze_text(new.map_or("", |j| paragraphs2[j].text), args, concept_regex);
+                let mut diff = compare_semantics(&chunk1, &chunk2, args);
+                diff.similarity_score = score;
+                diff
+            });
+            ChunkComparison {
+                old_lines: old.map(|i| (paragraphs1[i].first_line, paragraphs1[i].last_line)),
+                new_lines: new.map(|j| (paragraphs2[j].first_line, paragraphs2[j].last_line)),
+                status,
+                similarity_score: score,
+                weight,
+                diff,
+            }
+        })
+        .collect();
+    
+    if args.format == "json" {
+        let output = serde_json::json!({
+            "chunk_by": args.chunk_by,
+            "metric": args.metric,
+            "similarity_score": similarity_score,
+            "chunks": pairs.len(),
+            "counts": counts,
+            "lowest_chunks": lowest,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+    
+    println!("=== Chunked Comparison (paragraph, {}) ===", args.metric.name());
+    println!("Overall similarity: {:.3} (weighted by chunk length)", similarity_score);
+    println!("{} chunk(s): {} similar, {} changed, {} added, {} removed",
+             pairs.len(), counts["similar"], counts["changed"], counts["added"], counts["removed"]);
+    
+    let lines = |span: Option<(usize, usize)>| match span {
+        Some((first, last)) if first == last => first.to_string(),
+        Some((first, last)) => format!("{}-{}", first, last),
+        None => "-".to_string(),
+    };
+    if !lowest.is_empty() {
+        println!();
+        println!("Lowest-scoring chunks:");
+        for chunk in &lowest {
+            println!("{:.3}  {:<8} lines {} -> {}", chunk.similarity_score, chunk.status, lines(chunk.old_lines), lines(chunk.new_lines));
+        }
+    }
+    
+    for chunk in &lowest {
+        if let Some(diff) = &chunk.diff {
+            println!();
+            println!("##### lines {} -> {} ({}) #####", lines(chunk.old_lines), lines(chunk.new_lines), chunk.status);
+            output_diff(diff, args)?;
+        }
+    }
+    
+    Ok(())
+}
+
 fn read_input(filename: &str) -> Result<String> {
     if filename == "-" {
         read_stdin()
     } else {
-        Ok(fs::read_to_string(filename)?)
+        fs::read_to_string(filename).map_err(|e| anyhow::anyhow!("{}: {}", filename, e))
     }
 }
 
@@ -173,7 +733,7 @@ fn read_stdin() -> Result<String> {
     Ok(buffer)
 }
 
-fn analyze_text(text: &str, args: &Args) -> SemanticChunk {
+fn analyze_text(text: &str, args: &Args, concept_regex: Option<&Regex>) -> SemanticChunk {
     let processed_text = if args.ignore_case {
         text.to_lowercase()
     } else {
@@ -181,14 +741,22 @@ fn analyze_text(text: &str, args: &Args) -> SemanticChunk {
     };
     
     let processed_text = if args.ignore_whitespace {
-        Regex::new(r"\s+").unwrap().replace_all(&processed_text, " ").to_string()
+        static WHITESPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+        WHITESPACE.replace_all(&processed_text, " ").to_string()
     } else {
         processed_text
     };
     
-    let concepts = extract_concepts(&processed_text);
-    let facts = extract_facts(&processed_text);
+    // Markers are read before --semantic-only strips them, so --confidence-diff still sees them
     let confidence_markers = extract_confidence_markers(&processed_text);
+    let processed_text = if args.semantic_only {
+        strip_confidence_markers(&processed_text)
+    } else {
+        processed_text
+    };
+    
+    let concepts = extract_concepts(&processed_text, concept_regex, args.min_concept_freq);
+    let facts = extract_facts(&processed_text);
     let sentiment = calculate_sentiment(&processed_text);
     
     SemanticChunk {
@@ -200,47 +768,93 @@ fn analyze_text(text: &str, args: &Args) -> SemanticChunk {
     }
 }
 
-fn extract_concepts(text: &str) -> HashSet<String> {
-    let mut concepts = HashSet::new();
-    
-    // Extract noun phrases (simplified)
-    let noun_phrase_regex = Regex::new(r"\b[A-Z][a-z]+(?:\s+[A-Z][a-z]+)*\b").unwrap();
-    for cap in noun_phrase_regex.find_iter(text) {
-        concepts.insert(cap.as_str().to_string());
-    }
-    
-    // Extract technical terms
-    let tech_terms_regex = Regex::new(r"\b[a-z]+(?:_[a-z]+)*\b|\b[A-Z]{2,}\b").unwrap();
-    for cap in tech_terms_regex.find_iter(text) {
-        if cap.as_str().len() > 3 {
-            concepts.insert(cap.as_str().to_string());
+/// Function words that are capitalized only because they open a sentence.
+const SENTENCE_STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "although", "an", "and", "any", "are", "as", "at",
+    "because", "before", "but", "by", "each", "for", "from", "he", "her", "here", "his", "how",
+    "however", "i", "if", "in", "is", "it", "its", "many", "most", "my", "no", "not", "of", "on",
+    "or", "our", "she", "since", "so", "some", "that", "the", "their", "then", "there", "these",
+    "they", "this", "those", "to", "we", "what", "when", "which", "while", "who", "why", "with",
+    "yes", "you", "your",
+];
+
+fn extract_concepts(text: &str, concept_regex: Option<&Regex>, min_freq: usize) -> HashSet<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut add = |concept: &str| *counts.entry(concept.to_string()).or_insert(0) += 1;
+    
+    if let Some(regex) = concept_regex {
+        for cap in regex.captures_iter(text) {
+            if let Some(concept) = cap.get(1).or_else(|| cap.get(0)).filter(|m| !m.as_str().is_empty()) {
+                add(concept.as_str());
+            }
         }
-    }
-    
-    // Extract quoted concepts
-    let quoted_regex = Regex::new(r#""([^"]+)"|'([^']+)'"#).unwrap();
-    for cap in quoted_regex.captures_iter(text) {
-        if let Some(quoted) = cap.get(1).or_else(|| cap.get(2)) {
-            concepts.insert(quoted.as_str().to_string());
+    } else {
+        // Words that also appear in lowercase are ordinary words when they open a sentence
+        static LOWERCASE_WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[a-z]+\b").unwrap());
+        static NOUN_PHRASE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[A-Z][a-z]+(?:\s+[A-Z][a-z]+)*\b").unwrap());
+        static TECH_TERM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[a-z]+(?:_[a-z]+)*\b|\b[A-Z]{2,}\b").unwrap());
+        static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"]+)"|'([^']+)'"#).unwrap());
+        
+        let lowercase_words: HashSet<&str> = LOWERCASE_WORD
+            .find_iter(text)
+            .map(|m| m.as_str())
+            .collect();
+        
+        // Extract noun phrases (simplified)
+        for cap in NOUN_PHRASE.find_iter(text) {
+            let mut phrase = cap.as_str();
+            if starts_sentence(text, cap.start()) {
+                let first = phrase.split_whitespace().next().unwrap_or_default();
+                let lower = first.to_lowercase();
+                if SENTENCE_STOPWORDS.contains(&lower.as_str()) || lowercase_words.contains(lower.as_str()) {
+                    phrase = phrase[first.len()..].trim_start();
+                }
+            }
+            if !phrase.is_empty() {
+                add(phrase);
+            }
+        }
+        
+        // Extract technical terms
+        for cap in TECH_TERM.find_iter(text) {
+            if cap.as_str().len() > 3 {
+                add(cap.as_str());
+            }
+        }
+        
+        // Extract quoted concepts
+        for cap in QUOTED.captures_iter(text) {
+            if let Some(quoted) = cap.get(1).or_else(|| cap.get(2)) {
+                add(quoted.as_str());
+            }
         }
     }
     
-    concepts
+    counts.into_iter()
+        .filter(|(_, count)| *count >= min_freq)
+        .map(|(concept, _)| concept)
+        .collect()
+}
+
+/// Whether `pos` is the first word of a sentence or line.
+fn starts_sentence(text: &str, pos: usize) -> bool {
+    let before = text[..pos].trim_end_matches([' ', '\t', '"', '\'', '(']);
+    before.is_empty() || before.ends_with(['.', '!', '?', ':', '\n'])
 }
 
 fn extract_facts(text: &str) -> Vec<String> {
-    let mut facts = Vec::new();
+    // Statements with numbers, dates, or measurements
+    static FACT_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+        [
+            r"\b\d+(?:\.\d+)?\s*(?:percent|%|million|billion|thousand|years?|days?|hours?|minutes?|seconds?)\b",
+            r"\b(?:January|February|March|April|May|June|July|August|September|October|November|December)\s+\d{1,2},?\s+\d{4}\b",
+            r"\b\d{4}-\d{2}-\d{2}\b",
+            r"\b\d+(?:\.\d+)?\s*(?:kg|pounds?|lbs?|meters?|feet|inches?|miles?|km|celsius|fahrenheit)\b",
+        ].into_iter().map(|pattern| Regex::new(pattern).unwrap()).collect()
+    });
     
-    // Extract statements with numbers, dates, or measurements
-    let fact_patterns = [
-        r"\b\d+(?:\.\d+)?\s*(?:percent|%|million|billion|thousand|years?|days?|hours?|minutes?|seconds?)\b",
-        r"\b(?:January|February|March|April|May|June|July|August|September|October|November|December)\s+\d{1,2},?\s+\d{4}\b",
-        r"\b\d{4}-\d{2}-\d{2}\b",
-        r"\b\d+(?:\.\d+)?\s*(?:kg|pounds?|lbs?|meters?|feet|inches?|miles?|km|celsius|fahrenheit)\b",
-    ];
-    
-    for pattern in &fact_patterns {
-        let regex = Regex::new(pattern).unwrap();
+    let mut facts = Vec::new();
+    for regex in FACT_PATTERNS.iter() {
         for mat in regex.find_iter(text) {
             // Get surrounding context
             let start = text[..mat.start()].rfind('.').map(|i| i + 1).unwrap_or(0);
@@ -255,20 +869,26 @@ fn extract_facts(text: &str) -> Vec<String> {
     facts
 }
 
+/// Words and phrases that hedge a statement or stress certainty.
+const CONFIDENCE_MARKERS: &[&str] = &[
+    "likely", "probably", "might", "could", "perhaps", "possibly",
+    "seems", "appears", "suggests", "indicates", "presumably",
+    "allegedly", "supposedly", "apparently", "potentially",
+    "definitely", "certainly", "absolutely", "clearly", "obviously",
+    "undoubtedly", "without doubt", "surely", "indeed"
+];
+
 fn extract_confidence_markers(text: &str) -> Vec<String> {
-    let confidence_patterns = [
-        "likely", "probably", "might", "could", "perhaps", "possibly",
-        "seems", "appears", "suggests", "indicates", "presumably",
-        "allegedly", "supposedly", "apparently", "potentially",
-        "definitely", "certainly", "absolutely", "clearly", "obviously",
-        "undoubtedly", "without doubt", "surely", "indeed"
-    ];
+    static CONFIDENCE_PATTERNS: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+        CONFIDENCE_MARKERS.iter()
+            .map(|&pattern| (pattern, Regex::new(&format!(r"\b{}\b", regex::escape(pattern))).unwrap()))
+            .collect()
+    });
     
     let mut markers = Vec::new();
     let text_lower = text.to_lowercase();
     
-    for pattern in &confidence_patterns {
-        let regex = Regex::new(&format!(r"\b{}\b", regex::escape(pattern))).unwrap();
+    for (pattern, regex) in CONFIDENCE_PATTERNS.iter() {
         for mat in regex.find_iter(&text_lower) {
             // Get surrounding context (20 chars before and after)
             let start = mat.start().saturating_sub(20);
@@ -281,52 +901,125 @@ fn extract_confidence_markers(text: &str) -> Vec<String> {
     markers
 }
 
+/// The text without its confidence markers, each taken out with a comma and the spacing
+/// that follow it ("Clearly, it works" -> "it works"), for --semantic-only.
+fn strip_confidence_markers(text: &str) -> String {
+    static MARKER: LazyLock<Regex> = LazyLock::new(|| {
+        let markers: Vec<String> = CONFIDENCE_MARKERS.iter().map(|marker| regex::escape(marker)).collect();
+        Regex::new(&format!(r"(?i)\b(?:{})\b,?[ \t]*", markers.join("|"))).unwrap()
+    });
+    MARKER.replace_all(text, "").into_owned()
+}
+
+/// Word polarities from -3 (very negative) to 3 (very positive), sorted for binary search.
+const SENTIMENT_LEXICON: &[(&str, i32)] = &[
+    ("abuse", -3), ("accurate", 2), ("achieve", 1), ("achieved", 1), ("advantage", 2),
+    ("advantages", 2), ("afraid", -2), ("amazing", 3), ("angry", -3), ("annoying", -2),
+    ("anxious", -2), ("appreciate", 2), ("appropriate", 1), ("awesome", 3), ("awful", -3),
+    ("bad", -3), ("beautiful", 3), ("beneficial", 2), ("benefit", 2), ("benefits", 2), ("best", 3),
+    ("better", 2), ("brilliant", 3), ("broken", -2), ("bug", -1), ("buggy", -2), ("calm", 1),
+    ("capable", 1), ("careless", -2), ("catastrophic", -3), ("certain", 1), ("clean", 1),
+    ("clear", 1), ("comfortable", 2), ("compatible", 1), ("complete", 1), ("complicated", -1),
+    ("concern", -1), ("concerns", -1), ("confident", 2), ("confusing", -2), ("convenient", 2),
+    ("correct", 2), ("correctly", 2), ("corrupt", -3), ("corrupted", -2), ("costly", -2),
+    ("crash", -2), ("crashes", -2), ("critical", -1), ("damage", -3), ("damaged", -2),
+    ("danger", -2), ("dangerous", -2), ("deficient", -2), ("delighted", 3), ("dependable", 2),
+    ("difficult", -1), ("disappointed", -2), ("disappointing", -2), ("disaster", -3), ("easy", 1),
+    ("effective", 2), ("efficient", 2), ("elegant", 2), ("encouraging", 2), ("enjoy", 2),
+    ("error", -2), ("errors", -2), ("excellent", 3), ("exceptional", 3), ("excited", 3),
+    ("fail", -2), ("failed", -2), ("fails", -2), ("failure", -2), ("fake", -3), ("false", -1),
+    ("fantastic", 3), ("fast", 1), ("fault", -2), ("faulty", -2), ("favorable", 2), ("fine", 1),
+    ("fixed", 1), ("flawed", -2), ("fortunately", 2), ("fragile", -2), ("free", 1),
+    ("frustrating", -2), ("glad", 2), ("good", 2), ("great", 3), ("happy", 3), ("harm", -2),
+    ("harmful", -2), ("hate", -3), ("healthy", 2), ("helpful", 2), ("horrible", -3), ("ideal", 2),
+    ("impossible", -2), ("impressive", 3), ("improve", 2), ("improved", 2), ("improvement", 2),
+    ("improves", 2), ("inaccurate", -2), ("inadequate", -2), ("incomplete", -1),
+    ("inconsistent", -2), ("incorrect", -2), ("ineffective", -2), ("inefficient", -2),
+    ("insecure", -2), ("invalid", -2), ("issue", -1), ("issues", -1), ("lose", -2), ("loss", -3),
+    ("love", 3), ("lucky", 2), ("misleading", -3), ("mistake", -2), ("mistakes", -2),
+    ("negative", -2), ("nice", 2), ("optimal", 2), ("perfect", 3), ("pleasant", 2), ("pleased", 3),
+    ("poor", -2), ("poorly", -2), ("positive", 2), ("powerful", 2), ("precise", 2), ("problem", -2),
+    ("problematic", -2), ("problems", -2), ("productive", 2), ("profitable", 2), ("progress", 2),
+    ("promising", 2), ("proud", 2), ("recommend", 2), ("recommended", 2), ("reliable", 2),
+    ("resolved", 2), ("risk", -2), ("risks", -2), ("risky", -2), ("robust", 2), ("sad", -2),
+    ("safe", 2), ("satisfied", 2), ("secure", 2), ("severe", -2), ("simple", 1), ("slow", -1),
+    ("smooth", 1), ("solid", 2), ("stable", 2), ("strong", 2), ("struggle", -2), ("stupid", -2),
+    ("success", 2), ("successful", 3), ("successfully", 2), ("superb", 3), ("support", 1),
+    ("supported", 1), ("terrible", -3), ("thanks", 2), ("thorough", 1), ("threat", -2),
+    ("trust", 1), ("trusted", 2), ("ugly", -3), ("unclear", -1), ("unfortunately", -2),
+    ("unreliable", -2), ("unsafe", -2), ("unstable", -2), ("useful", 2), ("useless", -2),
+    ("valid", 1), ("valuable", 2), ("vulnerable", -2), ("warning", -1), ("weak", -2), ("well", 1),
+    ("win", 2), ("wonderful", 3), ("works", 1), ("worse", -3), ("worst", -3), ("worth", 2),
+    ("wrong", -2),
+];
+
+/// A negation this many words before a sentiment word flips its polarity ("not very good").
+const NEGATION_WINDOW: usize = 3;
+
+/// Tone of a text from -1.0 (negative) through 0.0 (neutral) to 1.0 (positive).
 fn calculate_sentiment(text: &str) -> f64 {
-    let positive_words = ["good", "great", "excellent", "positive", "successful", "correct", "accurate", "effective"];
-    let negative_words = ["bad", "poor", "terrible", "negative", "failed", "incorrect", "inaccurate", "ineffective"];
-    
-    let text_lower = text.to_lowercase();
-    let positive_count = positive_words.iter().map(|word| text_lower.matches(word).count()).sum::<usize>();
-    let negative_count = negative_words.iter().map(|word| text_lower.matches(word).count()).sum::<usize>();
-    
-    let total = positive_count + negative_count;
-    if total == 0 {
-        0.5 // Neutral
-    } else {
-        positive_count as f64 / total as f64
+    static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-z]+(?:'[a-z]+)?").unwrap());
+    let text_lower = text.to_lowercase().replace('\u{2019}', "'");
+    let is_negation = |word: &str| matches!(word, "not" | "no" | "never" | "cannot") || word.ends_with("n't");
+    
+    let mut total = 0;
+    // Negation does not reach across clause punctuation
+    for clause in text_lower.split(['.', '!', '?', ';', ':', ',']) {
+        let words: Vec<&str> = WORD.find_iter(clause).map(|m| m.as_str()).collect();
+        for (i, word) in words.iter().enumerate() {
+            let Ok(index) = SENTIMENT_LEXICON.binary_search_by(|(entry, _)| entry.cmp(word)) else {
+                continue;
+            };
+            let negated = words[i.saturating_sub(NEGATION_WINDOW)..i].iter().any(|w| is_negation(w));
+            let polarity = SENTIMENT_LEXICON[index].1;
+            total += if negated { -polarity } else { polarity };
+        }
     }
+    
+    // Squash the unbounded sum into -1..1; one mild word gives about +-0.25
+    let total = total as f64;
+    total / (total * total + 15.0).sqrt()
 }
 
 fn compare_semantics(chunk1: &SemanticChunk, chunk2: &SemanticChunk, args: &Args) -> SemanticDiff {
-    let similarity_score = calculate_similarity_score(chunk1, chunk2);
+    let similarity_score = calculate_similarity_score(chunk1, chunk2, args.metric);
     let concept_changes = compare_concepts(&chunk1.concepts, &chunk2.concepts);
     let fact_changes = compare_facts(&chunk1.facts, &chunk2.facts);
     let confidence_changes = compare_confidence(&chunk1.confidence_markers, &chunk2.confidence_markers);
-    let contradictions = find_contradictions(&chunk1.facts, &chunk2.facts);
-    let text_diff = if args.unified {
-        create_unified_diff(&chunk1.text, &chunk2.text, args.context)
+    let contradictions = find_contradictions(chunk1, chunk2);
+    let moves = if args.ignore_order { find_moves(&chunk1.text, &chunk2.text) } else { Vec::new() };
+    let mut word_changes = Vec::new();
+    let text_diff = if let Some(mode) = args.word_diff {
+        let mode = if args.color.enabled() { mode } else { WordDiffMode::Plain };
+        create_word_diff(&chunk1.text, &chunk2.text, args.context, mode, &moves, &mut word_changes)
+    } else if args.unified {
+        create_unified_diff(&chunk1.text, &chunk2.text, args.context, &moves)
     } else {
         create_simple_diff(&chunk1.text, &chunk2.text)
     };
     
     SemanticDiff {
         similarity_score,
+        metric: args.metric,
         concept_changes,
         fact_changes,
         confidence_changes,
         contradictions,
+        sentiment: SentimentChange {
+            old_sentiment: chunk1.sentiment,
+            new_sentiment: chunk2.sentiment,
+            shift: chunk2.sentiment - chunk1.sentiment,
+        },
         text_diff,
+        word_changes,
+        moves,
     }
 }
 
-fn calculate_similarity_score(chunk1: &SemanticChunk, chunk2: &SemanticChunk) -> f64 {
-    // Jaccard similarity for concepts
-    let intersection = chunk1.concepts.intersection(&chunk2.concepts).count();
-    let union = chunk1.concepts.union(&chunk2.concepts).count();
-    let concept_similarity = if union > 0 {
-        intersection as f64 / union as f64
-    } else {
-        1.0
+fn calculate_similarity_score(chunk1: &SemanticChunk, chunk2: &SemanticChunk, metric: SimilarityMetric) -> f64 {
+    let concept_similarity = match metric {
+        SimilarityMetric::Jaccard => jaccard_similarity(&chunk1.concepts, &chunk2.concepts),
+        SimilarityMetric::Cosine => cosine_similarity(&chunk1.text, &chunk2.text),
     };
     
     // Fact overlap
@@ -340,24 +1033,80 @@ fn calculate_similarity_score(chunk1: &SemanticChunk, chunk2: &SemanticChunk) ->
         1.0
     };
     
-    // Sentiment similarity
-    let sentiment_similarity = 1.0 - (chunk1.sentiment - chunk2.sentiment).abs();
+    // Sentiment similarity (scores span -1..1)
+    let sentiment_similarity = 1.0 - (chunk1.sentiment - chunk2.sentiment).abs() / 2.0;
     
     // Weighted average
-    (concept_similarity * 0.5 + fact_similarity * 0.3 + sentiment_similarity * 0.2)
+    concept_similarity * 0.5 + fact_similarity * 0.3 + sentiment_similarity * 0.2
 }
 
+fn jaccard_similarity(concepts1: &HashSet<String>, concepts2: &HashSet<String>) -> f64 {
+    let intersection = concepts1.intersection(concepts2).count();
+    let union = concepts1.union(concepts2).count();
+    if union > 0 {
+        intersection as f64 / union as f64
+    } else {
+        1.0
+    }
+}
+
+/// Cosine similarity of TF-IDF vectors over the lowercased word tokens of both texts.
+/// IDF is smoothed so that terms shared by both texts keep a non-zero weight.
+fn cosine_similarity(text1: &str, text2: &str) -> f64 {
+    static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\w+").unwrap());
+    let term_frequencies = |text: &str| {
+        let mut tf: HashMap<String, f64> = HashMap::new();
+        for word in WORD.find_iter(text) {
+            *tf.entry(word.as_str().to_lowercase()).or_insert(0.0) += 1.0;
+        }
+        tf
+    };
+    let tf1 = term_frequencies(text1);
+    let tf2 = term_frequencies(text2);
+    
+    if tf1.is_empty() && tf2.is_empty() {
+        return 1.0;
+    }
+    
+    // ln((1 + docs) / (1 + df)) + 1 with two documents
+    let idf = |term: &str| {
+        let df = tf1.contains_key(term) as usize + tf2.contains_key(term) as usize;
+        (3.0 / (1.0 + df as f64)).ln() + 1.0
+    };
+    let norm = |tf: &HashMap<String, f64>| {
+        tf.iter().map(|(term, count)| (count * idf(term)).powi(2)).sum::<f64>().sqrt()
+    };
+    
+    let dot: f64 = tf1.iter()
+        .filter_map(|(term, count1)| tf2.get(term).map(|count2| count1 * count2 * idf(term).powi(2)))
+        .sum();
+    let denominator = norm(&tf1) * norm(&tf2);
+    if denominator > 0.0 {
+        dot / denominator
+    } else {
+        0.0
+    }
+}
+
+/// Word overlap above which two statements say the same thing.
+const SIMILAR_OVERLAP: f64 = 0.6;
+
 fn facts_similar(fact1: &str, fact2: &str) -> bool {
     // Simple similarity check - could be enhanced with more sophisticated NLP
-    let words1: HashSet<&str> = fact1.split_whitespace().collect();
-    let words2: HashSet<&str> = fact2.split_whitespace().collect();
+    word_overlap(fact1, fact2) > SIMILAR_OVERLAP
+}
+
+/// Shared words over all words of the two texts, 0.0 when both are empty.
+fn word_overlap(text1: &str, text2: &str) -> f64 {
+    let words1: HashSet<&str> = text1.split_whitespace().collect();
+    let words2: HashSet<&str> = text2.split_whitespace().collect();
     let intersection = words1.intersection(&words2).count();
     let union = words1.union(&words2).count();
     
     if union > 0 {
-        intersection as f64 / union as f64 > 0.6
+        intersection as f64 / union as f64
     } else {
-        false
+        0.0
     }
 }
 
@@ -448,25 +1197,150 @@ fn calculate_confidence_level(markers: &[String]) -> f64 {
     }
 }
 
-fn find_contradictions(facts1: &[String], facts2: &[String]) -> Vec<Contradiction> {
+fn find_contradictions(chunk1: &SemanticChunk, chunk2: &SemanticChunk) -> Vec<Contradiction> {
     let mut contradictions = Vec::new();
     
     // Simple contradiction detection - look for opposite statements
-    for fact1 in facts1 {
-        for fact2 in facts2 {
+    for fact1 in &chunk1.facts {
+        for fact2 in &chunk2.facts {
             if might_contradict(fact1, fact2) {
                 contradictions.push(Contradiction {
                     statement1: fact1.clone(),
                     statement2: fact2.clone(),
                     contradiction_type: "potential_contradiction".to_string(),
+                    value1: None,
+                    value2: None,
                 });
             }
         }
     }
     
+    // Sentences about the same subject that disagree on a number or on negation
+    let sentences1 = split_sentences(&chunk1.text);
+    let sentences2 = split_sentences(&chunk2.text);
+    for sentence1 in &sentences1 {
+        for sentence2 in &sentences2 {
+            if sentence1 == sentence2 || contradictions.iter().any(|c| &c.statement1 == sentence1 && &c.statement2 == sentence2) {
+                continue;
+            }
+            if let Some(contradiction) = numeric_mismatch(sentence1, sentence2).or_else(|| negation_mismatch(sentence1, sentence2)) {
+                contradictions.push(contradiction);
+            }
+        }
+    }
+    
     contradictions
 }
 
+/// Split text into sentences at `.`, `!` or `?` followed by whitespace, and at line breaks.
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    
+    while let Some((i, c)) = chars.next() {
+        let at_end = match chars.peek() {
+            None => true,
+            Some(&(_, next)) => c == '\n' || (matches!(c, '.' | '!' | '?') && next.is_whitespace()),
+        };
+        if at_end {
+            let sentence = text[start..i + c.len_utf8()].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+            start = i + c.len_utf8();
+        }
+    }
+    
+    sentences
+}
+
+/// Lowercased words of a sentence, leaving out numbers.
+fn subject_words(sentence: &str) -> HashSet<String> {
+    static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[a-zA-Z][a-zA-Z']*\b").unwrap());
+    WORD.find_iter(sentence)
+        .map(|m| m.as_str().to_lowercase())
+        .collect()
+}
+
+/// Whether two sentences talk about the same thing, judged by their non-numeric words.
+fn same_subject(words1: &HashSet<String>, words2: &HashSet<String>) -> bool {
+    let common = words1.intersection(words2).count();
+    let union = words1.union(words2).count();
+    common >= 2 && common as f64 / union as f64 >= 0.6
+}
+
+/// Numbers in a sentence, with any currency symbol, scale or unit suffix ("$5M", "12%").
+fn numeric_values(sentence: &str) -> Vec<String> {
+    static NUMBER: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)[$€£]?\d+(?:[.,]\d+)*(?:\s*(?:%|percent|thousand|million|billion|trillion)|[kmb]\b)?").unwrap()
+    });
+    NUMBER.find_iter(sentence)
+        .map(|m| m.as_str().split_whitespace().collect::<String>())
+        .collect()
+}
+
+fn numeric_mismatch(sentence1: &str, sentence2: &str) -> Option<Contradiction> {
+    let values1 = numeric_values(sentence1);
+    let values2 = numeric_values(sentence2);
+    if values1.is_empty() || values2.is_empty() || values1 == values2 {
+        return None;
+    }
+    if !same_subject(&subject_words(sentence1), &subject_words(sentence2)) {
+        return None;
+    }
+    
+    let only_in = |values: &[String], other: &[String]| -> Vec<String> {
+        values.iter().filter(|v| !other.contains(v)).cloned().collect()
+    };
+    let differing1 = only_in(&values1, &values2);
+    let differing2 = only_in(&values2, &values1);
+    if differing1.is_empty() && differing2.is_empty() {
+        // Same numbers in a different order
+        return None;
+    }
+    
+    Some(Contradiction {
+        statement1: sentence1.to_string(),
+        statement2: sentence2.to_string(),
+        contradiction_type: "numeric_mismatch".to_string(),
+        value1: Some(differing1.join(", ")),
+        value2: Some(differing2.join(", ")),
+    })
+}
+
+/// "is safe" vs "is not safe": the same words, but only one sentence negated.
+fn negation_mismatch(sentence1: &str, sentence2: &str) -> Option<Contradiction> {
+    let negations = ["not", "no", "never", "cannot", "isn't", "aren't", "wasn't", "weren't",
+                     "don't", "doesn't", "didn't", "won't", "can't", "shouldn't"];
+    let is_negation = |word: &String| negations.contains(&word.as_str());
+    
+    let words1 = subject_words(sentence1);
+    let words2 = subject_words(sentence2);
+    let negated1 = words1.iter().any(is_negation);
+    let negated2 = words2.iter().any(is_negation);
+    if negated1 == negated2 {
+        return None;
+    }
+    
+    let affirmative = |words: &HashSet<String>| -> HashSet<String> {
+        words.iter().filter(|w| !is_negation(w)).cloned().collect()
+    };
+    let (core1, core2) = (affirmative(&words1), affirmative(&words2));
+    let common = core1.intersection(&core2).count();
+    if common < 2 || (common as f64 / core1.union(&core2).count() as f64) < 0.8 {
+        return None;
+    }
+    
+    Some(Contradiction {
+        statement1: sentence1.to_string(),
+        statement2: sentence2.to_string(),
+        contradiction_type: "negation".to_string(),
+        value1: None,
+        value2: None,
+    })
+}
+
 fn might_contradict(fact1: &str, fact2: &str) -> bool {
     let opposing_pairs = [
         ("increase", "decrease"),
@@ -498,61 +1372,378 @@ fn might_contradict(fact1: &str, fact2: &str) -> bool {
     false
 }
 
-fn create_unified_diff(text1: &str, text2: &str, context: usize) -> Vec<DiffLine> {
+/// One step of an edit script: an index into the old or new line list. Equal lines are
+/// given by their old index, as both sides read the same.
+#[derive(Debug, Clone, Copy)]
+enum Edit {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+    /// A line of a paragraph --ignore-order found elsewhere, where it left and where it arrived
+    MovedFrom(usize),
+    MovedTo(usize),
+}
+
+/// Myers' O(ND) shortest edit script between two line lists.
+fn myers_diff(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize;
+    
+    let mut v = vec![0isize; 2 * max + 2];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    
+    // Walk the trace backwards to recover the path
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal((x - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    
+    edits.reverse();
+    edits
+}
+
+/// Group the changes of an edit script into hunks of `context` surrounding edits,
+/// merging those whose context would overlap. Returns `start..end` ranges into `edits`.
+fn hunk_ranges(edits: &[Edit], context: usize) -> Vec<(usize, usize)> {
+    let changes: Vec<usize> = edits.iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    
+    let Some(&first_change) = changes.first() else {
+        return Vec::new();
+    };
+    
+    let mut hunks = Vec::new();
+    let mut hunk_start = first_change.saturating_sub(context);
+    let mut hunk_end = first_change;
+    for &change in &changes[1..] {
+        // Split only when more than 2 * context unchanged lines separate the changes
+        if change - hunk_end - 1 > 2 * context {
+            hunks.push((hunk_start, (hunk_end + context + 1).min(edits.len())));
+            hunk_start = change - context;
+        }
+        hunk_end = change;
+    }
+    hunks.push((hunk_start, (hunk_end + context + 1).min(edits.len())));
+    hunks
+}
+
+fn hunk_header(edits: &[Edit], start: usize, end: usize) -> DiffLine {
+    let hunk = &edits[start..end];
+    
+    // Position of the hunk in each file, counting from the first line it touches
+    let in_old = |e: &&Edit| !matches!(e, Edit::Insert(_) | Edit::MovedTo(_));
+    let in_new = |e: &&Edit| !matches!(e, Edit::Delete(_) | Edit::MovedFrom(_));
+    let old_start = edits[..start].iter().filter(in_old).count();
+    let new_start = edits[..start].iter().filter(in_new).count();
+    let old_len = hunk.iter().filter(in_old).count();
+    let new_len = hunk.iter().filter(in_new).count();
+    
+    DiffLine {
+        line_type: LineType::HunkHeader,
+        content: format!("@@ -{},{} +{},{} @@",
+                         if old_len > 0 { old_start + 1 } else { old_start }, old_len,
+                         if new_len > 0 { new_start + 1 } else { new_start }, new_len),
+        line_number: None,
+    }
+}
+
+/// The line edit script, with the lines of `moves` never matched in place: they leave as
+/// `MovedFrom` and arrive as `MovedTo` instead of being deleted and inserted.
+fn line_edits(lines1: &[&str], lines2: &[&str], moves: &[MoveChange]) -> Vec<Edit> {
+    if moves.is_empty() {
+        return myers_diff(lines1, lines2);
+    }
+    
+    let moved = |span: (usize, usize), line: usize| (span.0..=span.1).contains(&(line + 1));
+    let moved1: Vec<bool> = (0..lines1.len()).map(|i| moves.iter().any(|m| moved(m.old_lines, i))).collect();
+    let moved2: Vec<bool> = (0..lines2.len()).map(|j| moves.iter().any(|m| moved(m.new_lines, j))).collect();
+    // Keys no other line can equal stand in for the moved lines
+    let keys = |lines: &[&str], moved: &[bool], side: char| -> Vec<String> {
+        lines.iter().zip(moved).enumerate()
+            .map(|(i, (line, &moved))| if moved { format!("\0{}{}", side, i) } else { line.to_string() })
+            .collect()
+    };
+    let keys1 = keys(lines1, &moved1, '<');
+    let keys2 = keys(lines2, &moved2, '>');
+    
+    myers_diff(&keys1.iter().map(String::as_str).collect::<Vec<_>>(), &keys2.iter().map(String::as_str).collect::<Vec<_>>())
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Delete(i) if moved1[i] => Edit::MovedFrom(i),
+            Edit::Insert(j) if moved2[j] => Edit::MovedTo(j),
+            edit => edit,
+        })
+        .collect()
+}
+
+/// A line of a moved paragraph, marked `<` where it left and `>` where it arrived.
+fn moved_line(edit: Edit, lines1: &[&str], lines2: &[&str]) -> Option<DiffLine> {
+    let (marker, line, index) = match edit {
+        Edit::MovedFrom(i) => ('<', lines1[i], i),
+        Edit::MovedTo(j) => ('>', lines2[j], j),
+        _ => return None,
+    };
+    Some(DiffLine {
+        line_type: LineType::Moved,
+        content: format!("{}{}", marker, line),
+        line_number: Some(index + 1),
+    })
+}
+
+fn create_unified_diff(text1: &str, text2: &str, context: usize, moves: &[MoveChange]) -> Vec<DiffLine> {
     let lines1: Vec<&str> = text1.lines().collect();
     let lines2: Vec<&str> = text2.lines().collect();
+    let edits = line_edits(&lines1, &lines2, moves);
     
     let mut diff_lines = Vec::new();
+    for (start, end) in hunk_ranges(&edits, context) {
+        diff_lines.push(hunk_header(&edits, start, end));
+        
+        for edit in &edits[start..end] {
+            diff_lines.push(match *edit {
+                Edit::Equal(i) => DiffLine {
+                    line_type: LineType::Context,
+                    content: format!(" {}", lines1[i]),
+                    line_number: Some(i + 1),
+                },
+                Edit::Delete(i) => DiffLine {
+                    line_type: LineType::Removed,
+                    content: format!("-{}", lines1[i]),
+                    line_number: Some(i + 1),
+                },
+                Edit::Insert(j) => DiffLine {
+                    line_type: LineType::Added,
+                    content: format!("+{}", lines2[j]),
+                    line_number: Some(j + 1),
+                },
+                Edit::MovedFrom(_) | Edit::MovedTo(_) => moved_line(*edit, &lines1, &lines2).unwrap(),
+            });
+        }
+    }
     
-    // Simple line-by-line diff (could be enhanced with proper diff algorithm)
-    let max_lines = lines1.len().max(lines2.len());
+    diff_lines
+}
+
+/// Like the unified diff, but each removed line is paired with the added line that
+/// replaced it and shown once, with the changed words marked inline.
+fn create_word_diff(
+    text1: &str,
+    text2: &str,
+    context: usize,
+    mode: WordDiffMode,
+    moves: &[MoveChange],
+    word_changes: &mut Vec<WordChange>,
+) -> Vec<DiffLine> {
+    let lines1: Vec<&str> = text1.lines().collect();
+    let lines2: Vec<&str> = text2.lines().collect();
+    let edits = line_edits(&lines1, &lines2, moves);
     
-    for i in 0..max_lines {
-        let line1 = lines1.get(i);
-        let line2 = lines2.get(i);
+    let mut diff_lines = Vec::new();
+    for (start, end) in hunk_ranges(&edits, context) {
+        diff_lines.push(hunk_header(&edits, start, end));
         
-        match (line1, line2) {
-            (Some(l1), Some(l2)) => {
-                if l1 != l2 {
-                    diff_lines.push(DiffLine {
-                        line_type: LineType::Removed,
-                        content: format!("-{}", l1),
-                        line_number: Some(i + 1),
-                    });
-                    diff_lines.push(DiffLine {
-                        line_type: LineType::Added,
-                        content: format!("+{}", l2),
-                        line_number: Some(i + 1),
-                    });
-                } else {
-                    diff_lines.push(DiffLine {
-                        line_type: LineType::Context,
-                        content: format!(" {}", l1),
-                        line_number: Some(i + 1),
-                    });
-                }
+        let mut i = start;
+        while i < end {
+            if let Edit::Equal(old) = edits[i] {
+                diff_lines.push(DiffLine {
+                    line_type: LineType::Context,
+                    content: format!(" {}", lines1[old]),
+                    line_number: Some(old + 1),
+                });
+                i += 1;
+                continue;
+            }
+            
+            // A block of consecutive deletions and insertions; pair them up in order, leaving
+            // moved lines out
+            let block_end = edits[i..end].iter()
+                .position(|e| matches!(e, Edit::Equal(..)))
+                .map_or(end, |offset| i + offset);
+            diff_lines.extend(edits[i..block_end].iter().filter_map(|&e| moved_line(e, &lines1, &lines2)));
+            let removed: Vec<usize> = edits[i..block_end].iter()
+                .filter_map(|e| if let Edit::Delete(old) = e { Some(*old) } else { None })
+                .collect();
+            let added: Vec<usize> = edits[i..block_end].iter()
+                .filter_map(|e| if let Edit::Insert(new) = e { Some(*new) } else { None })
+                .collect();
+            
+            for (&old, &new) in removed.iter().zip(&added) {
+                let (rendered, changes) = diff_words(lines1[old], lines2[new], old + 1, new + 1, mode);
+                word_changes.extend(changes);
+                diff_lines.push(DiffLine {
+                    line_type: LineType::Modified,
+                    content: format!("~{}", rendered),
+                    line_number: Some(new + 1),
+                });
             }
-            (Some(l1), None) => {
+            for &old in removed.iter().skip(added.len()) {
                 diff_lines.push(DiffLine {
                     line_type: LineType::Removed,
-                    content: format!("-{}", l1),
-                    line_number: Some(i + 1),
+                    content: format!("-{}", lines1[old]),
+                    line_number: Some(old + 1),
                 });
             }
-            (None, Some(l2)) => {
+            for &new in added.iter().skip(removed.len()) {
                 diff_lines.push(DiffLine {
                     line_type: LineType::Added,
-                    content: format!("+{}", l2),
-                    line_number: Some(i + 1),
+                    content: format!("+{}", lines2[new]),
+                    line_number: Some(new + 1),
                 });
             }
-            (None, None) => break,
+            i = block_end;
         }
     }
     
     diff_lines
 }
 
+/// Split a line into words, runs of whitespace and single punctuation characters.
+/// Concatenating the tokens gives back the original line.
+fn tokenize_words(line: &str) -> Vec<&str> {
+    let class = |c: char| if c.is_alphanumeric() || c == '_' { 0 } else if c.is_whitespace() { 1 } else { 2 };
+    
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends_token = match chars.peek() {
+            None => true,
+            // Punctuation is never grouped, so "foo()." compares bracket by bracket
+            Some(&(_, next)) => class(c) == 2 || class(next) != class(c),
+        };
+        if ends_token {
+            let next_start = i + c.len_utf8();
+            tokens.push(&line[start..next_start]);
+            start = next_start;
+        }
+    }
+    tokens
+}
+
+/// Diff two lines token by token, rendering the new line with inline markers and
+/// returning one `Modified`, `Removed` or `Added` change per differing run.
+fn diff_words(
+    old_line: &str,
+    new_line: &str,
+    old_number: usize,
+    new_number: usize,
+    mode: WordDiffMode,
+) -> (String, Vec<WordChange>) {
+    let old_tokens = tokenize_words(old_line);
+    let new_tokens = tokenize_words(new_line);
+    let edits = myers_diff(&old_tokens, &new_tokens);
+    
+    let mut rendered = String::new();
+    let mut changes = Vec::new();
+    let mut removed = String::new();
+    let mut added = String::new();
+    
+    let mut flush = |rendered: &mut String, removed: &mut String, added: &mut String| {
+        if removed.is_empty() && added.is_empty() {
+            return;
+        }
+        match mode {
+            WordDiffMode::Plain => {
+                if !removed.is_empty() {
+                    rendered.push_str(&format!("{{-{}-}}", removed));
+                }
+                if !added.is_empty() {
+                    rendered.push_str(&format!("{{+{}+}}", added));
+                }
+            }
+            WordDiffMode::Color => {
+                if !removed.is_empty() {
+                    rendered.push_str(&format!("\x1b[31m{}\x1b[0m", removed));
+                }
+                if !added.is_empty() {
+                    rendered.push_str(&format!("\x1b[32m{}\x1b[0m", added));
+                }
+            }
+        }
+        
+        let old_value = (!removed.is_empty()).then(|| std::mem::take(removed));
+        let new_value = (!added.is_empty()).then(|| std::mem::take(added));
+        let change_type = match (&old_value, &new_value) {
+            (Some(_), Some(_)) => ChangeType::Modified,
+            (Some(_), None) => ChangeType::Removed,
+            _ => ChangeType::Added,
+        };
+        changes.push(WordChange {
+            change_type,
+            old_value,
+            new_value,
+            old_line: old_number,
+            new_line: new_number,
+        });
+    };
+    
+    for edit in edits {
+        match edit {
+            Edit::Equal(i) => {
+                flush(&mut rendered, &mut removed, &mut added);
+                rendered.push_str(old_tokens[i]);
+            }
+            Edit::Delete(i) => removed.push_str(old_tokens[i]),
+            Edit::Insert(j) => added.push_str(new_tokens[j]),
+            // Only line edit scripts mark moves
+            Edit::MovedFrom(_) | Edit::MovedTo(_) => {}
+        }
+    }
+    flush(&mut rendered, &mut removed, &mut added);
+    
+    (rendered, changes)
+}
+
 fn create_simple_diff(text1: &str, text2: &str) -> Vec<DiffLine> {
     if text1 == text2 {
         vec![DiffLine {
@@ -584,7 +1775,7 @@ fn output_diff(diff: &SemanticDiff, args: &Args) -> Result<()> {
     
     // Text output
     if args.similarity_score {
-        println!("Semantic similarity: {:.3}", diff.similarity_score);
+        println!("Semantic similarity: {:.3} ({})", diff.similarity_score, diff.metric.name());
         println!();
     }
     
@@ -595,6 +1786,7 @@ fn output_diff(diff: &SemanticDiff, args: &Args) -> Result<()> {
                 ChangeType::Added => "+",
                 ChangeType::Removed => "-",
                 ChangeType::Modified => "~",
+                ChangeType::Moved => ">",
             };
             println!("{} {}", symbol, change.concept);
         }
@@ -612,7 +1804,24 @@ fn output_diff(diff: &SemanticDiff, args: &Args) -> Result<()> {
                         println!("~ {} -> {}", old, new);
                     }
                 }
+                ChangeType::Moved => println!("> {}", change.fact),
+            }
+        }
+        println!();
+    }
+    
+    if !diff.moves.is_empty() {
+        let lines = |(first, last): (usize, usize)| {
+            if first == last { first.to_string() } else { format!("{}-{}", first, last) }
+        };
+        println!("=== Moved ===");
+        for change in &diff.moves {
+            let first_line = change.content.lines().next().unwrap_or("");
+            print!("> lines {} -> {}: {}", lines(change.old_lines), lines(change.new_lines), first_line);
+            if change.similarity < 1.0 {
+                print!(" (edited, {:.2} word overlap)", change.similarity);
             }
+            println!();
         }
         println!();
     }
@@ -626,43 +1835,60 @@ fn output_diff(diff: &SemanticDiff, args: &Args) -> Result<()> {
         println!();
     }
     
+    if args.sentiment_diff {
+        let sentiment = &diff.sentiment;
+        println!("=== Sentiment ===");
+        println!("{:+.2} ({}) -> {:+.2} ({}), shift {:+.2}",
+                 sentiment.old_sentiment, sentiment_label(sentiment.old_sentiment),
+                 sentiment.new_sentiment, sentiment_label(sentiment.new_sentiment),
+                 sentiment.shift);
+        println!();
+    }
+    
     if args.contradiction_check && !diff.contradictions.is_empty() {
         println!("=== Potential Contradictions ===");
         for contradiction in &diff.contradictions {
-            println!("! {} <-> {}", contradiction.statement1, contradiction.statement2);
+            println!("! [{}] {} <-> {}", contradiction.contradiction_type, contradiction.statement1, contradiction.statement2);
+            if let (Some(value1), Some(value2)) = (&contradiction.value1, &contradiction.value2) {
+                println!("    {} vs {}", value1, value2);
+            }
         }
         println!();
     }
     
-    if args.unified {
+    if args.unified || args.word_diff.is_some() {
         println!("=== Text Diff ===");
+        let use_color = args.color.enabled();
         for line in &diff.text_diff {
-            println!("{}", line.content);
+            let sgr = match line.line_type {
+                LineType::Added => "32",
+                LineType::Removed => "31",
+                LineType::HunkHeader => "36",
+                LineType::Moved => "35",
+                LineType::Context | LineType::Modified => "",
+            };
+            if use_color && !sgr.is_empty() {
+                println!("\x1b[{}m{}\x1b[0m", sgr, line.content);
+            } else {
+                println!("{}", line.content);
+            }
         }
     }
     
     Ok(())
 }
 
+fn sentiment_label(score: f64) -> &'static str {
+    if score >= 0.05 {
+        "positive"
+    } else if score <= -0.05 {
+        "negative"
+    } else {
+        "neutral"
+    }
+}
+
 fn output_json(diff: &SemanticDiff) -> Result<()> {
-    let mut output = serde_json::Map::new();
-    
-    output.insert("similarity_score".to_string(), 
-                  serde_json::Value::Number(serde_json::Number::from_f64(diff.similarity_score).unwrap()));
-    
-    output.insert("concept_changes".to_string(), 
-                  serde_json::Value::Number(serde_json::Number::from(diff.concept_changes.len())));
-    
-    output.insert("fact_changes".to_string(), 
-                  serde_json::Value::Number(serde_json::Number::from(diff.fact_changes.len())));
-    
-    output.insert("confidence_changes".to_string(), 
-                  serde_json::Value::Number(serde_json::Number::from(diff.confidence_changes.len())));
-    
-    output.insert("contradictions".to_string(), 
-                  serde_json::Value::Number(serde_json::Number::from(diff.contradictions.len())));
-    
-    println!("{}", serde_json::to_string_pretty(&output)?);
-    
+    println!("{}", serde_json::to_string_pretty(diff)?);
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn semdiff(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/chunks"))
+        .args(["--color", "never", "--chunk-by", "paragraph"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn unique_chunks_anchor_the_alignment() {
+    // The first and last paragraphs are anchors; between them the edited paragraph pairs
+    // with the one in its position and the inserted one is left over
+    let stdout = semdiff(&["old.txt", "new.txt"]);
+    assert!(stdout.starts_with(concat!(
+        "=== Chunked Comparison (paragraph, jaccard) ===\n",
+        "Overall similarity: 0.756 (weighted by chunk length)\n",
+        "4 chunk(s): 2 similar, 1 changed, 1 added, 0 removed\n",
+        "\n",
+        "Lowest-scoring chunks:\n",
+        "0.000  added    lines - -> 6\n",
+        "0.900  changed  lines 4 -> 4\n",
+        "1.000  similar  lines 1-2 -> 1-2\n",
+        "1.000  similar  lines 6 -> 8\n",
+    )), "{}", stdout);
+}
+
+#[test]
+fn lowest_chunks_limits_the_report() {
+    assert_eq!(
+        semdiff(&["--lowest-chunks", "1", "-u", "old.txt", "new.txt"]),
+        concat!(
+            "=== Chunked Comparison (paragraph, jaccard) ===\n",
+            "Overall similarity: 0.756 (weighted by chunk length)\n",
+            "4 chunk(s): 2 similar, 1 changed, 1 added, 0 removed\n",
+            "\n",
+            "Lowest-scoring chunks:\n",
+            "0.000  added    lines - -> 6\n",
+            "\n",
+            "##### lines - -> 6 (added) #####\n",
+            "=== Text Diff ===\n",
+            "@@ -0,0 +1,1 @@\n",
+            "+Deployment pipelines require manual approval tonight.\n",
+        )
+    );
+}
+
+#[test]
+fn json_weights_scores_by_words() {
+    let json: serde_json::Value = serde_json::from_str(&semdiff(&["--format", "json", "old.txt", "new.txt"])).unwrap();
+    // 10 + 0.9 * 6 + 0 * 6 + 5 out of 27 words
+    assert!((json["similarity_score"].as_f64().unwrap() - 20.4 / 27.0).abs() < 0.001, "{}", json);
+    assert_eq!(json["chunks"], 4);
+    assert_eq!(json["counts"], serde_json::json!({ "similar": 2, "changed": 1, "added": 1, "removed": 0 }));
+    
+    let weights: Vec<u64> = json["lowest_chunks"].as_array().unwrap().iter().map(|c| c["weight"].as_u64().unwrap()).collect();
+    assert_eq!(weights, [6, 6, 10, 5]);
+}
+
+#[test]
+fn ignore_case_aligns_case_only_edits() {
+    assert_eq!(
+        semdiff(&["-i", "--lowest-chunks", "0", "old.txt", "upper.txt"]),
+        "=== Chunked Comparison (paragraph, jaccard) ===\nOverall similarity: 1.000 (weighted by chunk length)\n3 chunk(s): 3 similar, 0 changed, 0 added, 0 removed\n"
+    );
+    assert!(semdiff(&["--lowest-chunks", "0", "old.txt", "upper.txt"]).contains("3 chunk(s): 2 similar, 1 changed"));
+}
@@ -0,0 +1,21 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// semdiff's output comparing `old` with `new`, written to files under `group/name` so
+/// each test has a directory of its own.
+pub fn semdiff(group: &str, name: &str, old: &str, new: &str, args: &[&str]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(group).join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("old.txt"), old).unwrap();
+    fs::write(dir.join("new.txt"), new).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(&dir)
+        .args(args)
+        .args(["old.txt", "new.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::semdiff;
+
+const OLD: &str = "Rust is fast. Rust is safe. Tokio runs tasks. Ticket ID-42 and ID-7 shipped.\n";
+const NEW: &str = "Go is fast. Go is simple. Go runs goroutines. Ticket ID-42 shipped.\n";
+
+/// The concept changes as sorted "change_type concept" strings.
+fn concept_changes(name: &str, args: &[&str]) -> Vec<String> {
+    let args = [&["--threshold", "0", "--concept-diff", "--format", "json"][..], args].concat();
+    let json: serde_json::Value = serde_json::from_str(&semdiff("concepts", name, OLD, NEW, &args)).unwrap();
+    let mut changes: Vec<String> = json["concept_changes"].as_array().unwrap().iter()
+        .map(|change| format!("{} {}", change["change_type"].as_str().unwrap(), change["concept"].as_str().unwrap()))
+        .collect();
+    changes.sort();
+    changes
+}
+
+#[test]
+fn every_concept_counts_by_default() {
+    let changes = concept_changes("default", &[]);
+    for expected in ["removed Rust", "removed Tokio", "removed safe", "added Go", "added goroutines", "added simple"] {
+        assert!(changes.contains(&expected.to_string()), "{}: {:?}", expected, changes);
+    }
+}
+
+#[test]
+fn min_concept_freq_drops_rare_concepts() {
+    assert_eq!(concept_changes("freq_2", &["--min-concept-freq", "2"]), ["added Go", "removed Rust"]);
+    // Go occurs three times, Rust only twice
+    assert_eq!(concept_changes("freq_3", &["--min-concept-freq", "3"]), ["added Go"]);
+}
+
+#[test]
+fn concept_regex_replaces_the_heuristics() {
+    // Group 1 when the pattern has one, the whole match otherwise
+    assert_eq!(concept_changes("group", &["--concept-regex", r"ID-(\d+)"]), ["removed 7"]);
+    assert_eq!(concept_changes("whole_match", &["--concept-regex", r"ID-\d+"]), ["removed ID-7"]);
+    assert_eq!(
+        concept_changes("with_freq", &["--concept-regex", r"\b[A-Z][a-z]+\b", "--min-concept-freq", "2"]),
+        ["added Go", "removed Rust"]
+    );
+}
+
+#[test]
+fn invalid_concept_regex_is_an_error() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .args(["--concept-regex", "(", "old.txt", "new.txt"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("semdiff: invalid --concept-regex:"), "{}", String::from_utf8_lossy(&output.stderr));
+}
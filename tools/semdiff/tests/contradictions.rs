@@ -0,0 +1,50 @@
+mod common;
+
+use common::semdiff;
+
+fn contradictions(name: &str, old: &str, new: &str) -> serde_json::Value {
+    let json: serde_json::Value = serde_json::from_str(&semdiff("contradictions", name, old, new, &["--contradiction-check", "--format", "json"])).unwrap();
+    json["contradictions"].clone()
+}
+
+#[test]
+fn changed_numbers_about_the_same_subject() {
+    assert_eq!(
+        contradictions("numeric", "Revenue was $5M in 2023.\n", "Revenue was $8M in 2023.\n"),
+        serde_json::json!([{
+            "statement1": "Revenue was $5M in 2023.", "statement2": "Revenue was $8M in 2023.",
+            "contradiction_type": "numeric_mismatch", "value1": "$5M", "value2": "$8M",
+        }])
+    );
+    
+    // A number about something else, or the same numbers in another order, is no contradiction
+    assert_eq!(contradictions("other_subject", "Revenue was $5M in 2023.\n", "Headcount was 40 in 2023.\n"), serde_json::json!([]));
+    assert_eq!(
+        contradictions("reordered", "Sales rose 5% and costs rose 8%.\n", "Costs rose 8% and sales rose 5%.\n"),
+        serde_json::json!([])
+    );
+}
+
+#[test]
+fn negating_the_same_statement() {
+    let found = contradictions("negation", "The bridge is safe for trucks.\n", "The bridge is not safe for trucks.\n");
+    assert_eq!(found.as_array().unwrap().len(), 1, "{}", found);
+    assert_eq!(found[0]["contradiction_type"], "negation");
+    assert_eq!(found[0]["value1"], serde_json::Value::Null);
+    
+    // Negating a different claim is not
+    assert_eq!(
+        contradictions("other_claim", "The bridge is safe for trucks.\n", "The tunnel is not open at night.\n"),
+        serde_json::json!([])
+    );
+}
+
+#[test]
+fn shown_despite_the_threshold_when_asked_for() {
+    let (old, new) = ("Revenue was $5M in 2023.\n", "Revenue was $8M in 2023.\n");
+    assert!(semdiff("contradictions", "unasked", old, new, &[]).starts_with("No significant semantic differences"));
+    assert_eq!(
+        semdiff("contradictions", "asked", old, new, &["--contradiction-check"]),
+        "=== Potential Contradictions ===\n! [numeric_mismatch] Revenue was $5M in 2023. <-> Revenue was $8M in 2023.\n    $5M vs $8M\n\n"
+    );
+}
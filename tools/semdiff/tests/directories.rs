@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn semdiff(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/dirs"))
+        .args(["--color", "never"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn summary_lists_every_file_on_either_side() {
+    let output = semdiff(&["old", "new"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(concat!(
+        "=== Directory Comparison (jaccard) ===\n",
+        "0.000  added    added.txt\n",
+        "0.454  changed  notes/changed.txt\n",
+        "0.000  removed  removed.txt\n",
+        "1.000  similar  same.txt\n",
+        "4 file(s): 1 similar, 1 changed, 1 added, 1 removed\n",
+    )), "{}", stdout);
+    assert!(!stdout.contains("##### same.txt"), "{}", stdout);
+}
+
+#[test]
+fn one_sided_files_diff_against_empty_text() {
+    let stdout = String::from_utf8(semdiff(&["-u", "old", "new"]).stdout).unwrap();
+    assert!(stdout.contains(concat!(
+        "##### added.txt (added) #####\n",
+        "=== Text Diff ===\n",
+        "@@ -0,0 +1,1 @@\n",
+        "+Monitoring dashboards track latency percentiles.\n",
+    )), "{}", stdout);
+    assert!(stdout.contains(concat!(
+        "##### removed.txt (removed) #####\n",
+        "=== Text Diff ===\n",
+        "@@ -1,1 +0,0 @@\n",
+        "-Legacy authentication tokens expire next quarter.\n",
+    )), "{}", stdout);
+}
+
+#[test]
+fn json_leaves_the_diff_out_of_similar_files() {
+    let json: serde_json::Value = serde_json::from_slice(&semdiff(&["--format", "json", "old", "new"]).stdout).unwrap();
+    assert_eq!(json["metric"], "jaccard");
+    let files = json["files"].as_array().unwrap();
+    let summary: Vec<(&str, &str, bool)> = files.iter()
+        .map(|f| (f["file"].as_str().unwrap(), f["status"].as_str().unwrap(), f.get("diff").is_some()))
+        .collect();
+    assert_eq!(summary, [
+        ("added.txt", "added", true),
+        ("notes/changed.txt", "changed", true),
+        ("removed.txt", "removed", true),
+        ("same.txt", "similar", false),
+    ]);
+}
+
+#[test]
+fn directory_needs_a_directory_to_compare_with() {
+    let output = semdiff(&["old", "new/same.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "semdiff: old is a directory; give a second directory to compare it with\n"
+    );
+    
+    assert_eq!(semdiff(&["--chunk-by", "paragraph", "old", "new"]).status.code(), Some(2));
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(args: &[&str]) -> String {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/reorder");
+    let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(fixtures)
+        .args(args)
+        .args(["--color", "never", "before.txt", "after.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn reordering_alone_is_not_significant_without_the_flag() {
+    assert_eq!(run(&["-u"]), "No significant semantic differences (similarity: 1.000, jaccard)\n");
+}
+
+#[test]
+fn moved_paragraphs_are_reported() {
+    let text = run(&["-u", "--ignore-order"]);
+    assert!(text.starts_with("=== Moved ===\n> lines 7 -> 1: The food is great.\n\n=== Text Diff ===\n"), "{}", text);
+    // Its lines are marked where they left and arrived, and nothing else changed
+    assert!(text.contains("\n>The food is great.\n"), "{}", text);
+    assert!(text.contains("\n<The food is great.\n"), "{}", text);
+    assert!(!text.lines().any(|line| line.starts_with("+T") || line.starts_with("-T")), "{}", text);
+}
+
+#[test]
+fn json_lists_moves() {
+    let json: serde_json::Value = serde_json::from_str(&run(&["--ignore-order", "--format", "json"])).unwrap();
+    assert_eq!(json["similarity_score"], 1.0);
+    assert_eq!(
+        json["moves"],
+        serde_json::json!([{
+            "change_type": "moved", "content": "The food is great.",
+            "old_lines": [7, 7], "new_lines": [1, 1], "similarity": 1.0,
+        }])
+    );
+}
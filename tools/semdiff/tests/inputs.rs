@@ -0,0 +1,76 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// A directory of the test's own holding old.txt to compare against.
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("inputs").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("old.txt"), "The cat sat on the mat.\n").unwrap();
+    dir
+}
+
+fn semdiff(name: &str, args: &[&str], stdin: Option<&str>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(fixture_dir(name))
+        .args(["--threshold", "0"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Dropping stdin closes it, so an empty pipe reads as empty text. semdiff may exit
+    // without reading, so a broken pipe is fine
+    let _ = child.stdin.take().unwrap().write_all(stdin.unwrap_or_default().as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn missing_file_names_the_file() {
+    for args in [["missing.txt", "old.txt"], ["old.txt", "missing.txt"]] {
+        let output = semdiff("missing", &args, None);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr(&output).starts_with("semdiff: missing.txt: "), "{}", stderr(&output));
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn piped_stdin_is_the_second_input() {
+    let omitted = semdiff("omitted", &["-u", "old.txt"], Some("Dogs chase cars.\n"));
+    assert!(omitted.status.success(), "{}", stderr(&omitted));
+    assert!(String::from_utf8_lossy(&omitted.stdout).contains("-The cat sat on the mat.\n+Dogs chase cars.\n"), "{}", String::from_utf8_lossy(&omitted.stdout));
+    
+    let dash = semdiff("dash", &["-u", "old.txt", "-"], Some("Dogs chase cars.\n"));
+    assert_eq!(dash.stdout, omitted.stdout);
+}
+
+#[test]
+fn stdin_for_both_inputs_is_a_usage_error() {
+    let output = semdiff("both", &["-", "-"], Some("text\n"));
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("stdin can only be used for one of the two inputs"), "{}", stderr(&output));
+}
+
+/// With a terminal on stdin there is nothing to read, so semdiff stops instead of waiting.
+#[cfg(target_os = "linux")]
+#[test]
+fn terminal_stdin_is_a_usage_error() {
+    // util-linux script(1) runs the command on a pseudo-terminal
+    let command = format!("{} old.txt", env!("CARGO_BIN_EXE_semdiff"));
+    let output = Command::new("script")
+        .current_dir(fixture_dir("terminal"))
+        .args(["-qec", &command, "/dev/null"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let transcript = String::from_utf8_lossy(&output.stdout);
+    assert!(transcript.contains("no second input: give two files or pipe text on stdin"), "{}", transcript);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::semdiff;
+
+const OLD: &str = "Paris is the capital of France. It has about 2 million people.\nThe museums are probably busy.\n";
+const NEW: &str = "Paris is the capital of France. It hosted the Olympics in 2024.\nThe museums are definitely busy.\n";
+
+fn report(name: &str, args: &[&str]) -> serde_json::Value {
+    let args = [&["--threshold", "0", "--format", "json"][..], args].concat();
+    serde_json::from_str(&semdiff("json_output", name, OLD, NEW, &args)).unwrap()
+}
+
+/// The README's JSON example, which should stay a subset of what semdiff writes.
+fn readme_example() -> serde_json::Value {
+    let readme = include_str!("../README.md");
+    let (_, rest) = readme.split_once("### JSON Format").unwrap();
+    let (_, block) = rest.split_once("```json\n").unwrap();
+    let (example, _) = block.split_once("```").unwrap();
+    serde_json::from_str(example).unwrap()
+}
+
+#[test]
+fn report_round_trips() {
+    let json = report("round_trip", &["--word-diff"]);
+    let reparsed: serde_json::Value = serde_json::from_str(&serde_json::to_string(&json).unwrap()).unwrap();
+    assert_eq!(reparsed, json);
+    assert!(!json["word_changes"].as_array().unwrap().is_empty(), "{}", json);
+}
+
+#[test]
+fn report_has_the_documented_fields() {
+    let json = report("fields", &[]);
+    assert!(json["similarity_score"].is_f64(), "{}", json);
+    assert_eq!(json["metric"], "jaccard");
+    for list in ["concept_changes", "fact_changes", "confidence_changes", "contradictions", "text_diff"] {
+        assert!(json[list].is_array(), "{}: {}", list, json);
+    }
+    for field in ["old_sentiment", "new_sentiment", "shift"] {
+        assert!(json["sentiment"][field].is_f64(), "{}: {}", field, json);
+    }
+    // Only present when asked for
+    assert!(json.get("word_changes").is_none(), "{}", json);
+    assert!(json.get("moves").is_none(), "{}", json);
+    
+    let fact = &json["fact_changes"][0];
+    for field in ["fact", "change_type", "old_value", "new_value"] {
+        assert!(fact.get(field).is_some(), "{}: {}", field, fact);
+    }
+    let line = &json["text_diff"][0];
+    for field in ["line_type", "content", "line_number"] {
+        assert!(line.get(field).is_some(), "{}: {}", field, line);
+    }
+}
+
+#[test]
+fn readme_example_matches_the_output() {
+    let json = report("readme", &[]);
+    let example = readme_example();
+    for (key, value) in example.as_object().unwrap() {
+        let actual = json.get(key).unwrap_or_else(|| panic!("README documents {} but the output has none", key));
+        // Entries of each list have the keys the README shows
+        if let (Some(documented), Some(written)) = (value.as_array().and_then(|v| v.first()), actual.as_array().and_then(|v| v.first())) {
+            for field in documented.as_object().unwrap().keys() {
+                assert!(written.get(field).is_some(), "{}.{}: {}", key, field, written);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// semdiff's JSON report comparing `old` with `new`, written to files named after the test.
+fn report(name: &str, old: &str, new: &str, metric: &str) -> serde_json::Value {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("metric").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("old.txt"), old).unwrap();
+    fs::write(dir.join("new.txt"), new).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(&dir)
+        .args(["--threshold", "0", "--format", "json", "--metric", metric, "old.txt", "new.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// The full report's score, or the summary's when nothing differed.
+fn score(report: &serde_json::Value) -> f64 {
+    report.get("similarity_score").unwrap_or(&report["similarity"]).as_f64().unwrap()
+}
+
+#[test]
+fn cosine_weighs_words_jaccard_skips() {
+    // Too short to be concepts, so Jaccard sees two empty sets
+    assert_eq!(score(&report("short_jaccard", "the cat\n", "the dog\n", "jaccard")), 1.0);
+    
+    // "the" has IDF 1, "cat" and "dog" ln(1.5) + 1: cosine 1 / (1 + idf²) ≈ 0.336,
+    // blended with full fact and sentiment similarity
+    let cosine = report("short_cosine", "the cat\n", "the dog\n", "cosine");
+    assert_eq!(cosine["metric"], "cosine");
+    assert!((score(&cosine) - 0.668).abs() < 0.001, "{}", score(&cosine));
+}
+
+#[test]
+fn cosine_of_disjoint_texts_is_zero() {
+    // Only the fact and sentiment halves of the score remain
+    let cosine = report("disjoint", "Apples grow on trees.\n", "Submarines dive under water.\n", "cosine");
+    assert!((score(&cosine) - 0.5).abs() < 0.001, "{}", score(&cosine));
+}
+
+#[test]
+fn identical_texts_are_one_under_both_metrics() {
+    for metric in ["jaccard", "cosine"] {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("metric").join("identical");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("same.txt"), "The server handles requests quickly.\n").unwrap();
+        
+        let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+            .current_dir(&dir)
+            .args(["--metric", metric, "same.txt", "same.txt"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("No significant semantic differences (similarity: 1.000, {})\n", metric)
+        );
+    }
+}
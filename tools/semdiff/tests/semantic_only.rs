@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn semdiff(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/hedging"))
+        .args(["--color", "never"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn hedging_only_edits_are_not_significant() {
+    assert_eq!(semdiff(&["--similarity-score", "hedged.txt", "confident.txt"]), "Semantic similarity: 0.833 (jaccard)\n\n");
+    assert_eq!(
+        semdiff(&["--semantic-only", "hedged.txt", "confident.txt"]),
+        "No significant semantic differences (similarity: 1.000, jaccard)\n"
+    );
+}
+
+#[test]
+fn other_edits_still_count() {
+    assert_eq!(
+        semdiff(&["--semantic-only", "--similarity-score", "hedged.txt", "reworded.txt"]),
+        "Semantic similarity: 0.833 (jaccard)\n\n"
+    );
+}
+
+#[test]
+fn confidence_diff_is_the_complementary_view() {
+    // The score ignores the markers, but the change in them is still reported
+    assert_eq!(
+        semdiff(&["--semantic-only", "--confidence-diff", "--similarity-score", "hedged.txt", "confident.txt"]),
+        concat!(
+            "Semantic similarity: 1.000 (jaccard)\n",
+            "\n",
+            "=== Confidence Changes ===\n",
+            "overall_confidence: 0.00 -> 0.60 (Markers: 2 -> 3)\n",
+            "\n",
+        )
+    );
+}
+
+#[test]
+fn chunks_differing_only_in_hedging_align_as_identical() {
+    assert_eq!(
+        semdiff(&["--semantic-only", "--chunk-by", "paragraph", "--lowest-chunks", "0", "hedged.txt", "confident.txt"]),
+        "=== Chunked Comparison (paragraph, jaccard) ===\nOverall similarity: 1.000 (weighted by chunk length)\n1 chunk(s): 1 similar, 0 changed, 0 added, 0 removed\n"
+    );
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::semdiff;
+
+#[test]
+fn shift_from_positive_to_negative() {
+    assert_eq!(
+        semdiff("sentiment", "flip", "The release is stable and reliable.\n", "The release is unstable. It is unreliable.\n", &["--threshold", "0", "--sentiment-diff"]),
+        "=== Sentiment ===\n+0.72 (positive) -> -0.72 (negative), shift -1.44\n\n"
+    );
+}
+
+#[test]
+fn neutral_text_and_one_mild_word() {
+    assert_eq!(
+        semdiff("sentiment", "mild", "The release shipped on Monday.\n", "The release works.\n", &["--threshold", "0", "--sentiment-diff"]),
+        "=== Sentiment ===\n+0.00 (neutral) -> +0.25 (positive), shift +0.25\n\n"
+    );
+}
+
+#[test]
+fn negation_flips_polarity_within_its_clause() {
+    // "not" turns "unreliable" positive but does not reach back past the comma to "stable"
+    assert_eq!(
+        semdiff("sentiment", "negation", "The release shipped on Monday.\n", "The release is stable, not unreliable.\n", &["--threshold", "0", "--sentiment-diff"]),
+        "=== Sentiment ===\n+0.00 (neutral) -> +0.72 (positive), shift +0.72\n\n"
+    );
+}
+
+#[test]
+fn json_always_carries_sentiment() {
+    let output = semdiff("sentiment", "json", "The release is stable and reliable.\n", "The release is unstable. It is unreliable.\n", &["--threshold", "0", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let sentiment = &json["sentiment"];
+    assert!((sentiment["old_sentiment"].as_f64().unwrap() - 0.718).abs() < 0.001, "{}", sentiment);
+    assert!((sentiment["new_sentiment"].as_f64().unwrap() + 0.718).abs() < 0.001, "{}", sentiment);
+    assert!((sentiment["shift"].as_f64().unwrap() + 1.437).abs() < 0.001, "{}", sentiment);
+}
+
+#[test]
+fn text_output_leaves_sentiment_out_without_the_flag() {
+    assert!(!semdiff("sentiment", "no_flag", "The release is stable.\n", "The release is unstable.\n", &["--threshold", "0"]).contains("=== Sentiment ==="));
+}
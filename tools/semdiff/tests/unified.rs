@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// `semdiff -u` of two texts, written to files named after the test.
+fn unified(name: &str, old: &str, new: &str) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("unified").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("old.txt"), old).unwrap();
+    fs::write(dir.join("new.txt"), new).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_semdiff"))
+        .current_dir(&dir)
+        .args(["-u", "--threshold", "0", "--color", "never", "old.txt", "new.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const CITIES: &str = "Berlin is large.\nMadrid is sunny.\n";
+
+/// Fourteen numbered lines with lines `changed` (1-based) replaced.
+fn numbered(changed: &[usize]) -> String {
+    (1..=14)
+        .map(|n| if changed.contains(&n) { format!("Changed line {n}.\n") } else { format!("Line number {n} here.\n") })
+        .collect()
+}
+
+#[test]
+fn insert_at_the_top() {
+    assert_eq!(
+        unified("top", CITIES, &format!("Paris is old.\n{CITIES}")),
+        "=== Text Diff ===\n@@ -1,2 +1,3 @@\n+Paris is old.\n Berlin is large.\n Madrid is sunny.\n"
+    );
+}
+
+#[test]
+fn empty_side_starts_at_line_zero() {
+    assert_eq!(
+        unified("empty_old", "", CITIES),
+        "=== Text Diff ===\n@@ -0,0 +1,2 @@\n+Berlin is large.\n+Madrid is sunny.\n"
+    );
+    assert_eq!(
+        unified("empty_new", CITIES, ""),
+        "=== Text Diff ===\n@@ -1,2 +0,0 @@\n-Berlin is large.\n-Madrid is sunny.\n"
+    );
+}
+
+#[test]
+fn hunks_merge_up_to_twice_the_context() {
+    // Six unchanged lines between the changes: the two contexts meet, one hunk
+    let headers = |text: &str| text.lines().filter(|line| line.starts_with("@@")).map(String::from).collect::<Vec<_>>();
+    assert_eq!(headers(&unified("merged", &numbered(&[]), &numbered(&[3, 10]))), ["@@ -1,13 +1,13 @@"]);
+    
+    // Seven: they split, with three context lines on each side
+    assert_eq!(headers(&unified("split", &numbered(&[]), &numbered(&[3, 11]))), ["@@ -1,6 +1,6 @@", "@@ -8,7 +8,7 @@"]);
+}
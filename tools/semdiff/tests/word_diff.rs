@@ -0,0 +1,47 @@
+mod common;
+
+use common::semdiff;
+
+const OLD: &str = "The cat sat on the mat.\nIt was warm.\n";
+const NEW: &str = "The dog sat on the rug.\nIt was warm.\nNew closing line here.\n";
+
+#[test]
+fn replaced_lines_show_once_with_inline_markers() {
+    assert_eq!(
+        semdiff("word_diff", "plain", OLD, NEW, &["--threshold", "0", "--word-diff", "--color", "never"]),
+        "=== Text Diff ===\n@@ -1,2 +1,3 @@\n~The {-cat-}{+dog+} sat on the {-mat-}{+rug+}.\n It was warm.\n+New closing line here.\n"
+    );
+}
+
+#[test]
+fn punctuation_compares_character_by_character() {
+    assert_eq!(
+        semdiff("word_diff", "punctuation", "Call foo() now.\n", "Call foo(bar) right now.\n", &["--threshold", "0", "--word-diff", "--color", "never"]),
+        "=== Text Diff ===\n@@ -1,1 +1,1 @@\n~Call foo({+bar+}) {+right +}now.\n"
+    );
+}
+
+#[test]
+fn color_needs_color_on() {
+    let colored = semdiff("word_diff", "color", OLD, NEW, &["--threshold", "0", "--word-diff=color", "--color", "always"]);
+    assert!(colored.contains("~The \x1b[31mcat\x1b[0m\x1b[32mdog\x1b[0m sat"), "{:?}", colored);
+    
+    // Falls back to plain markers
+    assert_eq!(semdiff("word_diff", "no_color", OLD, NEW, &["--threshold", "0", "--word-diff=color", "--color", "never"]), semdiff("word_diff", "plain_markers", OLD, NEW, &["--threshold", "0", "--word-diff", "--color", "never"]));
+}
+
+#[test]
+fn json_lists_each_changed_run() {
+    let json: serde_json::Value = serde_json::from_str(&semdiff("word_diff", "json", OLD, NEW, &["--threshold", "0", "--word-diff", "--format", "json"])).unwrap();
+    assert_eq!(
+        json["word_changes"],
+        serde_json::json!([
+            { "change_type": "modified", "old_value": "cat", "new_value": "dog", "old_line": 1, "new_line": 1 },
+            { "change_type": "modified", "old_value": "mat", "new_value": "rug", "old_line": 1, "new_line": 1 },
+        ])
+    );
+    
+    // Plain -u leaves them out
+    let json: serde_json::Value = serde_json::from_str(&semdiff("word_diff", "unified_json", OLD, NEW, &["--threshold", "0", "-u", "--format", "json"])).unwrap();
+    assert!(json.get("word_changes").is_none());
+}
@@ -1,9 +1,39 @@
 use clap::Parser;
+use serde::Deserialize;
 use std::collections::{HashMap, VecDeque};
-use std::io::{self, BufRead, BufReader};
-use std::time::{Duration, Instant};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::thread;
+use terminal_size::{terminal_size, Width};
+
+/// Switch to the alternate screen and hide the cursor, so the user's scrollback survives.
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+/// Show the cursor again and return to the normal screen.
+const LEAVE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+
+/// Set by the Ctrl-C handler; the render loop restores the terminal and exits.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Tokens/sec is measured over the tokens that arrived within this long before now.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Shortest span tokens/sec is measured over, so a few tokens arriving together don't read as
+/// a huge rate.
+const MIN_RATE_SPAN: Duration = Duration::from_millis(250);
+
+/// Box width when --width is not given and stdout is not a terminal.
+const DEFAULT_WIDTH: usize = 43;
+
+/// Narrowest box that still fits a label, a bar and a percentage.
+const MIN_WIDTH: usize = 30;
+
+/// NDJSON lines that could not be read as a token, reported once the screen is restored.
+static MALFORMED_LINES: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Parser, Debug)]
 #[command(name = "tokentop")]
@@ -33,9 +63,88 @@ struct Args {
     #[arg(long, default_value = "0.5")]
     confidence_threshold: f64,
     
+    /// Weight of each new tokens/sec measurement in its moving average (0-1, 1 = no smoothing)
+    #[arg(long, default_value = "0.3", value_parser = parse_smoothing)]
+    smoothing: f64,
+    
     /// Show raw tokens instead of analysis
     #[arg(long)]
     raw: bool,
+    
+    /// Read all of stdin, then print one summary without the full-screen display
+    #[arg(long, conflicts_with = "raw")]
+    once: bool,
+    
+    /// Format of the --once summary
+    #[arg(long, value_enum, default_value = "text", requires = "once")]
+    format: SummaryFormat,
+    
+    /// How tokens arrive on stdin
+    #[arg(long, value_enum, default_value = "text")]
+    input_format: InputFormat,
+    
+    /// Append the metrics shown at every display tick to this file
+    #[arg(long, value_name = "PATH")]
+    log: Option<PathBuf>,
+    
+    /// Format of --log records
+    #[arg(long, value_enum, default_value = "json", requires = "log")]
+    log_format: LogFormat,
+    
+    /// Width of the statistics box in columns (default: the terminal's width, re-read at
+    /// every tick)
+    #[arg(long, value_name = "COLUMNS", value_parser = clap::value_parser!(u16).range(MIN_WIDTH as i64..))]
+    width: Option<u16>,
+    
+    /// Run this shell command whenever a warning appears, with the warning text as $1 and
+    /// on stdin; its output is discarded
+    #[arg(long, value_name = "CMD", conflicts_with_all = ["raw", "once"])]
+    on_warning: Option<String>,
+    
+    /// Seconds before the same warning can run --on-warning again, should it clear and
+    /// come back
+    #[arg(long, value_name = "SECONDS", default_value = "60", requires = "on_warning")]
+    warning_cooldown: u64,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SummaryFormat {
+    Text,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum LogFormat {
+    /// One JSON object per line
+    Json,
+    /// Comma-separated values, with a header when the file is new
+    Csv,
+}
+
+const CSV_HEADER: &str = "timestamp,tokens_per_second,avg_perplexity,repetition_score,confidence_score,warnings";
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum InputFormat {
+    /// Plain text, split on whitespace; perplexity and confidence are estimated heuristically
+    Text,
+    /// One `{"token": "...", "logprob": -1.2}` object per line, as streamed by OpenAI-style APIs
+    Ndjson,
+}
+
+/// One streamed token with the log-probability the model gave it.
+#[derive(Debug, Deserialize)]
+struct TokenEvent {
+    token: String,
+    logprob: f64,
+}
+
+fn parse_smoothing(value: &str) -> Result<f64, String> {
+    let factor: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if factor > 0.0 && factor <= 1.0 {
+        Ok(factor)
+    } else {
+        Err("must be greater than 0 and at most 1".to_string())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,46 +153,53 @@ struct TokenStats {
     token: String,
     perplexity: f64,
     confidence: f64,
-    is_repetitive: bool,
 }
 
 #[derive(Debug)]
 struct AnalysisState {
     tokens_per_second: f64,
+    /// Whether `tokens_per_second` holds a measurement yet, so the average starts from the first one
+    rate_measured: bool,
     avg_perplexity: f64,
     repetition_score: f64,
     confidence_score: f64,
     detected_patterns: Vec<String>,
     warning_flags: Vec<String>,
     token_buffer: VecDeque<TokenStats>,
+    /// Tokens kept in `token_buffer`; `VecDeque::capacity` may be larger
+    buffer_size: usize,
     pattern_tracker: HashMap<String, usize>,
+    /// Occurrences of each 3-token phrase in `token_buffer`, keyed by the tokens joined with spaces
+    phrase_tracker: HashMap<String, usize>,
 }
 
 impl AnalysisState {
     fn new(buffer_size: usize) -> Self {
         Self {
             tokens_per_second: 0.0,
+            rate_measured: false,
             avg_perplexity: 0.0,
             repetition_score: 0.0,
             confidence_score: 0.0,
             detected_patterns: Vec::new(),
             warning_flags: Vec::new(),
             token_buffer: VecDeque::with_capacity(buffer_size),
+            buffer_size,
             pattern_tracker: HashMap::new(),
+            phrase_tracker: HashMap::new(),
         }
     }
     
     fn update(&mut self, token_stats: TokenStats, args: &Args) {
         // Add to buffer
-        if self.token_buffer.len() >= self.token_buffer.capacity() {
+        if self.token_buffer.len() >= self.buffer_size {
+            // The oldest token also starts the oldest phrase
+            if let Some(phrase) = self.phrase_at(0) {
+                decrement(&mut self.phrase_tracker, &phrase);
+            }
             if let Some(old_token) = self.token_buffer.pop_front() {
                 // Remove old pattern tracking
-                let count = self.pattern_tracker.get(&old_token.token).unwrap_or(&0);
-                if *count <= 1 {
-                    self.pattern_tracker.remove(&old_token.token);
-                } else {
-                    self.pattern_tracker.insert(old_token.token, count - 1);
-                }
+                decrement(&mut self.pattern_tracker, &old_token.token);
             }
         }
         
@@ -91,23 +207,53 @@ impl AnalysisState {
         *self.pattern_tracker.entry(token_stats.token.clone()).or_insert(0) += 1;
         
         self.token_buffer.push_back(token_stats);
+        if let Some(phrase) = self.token_buffer.len().checked_sub(3).and_then(|start| self.phrase_at(start)) {
+            *self.phrase_tracker.entry(phrase).or_insert(0) += 1;
+        }
         
         // Recalculate statistics
         self.calculate_metrics(args);
     }
     
-    fn calculate_metrics(&mut self, args: &Args) {
+    /// Fold the rate of the tokens within `RATE_WINDOW` before `now` into the moving average.
+    /// Called on every display tick, so the rate falls while no tokens arrive. The rate is
+    /// the intervals between those tokens over the time from the oldest to now, at least
+    /// `MIN_RATE_SPAN`, so a slow start is measured as it happened; with fewer than two
+    /// tokens their count over the window is used instead.
+    fn update_rate(&mut self, now: Instant, smoothing: f64) {
         if self.token_buffer.is_empty() {
             return;
         }
-        
-        let now = Instant::now();
-        
-        // Calculate tokens per second
-        let recent_tokens: Vec<_> = self.token_buffer.iter()
-            .filter(|t| now.duration_since(t.timestamp) < Duration::from_secs(1))
+        let window: Vec<Instant> = self.token_buffer.iter()
+            .map(|t| t.timestamp)
+            .filter(|&timestamp| now.saturating_duration_since(timestamp) < RATE_WINDOW)
             .collect();
-        self.tokens_per_second = recent_tokens.len() as f64;
+        let rate = match window.first() {
+            Some(&oldest) if window.len() > 1 => {
+                let span = now.saturating_duration_since(oldest).max(MIN_RATE_SPAN);
+                (window.len() - 1) as f64 / span.as_secs_f64()
+            }
+            _ => window.len() as f64 / RATE_WINDOW.as_secs_f64(),
+        };
+        
+        self.tokens_per_second = if self.rate_measured {
+            smoothing * rate + (1.0 - smoothing) * self.tokens_per_second
+        } else {
+            rate
+        };
+        self.rate_measured = true;
+    }
+    
+    /// The 3-token phrase starting at `start` in the buffer, if the buffer is long enough.
+    fn phrase_at(&self, start: usize) -> Option<String> {
+        let tokens: Vec<&str> = self.token_buffer.range(start..).take(3).map(|t| t.token.as_str()).collect();
+        (tokens.len() == 3).then(|| tokens.join(" "))
+    }
+    
+    fn calculate_metrics(&mut self, args: &Args) {
+        if self.token_buffer.is_empty() {
+            return;
+        }
         
         // Calculate average perplexity
         self.avg_perplexity = self.token_buffer.iter()
@@ -143,14 +289,13 @@ impl AnalysisState {
             .map(|t| t.token.clone())
             .collect();
         
-        // Check for 3-gram repetitions
-        for window in tokens.windows(3) {
-            let phrase = window.join(" ");
-            if let Some(&count) = self.pattern_tracker.get(&phrase) {
-                if count >= 3 {
-                    self.detected_patterns.push(format!("Repeated phrase: \"{}\" ({}x)", phrase, count));
-                }
-            }
+        // Check for 3-gram repetitions, most frequent first
+        let mut repeated: Vec<(&String, &usize)> = self.phrase_tracker.iter()
+            .filter(|(_, &count)| count >= 3)
+            .collect();
+        repeated.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        for (phrase, count) in repeated {
+            self.detected_patterns.push(format!("Repeated phrase: \"{}\" ({}x)", phrase, count));
         }
         
         // Check for listing patterns
@@ -209,64 +354,392 @@ fn main() {
     let args = Args::parse();
     
     if args.raw {
-        run_raw_mode();
+        run_raw_mode(args.input_format);
+        return;
+    }
+    
+    if args.once {
+        run_once(&args);
         return;
     }
     
     let (tx, rx) = mpsc::channel();
     
-    // Spawn input reader thread
+    // Spawn input reader thread; dropping `tx` at end of input disconnects the channel
+    let input_format = args.input_format;
     thread::spawn(move || {
         let stdin = io::stdin();
         let reader = BufReader::new(stdin.lock());
         
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                let tokens = tokenize_line(&line);
-                for token in tokens {
-                    let stats = analyze_token(&token);
-                    if tx.send(stats).is_err() {
-                        break;
-                    }
+        for line in reader.lines().map_while(Result::ok) {
+            for stats in read_tokens(&line, input_format) {
+                if tx.send(stats).is_err() {
+                    return;
                 }
             }
         }
     });
     
+    if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+        eprintln!("tokentop: cannot handle Ctrl-C: {}", e);
+    }
+    
+    // Open the log before taking over the screen, so a bad path is reported readably
+    let mut log = match args.log.as_ref().map(|path| MetricsLog::open(path, args.log_format)).transpose() {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("tokentop: {}: {}", args.log.as_ref().unwrap().display(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut log_error = None;
+    let mut hook = args.on_warning.as_ref().map(|command| WarningHook::new(command, Duration::from_secs(args.warning_cooldown)));
+    
     let mut state = AnalysisState::new(args.buffer_size);
     let mut last_update = Instant::now();
     
-    // Initialize terminal
-    print!("\x1b[2J\x1b[H"); // Clear screen and move cursor to top
+    print!("{}", ENTER_SCREEN);
     
-    loop {
+    let input_finished = loop {
         // Check for new tokens
-        while let Ok(token_stats) = rx.try_recv() {
-            state.update(token_stats, &args);
+        let mut disconnected = false;
+        loop {
+            match rx.try_recv() {
+                Ok(token_stats) => state.update(token_stats, &args),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+        if disconnected || INTERRUPTED.load(Ordering::SeqCst) {
+            break disconnected;
         }
         
         // Update display at specified interval
         if last_update.elapsed() >= Duration::from_millis(args.interval) {
+            state.update_rate(Instant::now(), args.smoothing);
             display_stats(&state, &args);
+            // Stop logging at the first write error, reporting it once the screen is restored
+            if let Some(Err(e)) = log.as_mut().map(|log| log.record(&state)) {
+                log_error = Some(e);
+                log = None;
+            }
+            if let Some(hook) = hook.as_mut() {
+                hook.check(&state.warning_flags, Instant::now());
+            }
             last_update = Instant::now();
         }
         
         thread::sleep(Duration::from_millis(10));
+    };
+    
+    print!("{}", LEAVE_SCREEN);
+    // Once all input is consumed, leave the final statistics on the normal screen
+    if input_finished {
+        state.update_rate(Instant::now(), args.smoothing);
+        print_stats(&state, &args);
+        if let Some(hook) = hook.as_mut() {
+            hook.check(&state.warning_flags, Instant::now());
+        }
+    }
+    let _ = io::stdout().flush();
+    
+    report_malformed_lines();
+    if let Some(hook) = hook.as_mut() {
+        hook.report_failures();
+    }
+    if let (Some(e), Some(path)) = (log_error, &args.log) {
+        eprintln!("tokentop: stopped logging to {}: {}", path.display(), e);
+    }
+}
+
+/// Runs --on-warning for each warning that was not raised at the previous display tick,
+/// unless the same warning ran it less than the cooldown ago. A hook that cannot start or
+/// fails never stops monitoring: with stderr redirected the failure is written there at
+/// once, otherwise it waits until the screen is restored.
+struct WarningHook {
+    command: String,
+    cooldown: Duration,
+    /// Warnings raised at the previous tick
+    active: Vec<String>,
+    /// When each warning last ran the command
+    fired: HashMap<String, Instant>,
+    /// Commands started and not yet reaped
+    running: Vec<(String, Child)>,
+    held_failures: Vec<String>,
+}
+
+impl WarningHook {
+    fn new(command: &str, cooldown: Duration) -> Self {
+        Self {
+            command: command.to_string(),
+            cooldown,
+            active: Vec::new(),
+            fired: HashMap::new(),
+            running: Vec::new(),
+            held_failures: Vec::new(),
+        }
+    }
+    
+    /// Run the command for each warning in `warnings` that is new since the last check.
+    fn check(&mut self, warnings: &[String], now: Instant) {
+        self.reap();
+        for warning in self.newly_raised(warnings, now) {
+            match self.spawn(&warning) {
+                Ok(child) => self.running.push((warning, child)),
+                Err(e) => self.fail(format!("--on-warning could not run for \"{}\": {}", warning, e)),
+            }
+        }
+    }
+    
+    /// The warnings not raised last time, leaving out those that fired within the cooldown.
+    fn newly_raised(&mut self, warnings: &[String], now: Instant) -> Vec<String> {
+        let raised: Vec<String> = warnings.iter()
+            .filter(|warning| !self.active.contains(warning))
+            .filter(|warning| self.fired.get(*warning).is_none_or(|&last| now.saturating_duration_since(last) >= self.cooldown))
+            .cloned()
+            .collect();
+        for warning in &raised {
+            self.fired.insert(warning.clone(), now);
+        }
+        self.active = warnings.to_vec();
+        raised
+    }
+    
+    fn spawn(&self, warning: &str) -> io::Result<Child> {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(&self.command);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.command).arg("tokentop").arg(warning);
+            command
+        };
+        let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+        // A command that ignores its stdin may already be gone, and a short line never
+        // fills the pipe, so the result does not matter
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{}", warning);
+        }
+        Ok(child)
+    }
+    
+    /// Collect the commands that have finished, noting those that failed.
+    fn reap(&mut self) {
+        let mut failures = Vec::new();
+        self.running.retain_mut(|(warning, child)| match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    failures.push(format!("--on-warning for \"{}\" exited with {}", warning, status));
+                }
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                failures.push(format!("--on-warning for \"{}\": {}", warning, e));
+                false
+            }
+        });
+        for failure in failures {
+            self.fail(failure);
+        }
     }
+    
+    fn fail(&mut self, message: String) {
+        if io::stderr().is_terminal() {
+            self.held_failures.push(message);
+        } else {
+            eprintln!("tokentop: {}", message);
+        }
+    }
+    
+    /// Print the failures held back while the screen was taken, including those of commands
+    /// that have finished since.
+    fn report_failures(&mut self) {
+        self.reap();
+        for message in self.held_failures.drain(..) {
+            eprintln!("tokentop: {}", message);
+        }
+    }
+}
+
+/// The --log file, written once per display tick and never to stdout, which belongs to the TUI.
+struct MetricsLog {
+    file: File,
+    format: LogFormat,
 }
 
-fn run_raw_mode() {
+impl MetricsLog {
+    /// Open `path` for appending; a new or empty CSV file gets a header line first.
+    fn open(path: &Path, format: LogFormat) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if matches!(format, LogFormat::Csv) && file.metadata()?.len() == 0 {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        Ok(Self { file, format })
+    }
+    
+    fn record(&mut self, state: &AnalysisState) -> io::Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+        // One write per record, so a reader tailing the file never sees half a line
+        self.file.write_all(format_record(self.format, timestamp, state).as_bytes())
+    }
+}
+
+/// One log line for `state`, terminator included; `timestamp` is in seconds since the Unix epoch.
+fn format_record(format: LogFormat, timestamp: f64, state: &AnalysisState) -> String {
+    match format {
+        LogFormat::Json => {
+            let record = serde_json::json!({
+                "timestamp": timestamp,
+                "tokens_per_second": state.tokens_per_second,
+                "avg_perplexity": state.avg_perplexity,
+                "repetition_score": state.repetition_score,
+                "confidence_score": state.confidence_score,
+                "warnings": state.warning_flags,
+            });
+            format!("{}\n", record)
+        }
+        LogFormat::Csv => format!(
+            "{:.3},{},{},{},{},{}\n",
+            timestamp,
+            state.tokens_per_second,
+            state.avg_perplexity,
+            state.repetition_score,
+            state.confidence_score,
+            csv_field(&state.warning_flags.join("; ")),
+        ),
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Decrease the count for `key`, dropping it at zero.
+fn decrement(tracker: &mut HashMap<String, usize>, key: &str) {
+    if let Some(count) = tracker.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            tracker.remove(key);
+        }
+    }
+}
+
+fn run_raw_mode(format: InputFormat) {
     let stdin = io::stdin();
     let reader = BufReader::new(stdin.lock());
     
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            let tokens = tokenize_line(&line);
-            for token in tokens {
-                println!("{}", token);
-            }
+    for line in reader.lines().map_while(Result::ok) {
+        for stats in read_tokens(&line, format) {
+            println!("{}", stats.token);
+        }
+    }
+    
+    report_malformed_lines();
+}
+
+/// Analyze the whole input, then print the final metrics once, with no cursor movement or
+/// screen switching, so the output can be captured in CI logs or piped on.
+fn run_once(args: &Args) {
+    let mut state = AnalysisState::new(args.buffer_size);
+    let mut total_tokens = 0;
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        for stats in read_tokens(&line, args.input_format) {
+            state.update(stats, args);
+            total_tokens += 1;
+        }
+    }
+    state.update_rate(Instant::now(), args.smoothing);
+    
+    match args.format {
+        SummaryFormat::Text => print!("{}", text_summary(&state, total_tokens)),
+        SummaryFormat::Json => println!("{}", json_summary(&state, total_tokens)),
+    }
+    if let Some(path) = &args.log {
+        if let Err(e) = MetricsLog::open(path, args.log_format).and_then(|mut log| log.record(&state)) {
+            eprintln!("tokentop: {}: {}", path.display(), e);
         }
     }
+    report_malformed_lines();
+}
+
+/// The --once summary as plain text. Rates are left out: a piped input arrives all at once.
+fn text_summary(state: &AnalysisState, total_tokens: usize) -> String {
+    let list = |items: &[String]| -> String {
+        if items.is_empty() {
+            "  none\n".to_string()
+        } else {
+            items.iter().map(|item| format!("  - {}\n", item)).collect()
+        }
+    };
+    format!(
+        "Tokens: {}\nPerplexity: {:.1}\nRepetition: {}%\nConfidence: {}%\nPatterns:\n{}Warnings:\n{}",
+        total_tokens,
+        state.avg_perplexity,
+        (state.repetition_score * 100.0) as u32,
+        (state.confidence_score * 100.0) as u32,
+        list(&state.detected_patterns),
+        list(&state.warning_flags),
+    )
+}
+
+fn json_summary(state: &AnalysisState, total_tokens: usize) -> serde_json::Value {
+    serde_json::json!({
+        "total_tokens": total_tokens,
+        "avg_perplexity": state.avg_perplexity,
+        "repetition_score": state.repetition_score,
+        "confidence_score": state.confidence_score,
+        "patterns": state.detected_patterns,
+        "warnings": state.warning_flags,
+    })
+}
+
+fn report_malformed_lines() {
+    let malformed = MALFORMED_LINES.load(Ordering::SeqCst);
+    if malformed > 0 {
+        eprintln!("tokentop: skipped {} malformed NDJSON line(s)", malformed);
+    }
+}
+
+/// The tokens on one line of input. Malformed NDJSON lines yield nothing and are counted
+/// in `MALFORMED_LINES`.
+fn read_tokens(line: &str, format: InputFormat) -> Vec<TokenStats> {
+    match format {
+        InputFormat::Text => tokenize_line(line).iter().map(|token| analyze_token(token)).collect(),
+        InputFormat::Ndjson if line.trim().is_empty() => Vec::new(),
+        InputFormat::Ndjson => match parse_token_event(line) {
+            Some(stats) => vec![stats],
+            None => {
+                MALFORMED_LINES.fetch_add(1, Ordering::SeqCst);
+                Vec::new()
+            }
+        },
+    }
+}
+
+/// Read one NDJSON token event. Its probability is `exp(logprob)`, which serves as the
+/// confidence, and its perplexity is the reciprocal. Positive log-probabilities are rejected.
+fn parse_token_event(line: &str) -> Option<TokenStats> {
+    let event: TokenEvent = serde_json::from_str(line).ok()?;
+    if event.logprob > 0.0 {
+        return None;
+    }
+    
+    Some(TokenStats {
+        timestamp: Instant::now(),
+        perplexity: (-event.logprob).exp(),
+        confidence: event.logprob.exp(),
+        token: event.token,
+    })
 }
 
 fn tokenize_line(line: &str) -> Vec<String> {
@@ -276,18 +749,17 @@ fn tokenize_line(line: &str) -> Vec<String> {
         .collect()
 }
 
+/// Heuristic stats for a token from plain text input, which carries no probabilities.
 fn analyze_token(token: &str) -> TokenStats {
     // Simplified token analysis - would use actual language models
     let perplexity = calculate_perplexity(token);
     let confidence = calculate_confidence(token);
-    let is_repetitive = is_repetitive_token(token);
     
     TokenStats {
         timestamp: Instant::now(),
         token: token.to_string(),
         perplexity,
         confidence,
-        is_repetitive,
     }
 }
 
@@ -311,71 +783,21 @@ fn calculate_confidence(token: &str) -> f64 {
     }
 }
 
-fn is_repetitive_token(token: &str) -> bool {
-    // Check if token has repetitive character patterns
-    if token.len() < 3 {
-        return false;
-    }
-    
-    let chars: Vec<char> = token.chars().collect();
-    for window in chars.windows(2) {
-        if window[0] == window[1] {
-            return true;
-        }
-    }
-    
-    false
-}
-
 fn display_stats(state: &AnalysisState, args: &Args) {
     // Move cursor to top and clear screen
     print!("\x1b[H\x1b[2J");
-    
-    // Draw border
-    println!("┌─ Token Statistics ──────────────────────┐");
-    println!("│ Tokens/sec: {:<28.1} │", state.tokens_per_second);
-    
-    let perplexity_status = if state.avg_perplexity > args.perplexity_threshold {
-        " ⚠️"
-    } else {
-        ""
-    };
-    println!("│ Perplexity: {:<24.1}{} │", state.avg_perplexity, perplexity_status);
-    
-    // Draw repetition bar
-    let repetition_bar = create_progress_bar(state.repetition_score, 10);
-    let repetition_percent = (state.repetition_score * 100.0) as u32;
-    println!("│ Repetition: {:<19} {}% │", repetition_bar, repetition_percent);
-    
-    // Draw confidence bar
-    let confidence_bar = create_progress_bar(state.confidence_score, 10);
-    let confidence_percent = (state.confidence_score * 100.0) as u32;
-    println!("│ Confidence: {:<19} {}% │", confidence_bar, confidence_percent);
-    println!("│                                         │");
-    
-    // Show detected patterns
-    if args.patterns && !state.detected_patterns.is_empty() {
-        println!("│ Live patterns detected:                 │");
-        for (i, pattern) in state.detected_patterns.iter().take(3).enumerate() {
-            println!("│ - {:<36} │", truncate_string(pattern, 36));
-        }
-    } else {
-        println!("│ No patterns detected                    │");
-        println!("│                                         │");
-        println!("│                                         │");
+    print_stats(state, args);
+}
+
+fn print_stats(state: &AnalysisState, args: &Args) {
+    for line in stats_box(state, args, box_width(args)) {
+        println!("{}", line);
     }
     
-    // Show warnings
-    if !state.warning_flags.is_empty() {
-        println!("│                                         │");
-        println!("│ ⚠️  Warnings:                            │");
-        for warning in &state.warning_flags {
-            println!("│ - {:<36} │", truncate_string(warning, 36));
-        }
+    if args.patterns {
+        println!("\nTracked phrases: {}", state.phrase_tracker.len());
     }
     
-    println!("└─────────────────────────────────────────┘");
-    
     // Show recent tokens if verbose
     if args.patterns && !state.token_buffer.is_empty() {
         println!("\nRecent tokens:");
@@ -397,6 +819,74 @@ fn display_stats(state: &AnalysisState, args: &Args) {
     }
 }
 
+/// Columns for the statistics box: --width, or else the terminal's current width, so a
+/// resize takes effect at the next tick.
+fn box_width(args: &Args) -> usize {
+    args.width.map(usize::from)
+        .or_else(|| terminal_size().map(|(Width(columns), _)| usize::from(columns)))
+        .unwrap_or(DEFAULT_WIDTH)
+        .max(MIN_WIDTH)
+}
+
+/// The bordered statistics box, `width` columns wide. Bars grow with the box, and patterns
+/// and warnings are truncated to fit it.
+fn stats_box(state: &AnalysisState, args: &Args, width: usize) -> Vec<String> {
+    // Columns between the "│ " and " │" borders
+    let inner = width - 4;
+    let row = |text: &str| {
+        let text = truncate_string(text, inner);
+        format!("│ {}{} │", text, " ".repeat(inner - text.chars().count()))
+    };
+    let item = |text: &str| row(&format!("- {}", truncate_string(text, inner - 2)));
+    // Room for a "Repetition: " label and a " 100%" figure
+    let bar_width = inner - 17;
+    
+    let mut lines = Vec::new();
+    let title = "─ Token Statistics ";
+    lines.push(format!("┌{}{}┐", title, "─".repeat(width - 2 - title.chars().count())));
+    lines.push(row(&format!("Tokens/sec: {:.1}", state.tokens_per_second)));
+    
+    // NO_COLOR asks for plain text, so the warning sign becomes [!]
+    let emoji = !ai_toolkit_core::no_color();
+    let perplexity_status = match (state.avg_perplexity > args.perplexity_threshold, emoji) {
+        (false, _) => "",
+        (true, true) => " ⚠️",
+        (true, false) => " [!]",
+    };
+    lines.push(row(&format!("Perplexity: {:.1}{}", state.avg_perplexity, perplexity_status)));
+    
+    let repetition_bar = create_progress_bar(state.repetition_score, bar_width);
+    lines.push(row(&format!("Repetition: {} {}%", repetition_bar, (state.repetition_score * 100.0) as u32)));
+    
+    let confidence_bar = create_progress_bar(state.confidence_score, bar_width);
+    lines.push(row(&format!("Confidence: {} {}%", confidence_bar, (state.confidence_score * 100.0) as u32)));
+    lines.push(row(""));
+    
+    // Show detected patterns
+    if args.patterns && !state.detected_patterns.is_empty() {
+        lines.push(row("Live patterns detected:"));
+        for pattern in state.detected_patterns.iter().take(3) {
+            lines.push(item(pattern));
+        }
+    } else {
+        lines.push(row("No patterns detected"));
+        lines.push(row(""));
+        lines.push(row(""));
+    }
+    
+    // Show warnings
+    if !state.warning_flags.is_empty() {
+        lines.push(row(""));
+        lines.push(row(if emoji { "⚠️  Warnings:" } else { "Warnings:" }));
+        for warning in &state.warning_flags {
+            lines.push(item(warning));
+        }
+    }
+    
+    lines.push(format!("└{}┘", "─".repeat(width - 2)));
+    lines
+}
+
 fn create_progress_bar(value: f64, width: usize) -> String {
     let filled = (value * width as f64) as usize;
     let empty = width - filled;
@@ -404,9 +894,230 @@ fn create_progress_bar(value: f64, width: usize) -> String {
 }
 
 fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
+    if s.chars().count() <= max_len {
         s.to_string()
     } else {
-        format!("{}...", &s[..max_len.saturating_sub(3)])
+        format!("{}...", s.chars().take(max_len.saturating_sub(3)).collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn buffer_evicts_at_buffer_size() {
+        let args = Args::parse_from(["tokentop", "--buffer-size", "7"]);
+        let mut state = AnalysisState::new(args.buffer_size);
+        
+        for i in 0..3 * args.buffer_size {
+            state.update(analyze_token(&format!("token{}", i % 5)), &args);
+        }
+        
+        assert_eq!(state.token_buffer.len(), args.buffer_size);
+        let mut expected: HashMap<String, usize> = HashMap::new();
+        for stats in &state.token_buffer {
+            *expected.entry(stats.token.clone()).or_insert(0) += 1;
+        }
+        assert_eq!(state.pattern_tracker, expected);
+        
+        let tokens: Vec<&str> = state.token_buffer.iter().map(|t| t.token.as_str()).collect();
+        let mut phrases: HashMap<String, usize> = HashMap::new();
+        for window in tokens.windows(3) {
+            *phrases.entry(window.join(" ")).or_insert(0) += 1;
+        }
+        assert_eq!(state.phrase_tracker, phrases);
+    }
+    
+    #[test]
+    fn repeated_phrases_are_detected() {
+        let args = Args::parse_from(["tokentop"]);
+        let mut state = AnalysisState::new(args.buffer_size);
+        for token in tokenize_line("as you can see as you can see as you can see") {
+            state.update(analyze_token(&token), &args);
+        }
+        
+        assert_eq!(state.phrase_tracker["as you can"], 3);
+        assert!(state.detected_patterns.contains(&"Repeated phrase: \"as you can\" (3x)".to_string()));
+        assert!(!state.detected_patterns.iter().any(|p| p.contains("can see as")));
+    }
+    
+    #[test]
+    fn ndjson_tokens_use_their_logprobs() {
+        let stats = read_tokens(r#"{"token":" the","logprob":-0.5}"#, InputFormat::Ndjson);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].token, " the");
+        assert!((stats[0].perplexity - 0.5f64.exp()).abs() < 1e-12);
+        assert!((stats[0].confidence - (-0.5f64).exp()).abs() < 1e-12);
+        
+        // A certain token has perplexity 1 and full confidence
+        let certain = parse_token_event(r#"{"token":"x","logprob":0}"#).unwrap();
+        assert_eq!((certain.perplexity, certain.confidence), (1.0, 1.0));
+        
+        assert!(read_tokens("", InputFormat::Ndjson).is_empty());
+        assert!(parse_token_event("not json").is_none());
+        assert!(parse_token_event(r#"{"token":"x"}"#).is_none());
+        assert!(parse_token_event(r#"{"token":"x","logprob":0.3}"#).is_none());
+    }
+    
+    #[test]
+    fn rate_spans_the_window_and_is_smoothed() {
+        let start = Instant::now();
+        let mut state = AnalysisState::new(100);
+        let add = |state: &mut AnalysisState, millis: u64| {
+            let mut stats = analyze_token("token");
+            stats.timestamp = start + Duration::from_millis(millis);
+            state.token_buffer.push_back(stats);
+        };
+        
+        // 5 tokens 100ms apart: 4 intervals over 0.4s
+        for millis in [0, 100, 200, 300, 400] {
+            add(&mut state, millis);
+        }
+        state.update_rate(start + Duration::from_millis(400), 0.5);
+        assert!((state.tokens_per_second - 10.0).abs() < 1e-9, "{}", state.tokens_per_second);
+        
+        // A burst of 10 tokens 10ms apart: the old ones fall out of the window, and 9
+        // intervals over MIN_RATE_SPAN rather than 90ms give 36/s, averaged half and half
+        // with the previous 10/s
+        for millis in (1500..1600).step_by(10) {
+            add(&mut state, millis);
+        }
+        state.update_rate(start + Duration::from_millis(1590), 0.5);
+        assert!((state.tokens_per_second - 23.0).abs() < 1e-9, "{}", state.tokens_per_second);
+        
+        // Nothing for a while: the rate decays towards zero
+        state.update_rate(start + Duration::from_millis(5000), 0.5);
+        assert!((state.tokens_per_second - 11.5).abs() < 1e-9, "{}", state.tokens_per_second);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn rate_falls_while_no_tokens_arrive() {
+        let start = Instant::now();
+        let mut state = AnalysisState::new(100);
+        for millis in (0..1000).step_by(100) {
+            let mut stats = analyze_token("token");
+            stats.timestamp = start + Duration::from_millis(millis);
+            state.token_buffer.push_back(stats);
+        }
+        
+        // Display ticks after the last token: the span grows and tokens leave the window
+        let rates: Vec<f64> = [900, 1200, 1500, 2000].into_iter()
+            .map(|millis| {
+                state.update_rate(start + Duration::from_millis(millis), 1.0);
+                state.tokens_per_second
+            })
+            .collect();
+        assert!((rates[0] - 10.0).abs() < 1e-9, "{:?}", rates);
+        assert!(rates.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", rates);
+        assert_eq!(rates[3], 0.0);
+    }
+    
+    #[test]
+    fn smoothing_must_be_a_fraction() {
+        assert!(Args::try_parse_from(["tokentop", "--smoothing", "0"]).is_err());
+        assert!(Args::try_parse_from(["tokentop", "--smoothing", "1.5"]).is_err());
+        assert_eq!(Args::parse_from(["tokentop", "--smoothing", "1"]).smoothing, 1.0);
+    }
+    
+    #[test]
+    fn log_records_carry_metrics_and_warnings() {
+        let args = Args::parse_from(["tokentop", "--confidence-threshold", "0.9"]);
+        let mut state = AnalysisState::new(args.buffer_size);
+        for token in tokenize_line("As an AI, I can't") {
+            state.update(analyze_token(&token), &args);
+        }
+        
+        let json: serde_json::Value = serde_json::from_str(&format_record(LogFormat::Json, 1700000000.5, &state)).unwrap();
+        assert_eq!(json["timestamp"], 1700000000.5);
+        assert_eq!(json["avg_perplexity"], state.avg_perplexity);
+        assert_eq!(json["warnings"], serde_json::json!(state.warning_flags));
+        
+        state.warning_flags = vec!["Low confidence".to_string(), "Marker: \"a, b\"".to_string()];
+        let csv = format_record(LogFormat::Csv, 1700000000.5, &state);
+        assert!(csv.starts_with("1700000000.500,"));
+        assert!(csv.ends_with(",\"Low confidence; Marker: \"\"a, b\"\"\"\n"));
+        assert_eq!(csv.trim_end().split(',').count(), CSV_HEADER.split(',').count() + 1);
+    }
+    
+    #[test]
+    fn stats_box_fits_the_width() {
+        let args = Args::parse_from(["tokentop", "--patterns", "--confidence-threshold", "0.9"]);
+        let mut state = AnalysisState::new(args.buffer_size);
+        for token in tokenize_line("as you can see as you can see as you can see ünïcödé") {
+            state.update(analyze_token(&token), &args);
+        }
+        state.detected_patterns.push(format!("Repeated phrase: \"{}\" (3x)", "long ".repeat(30)));
+        
+        for width in [MIN_WIDTH, DEFAULT_WIDTH, 120] {
+            let lines = stats_box(&state, &args, width);
+            for line in &lines {
+                assert_eq!(line.chars().count(), width, "{:?}", line);
+            }
+            assert!(lines.iter().any(|line| line.contains("...")));
+        }
+        
+        // Long patterns are only cut short when the box is too narrow for them
+        let wide = stats_box(&state, &args, 200);
+        assert!(wide.iter().any(|line| line.contains(&"long ".repeat(30))));
+        assert_eq!(wide[3].chars().filter(|c| matches!(c, '█' | '░')).count(), 200 - 4 - 17);
+    }
+    
+    #[test]
+    fn width_has_a_minimum() {
+        assert!(Args::try_parse_from(["tokentop", "--width", "12"]).is_err());
+        assert_eq!(box_width(&Args::parse_from(["tokentop", "--width", "80"])), 80);
+    }
+    
+    #[test]
+    fn once_summary_lists_patterns_and_warnings() {
+        let args = Args::parse_from(["tokentop", "--once", "--confidence-threshold", "0.9"]);
+        let mut state = AnalysisState::new(args.buffer_size);
+        let tokens = tokenize_line("as you can see as you can see as you can see");
+        for token in &tokens {
+            state.update(analyze_token(token), &args);
+        }
+        
+        let text = text_summary(&state, tokens.len());
+        assert!(text.starts_with("Tokens: 12\nPerplexity: "));
+        assert!(text.contains("Patterns:\n  - Repeated phrase: \"as you can\" (3x)\n"));
+        assert!(text.ends_with("Warnings:\n  - High repetition detected\n  - Low confidence\n"));
+        
+        let json = json_summary(&state, tokens.len());
+        assert_eq!(json["total_tokens"], 12);
+        assert_eq!(json["warnings"], serde_json::json!(["High repetition detected", "Low confidence"]));
+        assert!(!text_summary(&AnalysisState::new(10), 0).contains("- "));
+    }
+    
+    #[test]
+    fn each_warning_runs_the_hook_once_per_cooldown() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let warnings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let mut hook = WarningHook::new("true", Duration::from_secs(60));
+        
+        assert_eq!(hook.newly_raised(&warnings(&["Low confidence"]), at(0)), ["Low confidence"]);
+        // Still raised at the next tick
+        assert!(hook.newly_raised(&warnings(&["Low confidence"]), at(1)).is_empty());
+        // Cleared, then back within the cooldown: only the other warning is new
+        assert!(hook.newly_raised(&[], at(2)).is_empty());
+        assert_eq!(hook.newly_raised(&warnings(&["Low confidence", "Perplexity rising"]), at(3)), ["Perplexity rising"]);
+        // Back after the cooldown
+        hook.newly_raised(&[], at(4));
+        assert_eq!(hook.newly_raised(&warnings(&["Low confidence"]), at(61)), ["Low confidence"]);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn hook_gets_the_warning_as_argument_and_on_stdin() {
+        let path = std::env::temp_dir().join(format!("tokentop-hook-{}", std::process::id()));
+        let mut hook = WarningHook::new(&format!("{{ echo \"$1\"; cat; }} > '{}'", path.display()), Duration::ZERO);
+        hook.check(&["Low confidence".to_string()], Instant::now());
+        
+        let (warning, mut child) = hook.running.pop().unwrap();
+        assert_eq!(warning, "Low confidence");
+        assert!(child.wait().unwrap().success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Low confidence\nLow confidence\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}